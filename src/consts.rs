@@ -1,3 +1,97 @@
-use std::num::NonZeroUsize;
+use core::num::NonZeroUsize;
+use core::time::Duration;
 
-pub const DEFAULT_WEIGHT: NonZeroUsize = unsafe { NonZeroUsize::new_unchecked(20usize) };
+pub const DEFAULT_WEIGHT: NonZeroUsize = NonZeroUsize::new(20usize).unwrap();
+
+/// longest schedule the `Expanded` engine precomputes, longer cycles fall back to on-the-fly selection
+pub const MAX_SCHEDULE_LEN: usize = 1 << 20;
+
+/// points owned on the consistent hash ring per unit of weight, 160 for the default weight
+pub const RING_POINTS_PER_WEIGHT: usize = 8;
+
+/// most points an instance owns on the consistent hash ring, reached at a weight of 8192
+pub const MAX_RING_POINTS: usize = 1 << 16;
+
+/// time over which past latencies fade out of the `PeakEwma` average
+pub const DEFAULT_EWMA_DECAY: Duration = Duration::from_secs(10);
+
+/// share of its configured traffic an instance keeps under the `Bandit` engine, however it performs
+#[cfg(feature = "bandit")]
+pub const DEFAULT_BANDIT_MIN_SHARE: f64 = 0.1;
+
+/// total of the per-selection weights of the `Bandit` engine, the resolution of its shares
+#[cfg(feature = "bandit")]
+pub const BANDIT_SCALE: usize = 1 << 20;
+
+/// resolution of the `weight / (1 + in_flight)` ratios of the `WeightedLeastRequest` engine
+pub const LEAST_REQUEST_SCALE: usize = 1 << 10;
+
+/// resolution of the latency and error rate factors of the weights, as a multiple of the weights
+pub const ADAPTIVE_WEIGHT_SCALE: usize = 16;
+
+/// shortest time between two re-scalings of the weights by latency or error rate
+pub const ADAPTIVE_WEIGHT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// weight of each new outcome in the moving error rate of an instance, about the last 10 count
+pub const ERROR_RATE_SMOOTHING: f64 = 0.1;
+
+/// instance count from which the `rayon` feature calculates an `Expanded` schedule by chunks in parallel,
+/// see `benches/schedule.rs`
+#[cfg(feature = "rayon")]
+pub const PARALLEL_THRESHOLD: usize = 1 << 8;
+
+/// instances per chunk of a schedule calculated in parallel
+#[cfg(feature = "rayon")]
+pub const PARALLEL_CHUNK: usize = 1 << 7;
+
+/// instances a selection tracks in a fixed bitset when skipping those refused or failed,
+/// before spilling to the heap
+pub const INLINE_INDEX_SET: usize = 256;
+
+/// instances stored inline before the `smallvec` feature spills to the heap
+#[cfg(feature = "smallvec")]
+pub const INLINE_CAPACITY: usize = 8;
+
+/// per-instance storage, inline for small pools with the `smallvec` feature
+#[cfg(feature = "smallvec")]
+pub(crate) type InlineVec<T> = smallvec::SmallVec<[T; INLINE_CAPACITY]>;
+#[cfg(not(feature = "smallvec"))]
+pub(crate) type InlineVec<T> = alloc::vec::Vec<T>;
+
+/// time a built-in probe may take before counting as a failure
+#[cfg(all(any(feature = "http-probe", feature = "tcp-probe"), wrr_async))]
+pub const DEFAULT_PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// time before retrying a failed query of a discovery source
+#[cfg(all(
+    any(
+        feature = "consul",
+        feature = "etcd",
+        feature = "zookeeper",
+        feature = "redis"
+    ),
+    wrr_async
+))]
+pub const DISCOVERY_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// time a long-polling query of a discovery source may take beyond its wait
+#[cfg(all(
+    any(feature = "consul", feature = "etcd", feature = "zookeeper"),
+    wrr_async
+))]
+pub const DISCOVERY_TIMEOUT_SLACK: Duration = Duration::from_secs(10);
+
+/// positions a `RedisCursor` leases at once
+#[cfg(all(feature = "redis", wrr_async))]
+pub const DEFAULT_CURSOR_BATCH: u64 = 64;
+
+/// version written along with a serialized `WrrConfig` or `QueueSnapshot`, see `format.rs`
+#[cfg(feature = "serde")]
+pub const FORMAT_VERSION: u32 = 1;
+
+/// events kept for the receivers of `WrrQueue::subscribe`, lagging ones miss the oldest
+#[cfg(feature = "tokio")]
+pub const EVENT_CAPACITY: usize = 1024;
+
+/// time between two checks of the selections in flight of a draining instance
+pub const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(10);
@@ -0,0 +1,195 @@
+use crate::consts;
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// members of a queue fed from the healthy instances of a Consul service, kept up to date
+/// with blocking queries
+///
+/// only instances passing all their Consul health checks are members, each one a host and port,
+/// weighted with its `Weights.Passing`. Members are reconciled on each change of the service,
+/// a failed query leaves them as they are and is retried after a second.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{ConsulDiscovery, WrrQueue};
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = ConsulDiscovery::new("http://127.0.0.1:8500", "backend")
+///     .tag("primary")
+///     .spawn(writer);
+/// let (host, port) = reader.select().await.unwrap().data().clone();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ConsulDiscovery {
+    address: String,
+    service: String,
+    tag: Option<String>,
+    token: Option<String>,
+    wait: Duration,
+}
+
+/// handle of a running [`ConsulDiscovery`], the watch stops once it is dropped
+pub struct ConsulWatcher {
+    reader: Reader<(String, u16)>,
+    task: JoinHandle<()>,
+}
+
+/// entry of `/v1/health/service/:service`, only the fields used
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ServiceEntry {
+    node: Node,
+    service: Service,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Node {
+    address: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Service {
+    address: String,
+    port: u16,
+    weights: Option<Weights>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Weights {
+    passing: usize,
+}
+
+impl ConsulDiscovery {
+    /// watch `service` through the agent at `address`, e.g. `http://127.0.0.1:8500`
+    pub fn new(address: impl Into<String>, service: impl Into<String>) -> Self {
+        ConsulDiscovery {
+            address: address.into().trim_end_matches('/').to_string(),
+            service: service.into(),
+            tag: None,
+            token: None,
+            wait: Duration::from_secs(300),
+        }
+    }
+
+    /// only keep the instances tagged with `tag`
+    pub fn tag(mut self, tag: impl Into<String>) -> Self {
+        self.tag = Some(tag.into());
+        self
+    }
+
+    /// authenticate with the ACL `token`
+    pub fn token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// longest time a blocking query waits for a change, 5 minutes by default
+    pub fn wait(mut self, wait: Duration) -> Self {
+        self.wait = wait;
+        self
+    }
+
+    /// start watching into `writer`
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<(String, u16)>) -> ConsulWatcher {
+        ConsulWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<(String, u16)>) {
+        let client = reqwest::Client::new();
+        let mut index = 0;
+        loop {
+            match self.query(&client, index).await {
+                Ok((next, instances)) => {
+                    // the index may go backwards, e.g. after a snapshot restore, start over then
+                    index = if next < index { 0 } else { next };
+                    if writer.pending.reconcile_uncalculated(instances) {
+                        writer.publish().await;
+                    }
+                }
+                Err(e) => {
+                    trace::discovery_failed(&self.service, &e);
+                    tokio::time::sleep(consts::DISCOVERY_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    /// wait for the service to change past `index`, returning the new index and the healthy instances
+    async fn query(
+        &self,
+        client: &reqwest::Client,
+        index: u64,
+    ) -> Result<(u64, Vec<Instance<(String, u16)>>), reqwest::Error> {
+        let url = format!("{}/v1/health/service/{}", self.address, self.service);
+        let wait = format!("{}s", self.wait.as_secs().max(1));
+        let index = index.to_string();
+        let mut request = client
+            .get(url)
+            .query(&[
+                ("passing", "true"),
+                ("wait", wait.as_str()),
+                ("index", index.as_str()),
+            ])
+            // consul adds up to `wait / 16` of jitter to the wait
+            .timeout(self.wait + self.wait / 16 + consts::DISCOVERY_TIMEOUT_SLACK);
+        if let Some(tag) = &self.tag {
+            request = request.query(&[("tag", tag.as_str())]);
+        }
+        if let Some(token) = &self.token {
+            request = request.header("X-Consul-Token", token);
+        }
+        let response = request.send().await?.error_for_status()?;
+        let next = response
+            .headers()
+            .get("X-Consul-Index")
+            .and_then(|index| index.to_str().ok()?.parse().ok())
+            .unwrap_or(0);
+        let entries: Vec<ServiceEntry> = response.json().await?;
+        let mut instances: Vec<Instance<(String, u16)>> = Vec::new();
+        for entry in entries {
+            // an empty service address means the address of its node
+            let host = if entry.service.address.is_empty() {
+                entry.node.address
+            } else {
+                entry.service.address
+            };
+            let weight = entry.service.weights.map_or(1, |w| w.passing);
+            let weight = NonZeroUsize::new(weight).unwrap_or(NonZeroUsize::MIN);
+            let instance = Instance::new_with_weight((host, entry.service.port), weight);
+            if !instances.iter().any(|x| x.data() == instance.data()) {
+                instances.push(instance);
+            }
+        }
+        Ok((next, instances))
+    }
+}
+
+impl ConsulWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<(String, u16)> {
+        self.reader.clone()
+    }
+
+    /// stop watching, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for ConsulWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
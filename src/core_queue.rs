@@ -0,0 +1,219 @@
+//! `#![no_std]` queue of the `no_std` feature, on `alloc`, `spin` locks and core atomics
+//!
+//! only the `Expanded` engine is provided, falling back to `Smooth` like the std queue when the schedule
+//! would be too long to store. There is no clock, so nothing time-driven either: no ttl, slow start,
+//! cooldown, outlier ejection, quota nor latency weighting
+
+use crate::consts::{self, InlineVec};
+use crate::engine::{self, Engine};
+use crate::error::WrrError;
+use crate::instance::{checked, Instance, Member};
+use crate::lock::{Lock, ScheduleLock};
+use crate::schedule::Schedule;
+use crate::trace;
+use alloc::vec::Vec;
+use core::num::NonZeroUsize;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// weighted round-robin queue for `no_std` targets, such as embedded ones, kernels and bootloaders
+///
+/// the schedule is only replaced through `&mut self`, so that a select never spins on the lock,
+/// and concurrent selects take distinct positions of the cycle through an atomic cursor
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::WrrQueue;
+///
+/// let mut queue = WrrQueue::new();
+/// queue.insert_many([("a", 1usize), ("b", 2usize)]);
+///
+/// assert_eq!(Some(&"b"), queue.select().map(|instance| instance.data()));
+/// assert_eq!(Some(&"a"), queue.select().map(|instance| instance.data()));
+/// ```
+pub struct WrrQueue<T: Member> {
+    instance_list: InlineVec<Instance<T>>,
+    /// position of the next selection in the schedule
+    cursor: AtomicUsize,
+    /// true if the schedule would be too long, and instances are picked on the fly instead
+    schedule_fallback: bool,
+    /// current weights of the `Smooth` fallback, aligned with `instance_list`
+    smooth_weight: spin::Mutex<InlineVec<i128>>,
+    select_queue: ScheduleLock,
+}
+
+impl<T: Member> Default for WrrQueue<T> {
+    /// create a default WRR Queue, with no data
+    fn default() -> Self {
+        WrrQueue {
+            instance_list: InlineVec::new(),
+            cursor: AtomicUsize::new(0),
+            schedule_fallback: false,
+            smooth_weight: spin::Mutex::new(InlineVec::new()),
+            select_queue: Lock::new(Schedule::default()),
+        }
+    }
+}
+
+impl<T: Member> WrrQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the [`Engine`] actually selecting instances
+    ///
+    /// `Smooth` if the `Expanded` schedule would overflow or be too long to store
+    pub fn active_engine(&self) -> Engine {
+        if self.schedule_fallback {
+            Engine::Smooth
+        } else {
+            Engine::Expanded
+        }
+    }
+
+    /// insert a new instance, and re-calculate request queue
+    ///
+    /// false if the instance is already in the queue, or is a `(data, weight)` tuple with a zero weight,
+    /// see [`WrrQueue::try_insert`] to tell them apart
+    pub fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        self.recalculate_queue();
+        res
+    }
+
+    /// insert a new instance vec, and re-calculate request queue
+    /// recommended when have multiple instance to be inserted
+    pub fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        }
+        self.recalculate_queue();
+        res
+    }
+
+    /// insert a new `(data, weight)` instance, and re-calculate request queue
+    /// [`WrrError::ZeroWeight`] if the weight is zero, instead of panicking
+    pub fn try_insert<U: Into<usize>>(&mut self, instance: (T, U)) -> Result<bool, WrrError> {
+        let instance = Instance::try_new_with_weight(instance.0, instance.1.into())?;
+        Ok(self.insert(instance))
+    }
+
+    /// delete certain instance
+    pub fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        let Some(index) = self.instance_list.iter().position(|x| *x == instance) else {
+            return false;
+        };
+        self.instance_list.remove(index);
+        self.recalculate_queue();
+        true
+    }
+
+    /// change the weight of the instance holding `data`, false if not in the queue
+    pub fn update_weight(&mut self, data: &T, weight: NonZeroUsize) -> bool {
+        let Some(instance) = self.instance_list.iter_mut().find(|x| x.data() == data) else {
+            return false;
+        };
+        instance.set_weight(weight);
+        self.recalculate_queue();
+        true
+    }
+
+    /// clear instance and schedule in the queue, keeping the allocated capacity
+    pub fn clear_instance(&mut self) {
+        self.instance_list.clear();
+        self.smooth_weight.get_mut().clear();
+        self.schedule_fallback = false;
+        Lock::clear(&mut self.select_queue);
+    }
+
+    /// return the selected instance, None if instance_list is empty
+    pub fn select(&self) -> Option<&Instance<T>> {
+        self.try_select().ok()
+    }
+
+    /// return the selected instance, [`WrrError::Empty`] if instance_list is empty
+    pub fn try_select(&self) -> Result<&Instance<T>, WrrError> {
+        self.select_index()
+            .and_then(|index| self.instance_list.get(index).ok_or(WrrError::Empty))
+            .inspect_err(trace::select_failed)
+    }
+
+    /// number of instances in the queue
+    pub fn len(&self) -> usize {
+        self.instance_list.len()
+    }
+
+    /// true if there is no instance in the queue
+    pub fn is_empty(&self) -> bool {
+        self.instance_list.is_empty()
+    }
+
+    /// return a snapshot of current members and their weights, in insertion order
+    pub fn weights(&self) -> Vec<(&T, NonZeroUsize)> {
+        self.instance_list
+            .iter()
+            .map(|x| (x.data(), *x.weight()))
+            .collect()
+    }
+
+    fn insert_uncalculated(&mut self, instance: Instance<T>) -> bool {
+        if self
+            .instance_list
+            .iter()
+            .any(|x| x.data() == instance.data())
+        {
+            return false;
+        }
+        self.instance_list.push(instance);
+        true
+    }
+
+    fn select_index(&self) -> Result<usize, WrrError> {
+        if self.instance_list.is_empty() {
+            return Err(WrrError::Empty);
+        }
+        if self.schedule_fallback {
+            return engine::smooth_select_weights(&mut self.smooth_weight.lock(), |i| {
+                self.instance_list[i].weight().get()
+            })
+            .ok_or(WrrError::Empty);
+        }
+        let queue = Lock::read(&self.select_queue)?;
+        if queue.is_empty() {
+            return Err(WrrError::Empty);
+        }
+        let len = queue.len();
+        // wraps on the cycle rather than on `usize::MAX`, which 32-bit targets do reach
+        let pos = self
+            .cursor
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |pos| {
+                Some((pos + 1) % len)
+            })
+            .unwrap_or_else(|pos| pos);
+        Ok(queue.get(pos % len))
+    }
+
+    fn weight_vec(&self) -> Vec<usize> {
+        self.instance_list
+            .iter()
+            .map(|x| x.weight().get())
+            .collect()
+    }
+
+    fn recalculate_queue(&mut self) {
+        let weight_vec = self.weight_vec();
+        let cur_weight = core::mem::take(self.smooth_weight.get_mut());
+        *self.smooth_weight.get_mut() = engine::carried_weights(&weight_vec, &cur_weight);
+        let queue = engine::expanded_schedule(&weight_vec, consts::MAX_SCHEDULE_LEN);
+        self.schedule_fallback = queue.is_none();
+        if self.schedule_fallback {
+            trace::schedule_fallback(weight_vec.len(), consts::MAX_SCHEDULE_LEN);
+        }
+        let queue = queue.unwrap_or_default();
+        trace::recalculated(self.active_engine(), weight_vec.len(), queue.len());
+        Lock::replace(&mut self.select_queue, queue);
+    }
+}
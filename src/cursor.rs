@@ -0,0 +1,195 @@
+#[cfg(all(feature = "redis", wrr_async))]
+use crate::redis_cursor::CursorLease;
+#[cfg(target_has_atomic = "64")]
+use crate::sync::AtomicU64;
+#[cfg(not(target_has_atomic = "64"))]
+use crate::sync::AtomicUsize;
+use crate::sync::Ordering;
+use std::cell::Cell;
+
+// a 64-bit counter never wraps in practice, so `position % len` never jumps within the cycle.
+// Without 64-bit atomics the counter is kept below the schedule length instead
+#[cfg(target_has_atomic = "64")]
+type Counter = AtomicU64;
+#[cfg(not(target_has_atomic = "64"))]
+type Counter = AtomicUsize;
+
+/// shard handed to the next thread selecting from a sharded cursor
+static NEXT_SHARD: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+#[cfg(not(wrr_loom))]
+thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+}
+
+#[cfg(wrr_loom)]
+loom::thread_local! {
+    static SHARD: usize = NEXT_SHARD.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+}
+
+/// id handed to the next thread-local cursor, `0` marks an empty slot
+static NEXT_LOCAL_ID: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(1);
+
+/// thread-local cursors a thread keeps at once, the oldest one is re-seeded when exceeded
+const LOCAL_SLOTS: usize = 8;
+
+type LocalSlots = [(usize, u64); LOCAL_SLOTS];
+
+#[cfg(not(wrr_loom))]
+thread_local! {
+    static LOCAL: Cell<LocalSlots> = const { Cell::new([(0, 0); LOCAL_SLOTS]) };
+    static LOCAL_EVICT: Cell<usize> = const { Cell::new(0) };
+}
+
+#[cfg(wrr_loom)]
+loom::thread_local! {
+    static LOCAL: Cell<LocalSlots> = Cell::new([(0, 0); LOCAL_SLOTS]);
+    static LOCAL_EVICT: Cell<usize> = Cell::new(0);
+}
+
+/// one counter per cache line, so that shards never share a line
+#[repr(align(64))]
+#[derive(Debug)]
+struct Shard(Counter);
+
+impl Default for Shard {
+    fn default() -> Self {
+        Shard(Counter::new(0))
+    }
+}
+
+/// position in the schedule, a single counter, striped across shards, or local to each thread
+///
+/// each shard walks the whole schedule on its own, starting from a staggered offset,
+/// so each shard (and so the sum of all shards) keeps the weighted distribution.
+/// In thread-local mode, the shared counter only seeds the start of each thread.
+/// A leased cursor takes its positions from a counter shared by the replicas of the queue instead
+#[derive(Debug)]
+pub(crate) struct Cursor {
+    // shared with the cursors of the later snapshots of a split queue, see `Cursor::shared`
+    shards: std::sync::Arc<[Shard]>,
+    local: Option<usize>,
+    #[cfg(all(feature = "redis", wrr_async))]
+    lease: Option<std::sync::Arc<CursorLease>>,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Cursor::new(1)
+    }
+}
+
+impl Cursor {
+    pub(crate) fn new(shards: usize) -> Self {
+        Cursor {
+            shards: (0..shards.max(1)).map(|_| Shard::default()).collect(),
+            local: None,
+            #[cfg(all(feature = "redis", wrr_async))]
+            lease: None,
+        }
+    }
+
+    pub(crate) fn thread_local() -> Self {
+        Cursor {
+            local: Some(NEXT_LOCAL_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed)),
+            ..Cursor::new(1)
+        }
+    }
+
+    #[cfg(all(feature = "redis", wrr_async))]
+    pub(crate) fn leased(lease: std::sync::Arc<CursorLease>) -> Self {
+        Cursor {
+            lease: Some(lease),
+            ..Cursor::new(1)
+        }
+    }
+
+    /// a cursor of the same mode starting over, thread-local and leased positions are kept
+    pub(crate) fn fresh(&self) -> Self {
+        Cursor {
+            local: self.local,
+            #[cfg(all(feature = "redis", wrr_async))]
+            lease: self.lease.clone(),
+            ..Cursor::new(self.shards.len())
+        }
+    }
+
+    /// a cursor of the same mode walking the same counters, so that the selections made on either
+    /// advance both
+    pub(crate) fn shared(&self) -> Self {
+        Cursor {
+            shards: self.shards.clone(),
+            local: self.local,
+            #[cfg(all(feature = "redis", wrr_async))]
+            lease: self.lease.clone(),
+        }
+    }
+
+    /// advance the cursor of the current thread, returning its position in a schedule of `len`
+    pub(crate) fn next(&self, len: usize) -> usize {
+        let len = len.max(1);
+        #[cfg(all(feature = "redis", wrr_async))]
+        if let Some(lease) = &self.lease {
+            return (lease.next() % len as u64) as usize;
+        }
+        if let Some(id) = self.local {
+            return (self.next_local(id, len) % len as u64) as usize;
+        }
+        if self.shards.len() == 1 {
+            return (advance(&self.shards[0].0, len) % len as u64) as usize;
+        }
+        let shard = SHARD.with(|shard| *shard) % self.shards.len();
+        let offset = (len / self.shards.len() * shard) as u64;
+        (advance(&self.shards[shard].0, len).wrapping_add(offset) % len as u64) as usize
+    }
+
+    fn next_local(&self, id: usize, len: usize) -> u64 {
+        LOCAL.with(|local| {
+            let mut slots = local.get();
+            let slot = match slots.iter().position(|(slot_id, _)| *slot_id == id) {
+                Some(slot) => slot,
+                None => {
+                    let slot = LOCAL_EVICT.with(|evict| {
+                        let slot = evict.get();
+                        evict.set((slot + 1) % LOCAL_SLOTS);
+                        slot
+                    });
+                    slots[slot] = (id, advance(&self.shards[0].0, len));
+                    slot
+                }
+            };
+            let position = slots[slot].1;
+            slots[slot].1 = position.wrapping_add(1);
+            local.set(slots);
+            position
+        })
+    }
+
+    pub(crate) fn load(&self) -> u64 {
+        #[cfg(all(feature = "redis", wrr_async))]
+        if let Some(lease) = &self.lease {
+            return lease.peek();
+        }
+        self.shards[0].0.load(Ordering::Relaxed) as _
+    }
+
+    /// move the cursor to `cursor`, a leased cursor keeps following the shared counter
+    pub(crate) fn store(&mut self, cursor: u64) {
+        for shard in self.shards.iter() {
+            shard.0.store(cursor as _, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+fn advance(counter: &Counter, _len: usize) -> u64 {
+    counter.fetch_add(1, Ordering::Relaxed)
+}
+
+#[cfg(not(target_has_atomic = "64"))]
+fn advance(counter: &Counter, len: usize) -> u64 {
+    let prev = counter.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |cur| {
+        Some((cur % len + 1) % len)
+    });
+    prev.unwrap_or_else(|cur| cur) as u64
+}
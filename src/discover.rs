@@ -0,0 +1,153 @@
+use crate::instance::{Instance, Member};
+use crate::split::{Reader, Writer};
+use crate::trace;
+use std::convert::Infallible;
+use std::fmt::Display;
+use std::future::poll_fn;
+use std::pin::{pin, Pin};
+use std::task::{Context, Poll};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// change of the members of a queue, yielded by a [`Discover`] source
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum Change<T: PartialEq> {
+    /// insert an instance, or update the weight of the member holding its data
+    Insert(Instance<T>),
+    /// delete the member holding this data
+    Remove(T),
+    /// make the members exactly these instances, e.g. after a full resync of the source,
+    /// members kept along the way keep their state
+    Reset(Vec<Instance<T>>),
+}
+
+/// source of membership changes, applied to a queue by [`Writer::drive`]
+///
+/// a stream of [`Change`]s, polled until it ends. Errors are traced and do not end it, the
+/// source decides whether to go on after one. Implemented for tokio mpsc receivers, so that
+/// any task can feed a queue through a channel
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{Change, WrrQueue};
+///
+/// let (sender, receiver) = tokio::sync::mpsc::channel(16);
+/// let watcher = WrrQueue::new().drive(receiver);
+/// sender.send(Change::Insert(("10.0.0.1:80", 5usize).try_into().unwrap())).await.unwrap();
+/// ```
+pub trait Discover<T: PartialEq> {
+    type Error: Display;
+
+    /// poll the next change, None once the source has ended
+    fn poll_change(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Change<T>, Self::Error>>>;
+}
+
+impl<T: PartialEq> Discover<T> for mpsc::Receiver<Change<T>> {
+    type Error = Infallible;
+
+    fn poll_change(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Change<T>, Self::Error>>> {
+        self.poll_recv(cx).map(|change| change.map(Ok))
+    }
+}
+
+impl<T: PartialEq> Discover<T> for mpsc::UnboundedReceiver<Change<T>> {
+    type Error = Infallible;
+
+    fn poll_change(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Change<T>, Self::Error>>> {
+        self.poll_recv(cx).map(|change| change.map(Ok))
+    }
+}
+
+/// handle of a [`Writer`] driven by a [`Discover`] source, created by [`Writer::drive`]
+///
+/// driving stops once the handle is dropped, or once the source has ended
+pub struct DiscoverWatcher<T: Member> {
+    reader: Reader<T>,
+    task: JoinHandle<()>,
+}
+
+impl<T: Member + Clone + Send + Sync + 'static> Writer<T> {
+    /// move the writer to a background task applying the changes of `discover`
+    ///
+    /// the changes ready at once are applied together, readers seeing them in a single published snapshot.
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn drive<D>(self, discover: D) -> DiscoverWatcher<T>
+    where
+        D: Discover<T> + Send + 'static,
+    {
+        DiscoverWatcher {
+            reader: self.reader(),
+            task: tokio::spawn(self.run(discover)),
+        }
+    }
+
+    async fn run<D: Discover<T>>(mut self, discover: D) {
+        let mut discover = pin!(discover);
+        loop {
+            let (batch, ended) = poll_fn(|cx| poll_batch(discover.as_mut(), cx)).await;
+            let mut changed = false;
+            for change in batch {
+                changed |= match change {
+                    Change::Insert(instance) => self.pending.upsert_uncalculated(instance),
+                    Change::Remove(data) => self.pending.remove_uncalculated(&data),
+                    Change::Reset(instances) => self.pending.reconcile_uncalculated(instances),
+                };
+            }
+            if changed {
+                self.publish().await;
+            }
+            if ended {
+                return;
+            }
+        }
+    }
+}
+
+/// the changes ready at once, and whether the source has ended
+fn poll_batch<T: PartialEq, D: Discover<T>>(
+    mut discover: Pin<&mut D>,
+    cx: &mut Context<'_>,
+) -> Poll<(Vec<Change<T>>, bool)> {
+    let mut batch = Vec::new();
+    loop {
+        match discover.as_mut().poll_change(cx) {
+            Poll::Ready(Some(Ok(change))) => batch.push(change),
+            Poll::Ready(Some(Err(e))) => trace::discovery_failed("discover", &e),
+            Poll::Ready(None) => return Poll::Ready((batch, true)),
+            Poll::Pending if batch.is_empty() => return Poll::Pending,
+            Poll::Pending => return Poll::Ready((batch, false)),
+        }
+    }
+}
+
+impl<T: Member> DiscoverWatcher<T> {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<T> {
+        self.reader.clone()
+    }
+
+    /// true once the source has ended, the members are then left as last changed
+    pub fn is_finished(&self) -> bool {
+        self.task.is_finished()
+    }
+
+    /// stop driving the queue, the members are left as last changed
+    pub fn stop(self) {}
+}
+
+impl<T: Member> Drop for DiscoverWatcher<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,185 @@
+use crate::consts;
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::time::Instant;
+use crate::trace;
+use crate::wrr_queue::WrrQueue;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// weight given to each resolved address
+type WeightFn = Arc<dyn Fn(&SocketAddr) -> NonZeroUsize + Send + Sync>;
+
+/// members of a queue fed from the A and AAAA records of a hostname, re-resolved periodically
+///
+/// new addresses are inserted, and addresses no longer resolved are drained: taken out of
+/// rotation at once, and deleted after the drain timeout, so that selections in flight can complete.
+/// An address resolved again while draining starts over as a new member.
+/// A failed resolution leaves the members as they are.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{DnsDiscovery, WrrQueue};
+/// use std::time::Duration;
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = DnsDiscovery::new("backend.internal", 8080)
+///     .interval(Duration::from_secs(10))
+///     .spawn(writer);
+/// let addr = reader.select().await.unwrap();
+/// ```
+#[derive(Clone)]
+pub struct DnsDiscovery {
+    host: String,
+    port: u16,
+    interval: Duration,
+    drain_timeout: Option<Duration>,
+    weight: WeightFn,
+}
+
+/// handle of a running [`DnsDiscovery`], the resolution stops once it is dropped
+pub struct DnsWatcher {
+    reader: Reader<SocketAddr>,
+    task: JoinHandle<()>,
+}
+
+impl DnsDiscovery {
+    /// resolve `host` every 30 seconds, each address serving on `port` with the default weight
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        DnsDiscovery {
+            host: host.into(),
+            port,
+            interval: Duration::from_secs(30),
+            drain_timeout: None,
+            weight: Arc::new(|_| consts::DEFAULT_WEIGHT),
+        }
+    }
+
+    /// resolve the hostname once per `interval`
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// keep removed addresses draining for `timeout` before deleting them, one interval by default
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// give every address the same `weight`
+    pub fn weight(mut self, weight: NonZeroUsize) -> Self {
+        self.weight = Arc::new(move |_| weight);
+        self
+    }
+
+    /// give each address the weight returned by `weight`, e.g. to favor a subnet
+    pub fn weight_with<F>(mut self, weight: F) -> Self
+    where
+        F: Fn(&SocketAddr) -> NonZeroUsize + Send + Sync + 'static,
+    {
+        self.weight = Arc::new(weight);
+        self
+    }
+
+    /// start resolving into `writer`, right away and then once per interval
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<SocketAddr>) -> DnsWatcher {
+        DnsWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<SocketAddr>) {
+        let drain_timeout = self.drain_timeout.unwrap_or(self.interval);
+        let mut draining: HashMap<SocketAddr, Instant> = HashMap::new();
+        loop {
+            match tokio::net::lookup_host((self.host.as_str(), self.port)).await {
+                Ok(addrs) => {
+                    let mut seen = HashSet::new();
+                    let resolved: Vec<SocketAddr> =
+                        addrs.filter(|addr| seen.insert(*addr)).collect();
+                    if self.apply(&mut writer, &resolved, &mut draining, drain_timeout) {
+                        writer.publish().await;
+                    }
+                }
+                Err(e) => trace::discovery_failed(&self.host, &e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// reconcile the members with `resolved`, true if anything changed
+    ///
+    /// the queue is changed without re-calculating it, the snapshot published next is calculated once
+    fn apply(
+        &self,
+        writer: &mut Writer<SocketAddr>,
+        resolved: &[SocketAddr],
+        draining: &mut HashMap<SocketAddr, Instant>,
+        drain_timeout: Duration,
+    ) -> bool {
+        let queue = &mut writer.pending;
+        let now = Instant::now();
+        let lookup: HashSet<&SocketAddr> = resolved.iter().collect();
+        let mut changed = false;
+        // drained long enough, or back in the records and to be inserted anew
+        draining.retain(|addr, deadline| {
+            if *deadline > now && !lookup.contains(addr) {
+                return true;
+            }
+            if let Some(weight) = weight_of(queue, addr) {
+                changed |= queue.delete_uncalculated(Instance::new_with_weight(*addr, weight));
+            }
+            false
+        });
+        let gone: Vec<SocketAddr> = (0..queue.len())
+            .map(|index| *queue.instance_at(index).data())
+            .filter(|addr| !lookup.contains(addr) && !draining.contains_key(addr))
+            .collect();
+        for addr in gone {
+            changed |= queue.drain_uncalculated(&addr).is_some();
+            draining.insert(addr, now + drain_timeout);
+        }
+        for addr in resolved {
+            let weight = (self.weight)(addr);
+            changed |= match weight_of(queue, addr) {
+                None => queue.insert_uncalculated(Instance::new_with_weight(*addr, weight)),
+                Some(current) if current != weight => {
+                    queue.update_weight_uncalculated(addr, weight)
+                }
+                Some(_) => false,
+            };
+        }
+        changed
+    }
+}
+
+fn weight_of(queue: &WrrQueue<SocketAddr>, addr: &SocketAddr) -> Option<NonZeroUsize> {
+    queue
+        .position_of(addr)
+        .map(|index| *queue.instance_at(index).weight())
+}
+
+impl DnsWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<SocketAddr> {
+        self.reader.clone()
+    }
+
+    /// stop resolving, the members are left as last resolved
+    pub fn stop(self) {}
+}
+
+impl Drop for DnsWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
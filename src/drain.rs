@@ -0,0 +1,80 @@
+use crate::consts;
+#[cfg(wrr_async)]
+use crate::runtime;
+use crate::sync::{Arc, AtomicUsize, Ordering};
+use crate::time::Instant;
+use std::time::Duration;
+
+/// completion of a drain started by [`WrrQueue::drain`](crate::WrrQueue::drain)
+///
+/// the instance is no longer selected, the drain completes once every [`InFlight`](crate::InFlight)
+/// mark of it is dropped, so that it can be safely decommissioned.
+/// The handle does not borrow the queue.
+#[derive(Debug, Clone)]
+pub struct Drain {
+    in_flight: Arc<AtomicUsize>,
+}
+
+impl Drain {
+    pub(crate) fn new(in_flight: &Arc<AtomicUsize>) -> Self {
+        Drain {
+            in_flight: in_flight.clone(),
+        }
+    }
+
+    /// number of selections of the instance still in flight
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// true once no selection of the instance is in flight
+    pub fn is_drained(&self) -> bool {
+        self.in_flight() == 0
+    }
+}
+
+#[cfg(wrr_async)]
+impl Drain {
+    /// wait until no selection of the instance is in flight
+    pub async fn wait(&self) {
+        while !self.is_drained() {
+            runtime::sleep(consts::DRAIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// wait until no selection of the instance is in flight, false if still not after `timeout`
+    pub async fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.is_drained() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            runtime::sleep(left.min(consts::DRAIN_POLL_INTERVAL)).await;
+        }
+        true
+    }
+}
+
+#[cfg(wrr_sync)]
+impl Drain {
+    /// block until no selection of the instance is in flight
+    pub fn wait(&self) {
+        while !self.is_drained() {
+            std::thread::sleep(consts::DRAIN_POLL_INTERVAL);
+        }
+    }
+
+    /// block until no selection of the instance is in flight, false if still not after `timeout`
+    pub fn wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.is_drained() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                return false;
+            }
+            std::thread::sleep(left.min(consts::DRAIN_POLL_INTERVAL));
+        }
+        true
+    }
+}
@@ -0,0 +1,681 @@
+use crate::consts::{self, InlineVec};
+use crate::schedule::Schedule;
+use alloc::{vec, vec::Vec};
+use num::integer::gcd;
+
+/// selection engine of the queue
+///
+/// - `Expanded` (default): one full smooth weighted round-robin cycle is precomputed into a schedule,
+///   each selection is a single lookup. The schedule holds `sum(weights) / gcd(weights)` entries
+/// - `Smooth`: each selection is computed on the fly with the nginx smooth weighted round-robin,
+///   O(n) per selection with O(n) state. Recommended when weights are large and mostly coprime,
+///   e.g. `7, 11, 13, 997`, where the expanded schedule would be too long to store
+/// - `RoundRobin`: weights are ignored and members are cycled uniformly in insertion order,
+///   no schedule is computed at all. For pools of equal backends
+/// - `LeastConnections`: picks the instance with the lowest ratio of selections in flight
+///   to weight, see [`WrrQueue::select_tracked`](crate::WrrQueue::select_tracked).
+///   Ties rotate like `RoundRobin`, O(n) per selection
+/// - `PeakEwma`: picks the instance with the lowest `latency * (in_flight + 1) / weight`, where
+///   latency is the peak exponentially weighted moving average of the latencies reported with
+///   [`WrrQueue::report_latency`](crate::WrrQueue::report_latency), so that slow instances receive
+///   less traffic on their own. Instances with no report yet are probed first
+/// - `DeficitRoundRobin`: each selection spends a cost reported by the caller,
+///   see [`WrrQueue::select_with_cost`](crate::WrrQueue::select_with_cost). Instances take turns,
+///   each turn granting a credit of its weight, so that heavier jobs consume more of an instance's share.
+///   A plain `select` costs `1`
+/// - `WeightedLeastRequest`: smooth weighted round-robin over `weight / (1 + in_flight)`,
+///   as Envoy's weighted least-request, so that the configured weights still spread the traffic,
+///   while instances with selections in flight are picked less often. See
+///   [`WrrQueue::select_tracked`](crate::WrrQueue::select_tracked), O(n) per selection
+/// - `Bandit` (experimental, feature `bandit`): instances are arms of a multi-armed bandit, rewarded
+///   with their success rate, from the outcomes recorded by e.g. [`WrrQueue::run_on`](crate::WrrQueue::run_on),
+///   times their speed relative to the fastest one, from [`WrrQueue::report_latency`](crate::WrrQueue::report_latency).
+///   Each instance keeps a minimum share of its configured traffic to keep exploring, see
+///   [`WrrQueue::bandit_min_share`](crate::WrrQueue::bandit_min_share), the rest goes to the best
+///   rewarded ones. O(n) per selection
+///
+/// `Expanded` and `Smooth` produce the same sequence. If the cycle of `Expanded` would overflow, or exceed
+/// 2^20 entries, the queue falls back to `Smooth` instead of allocating it,
+/// see [`WrrQueue::active_engine`](crate::WrrQueue::active_engine), unless an approximated
+/// schedule is allowed with [`WrrQueue::max_schedule_len`](crate::WrrQueue::max_schedule_len).
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{Engine, WrrQueue};
+///
+/// let queue: WrrQueue<&str> = WrrQueue::with_engine(Engine::Smooth);
+/// assert_eq!(Engine::Smooth, queue.engine());
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Engine {
+    #[default]
+    Expanded,
+    Smooth,
+    RoundRobin,
+    LeastConnections,
+    PeakEwma,
+    DeficitRoundRobin,
+    WeightedLeastRequest,
+    #[cfg(feature = "bandit")]
+    Bandit,
+}
+
+/// length of one full cycle of smooth weighted round-robin, None if it overflows `usize`
+pub(crate) fn cycle_len(weight_vec: &[usize]) -> Option<usize> {
+    let divisor = weight_vec.iter().fold(0, |acc, w| gcd(acc, *w));
+    if divisor == 0 {
+        return Some(0);
+    }
+    weight_vec
+        .iter()
+        .try_fold(0usize, |acc, w| acc.checked_add(w / divisor))
+}
+
+/// precompute one full cycle of smooth weighted round-robin,
+/// None if the cycle is longer than `max_len`
+pub(crate) fn expanded_schedule(weight_vec: &[usize], max_len: usize) -> Option<Schedule> {
+    let cycle_len = cycle_len(weight_vec).filter(|len| *len <= max_len)?;
+    #[cfg(feature = "rayon")]
+    if weight_vec.len() >= consts::PARALLEL_THRESHOLD {
+        return Some(Schedule::from_picks(par_expanded_picks(
+            weight_vec, cycle_len,
+        )));
+    }
+    let mut cur_weight_vec = initial_weights(weight_vec);
+    Some(Schedule::from_picks(
+        (0..cycle_len).map(|_| select_instance(weight_vec, &mut cur_weight_vec)),
+    ))
+}
+
+/// precompute one cycle spreading the picks of each instance as evenly as possible,
+/// None if the cycle is longer than `max_len`
+///
+/// instances are placed from the heaviest down, each one spread evenly over the slots
+/// still free, so that the heaviest instance never bursts beyond its share and lighter ones
+/// fill its gaps. Holds the same number of picks per instance as [`expanded_schedule`].
+/// O(len log len), each pick taking the free slot of its rank in `FreeSlots`
+pub(crate) fn interleaved_schedule(weight_vec: &[usize], max_len: usize) -> Option<Schedule> {
+    let cycle_len = cycle_len(weight_vec).filter(|len| *len <= max_len)?;
+    let divisor = weight_vec.iter().fold(0, |acc, w| gcd(acc, *w)).max(1);
+    let mut order: Vec<usize> = (0..weight_vec.len()).collect();
+    order.sort_by_key(|i| core::cmp::Reverse(weight_vec[*i]));
+    let mut slots: Vec<Option<usize>> = vec![None; cycle_len];
+    let mut free_slots = FreeSlots::new(cycle_len);
+    let mut free = cycle_len as u128;
+    for index in order {
+        let weight = (weight_vec[index] / divisor) as u128;
+        // ranks among the slots free before this instance, each pick taken shifting the later ones down
+        for k in 0..weight {
+            slots[free_slots.take((k * free / weight - k) as usize)] = Some(index);
+        }
+        free -= weight;
+    }
+    Some(Schedule::from_picks(slots.into_iter().flatten()))
+}
+
+/// free slots of a schedule being filled, as a Fenwick tree counting them
+///
+/// finds and takes the free slot of a given rank in O(log len)
+struct FreeSlots {
+    /// 1-based, entry `i` counts the free slots in `(i - lowbit(i), i]`
+    tree: Vec<usize>,
+}
+
+impl FreeSlots {
+    fn new(len: usize) -> Self {
+        let mut tree = vec![0; len + 1];
+        for i in 1..=len {
+            tree[i] += 1;
+            let parent = i + (i & i.wrapping_neg());
+            if parent <= len {
+                tree[parent] += tree[i];
+            }
+        }
+        FreeSlots { tree }
+    }
+
+    /// take the free slot of `rank`, counted from 0 in slot order
+    fn take(&mut self, rank: usize) -> usize {
+        let len = self.tree.len() - 1;
+        let (mut pos, mut rank) = (0, rank);
+        let mut step = (len + 1).next_power_of_two() / 2;
+        while step > 0 {
+            if pos + step <= len && self.tree[pos + step] <= rank {
+                pos += step;
+                rank -= self.tree[pos];
+            }
+            step /= 2;
+        }
+        let mut i = pos + 1;
+        while i <= len {
+            self.tree[i] -= 1;
+            i += i & i.wrapping_neg();
+        }
+        pos
+    }
+}
+
+/// scale weights down so that their sum fits in `max_len`, None if there are more than `max_len` - 1 instances
+///
+/// each scaled weight is at most one below its exact share of `max_len`, and never zero
+pub(crate) fn scaled_weights(weight_vec: &[usize], max_len: usize) -> Option<Vec<usize>> {
+    let budget = max_len.checked_sub(weight_vec.len()).filter(|b| *b > 0)? as u128;
+    let sum: u128 = weight_vec.iter().map(|w| *w as u128).sum();
+    Some(
+        weight_vec
+            .iter()
+            .map(|w| match *w {
+                // left out of rotation, e.g. a standby instance
+                0 => 0,
+                w => ((w as u128 * budget / sum) as usize).max(1),
+            })
+            .collect(),
+    )
+}
+
+/// largest relative error of the selection share of an instance, between exact and scaled weights
+pub(crate) fn share_error(weight_vec: &[usize], scaled_vec: &[usize]) -> f64 {
+    let sum: f64 = weight_vec.iter().map(|w| *w as f64).sum();
+    let scaled_sum: f64 = scaled_vec.iter().map(|w| *w as f64).sum();
+    weight_vec
+        .iter()
+        .zip(scaled_vec)
+        .filter(|(w, _)| **w > 0)
+        .map(|(w, s)| {
+            let share = *w as f64 / sum;
+            (*s as f64 / scaled_sum - share).abs() / share
+        })
+        .fold(0.0, f64::max)
+}
+
+/// patch a schedule holding exactly `old_weight` picks of `index`, so that it holds `new_weight`
+///
+/// the picks added or removed are spread evenly, which is O(len) instead of
+/// the O(n * len) of re-calculating the whole schedule
+pub(crate) fn patch_schedule(
+    queue: &Schedule,
+    index: usize,
+    old_weight: usize,
+    new_weight: usize,
+) -> Schedule {
+    if new_weight >= old_weight {
+        let added = (new_weight - old_weight) as u128;
+        let len = queue.len() + (new_weight - old_weight);
+        let mut old_queue = queue.iter();
+        Schedule::from_picks((0..len as u128).map(|j| {
+            if (j + 1) * added / len as u128 > j * added / len as u128 {
+                index
+            } else {
+                old_queue.next().unwrap_or(index)
+            }
+        }))
+    } else {
+        let removed = (old_weight - new_weight) as u128;
+        let old_weight = old_weight as u128;
+        let mut seen = 0u128;
+        Schedule::from_picks(queue.iter().filter(|i| {
+            if *i != index {
+                return true;
+            }
+            let m = seen;
+            seen += 1;
+            (m + 1) * removed / old_weight == m * removed / old_weight
+        }))
+    }
+}
+
+/// initial current weights of a smooth weighted round-robin cycle
+///
+/// current weights are kept in `i128`, so that even `usize::MAX` weights cannot overflow
+pub(crate) fn initial_weights(weight_vec: &[usize]) -> InlineVec<i128> {
+    weight_vec.iter().map(|u| *u as i128).collect()
+}
+
+/// current weights carried over a recalculation, so that the sequence continues where it was
+///
+/// `cur_weight` must be aligned with `weight_vec`, members added since start at their weight.
+/// Weights are shifted evenly to restore the invariant `sum(cur_weight) == sum(weights)`,
+/// otherwise the initial weights are returned
+pub(crate) fn carried_weights(weight_vec: &[usize], cur_weight: &[i128]) -> InlineVec<i128> {
+    if weight_vec.len() != cur_weight.len() || weight_vec.is_empty() {
+        return initial_weights(weight_vec);
+    }
+    let len = weight_vec.len() as i128;
+    let diff =
+        weight_vec.iter().map(|w| *w as i128).sum::<i128>() - cur_weight.iter().sum::<i128>();
+    cur_weight
+        .iter()
+        .enumerate()
+        .map(|(i, cur)| cur + diff.div_euclid(len) + i128::from((i as i128) < diff.rem_euclid(len)))
+        .collect()
+}
+
+/// instances a pick skips
+pub(crate) trait Excluded {
+    fn excludes(&self, index: usize) -> bool;
+
+    /// true if no instance is skipped
+    fn is_empty(&self) -> bool;
+}
+
+impl Excluded for [usize] {
+    fn excludes(&self, index: usize) -> bool {
+        self.contains(&index)
+    }
+
+    fn is_empty(&self) -> bool {
+        <[usize]>::is_empty(self)
+    }
+}
+
+impl<const N: usize> Excluded for [usize; N] {
+    fn excludes(&self, index: usize) -> bool {
+        self.contains(&index)
+    }
+
+    fn is_empty(&self) -> bool {
+        N == 0
+    }
+}
+
+impl Excluded for Vec<usize> {
+    fn excludes(&self, index: usize) -> bool {
+        self.contains(&index)
+    }
+
+    fn is_empty(&self) -> bool {
+        Vec::is_empty(self)
+    }
+}
+
+/// instance indices, in a fixed bitset up to `INLINE_INDEX_SET`, spilling to the heap past it
+///
+/// tracks the instances refused or failed along a selection, without allocating for small pools
+#[derive(Debug, Default)]
+pub(crate) struct IndexSet {
+    bits: [u64; consts::INLINE_INDEX_SET / 64],
+    spilled: Vec<usize>,
+}
+
+impl IndexSet {
+    pub(crate) fn insert(&mut self, index: usize) {
+        match self.bits.get_mut(index / 64) {
+            Some(word) => *word |= 1 << (index % 64),
+            None => self.spilled.push(index),
+        }
+    }
+}
+
+impl Excluded for IndexSet {
+    fn excludes(&self, index: usize) -> bool {
+        match self.bits.get(index / 64) {
+            Some(word) => word & (1 << (index % 64)) != 0,
+            None => self.spilled.contains(&index),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bits.iter().all(|word| *word == 0) && self.spilled.is_empty()
+    }
+}
+
+/// instances skipped by a selection: out of rotation, excluded by the caller,
+/// or refused by their limits along the way
+pub(crate) struct Skip<'a, E: ?Sized> {
+    skipped: &'a [usize],
+    excluded: &'a E,
+    refused: IndexSet,
+}
+
+impl<'a, E: Excluded + ?Sized> Skip<'a, E> {
+    pub(crate) fn new(skipped: &'a [usize], excluded: &'a E) -> Self {
+        Skip {
+            skipped,
+            excluded,
+            refused: IndexSet::default(),
+        }
+    }
+
+    /// skip `index` for the rest of the selection
+    pub(crate) fn refuse(&mut self, index: usize) {
+        self.refused.insert(index);
+    }
+}
+
+impl<E: Excluded + ?Sized> Excluded for Skip<'_, E> {
+    fn excludes(&self, index: usize) -> bool {
+        self.refused.excludes(index)
+            || self.skipped.contains(&index)
+            || self.excluded.excludes(index)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.skipped.is_empty() && self.excluded.is_empty() && self.refused.is_empty()
+    }
+}
+
+/// compute the next smooth weighted round-robin pick on the fly, skipping `excluded` instances
+pub(crate) fn smooth_select(
+    weight_vec: &[usize],
+    cur_weight: &mut [i128],
+    excluded: &(impl Excluded + ?Sized),
+) -> Option<usize> {
+    let mut selected: Option<usize> = None;
+    let mut acc = 0i128;
+    for (i, weight) in weight_vec.iter().enumerate() {
+        if excluded.excludes(i) {
+            continue;
+        }
+        let weight = *weight as i128;
+        cur_weight[i] += weight;
+        acc += weight;
+        if selected.is_none_or(|s| cur_weight[s] < cur_weight[i]) {
+            selected = Some(i);
+        }
+    }
+    let selected = selected?;
+    cur_weight[selected] -= acc;
+    Some(selected)
+}
+
+/// pick the instance of the lowest load `cost` among `len` ones, scanning from `start`
+/// so that ties go to the first instance after it, skipping `excluded` instances
+pub(crate) fn least_loaded(
+    len: usize,
+    cost: impl Fn(usize) -> f64,
+    start: usize,
+    excluded: &(impl Excluded + ?Sized),
+) -> Option<usize> {
+    let mut selected: Option<(usize, f64)> = None;
+    for i in (0..len).map(|offset| (start + offset) % len) {
+        if excluded.excludes(i) {
+            continue;
+        }
+        let cost = cost(i);
+        if selected.is_none_or(|(_, c)| cost < c) {
+            selected = Some((i, cost));
+        }
+    }
+    selected.map(|(i, _)| i)
+}
+
+/// turn and deficit counters of deficit round-robin
+///
+/// deficits are aligned with the instance list, and start over when its length changes
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Deficits {
+    turn: usize,
+    deficits: Vec<u128>,
+}
+
+impl Deficits {
+    pub(crate) fn new(turn: usize, deficits: Vec<u128>) -> Self {
+        Deficits { turn, deficits }
+    }
+
+    pub(crate) fn parts(&self) -> (usize, &[u128]) {
+        (self.turn, &self.deficits)
+    }
+}
+
+/// pick the instance able to pay `cost` out of its deficit, skipping `excluded` instances
+///
+/// the instance holding the turn keeps it while its deficit pays for the selections,
+/// then the turn moves to the next instance, crediting it `quantum * weight`
+pub(crate) fn deficit_select(
+    weight_vec: &[usize],
+    state: &mut Deficits,
+    quantum: usize,
+    cost: usize,
+    excluded: &(impl Excluded + ?Sized),
+) -> Option<usize> {
+    let len = weight_vec.len();
+    if (0..len).all(|i| excluded.excludes(i)) {
+        return None;
+    }
+    let credit = |i: usize| weight_vec[i].max(1) as u128 * quantum.max(1) as u128;
+    if state.deficits.len() != len {
+        state.deficits = vec![0; len];
+        state.turn = 0;
+        state.deficits[0] = credit(0);
+    }
+    let cost = cost as u128;
+    loop {
+        for _ in 0..len {
+            let turn = state.turn;
+            if !excluded.excludes(turn) && state.deficits[turn] >= cost {
+                state.deficits[turn] -= cost;
+                return Some(turn);
+            }
+            state.turn = (turn + 1) % len;
+            state.deficits[state.turn] += credit(state.turn);
+        }
+        // a cost above every deficit: skip the rounds where nobody could pay at once
+        let rounds = (0..len)
+            .filter(|i| !excluded.excludes(*i))
+            .map(|i| (cost - state.deficits[i].min(cost)).div_ceil(credit(i)))
+            .min()
+            .unwrap_or(0);
+        for (i, deficit) in state.deficits.iter_mut().enumerate() {
+            *deficit += rounds.saturating_sub(1) * credit(i);
+        }
+    }
+}
+
+/// effective weight of the `WeightedLeastRequest` engine, `weight / (1 + in_flight)`
+/// scaled by `LEAST_REQUEST_SCALE`, so that the ratios keep their precision
+pub(crate) fn least_request_weight(weight: usize, in_flight: usize) -> usize {
+    let scaled =
+        weight as u128 * crate::consts::LEAST_REQUEST_SCALE as u128 / (in_flight as u128 + 1);
+    (scaled.min(usize::MAX as u128) as usize).max(1)
+}
+
+/// per-selection weights of the `Bandit` engine, summing to about `BANDIT_SCALE`
+///
+/// each instance keeps `min_share` of its configured share, the rest is split in proportion
+/// to `weight * reward`. Only the sums are kept, so that each weight is computed on the fly
+#[cfg(feature = "bandit")]
+pub(crate) struct BanditShares {
+    sum: f64,
+    rewarded: f64,
+    min_share: f64,
+}
+
+#[cfg(feature = "bandit")]
+impl BanditShares {
+    /// shares over the `(weight, reward)` of every instance
+    pub(crate) fn new(arms: impl Iterator<Item = (usize, f64)>, min_share: f64) -> Self {
+        let (sum, rewarded) = arms.fold((0.0, 0.0), |(sum, rewarded), (w, r)| {
+            (sum + w as f64, rewarded + w as f64 * r)
+        });
+        BanditShares {
+            sum,
+            rewarded,
+            min_share,
+        }
+    }
+
+    /// per-selection weight of an instance of `weight` and `reward`, zero weights are left out
+    pub(crate) fn weight(&self, weight: usize, reward: f64) -> usize {
+        if weight == 0 {
+            return 0;
+        }
+        let w = weight as f64;
+        let share =
+            self.min_share * w / self.sum + (1.0 - self.min_share) * w * reward / self.rewarded;
+        ((share * crate::consts::BANDIT_SCALE as f64).round() as usize).max(1)
+    }
+}
+
+/// compute the next smooth weighted round-robin pick over the weights of `weight`, skipping zero weights
+///
+/// weights are computed on the fly, once per instance of `cur_weight`, so that engines deriving
+/// them on each selection, such as `WeightedLeastRequest`, pick without collecting them first
+pub(crate) fn smooth_select_weights(
+    cur_weight: &mut [i128],
+    weight: impl Fn(usize) -> usize,
+) -> Option<usize> {
+    let mut selected: Option<usize> = None;
+    let mut acc = 0i128;
+    for i in 0..cur_weight.len() {
+        let weight = weight(i);
+        if weight == 0 {
+            continue;
+        }
+        cur_weight[i] += weight as i128;
+        acc += weight as i128;
+        if selected.is_none_or(|s| cur_weight[s] < cur_weight[i]) {
+            selected = Some(i);
+        }
+    }
+    let selected = selected?;
+    cur_weight[selected] -= acc;
+    Some(selected)
+}
+
+// kept free of allocation and formatting, as it runs once per scheduled pick
+fn select_instance(weight_vec: &[usize], cur_weight: &mut [i128]) -> usize {
+    debug_assert!(!weight_vec.is_empty(), "instance list is empty");
+    if weight_vec.is_empty() {
+        return 0;
+    }
+    let mut selected = 0;
+    let mut acc = 0i128;
+    for i in 0..weight_vec.len() {
+        cur_weight[i] += weight_vec[i] as i128;
+        acc += weight_vec[i] as i128;
+        if cur_weight[selected] < cur_weight[i] {
+            selected = i;
+        }
+    }
+    cur_weight[selected] -= acc;
+    selected
+}
+
+/// picks of one `Expanded` cycle of `cycle_len`, calculated by chunks of `PARALLEL_CHUNK` instances
+///
+/// each chunk schedules its own instances on a thread of its own, then the chunk schedules are merged
+/// by a smooth weighted round-robin over the chunk totals. Every instance gets as many picks per cycle
+/// as with the sequential calculation, in a different but as smooth an order, and the chunks do not
+/// depend on the number of threads, so neither does the schedule
+#[cfg(feature = "rayon")]
+fn par_expanded_picks(weight_vec: &[usize], cycle_len: usize) -> impl Iterator<Item = usize> {
+    use rayon::prelude::*;
+
+    let divisor = weight_vec.iter().fold(0, |acc, w| gcd(acc, *w)).max(1);
+    let chunks: Vec<Vec<usize>> = weight_vec
+        .par_chunks(consts::PARALLEL_CHUNK)
+        .enumerate()
+        .map(|(chunk, weights)| {
+            let offset = chunk * consts::PARALLEL_CHUNK;
+            let mut cur_weight = initial_weights(weights);
+            // reduced before summing, as the raw weights may overflow where the cycle does not
+            let len = weights
+                .iter()
+                .try_fold(0usize, |acc, w| acc.checked_add(w / divisor))
+                .expect("a chunk is no longer than the cycle");
+            (0..len)
+                .map(|_| offset + select_instance(weights, &mut cur_weight))
+                .collect()
+        })
+        .collect();
+    // a cycle is a multiple of the cycle over the totals, each chunk is picked exactly its length
+    let totals: Vec<usize> = chunks.iter().map(|picks| picks.len()).collect();
+    let mut cur_weight = initial_weights(&totals);
+    let mut next = vec![0; chunks.len()];
+    (0..cycle_len).map(move |_| {
+        let chunk = select_instance(&totals, &mut cur_weight);
+        next[chunk] += 1;
+        chunks[chunk][next[chunk] - 1]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interleaved_schedule_test() {
+        // the placement of re-listing the free slots for each instance
+        fn listed(weight_vec: &[usize]) -> Vec<usize> {
+            let cycle_len = cycle_len(weight_vec).unwrap();
+            let divisor = weight_vec.iter().fold(0, |acc, w| gcd(acc, *w)).max(1);
+            let mut order: Vec<usize> = (0..weight_vec.len()).collect();
+            order.sort_by_key(|i| core::cmp::Reverse(weight_vec[*i]));
+            let mut slots = vec![None; cycle_len];
+            for index in order {
+                let free: Vec<usize> = (0..cycle_len).filter(|s| slots[*s].is_none()).collect();
+                let weight = weight_vec[index] / divisor;
+                for k in 0..weight {
+                    slots[free[k * free.len() / weight]] = Some(index);
+                }
+            }
+            slots.into_iter().flatten().collect()
+        }
+        for weight_vec in [
+            vec![7, 2, 2],
+            vec![1],
+            vec![3, 0, 5, 1],
+            vec![4, 6, 8, 10],
+            (1..40).map(|i| i * 7 % 13 + 1).collect(),
+        ] {
+            let schedule = interleaved_schedule(&weight_vec, consts::MAX_SCHEDULE_LEN).unwrap();
+            let picks: Vec<usize> = (0..schedule.len()).map(|i| schedule.get(i)).collect();
+            assert_eq!(listed(&weight_vec), picks);
+        }
+    }
+
+    #[test]
+    fn interleaved_schedule_large_pool_test() {
+        // 20000 instances over a cycle of about 2^20, 2 * 10^10 steps when listing the free slots per instance
+        let weight_vec: Vec<usize> = (0..20_000).map(|i| i % 100 + 1).collect();
+        let start = std::time::Instant::now();
+        let schedule = interleaved_schedule(&weight_vec, consts::MAX_SCHEDULE_LEN).unwrap();
+        assert!(start.elapsed() < std::time::Duration::from_secs(10));
+        let mut counts = vec![0; weight_vec.len()];
+        for i in 0..schedule.len() {
+            counts[schedule.get(i)] += 1;
+        }
+        assert_eq!(weight_vec, counts);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_expanded_picks_test() {
+        // a left out instance, and weights sharing a divisor across chunks
+        let weight_vec: Vec<usize> = (0..consts::PARALLEL_THRESHOLD * 2)
+            .map(|i| if i == 1 { 0 } else { 2 + 2 * (i % 3) })
+            .collect();
+        let cycle_len = cycle_len(&weight_vec).unwrap();
+        let mut counts = vec![0; weight_vec.len()];
+        for pick in par_expanded_picks(&weight_vec, cycle_len) {
+            counts[pick] += 1;
+        }
+        // the same picks per cycle as the sequential calculation
+        let weights: Vec<usize> = weight_vec.iter().map(|w| w / 2).collect();
+        assert_eq!(weights, counts);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn par_expanded_picks_large_weights_test() {
+        // the raw weights add up past `usize::MAX`, reduced by their gcd the cycle is short
+        let divisor = 1usize << (usize::BITS - 2);
+        let weight_vec: Vec<usize> = (0..consts::PARALLEL_THRESHOLD)
+            .map(|i| divisor * (1 + i % 2))
+            .collect();
+        let cycle_len = cycle_len(&weight_vec).unwrap();
+        assert_eq!(consts::PARALLEL_THRESHOLD / 2 * 3, cycle_len);
+        let mut counts = vec![0; weight_vec.len()];
+        for pick in par_expanded_picks(&weight_vec, cycle_len) {
+            counts[pick] += 1;
+        }
+        let weights: Vec<usize> = weight_vec.iter().map(|w| w / divisor).collect();
+        assert_eq!(weights, counts);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::config::WrrConfig;
+use crate::instance::Instance;
+use std::env::VarError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// error returned when parsing a [`WrrConfig`] from a compact string, e.g. an environment variable
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum EnvConfigError {
+    /// the variable is not set, or not unicode
+    Var(String, VarError),
+    /// the data of the entry at this position does not parse, along with the reason
+    Data(usize, String),
+    /// the weight of the entry at this position is not a positive integer
+    Weight(usize, String),
+    /// the entry at this position is listed twice
+    Duplicate(usize),
+}
+
+impl Display for EnvConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EnvConfigError::Var(name, e) => write!(f, "cannot read {name}: {e}"),
+            EnvConfigError::Data(index, e) => write!(f, "invalid data in entry {index}: {e}"),
+            EnvConfigError::Weight(index, weight) => write!(
+                f,
+                "invalid weight `{weight}` in entry {index}, expected a positive integer"
+            ),
+            EnvConfigError::Duplicate(index) => write!(f, "entry {index} is a duplicate"),
+        }
+    }
+}
+
+impl std::error::Error for EnvConfigError {}
+
+/// parse a comma separated list of `data=weight` entries, e.g. `10.0.0.1:80=5,10.0.0.2:80=1`
+///
+/// the weight is split at the last `=` and may be left out for the default one, blanks around
+/// entries and empty entries are ignored
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::WrrConfig;
+///
+/// let config: WrrConfig<String> = "10.0.0.1:80=5, 10.0.0.2:80".parse().unwrap();
+/// assert_eq!(2, config.instances().len());
+/// assert_eq!(5, config.instances()[0].weight().get());
+/// ```
+impl<T> FromStr for WrrConfig<T>
+where
+    T: PartialEq + FromStr,
+    T::Err: Display,
+{
+    type Err = EnvConfigError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut instances: Vec<Instance<T>> = Vec::new();
+        let entries = s.split(',').map(str::trim).filter(|x| !x.is_empty());
+        for (index, entry) in entries.enumerate() {
+            let (data, weight) = match entry.rsplit_once('=') {
+                Some((data, weight)) => (data.trim_end(), Some(weight.trim_start())),
+                None => (entry, None),
+            };
+            let data = data
+                .parse()
+                .map_err(|e: T::Err| EnvConfigError::Data(index, e.to_string()))?;
+            let instance = match weight {
+                Some(weight) => weight
+                    .parse()
+                    .ok()
+                    .and_then(|weight| Instance::try_new_with_weight(data, weight).ok())
+                    .ok_or_else(|| EnvConfigError::Weight(index, weight.to_string()))?,
+                None => Instance::new(data),
+            };
+            if instances.contains(&instance) {
+                return Err(EnvConfigError::Duplicate(index));
+            }
+            instances.push(instance);
+        }
+        Ok(WrrConfig::new(instances))
+    }
+}
+
+impl<T> WrrConfig<T>
+where
+    T: PartialEq + FromStr,
+    T::Err: Display,
+{
+    /// read a config from the environment variable `name`, see its `FromStr` implementation
+    pub fn from_env(name: &str) -> Result<Self, EnvConfigError> {
+        std::env::var(name)
+            .map_err(|e| EnvConfigError::Var(name.to_string(), e))?
+            .parse()
+    }
+}
@@ -0,0 +1,38 @@
+use alloc::vec::Vec;
+
+/// error returned by the fallible queue operations
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{Instance, WrrError};
+///
+/// assert_eq!(Err(WrrError::ZeroWeight), Instance::try_new_with_weight("data", 0));
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, thiserror::Error)]
+pub enum WrrError {
+    /// there is no instance to select from
+    #[error("no instance in the queue")]
+    Empty,
+    /// the weight of an instance is zero
+    #[error("instance weight must be non-zero")]
+    ZeroWeight,
+    /// the schedule lock is poisoned, a writer panicked while holding it
+    #[error("schedule lock is poisoned")]
+    Poisoned,
+    /// the schedule cannot be read without waiting: a split queue is publishing over the snapshot
+    /// a reader points at, see [`Reader::try_select`](crate::Reader::try_select), or the schedule of
+    /// an async queue is held or has a recalculation pending, see `WrrQueue::try_select`
+    #[error("snapshot is being published")]
+    WouldBlock,
+}
+
+/// error returned by `select_with_retry` when no attempt succeeded
+#[derive(PartialEq, Eq, Debug, Clone, thiserror::Error)]
+pub enum RetryError<E> {
+    /// no instance could be selected at all
+    #[error("{0}")]
+    Queue(WrrError),
+    /// every attempted instance failed, errors are kept in attempt order
+    #[error("every attempt failed: {0:?}")]
+    Exhausted(Vec<E>),
+}
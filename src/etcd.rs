@@ -0,0 +1,170 @@
+use crate::consts;
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use etcd_client::{Client, EventType, GetOptions, KeyValue, WatchOptions};
+use tokio::task::JoinHandle;
+
+/// members of a queue fed from the keys under a prefix of etcd, applied as they are watched
+///
+/// each key is the prefix followed by an endpoint, its value the weight of the endpoint,
+/// e.g. `/services/backend/10.0.0.1:80` holding `5`. An empty value is the default weight,
+/// keys holding anything else than a positive integer are skipped.
+///
+/// the keys are read once, then watched from the next revision on. After a lost connection
+/// the watch resumes from the last revision applied, and the keys are only read again if
+/// that revision was compacted away.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{EtcdDiscovery, WrrQueue};
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = EtcdDiscovery::new(["http://127.0.0.1:2379"], "/services/backend/").spawn(writer);
+/// let endpoint = reader.select().await.unwrap();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct EtcdDiscovery {
+    endpoints: Vec<String>,
+    prefix: String,
+}
+
+/// handle of a running [`EtcdDiscovery`], the watch stops once it is dropped
+pub struct EtcdWatcher {
+    reader: Reader<String>,
+    task: JoinHandle<()>,
+}
+
+impl EtcdDiscovery {
+    /// watch the keys under `prefix` on the etcd cluster reached at `endpoints`
+    pub fn new<E: Into<String>>(
+        endpoints: impl IntoIterator<Item = E>,
+        prefix: impl Into<String>,
+    ) -> Self {
+        EtcdDiscovery {
+            endpoints: endpoints.into_iter().map(Into::into).collect(),
+            prefix: prefix.into(),
+        }
+    }
+
+    /// start watching into `writer`
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<String>) -> EtcdWatcher {
+        EtcdWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<String>) {
+        let mut members = Vec::new();
+        let mut revision = None;
+        loop {
+            if let Err(e) = self.follow(&mut writer, &mut members, &mut revision).await {
+                trace::discovery_failed(&self.prefix, &e);
+            }
+            tokio::time::sleep(consts::DISCOVERY_RETRY_DELAY).await;
+        }
+    }
+
+    /// apply the keys to `writer` until the watch ends, `revision` being the last one applied
+    async fn follow(
+        &self,
+        writer: &mut Writer<String>,
+        members: &mut Vec<Instance<String>>,
+        revision: &mut Option<i64>,
+    ) -> Result<(), etcd_client::Error> {
+        let mut client = Client::connect(&self.endpoints, None).await?;
+        let start = match *revision {
+            Some(revision) => revision + 1,
+            None => {
+                let options = GetOptions::new().with_prefix();
+                let response = client.get(self.prefix.as_str(), Some(options)).await?;
+                *members = response
+                    .kvs()
+                    .iter()
+                    .filter_map(|kv| self.instance(kv))
+                    .collect();
+                publish(writer, members).await;
+                let read = response.header().map_or(0, |header| header.revision());
+                *revision = Some(read);
+                read + 1
+            }
+        };
+        let options = WatchOptions::new().with_prefix().with_start_revision(start);
+        let (_watcher, mut stream) = client.watch(self.prefix.as_str(), Some(options)).await?;
+        while let Some(response) = stream.message().await? {
+            if response.compact_revision() > 0 {
+                // the revisions to resume from are gone, start over from a fresh read
+                *revision = None;
+                return Ok(());
+            }
+            for event in response.events() {
+                let Some(kv) = event.kv() else {
+                    continue;
+                };
+                if let Some(endpoint) = self.endpoint(kv) {
+                    members.retain(|x| x.data() != endpoint);
+                }
+                if event.event_type() == EventType::Put {
+                    members.extend(self.instance(kv));
+                }
+                *revision = Some(kv.mod_revision());
+            }
+            publish(writer, members).await;
+            if response.canceled() {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    /// endpoint of a key under the prefix, None if it is not valid utf-8 or empty
+    fn endpoint<'a>(&self, kv: &'a KeyValue) -> Option<&'a str> {
+        let key = kv.key_str().ok()?;
+        key.strip_prefix(self.prefix.as_str())
+            .filter(|endpoint| !endpoint.is_empty())
+    }
+
+    /// instance of a key, None if its endpoint or its weight are not valid
+    fn instance(&self, kv: &KeyValue) -> Option<Instance<String>> {
+        let endpoint = self.endpoint(kv)?.to_string();
+        let weight = kv.value_str().ok()?.trim();
+        if weight.is_empty() {
+            return Some(Instance::new(endpoint));
+        }
+        let instance = weight
+            .parse()
+            .ok()
+            .and_then(|weight| Instance::try_new_with_weight(endpoint, weight).ok());
+        if instance.is_none() {
+            trace::discovery_failed(kv.key_str().unwrap_or_default(), &"invalid weight");
+        }
+        instance
+    }
+}
+
+/// reconcile `writer` with `members`, and publish them if anything changed
+async fn publish(writer: &mut Writer<String>, members: &[Instance<String>]) {
+    if writer.pending.reconcile_uncalculated(members.to_vec()) {
+        writer.publish().await;
+    }
+}
+
+impl EtcdWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<String> {
+        self.reader.clone()
+    }
+
+    /// stop watching, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for EtcdWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,244 @@
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// polls of the delta api between two full fetches of the application
+const POLLS_PER_FETCH: usize = 10;
+
+/// members of a queue fed from the `UP` instances of a Eureka application
+///
+/// the application is fetched once, then kept up to date with the delta api of the registry,
+/// polled once per interval, and fetched in full again every 10 polls to catch up with deltas
+/// missed in between. Each member is an ip address and port, weighted with the metadata
+/// entry `weight` of its instance, or the default weight if it has none.
+/// A failed poll leaves the members as they are.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{EurekaDiscovery, WrrQueue};
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = EurekaDiscovery::new("http://127.0.0.1:8761/eureka", "BACKEND").spawn(writer);
+/// let (host, port) = reader.select().await.unwrap().data().clone();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct EurekaDiscovery {
+    address: String,
+    app: String,
+    weight_key: String,
+    interval: Duration,
+}
+
+/// handle of a running [`EurekaDiscovery`], the polling stops once it is dropped
+pub struct EurekaWatcher {
+    reader: Reader<(String, u16)>,
+    task: JoinHandle<()>,
+}
+
+/// answer of `/apps/:app`
+#[derive(Deserialize)]
+struct AppResponse {
+    application: Application,
+}
+
+/// answer of `/apps/delta`
+#[derive(Deserialize)]
+struct DeltaResponse {
+    applications: Applications,
+}
+
+#[derive(Deserialize)]
+struct Applications {
+    #[serde(default)]
+    application: Vec<Application>,
+}
+
+#[derive(Deserialize)]
+struct Application {
+    name: String,
+    #[serde(default)]
+    instance: Vec<EurekaInstance>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct EurekaInstance {
+    instance_id: Option<String>,
+    host_name: String,
+    ip_addr: String,
+    status: String,
+    port: Port,
+    #[serde(default)]
+    metadata: HashMap<String, String>,
+    action_type: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Port {
+    #[serde(rename = "$")]
+    port: u16,
+}
+
+impl EurekaInstance {
+    /// id of the instance in the registry
+    fn id(&self) -> String {
+        match &self.instance_id {
+            Some(id) => id.clone(),
+            None => format!("{}:{}", self.host_name, self.port.port),
+        }
+    }
+}
+
+impl EurekaDiscovery {
+    /// poll the instances of `app` on the registry at `address`, e.g. `http://127.0.0.1:8761/eureka`,
+    /// every 30 seconds
+    pub fn new(address: impl Into<String>, app: impl Into<String>) -> Self {
+        EurekaDiscovery {
+            address: address.into().trim_end_matches('/').to_string(),
+            app: app.into(),
+            weight_key: "weight".to_string(),
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    /// read the weight of each instance from its metadata entry `key`
+    pub fn weight_key(mut self, key: impl Into<String>) -> Self {
+        self.weight_key = key.into();
+        self
+    }
+
+    /// poll the registry once per `interval`
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// start polling into `writer`, right away and then once per interval
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<(String, u16)>) -> EurekaWatcher {
+        EurekaWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<(String, u16)>) {
+        let client = reqwest::Client::new();
+        // instances `UP` by id, in the order they were seen
+        let mut members: Vec<(String, Instance<(String, u16)>)> = Vec::new();
+        let mut fetched = false;
+        for poll in 0usize.. {
+            let res = if !fetched || poll % POLLS_PER_FETCH == 0 {
+                self.fetch(&client, &mut members).await
+            } else {
+                self.delta(&client, &mut members).await
+            };
+            match res {
+                Ok(()) => {
+                    fetched = true;
+                    let instances = members.iter().map(|(_, x)| x.clone()).collect();
+                    if writer.pending.reconcile_uncalculated(instances) {
+                        writer.publish().await;
+                    }
+                }
+                Err(e) => trace::discovery_failed(&self.app, &e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// replace `members` with the instances of the application
+    async fn fetch(
+        &self,
+        client: &reqwest::Client,
+        members: &mut Vec<(String, Instance<(String, u16)>)>,
+    ) -> Result<(), reqwest::Error> {
+        let url = format!("{}/apps/{}", self.address, self.app);
+        let response: AppResponse = self.get(client, url).await?;
+        members.clear();
+        for instance in response.application.instance {
+            self.apply(members, instance);
+        }
+        Ok(())
+    }
+
+    /// apply to `members` the changes of the application since the last poll
+    async fn delta(
+        &self,
+        client: &reqwest::Client,
+        members: &mut Vec<(String, Instance<(String, u16)>)>,
+    ) -> Result<(), reqwest::Error> {
+        let url = format!("{}/apps/delta", self.address);
+        let response: DeltaResponse = self.get(client, url).await?;
+        let apps = response.applications.application.into_iter();
+        for app in apps.filter(|app| app.name.eq_ignore_ascii_case(&self.app)) {
+            for instance in app.instance {
+                self.apply(members, instance);
+            }
+        }
+        Ok(())
+    }
+
+    async fn get<R: serde::de::DeserializeOwned>(
+        &self,
+        client: &reqwest::Client,
+        url: String,
+    ) -> Result<R, reqwest::Error> {
+        client
+            .get(url)
+            .header("Accept", "application/json")
+            .timeout(self.interval.max(Duration::from_secs(1)))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+    }
+
+    /// record `instance` in `members`, only kept while `UP` and not deleted
+    fn apply(
+        &self,
+        members: &mut Vec<(String, Instance<(String, u16)>)>,
+        instance: EurekaInstance,
+    ) {
+        let id = instance.id();
+        members.retain(|(x, _)| *x != id);
+        if instance.status != "UP" || instance.action_type.as_deref() == Some("DELETED") {
+            return;
+        }
+        let data = (instance.ip_addr, instance.port.port);
+        let member = match instance.metadata.get(&self.weight_key) {
+            Some(weight) => match weight.trim().parse() {
+                Ok(weight) => Instance::try_new_with_weight(data, weight).ok(),
+                Err(_) => None,
+            },
+            None => Some(Instance::new(data)),
+        };
+        match member {
+            Some(member) => members.push((id, member)),
+            None => trace::discovery_failed(&id, &"invalid weight"),
+        }
+    }
+}
+
+impl EurekaWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<(String, u16)> {
+        self.reader.clone()
+    }
+
+    /// stop polling, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for EurekaWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,121 @@
+use crate::hooks::Hooks;
+use crate::instance::{Instance, Member};
+use crate::state::Health;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+
+/// change of the members of a queue or of their health, received from [`WrrQueue::subscribe`]
+///
+/// events are sent as the changes happen, so that metrics, logs or UIs can follow the queue
+/// without polling it. Ejections and their end are noticed on the next `select`, see [`Hooks`]
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{QueueEvent, WrrQueue};
+///
+/// let mut queue: WrrQueue<&str> = WrrQueue::new();
+/// let mut events = queue.subscribe();
+/// assert!(events.try_recv().is_err());
+/// ```
+///
+/// [`WrrQueue::subscribe`]: crate::WrrQueue::subscribe
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub enum QueueEvent<T: PartialEq> {
+    /// an instance is inserted
+    Inserted(Instance<T>),
+    /// an instance is deleted, cleared or removed by its ttl
+    Removed(Instance<T>),
+    /// the weight of an instance changed `from` a previous one
+    WeightChanged {
+        instance: Instance<T>,
+        from: NonZeroUsize,
+    },
+    /// an instance up went out of rotation, marked down, ejected, paused or draining
+    MarkedDown {
+        instance: Instance<T>,
+        health: Health,
+    },
+    /// an instance is back in rotation
+    Recovered(Instance<T>),
+}
+
+/// sender of the events of a queue, along with the hooks emitting them
+pub(crate) struct Events<T: Member> {
+    sender: broadcast::Sender<QueueEvent<T>>,
+    // built where `T: Clone` is known, the queue itself does not require it
+    hooks: Arc<dyn Hooks<T>>,
+}
+
+/// hooks sending each change as a [`QueueEvent`]
+struct EventHooks<T: PartialEq> {
+    sender: broadcast::Sender<QueueEvent<T>>,
+}
+
+impl<T: Member + Clone + Send + Sync + 'static> Events<T> {
+    pub(crate) fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Events {
+            hooks: Arc::new(EventHooks {
+                sender: sender.clone(),
+            }),
+            sender,
+        }
+    }
+}
+
+impl<T: Member> Events<T> {
+    pub(crate) fn subscribe(&self) -> broadcast::Receiver<QueueEvent<T>> {
+        self.sender.subscribe()
+    }
+
+    pub(crate) fn hooks(&self) -> &dyn Hooks<T> {
+        &*self.hooks
+    }
+}
+
+impl<T: Member> Clone for Events<T> {
+    fn clone(&self) -> Self {
+        Events {
+            sender: self.sender.clone(),
+            hooks: self.hooks.clone(),
+        }
+    }
+}
+
+impl<T: PartialEq> EventHooks<T> {
+    // an event with no receiver is not even cloned
+    fn send(&self, event: impl FnOnce() -> QueueEvent<T>) {
+        if self.sender.receiver_count() > 0 {
+            let _ = self.sender.send(event());
+        }
+    }
+}
+
+impl<T: Member + Clone + Send + Sync> Hooks<T> for EventHooks<T> {
+    fn on_insert(&self, instance: &Instance<T>) {
+        self.send(|| QueueEvent::Inserted(instance.clone()));
+    }
+
+    fn on_remove(&self, instance: &Instance<T>) {
+        self.send(|| QueueEvent::Removed(instance.clone()));
+    }
+
+    fn on_weight_change(&self, instance: &Instance<T>, from: NonZeroUsize) {
+        self.send(|| QueueEvent::WeightChanged {
+            instance: instance.clone(),
+            from,
+        });
+    }
+
+    fn on_state_change(&self, instance: &Instance<T>, from: Health, to: Health) {
+        if from == Health::Up {
+            self.send(|| QueueEvent::MarkedDown {
+                instance: instance.clone(),
+                health: to,
+            });
+        } else if to == Health::Up {
+            self.send(|| QueueEvent::Recovered(instance.clone()));
+        }
+    }
+}
@@ -0,0 +1,17 @@
+/// what happens to an instance not refreshed within its ttl, see [`WrrQueue::ttl`](crate::WrrQueue::ttl)
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{Expiry, WrrQueue};
+/// use std::time::Duration;
+///
+/// let queue: WrrQueue<&str> = WrrQueue::new().ttl(Duration::from_secs(30), Expiry::MarkDown);
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub enum Expiry {
+    /// mark the instance down, back up on its next [`WrrQueue::touch`](crate::WrrQueue::touch)
+    #[default]
+    MarkDown,
+    /// delete the instance from the queue
+    Remove,
+}
@@ -0,0 +1,94 @@
+//! C bindings over a queue of opaque pointers, declared in `include/async_wrr_queue.h`
+//!
+//! the queue only stores the pointers, it never dereferences nor frees them.
+//! Each function only takes a queue created by [`wrr_queue_new`], and a queue must not be used
+//! from several threads at once. Build a library to link against with:
+//!
+//! ```bash
+//! cargo rustc --release --no-default-features --features blocking,ffi --crate-type cdylib
+//! ```
+
+use crate::instance::Instance;
+use crate::wrr_queue::WrrQueue;
+use std::ffi::c_void;
+
+/// queue handed out to C, opaque on the C side
+pub struct FfiQueue {
+    queue: WrrQueue<*mut c_void>,
+}
+
+/// create an empty queue, to be released with [`wrr_queue_free`]
+#[no_mangle]
+pub extern "C" fn wrr_queue_new() -> *mut FfiQueue {
+    Box::into_raw(Box::new(FfiQueue {
+        queue: WrrQueue::new(),
+    }))
+}
+
+/// insert `data` with `weight`, and re-calculate the schedule
+///
+/// false if `queue` is null, `weight` is zero, or `data` was already inserted
+///
+/// # Safety
+///
+/// `queue` must be null or returned by [`wrr_queue_new`] and not freed yet
+#[no_mangle]
+pub unsafe extern "C" fn wrr_queue_insert(
+    queue: *mut FfiQueue,
+    data: *mut c_void,
+    weight: usize,
+) -> bool {
+    let Some(ffi) = queue.as_mut() else {
+        return false;
+    };
+    let Ok(instance) = Instance::try_new_with_weight(data, weight) else {
+        return false;
+    };
+    let res = ffi.queue.insert_uncalculated(instance);
+    ffi.queue.recalculate_queue();
+    res
+}
+
+/// select an instance, returning its index in insertion order
+///
+/// -1 if `queue` is null or has no instance up
+///
+/// # Safety
+///
+/// `queue` must be null or returned by [`wrr_queue_new`] and not freed yet
+#[no_mangle]
+pub unsafe extern "C" fn wrr_queue_select(queue: *mut FfiQueue) -> isize {
+    let Some(ffi) = queue.as_mut() else {
+        return -1;
+    };
+    ffi.queue.recalculate_if_dirty();
+    match ffi.queue.select_index() {
+        Ok(idx) => idx as isize,
+        Err(_) => -1,
+    }
+}
+
+/// the pointer inserted at `index`, null if `queue` is null or `index` is out of range
+///
+/// # Safety
+///
+/// `queue` must be null or returned by [`wrr_queue_new`] and not freed yet
+#[no_mangle]
+pub unsafe extern "C" fn wrr_queue_data(queue: *const FfiQueue, index: usize) -> *mut c_void {
+    match queue.as_ref() {
+        Some(ffi) if index < ffi.queue.len() => *ffi.queue.instance_at(index).data(),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// release `queue`, the pointers inserted are left to the caller
+///
+/// # Safety
+///
+/// `queue` must be null or returned by [`wrr_queue_new`] and not freed yet
+#[no_mangle]
+pub unsafe extern "C" fn wrr_queue_free(queue: *mut FfiQueue) {
+    if !queue.is_null() {
+        drop(Box::from_raw(queue));
+    }
+}
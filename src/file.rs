@@ -0,0 +1,93 @@
+use crate::config::WrrConfig;
+use serde::de::DeserializeOwned;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+/// error returned when loading a [`WrrConfig`] from a file
+#[derive(Debug)]
+pub enum ConfigError {
+    /// the file cannot be read
+    Io(PathBuf, std::io::Error),
+    /// the extension is none of `json`, `toml`, `yaml` or `yml`
+    UnknownFormat(PathBuf),
+    /// the file is not valid json, or does not describe a config
+    Json(PathBuf, serde_json::Error),
+    /// the file is not valid toml, or does not describe a config
+    Toml(PathBuf, toml::de::Error),
+    /// the file is not valid yaml, or does not describe a config
+    Yaml(PathBuf, serde_yaml::Error),
+    /// the instance at this position is listed twice
+    Duplicate(PathBuf, usize),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "cannot read {}: {e}", path.display()),
+            ConfigError::UnknownFormat(path) => write!(
+                f,
+                "unknown config format of {}, expected a .json, .toml, .yaml or .yml file",
+                path.display()
+            ),
+            ConfigError::Json(path, e) => write!(f, "invalid json in {}: {e}", path.display()),
+            ConfigError::Toml(path, e) => write!(f, "invalid toml in {}: {e}", path.display()),
+            ConfigError::Yaml(path, e) => write!(f, "invalid yaml in {}: {e}", path.display()),
+            ConfigError::Duplicate(path, index) => {
+                write!(f, "instance {index} of {} is a duplicate", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io(_, e) => Some(e),
+            ConfigError::Json(_, e) => Some(e),
+            ConfigError::Toml(_, e) => Some(e),
+            ConfigError::Yaml(_, e) => Some(e),
+            ConfigError::UnknownFormat(_) | ConfigError::Duplicate(..) => None,
+        }
+    }
+}
+
+impl<T: PartialEq + DeserializeOwned> WrrConfig<T> {
+    /// read a config from a json, toml or yaml file, told apart by its extension
+    ///
+    /// the file holds an optional `engine` and a list of `instances`, each one a `data`
+    /// and an optional `weight`, e.g. in toml:
+    ///
+    /// ```toml
+    /// engine = "smooth"
+    ///
+    /// [[instances]]
+    /// data = "10.0.0.1:80"
+    /// weight = 5
+    ///
+    /// [[instances]]
+    /// data = "10.0.0.2:80"
+    /// ```
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|e| e.to_str());
+        if !matches!(extension, Some("json" | "toml" | "yaml" | "yml")) {
+            return Err(ConfigError::UnknownFormat(path.to_path_buf()));
+        }
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(path.to_path_buf(), e))?;
+        let config: Self = match extension {
+            Some("json") => serde_json::from_str(&content)
+                .map_err(|e| ConfigError::Json(path.to_path_buf(), e))?,
+            Some("toml") => {
+                toml::from_str(&content).map_err(|e| ConfigError::Toml(path.to_path_buf(), e))?
+            }
+            _ => serde_yaml::from_str(&content)
+                .map_err(|e| ConfigError::Yaml(path.to_path_buf(), e))?,
+        };
+        let instances = config.instances();
+        match (1..instances.len()).find(|i| instances[..*i].contains(&instances[*i])) {
+            Some(index) => Err(ConfigError::Duplicate(path.to_path_buf(), index)),
+            None => Ok(config),
+        }
+    }
+}
@@ -0,0 +1,25 @@
+//! version of the serialized [`WrrConfig`](crate::WrrConfig) and [`QueueSnapshot`](crate::QueueSnapshot)
+//!
+//! both are written along with the current [`consts::FORMAT_VERSION`], and read back tolerantly:
+//! a missing version is the unversioned format of the first releases, fields added since
+//! fall back to their defaults when missing, and fields unknown to this version are ignored,
+//! so that state persisted by an older release, or a newer one, still loads after an upgrade
+
+use crate::consts;
+
+/// version field of a serialized format, always written as the current one
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub(crate) struct FormatVersion;
+
+impl serde::Serialize for FormatVersion {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(consts::FORMAT_VERSION)
+    }
+}
+
+// every version read so far shares the layout of the current one, up to defaulted fields
+impl<'de> serde::Deserialize<'de> for FormatVersion {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <u32 as serde::Deserialize>::deserialize(deserializer).map(|_| FormatVersion)
+    }
+}
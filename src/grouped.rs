@@ -0,0 +1,221 @@
+use crate::instance::{checked, Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::num::NonZeroUsize;
+
+/// two-level weighted round-robin: traffic is split across groups by group weight,
+/// then balanced inside the selected group by instance weight
+///
+/// e.g. regions weighted by capacity, each holding its own weighted backends.
+/// Empty groups are skipped, so that their share goes to the other groups
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::GroupedWrrQueue;
+///
+/// let mut queue = GroupedWrrQueue::new();
+/// queue.insert_group(("eu", 1usize)).await;
+/// queue.insert_group(("us", 3usize)).await;
+/// queue.insert(&"eu", ("eu-1", 1usize)).await;
+/// queue.insert(&"us", ("us-1", 1usize)).await;
+/// queue.insert(&"us", ("us-2", 2usize)).await;
+///
+/// let selected = queue.select().await;
+/// ```
+pub struct GroupedWrrQueue<G: Member + Clone, T: Member> {
+    groups: WrrQueue<G>,
+    members: Vec<(Instance<G>, WrrQueue<T>)>,
+}
+
+impl<G: Member + Clone, T: Member> Default for GroupedWrrQueue<G, T> {
+    fn default() -> Self {
+        GroupedWrrQueue {
+            groups: WrrQueue::default(),
+            members: Vec::new(),
+        }
+    }
+}
+
+impl<G: Member + Clone, T: Member> GroupedWrrQueue<G, T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// the queue balancing inside `group`, None if there is no such group
+    pub fn group(&self, group: &G) -> Option<&WrrQueue<T>> {
+        self.position_of(group).map(|pos| &self.members[pos].1)
+    }
+
+    /// return a snapshot of current groups and their weights, in insertion order
+    pub fn group_weights(&self) -> Vec<(&G, NonZeroUsize)> {
+        self.groups.weights()
+    }
+
+    fn position_of(&self, group: &G) -> Option<usize> {
+        self.members.iter().position(|(g, _)| g.data() == group)
+    }
+}
+
+#[cfg(wrr_async)]
+impl<G: Member + Clone, T: Member> GroupedWrrQueue<G, T> {
+    /// insert a new empty group, false if it is already in the queue
+    pub async fn insert_group(&mut self, group: impl TryInto<Instance<G>>) -> bool {
+        let Some(group) = checked(group) else {
+            return false;
+        };
+        if self.position_of(group.data()).is_some() {
+            return false;
+        }
+        self.groups.insert(group.clone()).await;
+        self.members.push((group, WrrQueue::new()));
+        true
+    }
+
+    /// insert `queue` as a group, e.g. to pick its engine, false if the group is already in the queue
+    pub async fn insert_group_with(
+        &mut self,
+        group: impl TryInto<Instance<G>>,
+        queue: WrrQueue<T>,
+    ) -> bool {
+        let Some(group) = checked(group) else {
+            return false;
+        };
+        if self.position_of(group.data()).is_some() {
+            return false;
+        }
+        self.groups.insert(group.clone()).await;
+        self.members.push((group, queue));
+        true
+    }
+
+    /// delete a group along with its instances
+    pub async fn delete_group(&mut self, group: &G) -> Option<WrrQueue<T>> {
+        let pos = self.position_of(group)?;
+        let (group, queue) = self.members.remove(pos);
+        self.groups.delete_instance(group).await;
+        Some(queue)
+    }
+
+    /// change the weight of `group`, false if there is no such group
+    pub fn update_group_weight(&mut self, group: &G, weight: NonZeroUsize) -> bool {
+        match self.position_of(group) {
+            Some(pos) => {
+                self.members[pos].0.set_weight(weight);
+                self.groups.update_weight(group, weight)
+            }
+            None => false,
+        }
+    }
+
+    /// insert a new instance in `group`, false if there is no such group or the instance is in it
+    pub async fn insert(&mut self, group: &G, instance: impl TryInto<Instance<T>>) -> bool {
+        match self.position_of(group) {
+            Some(pos) => self.members[pos].1.insert(instance).await,
+            None => false,
+        }
+    }
+
+    /// delete certain instance from `group`
+    pub async fn delete_instance(&mut self, group: &G, instance: Instance<T>) -> bool {
+        match self.position_of(group) {
+            Some(pos) => self.members[pos].1.delete_instance(instance).await,
+            None => false,
+        }
+    }
+
+    /// select a group by group weight, then an instance inside it, None if every group is empty
+    pub async fn select(&mut self) -> Option<&Instance<T>> {
+        let mut selected = None;
+        for _ in 0..self.groups.len() {
+            let group = self.groups.select().await?.data();
+            let pos = self.members.iter().position(|(g, _)| g.data() == group);
+            if let Some(pos) = pos.filter(|pos| !self.members[*pos].1.is_empty()) {
+                selected = Some(pos);
+                break;
+            }
+        }
+        self.members[selected?].1.select().await
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<G: Member + Clone, T: Member> GroupedWrrQueue<G, T> {
+    /// insert a new empty group, false if it is already in the queue
+    pub fn insert_group(&mut self, group: impl TryInto<Instance<G>>) -> bool {
+        let Some(group) = checked(group) else {
+            return false;
+        };
+        if self.position_of(group.data()).is_some() {
+            return false;
+        }
+        self.groups.insert(group.clone());
+        self.members.push((group, WrrQueue::new()));
+        true
+    }
+
+    /// insert `queue` as a group, e.g. to pick its engine, false if the group is already in the queue
+    pub fn insert_group_with(
+        &mut self,
+        group: impl TryInto<Instance<G>>,
+        queue: WrrQueue<T>,
+    ) -> bool {
+        let Some(group) = checked(group) else {
+            return false;
+        };
+        if self.position_of(group.data()).is_some() {
+            return false;
+        }
+        self.groups.insert(group.clone());
+        self.members.push((group, queue));
+        true
+    }
+
+    /// delete a group along with its instances
+    pub fn delete_group(&mut self, group: &G) -> Option<WrrQueue<T>> {
+        let pos = self.position_of(group)?;
+        let (group, queue) = self.members.remove(pos);
+        self.groups.delete_instance(group);
+        Some(queue)
+    }
+
+    /// change the weight of `group`, false if there is no such group
+    pub fn update_group_weight(&mut self, group: &G, weight: NonZeroUsize) -> bool {
+        match self.position_of(group) {
+            Some(pos) => {
+                self.members[pos].0.set_weight(weight);
+                self.groups.update_weight(group, weight)
+            }
+            None => false,
+        }
+    }
+
+    /// insert a new instance in `group`, false if there is no such group or the instance is in it
+    pub fn insert(&mut self, group: &G, instance: impl TryInto<Instance<T>>) -> bool {
+        match self.position_of(group) {
+            Some(pos) => self.members[pos].1.insert(instance),
+            None => false,
+        }
+    }
+
+    /// delete certain instance from `group`
+    pub fn delete_instance(&mut self, group: &G, instance: Instance<T>) -> bool {
+        match self.position_of(group) {
+            Some(pos) => self.members[pos].1.delete_instance(instance),
+            None => false,
+        }
+    }
+
+    /// select a group by group weight, then an instance inside it, None if every group is empty
+    pub fn select(&mut self) -> Option<&Instance<T>> {
+        let mut selected = None;
+        for _ in 0..self.groups.len() {
+            let group = self.groups.select()?.data();
+            let pos = self.members.iter().position(|(g, _)| g.data() == group);
+            if let Some(pos) = pos.filter(|pos| !self.members[*pos].1.is_empty()) {
+                selected = Some(pos);
+                break;
+            }
+        }
+        self.members[selected?].1.select()
+    }
+}
@@ -0,0 +1,224 @@
+use crate::background::BackgroundWriter;
+use crate::instance::Member;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::future::Future;
+use std::hash::{BuildHasher, BuildHasherDefault};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::{JoinHandle, JoinSet};
+
+/// active health check of an instance, run periodically by a [`HealthCheck`]
+///
+/// implemented for closures returning a future, e.g. `|data: &&str| async { true }`
+pub trait HealthProbe<T>: Send + Sync + 'static {
+    /// true if the instance holding `data` is healthy
+    fn probe(&self, data: &T) -> impl Future<Output = bool> + Send;
+}
+
+impl<T, F, Fut> HealthProbe<T> for F
+where
+    F: Fn(&T) -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = bool> + Send,
+{
+    fn probe(&self, data: &T) -> impl Future<Output = bool> + Send {
+        self(data)
+    }
+}
+
+/// active health-check runner, marking instances of a [`BackgroundWriter`] down and up
+///
+/// every instance is probed concurrently once per interval, jittered so that many clients do not
+/// probe in lockstep. A probe not answering within the interval counts as a failure.
+/// An instance is marked down after `fall` failed probes in a row, and up after `rise` successful ones.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{HealthCheck, WrrQueue};
+/// use std::time::Duration;
+///
+/// let (reader, writer) = queue.split();
+/// let writer = writer.into_background();
+/// let checker = HealthCheck::new(Duration::from_secs(5))
+///     .rise(2)
+///     .fall(3)
+///     .spawn(writer.clone(), |data: &&str| async { true });
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct HealthCheck {
+    interval: Duration,
+    jitter: f64,
+    rise: usize,
+    fall: usize,
+    seed: Option<u64>,
+}
+
+/// handle of a running [`HealthCheck`], the checks stop once it is dropped
+pub struct HealthChecker {
+    task: JoinHandle<()>,
+}
+
+/// probe results in a row of an instance
+struct Streak<T> {
+    data: T,
+    up: bool,
+    count: usize,
+}
+
+impl HealthCheck {
+    /// probe every instance once per `interval`, jittered by 10%,
+    /// marking it down after 1 failure and up after 1 success
+    pub fn new(interval: Duration) -> Self {
+        HealthCheck {
+            interval,
+            jitter: 0.1,
+            rise: 1,
+            fall: 1,
+            seed: None,
+        }
+    }
+
+    /// spread each interval randomly by up to `jitter` of it, from `0` to `1`
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// jitter the intervals from `seed` instead of at random
+    ///
+    /// checkers given the same seed sleep the same intervals, so that a test suite can reproduce
+    /// the order in which instances are marked down or up
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// successful probes in a row marking a down instance up
+    pub fn rise(mut self, rise: usize) -> Self {
+        self.rise = rise.max(1);
+        self
+    }
+
+    /// failed probes in a row marking an up instance down
+    pub fn fall(mut self, fall: usize) -> Self {
+        self.fall = fall.max(1);
+        self
+    }
+
+    /// start probing the members of `writer`
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn<T, P>(self, writer: BackgroundWriter<T>, probe: P) -> HealthChecker
+    where
+        T: Member + Clone + Send + Sync + 'static,
+        P: HealthProbe<T>,
+    {
+        HealthChecker {
+            task: tokio::spawn(self.run(writer, Arc::new(probe))),
+        }
+    }
+
+    async fn run<T, P>(self, writer: BackgroundWriter<T>, probe: Arc<P>)
+    where
+        T: Member + Clone + Send + Sync + 'static,
+        P: HealthProbe<T>,
+    {
+        let mut streaks: Vec<Streak<T>> = Vec::new();
+        for interval in self.intervals() {
+            tokio::time::sleep(interval).await;
+            let snapshot = writer.reader().snapshot();
+            let mut probes = JoinSet::new();
+            for (data, _) in snapshot.weights() {
+                let (data, probe, timeout) = (data.clone(), probe.clone(), self.interval);
+                probes.spawn(async move {
+                    let up = tokio::time::timeout(timeout, probe.probe(&data)).await;
+                    (data, up.unwrap_or(false))
+                });
+            }
+            let mut results = Vec::new();
+            while let Some(result) = probes.join_next().await {
+                // a panicking probe only loses its own result
+                if let Ok(result) = result {
+                    results.push(result);
+                }
+            }
+            // forget the instances deleted since the last round
+            streaks.retain(|streak| snapshot.is_up(&streak.data).is_some());
+            for (data, up) in results {
+                let streak = match streaks.iter().position(|streak| streak.data == data) {
+                    Some(index) => &mut streaks[index],
+                    None => {
+                        let up_now = snapshot.is_up(&data).unwrap_or(true);
+                        streaks.push(Streak {
+                            data,
+                            up: up_now,
+                            count: 0,
+                        });
+                        streaks.last_mut().unwrap()
+                    }
+                };
+                if up == streak.up {
+                    streak.count = 0;
+                    continue;
+                }
+                streak.count += 1;
+                if streak.count >= if up { self.rise } else { self.fall } {
+                    streak.up = up;
+                    streak.count = 0;
+                    if up {
+                        writer.mark_up(streak.data.clone());
+                    } else {
+                        writer.mark_down(streak.data.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    /// successive intervals between two rounds of probes, jittered from the seed if any
+    fn intervals(&self) -> impl Iterator<Item = Duration> + '_ {
+        let random = RandomState::new();
+        (0u64..).map(move |tick| {
+            self.jittered(match self.seed {
+                Some(seed) => BuildHasherDefault::<DefaultHasher>::default().hash_one((seed, tick)),
+                None => random.hash_one(tick),
+            })
+        })
+    }
+
+    /// interval spread by the jitter, `seed` picking where
+    fn jittered(&self, seed: u64) -> Duration {
+        let offset = (seed as f64 / u64::MAX as f64) * 2.0 - 1.0;
+        self.interval.mul_f64((1.0 + offset * self.jitter).max(0.0))
+    }
+}
+
+impl HealthChecker {
+    /// stop the checks, instances keep the state they were last marked with
+    pub fn stop(self) {}
+}
+
+impl Drop for HealthChecker {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeded_intervals_test() {
+        let check = HealthCheck::new(Duration::from_secs(1)).jitter(0.5);
+        let intervals = |check: &HealthCheck| check.intervals().take(16).collect::<Vec<_>>();
+        let seeded = intervals(&check.clone().seed(7));
+        assert_eq!(seeded, intervals(&check.clone().seed(7)));
+        assert_ne!(seeded, intervals(&check.clone().seed(8)));
+        assert!(seeded
+            .iter()
+            .all(|interval| (500..=1500).contains(&interval.as_millis())));
+        // jittered at all
+        assert!(seeded.iter().any(|interval| *interval != seeded[0]));
+    }
+}
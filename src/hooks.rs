@@ -0,0 +1,42 @@
+use crate::instance::{Instance, Member};
+use crate::state::Health;
+use std::num::NonZeroUsize;
+
+/// lifecycle callbacks of a queue, registered with [`WrrQueue::hooks`](crate::WrrQueue::hooks)
+///
+/// every callback does nothing by default, implement the ones needed for logging, metrics
+/// or connection warmup. They run synchronously within the call changing or selecting,
+/// so keep them cheap.
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{Health, Hooks, Instance, WrrQueue};
+///
+/// struct Log;
+///
+/// impl Hooks<&'static str> for Log {
+///     fn on_state_change(&self, instance: &Instance<&'static str>, from: Health, to: Health) {
+///         println!("{} went from {from:?} to {to:?}", instance.data());
+///     }
+/// }
+///
+/// let queue: WrrQueue<&'static str> = WrrQueue::new().hooks(Log);
+/// ```
+pub trait Hooks<T: Member>: Send + Sync {
+    /// an instance is inserted
+    fn on_insert(&self, _instance: &Instance<T>) {}
+
+    /// an instance is deleted, cleared or removed by its ttl
+    fn on_remove(&self, _instance: &Instance<T>) {}
+
+    /// the weight of an instance changed, `from` being the previous one
+    fn on_weight_change(&self, _instance: &Instance<T>, _from: NonZeroUsize) {}
+
+    /// the health of an instance changed, e.g. marked down, ejected, paused or draining
+    ///
+    /// ejections and their end are noticed on the next `select`
+    fn on_state_change(&self, _instance: &Instance<T>, _from: Health, _to: Health) {}
+
+    /// an instance is selected, through any of the selection methods of the queue
+    fn on_select(&self, _instance: &Instance<T>) {}
+}
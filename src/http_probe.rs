@@ -0,0 +1,109 @@
+use crate::consts;
+use crate::health::HealthProbe;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+/// ready-made [`HealthProbe`] issuing a `GET` request to a url derived from each instance
+///
+/// an instance is healthy if it answers with a status in the expected range, `200..=299` by
+/// default, within the timeout, 5 seconds by default. Only plain `http://` urls are supported,
+/// each request is sent on a fresh connection, on the blocking thread pool of tokio.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{HealthCheck, HttpProbe};
+/// use std::time::Duration;
+///
+/// let probe = HttpProbe::new(|addr: &String| format!("http://{addr}/healthz")).status(200..=399);
+/// let checker = HealthCheck::new(Duration::from_secs(5)).spawn(writer.clone(), probe);
+/// ```
+#[derive(Clone)]
+pub struct HttpProbe<F> {
+    url: F,
+    status: RangeInclusive<u16>,
+    timeout: Duration,
+}
+
+impl<F> HttpProbe<F> {
+    /// probe the url returned by `url` for each instance
+    pub fn new<T>(url: F) -> Self
+    where
+        F: Fn(&T) -> String,
+    {
+        HttpProbe {
+            url,
+            status: 200..=299,
+            timeout: consts::DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// give up on a request after `timeout`, counting it as a failure
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// status codes of a healthy instance
+    pub fn status(mut self, status: RangeInclusive<u16>) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl<T, F> HealthProbe<T> for HttpProbe<F>
+where
+    F: Fn(&T) -> String + Send + Sync + 'static,
+{
+    fn probe(&self, data: &T) -> impl Future<Output = bool> + Send {
+        let url = (self.url)(data);
+        let (status, timeout) = (self.status.clone(), self.timeout);
+        async move {
+            let code = tokio::task::spawn_blocking(move || get(&url, timeout)).await;
+            code.ok()
+                .flatten()
+                .is_some_and(|code| status.contains(&code))
+        }
+    }
+}
+
+/// status code answered to a `GET` of `url` within `timeout`, None if the request failed
+fn get(url: &str, timeout: Duration) -> Option<u16> {
+    let rest = url.strip_prefix("http://")?;
+    let (authority, path) = match rest.find('/') {
+        Some(index) => rest.split_at(index),
+        None => (rest, "/"),
+    };
+    let addr = if authority.contains(':') {
+        authority.to_string()
+    } else {
+        format!("{authority}:80")
+    };
+    let addr = addr.to_socket_addrs().ok()?.next()?;
+    let mut stream = TcpStream::connect_timeout(&addr, timeout).ok()?;
+    stream.set_read_timeout(Some(timeout)).ok()?;
+    stream.set_write_timeout(Some(timeout)).ok()?;
+    let request = format!(
+        "GET {path} HTTP/1.1\r\nHost: {authority}\r\nUser-Agent: async_wrr_queue\r\nConnection: close\r\n\r\n"
+    );
+    stream.write_all(request.as_bytes()).ok()?;
+    // only the status line is needed, e.g. `HTTP/1.1 200 OK`
+    let mut head = Vec::new();
+    let mut buf = [0u8; 256];
+    while !head.windows(2).any(|w| w == b"\r\n") && head.len() < 1024 {
+        let read = stream.read(&mut buf).ok()?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+    let line = std::str::from_utf8(&head).ok()?.lines().next()?;
+    let mut parts = line.split_whitespace();
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
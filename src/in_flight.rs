@@ -0,0 +1,27 @@
+use crate::sync::{Arc, AtomicUsize, Ordering};
+
+/// in-flight mark of an instance selected with `select_tracked`, released when dropped
+///
+/// the mark does not borrow the queue, so that it can be moved along with the request.
+/// The `LeastConnections` [`Engine`](crate::Engine) picks the instance with the fewest
+/// selections in flight relative to its weight, `WeightedLeastRequest` lowers the weight
+/// of an instance with each of them
+#[derive(Debug)]
+pub struct InFlight {
+    counter: Arc<AtomicUsize>,
+}
+
+impl InFlight {
+    pub(crate) fn new(counter: &Arc<AtomicUsize>) -> Self {
+        counter.fetch_add(1, Ordering::Relaxed);
+        InFlight {
+            counter: counter.clone(),
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,58 @@
+use crate::consts::InlineVec;
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash};
+
+/// positions of the instances holding each data, keyed by the hash of the data
+///
+/// a bucket may hold positions of different data colliding on the same hash,
+/// or of the same data inserted with different weights
+#[derive(Debug, Default, Clone)]
+pub(crate) struct MemberIndex {
+    hasher: RandomState,
+    positions: HashMap<u64, InlineVec<usize>>,
+}
+
+impl MemberIndex {
+    /// positions that may hold `data`, in ascending order
+    pub(crate) fn candidates<T: Hash>(&self, data: &T) -> &[usize] {
+        self.positions
+            .get(&self.hasher.hash_one(data))
+            .map_or(&[], |positions| positions)
+    }
+
+    /// record `data` pushed at `position`, the end of the instance list
+    pub(crate) fn insert<T: Hash>(&mut self, data: &T, position: usize) {
+        self.positions
+            .entry(self.hasher.hash_one(data))
+            .or_default()
+            .push(position);
+    }
+
+    /// forget `data` at `position`, and shift the positions after it
+    ///
+    /// O(n), as the removal from the instance list: members keep their insertion order,
+    /// which `weights`, `RoundRobin` and the schedules follow, so no swap-remove
+    pub(crate) fn remove<T: Hash>(&mut self, data: &T, position: usize) {
+        let hash = self.hasher.hash_one(data);
+        if let Some(positions) = self.positions.get_mut(&hash) {
+            positions.retain(|p| *p != position);
+            if positions.is_empty() {
+                self.positions.remove(&hash);
+            }
+        }
+        for p in self
+            .positions
+            .values_mut()
+            .flat_map(|positions| positions.iter_mut())
+        {
+            if *p > position {
+                *p -= 1;
+            }
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.positions.clear();
+    }
+}
@@ -1,6 +1,26 @@
 use crate::consts;
-use std::num::NonZeroUsize;
-use std::ops::Deref;
+use crate::error::WrrError;
+use crate::trace;
+#[cfg(feature = "hash")]
+use core::hash::Hash;
+use core::num::NonZeroUsize;
+use core::ops::Deref;
+
+/// bound on the data held by a queue: `PartialEq`, or `Hash + Eq` with the `hash` feature
+///
+/// implemented for every type satisfying it
+#[cfg(not(feature = "hash"))]
+pub trait Member: PartialEq {}
+#[cfg(not(feature = "hash"))]
+impl<T: PartialEq> Member for T {}
+
+/// bound on the data held by a queue: `PartialEq`, or `Hash + Eq` with the `hash` feature
+///
+/// implemented for every type satisfying it
+#[cfg(feature = "hash")]
+pub trait Member: Hash + Eq {}
+#[cfg(feature = "hash")]
+impl<T: Hash + Eq> Member for T {}
 
 /// instance to be selected
 ///
@@ -15,12 +35,20 @@ use std::ops::Deref;
 /// Instance::new(123);
 /// Instance::new_with_weight(String::from("String"), NonZeroUsize::new(2).unwrap());
 ///
-/// let instance: Instance<_> = ("data", 3usize).into();
+/// let instance: Instance<_> = ("data", 3usize).try_into().unwrap();
 ///
 /// assert_eq!(&"data", instance.data());
 /// assert_eq!(&NonZeroUsize::new(3).unwrap(), instance.weight());
 /// ```
-#[derive(PartialEq, Eq, Debug)]
+///
+/// with the `serde` feature, it (de)serializes as `{ "data": .., "weight": .. }`. A missing weight
+/// is the default one, and a zero weight is rejected with [`WrrError::ZeroWeight`]
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "RawInstance<T>")
+)]
 pub struct Instance<T: PartialEq> {
     data: T,
     weight: NonZeroUsize,
@@ -38,6 +66,12 @@ impl<T: PartialEq> Instance<T> {
         Instance { data, weight }
     }
 
+    /// create an instance with a plain weight, [`WrrError::ZeroWeight`] if `weight` is zero
+    pub fn try_new_with_weight(data: T, weight: usize) -> Result<Self, WrrError> {
+        let weight = NonZeroUsize::new(weight).ok_or(WrrError::ZeroWeight)?;
+        Ok(Instance { data, weight })
+    }
+
     pub fn data(&self) -> &T {
         &self.data
     }
@@ -45,15 +79,54 @@ impl<T: PartialEq> Instance<T> {
     pub fn weight(&self) -> &NonZeroUsize {
         &self.weight
     }
+
+    pub(crate) fn set_weight(&mut self, weight: NonZeroUsize) {
+        self.weight = weight;
+    }
 }
 
-impl<T: PartialEq, U: Into<usize>> From<(T, U)> for Instance<T> {
-    fn from(value: (T, U)) -> Self {
-        Instance {
-            data: value.0,
-            weight: NonZeroUsize::new(value.1.into()).unwrap(),
-        }
+/// instance as read by serde, before its weight is checked
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct RawInstance<T> {
+    data: T,
+    #[serde(default = "default_weight")]
+    weight: usize,
+}
+
+#[cfg(feature = "serde")]
+fn default_weight() -> usize {
+    consts::DEFAULT_WEIGHT.get()
+}
+
+#[cfg(feature = "serde")]
+impl<T: PartialEq> TryFrom<RawInstance<T>> for Instance<T> {
+    type Error = WrrError;
+
+    fn try_from(raw: RawInstance<T>) -> Result<Self, Self::Error> {
+        Instance::try_new_with_weight(raw.data, raw.weight)
+    }
+}
+
+/// a `(data, weight)` tuple, [`WrrError::ZeroWeight`] if the weight is zero
+///
+/// the inserts of the queues take `impl TryInto<Instance<T>>`, and refuse such an instance
+/// as they refuse a duplicate one
+impl<T: PartialEq, U: Into<usize>> TryFrom<(T, U)> for Instance<T> {
+    type Error = WrrError;
+
+    fn try_from((data, weight): (T, U)) -> Result<Self, Self::Error> {
+        Instance::try_new_with_weight(data, weight.into())
+    }
+}
+
+/// convert an instance passed to an insert, `None` if its weight is zero
+pub(crate) fn checked<T: PartialEq>(instance: impl TryInto<Instance<T>>) -> Option<Instance<T>> {
+    let instance = instance.try_into().ok();
+    if instance.is_none() {
+        trace::zero_weight_refused();
     }
+    instance
 }
 
 impl<T: PartialEq> Deref for Instance<T> {
@@ -0,0 +1,95 @@
+use crate::error::WrrError;
+use crate::in_flight::InFlight;
+use crate::instance::{Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::iter::FusedIterator;
+use std::ops::Deref;
+
+/// instance selected by [`WrrQueue::select_lease`], counted in flight until dropped
+///
+/// dereferences to the selected [`Instance`], and borrows the queue for as long as the work
+/// against it lasts, its outcome can be reported through the lease itself. Leases taken from
+/// [`WrrQueue::leases`] share the borrow, so that several of them can be held at once
+pub struct Lease<'a, T: Member> {
+    queue: &'a WrrQueue<T>,
+    index: usize,
+    _in_flight: InFlight,
+}
+
+impl<'a, T: Member> Lease<'a, T> {
+    pub(crate) fn new(queue: &'a WrrQueue<T>, index: usize, in_flight: InFlight) -> Self {
+        Lease {
+            queue,
+            index,
+            _in_flight: in_flight,
+        }
+    }
+
+    pub fn instance(&self) -> &'a Instance<T> {
+        self.queue.instance_at(self.index)
+    }
+
+    /// release the lease, recording a successful request against the instance
+    pub fn report_success(self) {
+        self.queue.record(self.index, true);
+    }
+
+    /// release the lease, recording a failed request against the instance
+    pub fn report_failure(self) {
+        self.queue.record(self.index, false);
+    }
+}
+
+impl<T: Member> Deref for Lease<'_, T> {
+    type Target = Instance<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.instance()
+    }
+}
+
+/// leases of successive selections of a [`WrrQueue`], see [`WrrQueue::leases`]
+///
+/// endless, unless the queue has no instance up. Each [`Lease`] borrows the queue on its own,
+/// so that leases taken one after the other can be held at the same time
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::WrrQueue;
+///
+/// let mut queue = WrrQueue::new();
+/// queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+/// let mut leases = queue.leases();
+/// let (first, second) = (leases.next().unwrap(), leases.next().unwrap());
+/// ```
+pub struct Leases<'a, T: Member> {
+    queue: &'a WrrQueue<T>,
+    done: bool,
+}
+
+impl<'a, T: Member> Leases<'a, T> {
+    pub(crate) fn new(queue: &'a WrrQueue<T>) -> Self {
+        Leases { queue, done: false }
+    }
+}
+
+impl<'a, T: Member> Iterator for Leases<'a, T> {
+    type Item = Lease<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.queue.select_index() {
+            Ok(idx) => Some(self.queue.lease_at(idx)),
+            Err(WrrError::Poisoned) => panic!("Read access acquired failed"),
+            Err(_) => {
+                self.done = true;
+                None
+            }
+        }
+    }
+}
+
+impl<T: Member> FusedIterator for Leases<'_, T> {}
@@ -1,18 +1,329 @@
-#![doc = include_str!("../README.md")]
+// the example of the readme runs on the async queue, missing from the synchronous-only flavors
+#![cfg_attr(any(wrr_async, wrr_async_wrapper), doc = include_str!("../README.md"))]
+// tests run on the host, linking std whatever the flavor
+#![cfg_attr(all(not(wrr_std), not(test)), no_std)]
 
+extern crate alloc;
+
+#[cfg(wrr_std)]
 mod wrr_queue;
 
+#[cfg(any(wrr_async, wrr_async_wrapper))]
+mod async_queue;
+
+#[cfg(any(wrr_async, wrr_async_wrapper))]
+mod attempt;
+
 mod instance;
 
+#[cfg(wrr_std)]
+mod config;
+
+#[cfg(not(wrr_std))]
+mod core_queue;
+
+#[cfg(all(feature = "consul", wrr_async))]
+mod consul;
+
+#[cfg(wrr_std)]
+mod env;
+
+mod error;
+
+#[cfg(feature = "tokio")]
+mod events;
+
+#[cfg(all(feature = "etcd", wrr_async))]
+mod etcd;
+
+#[cfg(all(feature = "eureka", wrr_async))]
+mod eureka;
+
+#[cfg(wrr_std)]
+mod expiry;
+
+#[cfg(feature = "config-file")]
+mod file;
+
+#[cfg(feature = "serde")]
+mod format;
+
+#[cfg(feature = "ffi")]
+pub mod ffi;
+
+#[cfg(wrr_std)]
+mod cursor;
+
+#[cfg(wrr_discovery)]
+mod discover;
+
+#[cfg(all(feature = "dns", wrr_async))]
+mod dns;
+
+#[cfg(wrr_std)]
+mod drain;
+
+#[cfg_attr(not(wrr_std), allow(dead_code))]
+mod engine;
+
+#[cfg(wrr_std)]
+mod grouped;
+
+#[cfg(all(feature = "tokio", wrr_async))]
+mod health;
+
+#[cfg(all(feature = "hash", wrr_std))]
+mod index;
+
+#[cfg(all(feature = "http-probe", wrr_async))]
+mod http_probe;
+
+#[cfg(wrr_std)]
+mod hooks;
+
+#[cfg(wrr_std)]
+mod in_flight;
+
+#[cfg(wrr_std)]
+mod lease;
+
+mod lock;
+
+#[cfg(all(feature = "nacos", wrr_async))]
+mod nacos;
+
+#[cfg(feature = "tokio")]
+mod membership;
+
+#[cfg(wrr_std)]
+mod outlier;
+
+#[cfg(wrr_std)]
+mod priority;
+
+#[cfg(feature = "python")]
+pub mod python;
+
+#[cfg(wrr_std)]
+mod qos;
+
+#[cfg(all(feature = "redis", wrr_async))]
+mod redis_cursor;
+
+#[cfg(all(feature = "redis", wrr_async))]
+mod redis_membership;
+
+#[cfg(wrr_std)]
+mod ring;
+
+#[cfg(any(wrr_async, wrr_async_wrapper))]
+mod runtime;
+
+mod schedule;
+
+#[cfg(wrr_sync)]
+mod selections;
+
+#[cfg(wrr_std)]
+mod shift;
+
+#[cfg(wrr_std)]
+mod snapshot;
+
+#[cfg(all(feature = "srv", wrr_async))]
+mod srv;
+
+#[cfg(wrr_std)]
+mod state;
+
+#[cfg(wrr_std)]
+mod split;
+
+#[cfg(all(feature = "stream", any(wrr_async, wrr_async_wrapper)))]
+mod stream;
+
+#[cfg(wrr_std)]
+mod subset;
+
+#[cfg(all(feature = "tcp-probe", wrr_async))]
+mod tcp_probe;
+
+#[cfg(wrr_std)]
+mod sync;
+
+#[cfg(wrr_std)]
+mod task_queue;
+
+#[cfg(wrr_std)]
+mod time;
+
+#[cfg_attr(not(wrr_std), allow(dead_code))]
+mod trace;
+
+#[cfg(wrr_std)]
+mod traffic_split;
+
+#[cfg(wrr_std)]
+mod update;
+
+#[cfg(all(feature = "watch", wrr_async))]
+mod watch;
+
+#[cfg(all(feature = "tokio", wrr_async))]
+mod background;
+
+#[cfg(all(feature = "zookeeper", wrr_async))]
+mod zookeeper;
+
+#[cfg_attr(not(wrr_std), allow(dead_code))]
 pub(crate) mod consts;
 
-#[cfg(all(feature = "tokio", feature = "blocking"))]
+#[cfg(all(feature = "tokio", target_arch = "wasm32", target_os = "unknown"))]
+compile_error!(
+    "feature 'tokio' is not supported on wasm32-unknown-unknown, consider 'async-lock' or 'blocking'"
+);
+
+#[cfg(all(
+    feature = "rayon",
+    target_arch = "wasm32",
+    target_os = "unknown",
+    not(target_feature = "atomics")
+))]
+compile_error!(
+    "feature 'rayon' needs threads, which wasm32-unknown-unknown does not have without 'atomics'"
+);
+
+#[cfg(all(feature = "async-std", feature = "async-lock"))]
+compile_error!("feature 'async-std' and 'async-lock' cannot be enabled together");
+
+#[cfg(not(any(
+    feature = "tokio",
+    feature = "async-std",
+    feature = "async-lock",
+    feature = "blocking",
+    feature = "local",
+    feature = "no_std"
+)))]
+compile_error!(
+    "feature 'tokio', 'async-std', 'async-lock', 'blocking', 'local' or 'no_std' must be enabled"
+);
+
+#[cfg(all(
+    feature = "no_std",
+    any(
+        feature = "tokio",
+        feature = "async-std",
+        feature = "async-lock",
+        feature = "blocking",
+        feature = "local",
+        feature = "stream",
+        feature = "ffi",
+        feature = "tracing",
+        feature = "serde",
+        feature = "arc-swap",
+        feature = "rayon",
+        feature = "parking_lot",
+        feature = "bandit"
+    )
+))]
+compile_error!(
+    "feature 'no_std' only goes along with 'log', 'hash' or 'smallvec', build it with default-features = false"
+);
+
+#[cfg(all(feature = "local", feature = "blocking"))]
+compile_error!("feature 'local' and 'blocking' cannot be enabled together");
+
+#[cfg(all(feature = "local", any(feature = "arc-swap", feature = "parking_lot")))]
 compile_error!(
-    "feature 'tokio' and 'blocking' cannot be enabled together, consider disable default features"
+    "feature 'local' has no atomics nor locks to swap, 'arc-swap' and 'parking_lot' do not apply"
 );
 
-#[cfg(not(any(feature = "tokio", feature = "blocking")))]
-compile_error!("feature 'tokio' or 'blocking' must be enabled");
+#[cfg(all(
+    wrr_loom,
+    any(wrr_async, wrr_async_wrapper, feature = "arc-swap", feature = "local")
+))]
+compile_error!("loom model checking only drives the 'blocking' feature, without 'arc-swap'");
 
-pub use instance::Instance;
+#[cfg(any(wrr_async, wrr_async_wrapper))]
+pub use async_queue::AsyncWrrQueue;
+#[cfg(all(feature = "tokio", wrr_async))]
+pub use background::BackgroundWriter;
+#[cfg(wrr_std)]
+pub use config::WrrConfig;
+#[cfg(all(feature = "consul", wrr_async))]
+pub use consul::{ConsulDiscovery, ConsulWatcher};
+#[cfg(not(wrr_std))]
+pub use core_queue::WrrQueue;
+#[cfg(wrr_discovery)]
+pub use discover::{Change, Discover, DiscoverWatcher};
+#[cfg(all(feature = "dns", wrr_async))]
+pub use dns::{DnsDiscovery, DnsWatcher};
+#[cfg(wrr_std)]
+pub use drain::Drain;
+pub use engine::Engine;
+#[cfg(wrr_std)]
+pub use env::EnvConfigError;
+pub use error::{RetryError, WrrError};
+#[cfg(all(feature = "etcd", wrr_async))]
+pub use etcd::{EtcdDiscovery, EtcdWatcher};
+#[cfg(all(feature = "eureka", wrr_async))]
+pub use eureka::{EurekaDiscovery, EurekaWatcher};
+#[cfg(feature = "tokio")]
+pub use events::QueueEvent;
+#[cfg(wrr_std)]
+pub use expiry::Expiry;
+#[cfg(feature = "config-file")]
+pub use file::ConfigError;
+#[cfg(wrr_std)]
+pub use grouped::GroupedWrrQueue;
+#[cfg(all(feature = "tokio", wrr_async))]
+pub use health::{HealthCheck, HealthChecker, HealthProbe};
+#[cfg(wrr_std)]
+pub use hooks::Hooks;
+#[cfg(all(feature = "http-probe", wrr_async))]
+pub use http_probe::HttpProbe;
+#[cfg(wrr_std)]
+pub use in_flight::InFlight;
+pub use instance::{Instance, Member};
+#[cfg(wrr_std)]
+pub use lease::{Lease, Leases};
+#[cfg(feature = "tokio")]
+pub use membership::Membership;
+#[cfg(all(feature = "nacos", wrr_async))]
+pub use nacos::{NacosDiscovery, NacosWatcher};
+#[cfg(wrr_std)]
+pub use outlier::OutlierDetection;
+#[cfg(wrr_std)]
+pub use priority::PriorityQueue;
+#[cfg(all(feature = "redis", wrr_async))]
+pub use redis_cursor::RedisCursor;
+#[cfg(all(feature = "redis", wrr_async))]
+pub use redis_membership::{RedisMembership, RedisWatcher};
+#[cfg(wrr_sync)]
+pub use selections::Selections;
+#[cfg(wrr_std)]
+pub use snapshot::{MemberSnapshot, QueueSnapshot};
+#[cfg(wrr_std)]
+pub use split::{Reader, Selected, Writer};
+#[cfg(all(feature = "srv", wrr_async))]
+pub use srv::{SrvDiscovery, SrvTarget, SrvWatcher};
+#[cfg(wrr_std)]
+pub use state::{Health, HealthSummary, InstanceStats};
+#[cfg(all(feature = "stream", any(wrr_async, wrr_async_wrapper)))]
+pub use stream::SelectStream;
+#[cfg(wrr_std)]
+pub use subset::SubsetWrrQueue;
+#[cfg(wrr_std)]
+pub use task_queue::WrrTaskQueue;
+#[cfg(all(feature = "tcp-probe", wrr_async))]
+pub use tcp_probe::TcpProbe;
+#[cfg(wrr_std)]
+pub use traffic_split::TrafficSplit;
+#[cfg(wrr_std)]
+pub use update::Update;
+#[cfg(all(feature = "watch", wrr_async))]
+pub use watch::ConfigWatcher;
+#[cfg(wrr_std)]
 pub use wrr_queue::WrrQueue;
+#[cfg(all(feature = "zookeeper", wrr_async))]
+pub use zookeeper::{ZooKeeperDiscovery, ZooKeeperWatcher};
@@ -0,0 +1,124 @@
+//! lock guarding the schedule, picked by the flavor features
+//!
+//! the schedule is only ever replaced through `&mut WrrQueue`, so a reader never waits on a writer,
+//! and an atomically swapped snapshot would gain nothing here: `arc-swap` only applies where
+//! readers and the writer do run concurrently, to the snapshots published by a split queue.
+//! Selection and recalculation are written once against [`Lock`], the async and blocking methods
+//! only differ by their signature, and a new flavor only needs to implement [`Lock`] for its lock
+
+use crate::error::WrrError;
+use crate::schedule::Schedule;
+#[cfg(wrr_sync)]
+use std::sync::PoisonError;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// lock over the schedule, sealed to the ones the flavors use
+pub trait Lock: sealed::Sealed {
+    type Guard<'a>: core::ops::Deref<Target = Schedule>
+    where
+        Self: 'a;
+
+    fn new(schedule: Schedule) -> Self;
+
+    /// read access, never waiting as writes go through `&mut self`
+    fn read(&self) -> Result<Self::Guard<'_>, WrrError>;
+
+    /// store `schedule`, returning the previous one
+    fn replace(&mut self, schedule: Schedule) -> Schedule;
+
+    /// empty the schedule, keeping its allocated capacity
+    fn clear(&mut self);
+}
+
+#[cfg(wrr_async)]
+pub(crate) type ScheduleLock = crate::runtime::RwLock<Schedule>;
+#[cfg(wrr_sync)]
+pub(crate) type ScheduleLock = crate::sync::RwLock<Schedule>;
+#[cfg(not(wrr_std))]
+pub(crate) type ScheduleLock = spin::RwLock<Schedule>;
+
+#[cfg(wrr_async)]
+impl sealed::Sealed for crate::runtime::RwLock<Schedule> {}
+
+#[cfg(wrr_async)]
+impl Lock for crate::runtime::RwLock<Schedule> {
+    type Guard<'a> = crate::runtime::RwLockReadGuard<'a, Schedule>;
+
+    fn new(schedule: Schedule) -> Self {
+        crate::runtime::RwLock::new(schedule)
+    }
+
+    fn read(&self) -> Result<Self::Guard<'_>, WrrError> {
+        Ok(crate::runtime::try_read(self).expect("schedule is only written through &mut self"))
+    }
+
+    fn replace(&mut self, schedule: Schedule) -> Schedule {
+        core::mem::replace(self.get_mut(), schedule)
+    }
+
+    fn clear(&mut self) {
+        self.get_mut().clear();
+    }
+}
+
+#[cfg(wrr_sync)]
+impl sealed::Sealed for crate::sync::RwLock<Schedule> {}
+
+// loom locks have no `get_mut`, so writes take the lock, which is never contended
+#[cfg(wrr_sync)]
+impl Lock for crate::sync::RwLock<Schedule> {
+    type Guard<'a> = crate::sync::RwLockReadGuard<'a, Schedule>;
+
+    fn new(schedule: Schedule) -> Self {
+        crate::sync::RwLock::new(schedule)
+    }
+
+    fn read(&self) -> Result<Self::Guard<'_>, WrrError> {
+        crate::sync::RwLock::read(self).map_err(|_| WrrError::Poisoned)
+    }
+
+    // the schedule is fully rebuilt, so a poisoned lock is safe to recover
+    fn replace(&mut self, schedule: Schedule) -> Schedule {
+        let previous = core::mem::replace(
+            &mut *self.write().unwrap_or_else(PoisonError::into_inner),
+            schedule,
+        );
+        #[cfg(not(wrr_loom))]
+        self.clear_poison();
+        previous
+    }
+
+    fn clear(&mut self) {
+        self.write().unwrap_or_else(PoisonError::into_inner).clear();
+        #[cfg(not(wrr_loom))]
+        self.clear_poison();
+    }
+}
+
+#[cfg(not(wrr_std))]
+impl sealed::Sealed for spin::RwLock<Schedule> {}
+
+// a spin lock is never poisoned, and never spins as writes go through `&mut self`
+#[cfg(not(wrr_std))]
+impl Lock for spin::RwLock<Schedule> {
+    type Guard<'a> = spin::RwLockReadGuard<'a, Schedule>;
+
+    fn new(schedule: Schedule) -> Self {
+        spin::RwLock::new(schedule)
+    }
+
+    fn read(&self) -> Result<Self::Guard<'_>, WrrError> {
+        Ok(spin::RwLock::read(self))
+    }
+
+    fn replace(&mut self, schedule: Schedule) -> Schedule {
+        core::mem::replace(self.get_mut(), schedule)
+    }
+
+    fn clear(&mut self) {
+        self.get_mut().clear();
+    }
+}
@@ -0,0 +1,45 @@
+/// generation of the members of a queue, along with a summary of them
+///
+/// published on a `tokio::sync::watch` channel on each change, see [`WrrQueue::watch_membership`]
+/// and [`Reader::watch_membership`], so that dependent components can react to topology updates
+/// without polling the queue. Generations only ever increase, a receiver lagging behind
+/// skips the intermediate ones
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::WrrQueue;
+///
+/// let mut queue: WrrQueue<&str> = WrrQueue::new();
+/// let membership = queue.watch_membership();
+/// assert_eq!(0, membership.borrow().generation());
+/// ```
+///
+/// [`WrrQueue::watch_membership`]: crate::WrrQueue::watch_membership
+/// [`Reader::watch_membership`]: crate::Reader::watch_membership
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct Membership {
+    pub(crate) generation: u64,
+    pub(crate) len: usize,
+    pub(crate) in_rotation: usize,
+}
+
+impl Membership {
+    /// number of changes since the channel was created
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// number of members, in rotation or not
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// number of members selected from, neither down, paused, draining, ejected nor idle standby
+    pub fn in_rotation(&self) -> usize {
+        self.in_rotation
+    }
+}
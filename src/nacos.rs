@@ -0,0 +1,192 @@
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use serde::Deserialize;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// scale of the weights of Nacos, floats with two meaningful decimals, into instance weights
+const WEIGHT_SCALE: f64 = 100.0;
+
+/// members of a queue fed from the instances of a Nacos service, polled periodically
+///
+/// only instances enabled, healthy and with a positive weight are members, each one a host
+/// and port. Nacos weights are floats, an instance of weight `1.5` gets the weight `150`,
+/// so that fractions keep their share. A failed poll leaves the members as they are.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{NacosDiscovery, WrrQueue};
+/// use std::time::Duration;
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = NacosDiscovery::new("http://127.0.0.1:8848", "backend")
+///     .namespace("prod")
+///     .interval(Duration::from_secs(5))
+///     .spawn(writer);
+/// let (host, port) = reader.select().await.unwrap().data().clone();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct NacosDiscovery {
+    address: String,
+    service: String,
+    group: String,
+    namespace: Option<String>,
+    clusters: Vec<String>,
+    access_token: Option<String>,
+    interval: Duration,
+}
+
+/// handle of a running [`NacosDiscovery`], the polling stops once it is dropped
+pub struct NacosWatcher {
+    reader: Reader<(String, u16)>,
+    task: JoinHandle<()>,
+}
+
+/// answer of `/nacos/v1/ns/instance/list`, only the fields used
+#[derive(Deserialize)]
+struct ServiceInfo {
+    #[serde(default)]
+    hosts: Vec<NacosInstance>,
+}
+
+#[derive(Deserialize)]
+struct NacosInstance {
+    ip: String,
+    port: u16,
+    weight: f64,
+    healthy: bool,
+    enabled: bool,
+}
+
+impl NacosDiscovery {
+    /// poll `service` of the `DEFAULT_GROUP` on the server at `address`, e.g. `http://127.0.0.1:8848`,
+    /// every 10 seconds
+    pub fn new(address: impl Into<String>, service: impl Into<String>) -> Self {
+        NacosDiscovery {
+            address: address.into().trim_end_matches('/').to_string(),
+            service: service.into(),
+            group: "DEFAULT_GROUP".to_string(),
+            namespace: None,
+            clusters: Vec::new(),
+            access_token: None,
+            interval: Duration::from_secs(10),
+        }
+    }
+
+    /// look the service up in `group`
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = group.into();
+        self
+    }
+
+    /// look the service up in the namespace of id `namespace`, the public one by default
+    pub fn namespace(mut self, namespace: impl Into<String>) -> Self {
+        self.namespace = Some(namespace.into());
+        self
+    }
+
+    /// only keep the instances of `cluster`, may be called for several clusters
+    pub fn cluster(mut self, cluster: impl Into<String>) -> Self {
+        self.clusters.push(cluster.into());
+        self
+    }
+
+    /// authenticate with `token`, as returned by the login api of Nacos
+    pub fn access_token(mut self, token: impl Into<String>) -> Self {
+        self.access_token = Some(token.into());
+        self
+    }
+
+    /// poll the service once per `interval`
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// start polling into `writer`, right away and then once per interval
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<(String, u16)>) -> NacosWatcher {
+        NacosWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<(String, u16)>) {
+        let client = reqwest::Client::new();
+        loop {
+            match self.poll(&client).await {
+                Ok(instances) => {
+                    if writer.pending.reconcile_uncalculated(instances) {
+                        writer.publish().await;
+                    }
+                }
+                Err(e) => trace::discovery_failed(&self.service, &e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    /// instances of the service currently taking traffic
+    async fn poll(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<Vec<Instance<(String, u16)>>, reqwest::Error> {
+        let url = format!("{}/nacos/v1/ns/instance/list", self.address);
+        let mut query = vec![
+            ("serviceName", self.service.clone()),
+            ("groupName", self.group.clone()),
+            ("clusters", self.clusters.join(",")),
+        ];
+        if let Some(namespace) = &self.namespace {
+            query.push(("namespaceId", namespace.clone()));
+        }
+        if let Some(token) = &self.access_token {
+            query.push(("accessToken", token.clone()));
+        }
+        let info: ServiceInfo = client
+            .get(url)
+            .query(&query)
+            .timeout(self.interval.max(Duration::from_secs(1)))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut instances: Vec<Instance<(String, u16)>> = Vec::new();
+        for host in info.hosts {
+            if !host.enabled || !host.healthy {
+                continue;
+            }
+            let Some(weight) = NonZeroUsize::new((host.weight * WEIGHT_SCALE).round() as usize)
+            else {
+                continue;
+            };
+            let instance = Instance::new_with_weight((host.ip, host.port), weight);
+            if !instances.iter().any(|x| x.data() == instance.data()) {
+                instances.push(instance);
+            }
+        }
+        Ok(instances)
+    }
+}
+
+impl NacosWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<(String, u16)> {
+        self.reader.clone()
+    }
+
+    /// stop polling, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for NacosWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
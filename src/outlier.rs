@@ -0,0 +1,111 @@
+use crate::consts;
+use crate::time::Instant;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// passive outlier detection, ejecting instances out of rotation from the outcomes reported
+///
+/// outcomes are those recorded by e.g. [`WrrQueue::run_on`](crate::WrrQueue::run_on)
+/// or [`WrrQueue::report_failure`](crate::WrrQueue::report_failure). An ejected instance is
+/// restored on its own once the ejection time is over, with a clean record.
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{OutlierDetection, WrrQueue};
+/// use std::time::Duration;
+///
+/// let detection = OutlierDetection::new(Duration::from_secs(30))
+///     .consecutive_failures(5)
+///     .failure_rate(0.5, 20);
+/// let queue: WrrQueue<&str> = WrrQueue::new().outlier_detection(detection);
+/// ```
+#[derive(PartialEq, Debug, Clone)]
+pub struct OutlierDetection {
+    ejection: Duration,
+    consecutive_failures: Option<usize>,
+    failure_rate: Option<(f64, usize)>,
+}
+
+impl OutlierDetection {
+    /// eject instances for `ejection`, set what triggers an ejection with the other methods
+    pub fn new(ejection: Duration) -> Self {
+        OutlierDetection {
+            ejection,
+            consecutive_failures: None,
+            failure_rate: None,
+        }
+    }
+
+    /// eject an instance after `failures` consecutive failed requests
+    pub fn consecutive_failures(mut self, failures: usize) -> Self {
+        self.consecutive_failures = Some(failures.max(1));
+        self
+    }
+
+    /// eject an instance once more than `rate` of its last `window` requests failed, from `0` to `1`
+    pub fn failure_rate(mut self, rate: f64, window: usize) -> Self {
+        self.failure_rate = Some((rate.clamp(0.0, 1.0), window.max(1)));
+        self
+    }
+}
+
+/// recent outcomes of an instance, and the end of its ejection
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Outcomes {
+    window: VecDeque<bool>,
+    ejected_until: Option<Instant>,
+    /// exponentially weighted moving average of the failures, from `0` to `1`
+    error_rate: f64,
+}
+
+impl Outcomes {
+    /// fold an outcome into the moving error rate
+    pub(crate) fn observe(&mut self, success: bool) {
+        let sample = if success { 0.0 } else { 1.0 };
+        self.error_rate += (sample - self.error_rate) * consts::ERROR_RATE_SMOOTHING;
+    }
+
+    pub(crate) fn error_rate(&self) -> f64 {
+        self.error_rate
+    }
+
+    /// record an outcome following `consecutive_failures` failures in a row,
+    /// true if it ejects the instance
+    pub(crate) fn record(
+        &mut self,
+        success: bool,
+        consecutive_failures: usize,
+        detection: &OutlierDetection,
+    ) -> bool {
+        let now = Instant::now();
+        if self.ejected_until.is_some_and(|until| now < until) {
+            return false;
+        }
+        let mut eject = detection
+            .consecutive_failures
+            .is_some_and(|failures| consecutive_failures >= failures);
+        if let Some((rate, window)) = detection.failure_rate {
+            self.window.push_back(success);
+            while self.window.len() > window {
+                self.window.pop_front();
+            }
+            let failures = self.window.iter().filter(|success| !**success).count();
+            eject |= self.window.len() == window && failures as f64 > rate * window as f64;
+        }
+        if eject {
+            self.window.clear();
+            self.ejected_until = Some(now + detection.ejection);
+        }
+        eject
+    }
+
+    /// end of the ejection in progress at `now`, if any
+    pub(crate) fn ejected_until(&self, now: Instant) -> Option<Instant> {
+        self.ejected_until.filter(|until| now < *until)
+    }
+
+    /// end of the last ejection over at `now`, if any
+    pub(crate) fn restored_at(&self, now: Instant) -> Option<Instant> {
+        self.ejected_until.filter(|until| now >= *until)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::instance::{Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::collections::BTreeMap;
+
+/// instances split into priority tiers, each one a [`WrrQueue`]
+///
+/// selection uses the tier of the highest priority (the lowest number) holding an instance,
+/// lower tiers only receive traffic once every tier above them is empty.
+/// The standard primary/backup pattern: primaries at priority `0`, backups at `1`
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::PriorityQueue;
+///
+/// let mut queue = PriorityQueue::new();
+/// queue.insert(0, ("primary", 1usize)).await;
+/// queue.insert(1, ("backup", 1usize)).await;
+/// assert_eq!(&"primary", queue.select().await.unwrap().data());
+///
+/// queue.delete_instance(0, ("primary", 1usize).try_into().unwrap()).await;
+/// assert_eq!(&"backup", queue.select().await.unwrap().data());
+/// ```
+pub struct PriorityQueue<T: Member> {
+    tiers: BTreeMap<u32, WrrQueue<T>>,
+}
+
+impl<T: Member> Default for PriorityQueue<T> {
+    fn default() -> Self {
+        PriorityQueue {
+            tiers: BTreeMap::new(),
+        }
+    }
+}
+
+impl<T: Member> PriorityQueue<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// use `queue` as the tier of `priority`, e.g. to pick its engine, returning the replaced tier
+    pub fn insert_tier(&mut self, priority: u32, queue: WrrQueue<T>) -> Option<WrrQueue<T>> {
+        self.tiers.insert(priority, queue)
+    }
+
+    /// the tier of `priority`, None if nothing was ever inserted at it
+    pub fn tier(&self, priority: u32) -> Option<&WrrQueue<T>> {
+        self.tiers.get(&priority)
+    }
+
+    /// the tier of `priority`, for changes not covered by the tiered methods
+    pub fn tier_mut(&mut self, priority: u32) -> Option<&mut WrrQueue<T>> {
+        self.tiers.get_mut(&priority)
+    }
+
+    /// priority of the tier selections currently go to, None if every tier is empty
+    pub fn active_priority(&self) -> Option<u32> {
+        self.tiers
+            .iter()
+            .find(|(_, tier)| !tier.is_empty())
+            .map(|(priority, _)| *priority)
+    }
+
+    fn active_tier(&mut self) -> Option<&mut WrrQueue<T>> {
+        self.tiers.values_mut().find(|tier| !tier.is_empty())
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member> PriorityQueue<T> {
+    /// insert a new instance in the tier of `priority`, and re-calculate its queue
+    pub async fn insert(&mut self, priority: u32, instance: impl TryInto<Instance<T>>) -> bool {
+        self.tiers
+            .entry(priority)
+            .or_default()
+            .insert(instance)
+            .await
+    }
+
+    /// delete certain instance from the tier of `priority`
+    pub async fn delete_instance(&mut self, priority: u32, instance: Instance<T>) -> bool {
+        match self.tiers.get_mut(&priority) {
+            Some(tier) => tier.delete_instance(instance).await,
+            None => false,
+        }
+    }
+
+    /// return the instance selected in the highest priority tier holding one, None if all are empty
+    pub async fn select(&mut self) -> Option<&Instance<T>> {
+        self.active_tier()?.select().await
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member> PriorityQueue<T> {
+    /// insert a new instance in the tier of `priority`, and re-calculate its queue
+    pub fn insert(&mut self, priority: u32, instance: impl TryInto<Instance<T>>) -> bool {
+        self.tiers.entry(priority).or_default().insert(instance)
+    }
+
+    /// delete certain instance from the tier of `priority`
+    pub fn delete_instance(&mut self, priority: u32, instance: Instance<T>) -> bool {
+        match self.tiers.get_mut(&priority) {
+            Some(tier) => tier.delete_instance(instance),
+            None => false,
+        }
+    }
+
+    /// return the instance selected in the highest priority tier holding one, None if all are empty
+    pub fn select(&mut self) -> Option<&Instance<T>> {
+        self.active_tier()?.select()
+    }
+}
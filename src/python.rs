@@ -0,0 +1,159 @@
+//! python bindings of the blocking queue, built into the `async_wrr_queue` module with maturin
+//!
+//! ```python
+//! from async_wrr_queue import Instance, WrrQueue
+//!
+//! queue = WrrQueue()
+//! queue.insert_many([Instance("a", 1), Instance("b", 2)])
+//! assert queue.select().data == "b"
+//! ```
+//!
+//! members are python objects compared with `==`, and hashed with `hash` under the `hash` feature.
+//! Selecting never calls into python, only membership changes do
+
+use crate::error::WrrError;
+use crate::instance::Instance;
+use crate::wrr_queue::WrrQueue;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+#[cfg(feature = "hash")]
+use std::hash::{Hash, Hasher};
+
+/// python object held by the queue
+pub struct PyMember(Py<PyAny>);
+
+impl PartialEq for PyMember {
+    // an `==` raising an exception is taken as not equal
+    fn eq(&self, other: &Self) -> bool {
+        Python::with_gil(|py| self.0.bind(py).eq(other.0.bind(py)).unwrap_or(false))
+    }
+}
+
+#[cfg(feature = "hash")]
+impl Eq for PyMember {}
+
+#[cfg(feature = "hash")]
+impl Hash for PyMember {
+    // unhashable objects all land in the same bucket, and are told apart by `==`
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        Python::with_gil(|py| self.0.bind(py).hash().unwrap_or(0)).hash(state);
+    }
+}
+
+/// python `Instance`: any object along with its weight
+#[pyclass(name = "Instance", module = "async_wrr_queue", frozen)]
+pub struct PyInstance {
+    data: Py<PyAny>,
+    weight: usize,
+}
+
+#[pymethods]
+impl PyInstance {
+    #[new]
+    #[pyo3(signature = (data, weight = 1))]
+    fn new(data: Py<PyAny>, weight: usize) -> PyResult<Self> {
+        if weight == 0 {
+            return Err(PyValueError::new_err(WrrError::ZeroWeight.to_string()));
+        }
+        Ok(PyInstance { data, weight })
+    }
+
+    #[getter]
+    fn data(&self, py: Python<'_>) -> Py<PyAny> {
+        self.data.clone_ref(py)
+    }
+
+    #[getter]
+    fn weight(&self) -> usize {
+        self.weight
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> PyResult<String> {
+        Ok(format!(
+            "Instance({}, {})",
+            self.data.bind(py).repr()?,
+            self.weight
+        ))
+    }
+}
+
+impl PyInstance {
+    fn to_instance(&self, py: Python<'_>) -> Instance<PyMember> {
+        Instance::try_new_with_weight(PyMember(self.data.clone_ref(py)), self.weight)
+            .expect("weight checked on creation")
+    }
+
+    fn from_instance(py: Python<'_>, instance: &Instance<PyMember>) -> Self {
+        PyInstance {
+            data: instance.data().0.clone_ref(py),
+            weight: instance.weight().get(),
+        }
+    }
+}
+
+/// python `WrrQueue`, the blocking [`WrrQueue`]
+#[pyclass(name = "WrrQueue", module = "async_wrr_queue")]
+pub struct PyWrrQueue {
+    queue: WrrQueue<PyMember>,
+}
+
+#[pymethods]
+impl PyWrrQueue {
+    #[new]
+    fn new() -> Self {
+        PyWrrQueue {
+            queue: WrrQueue::new(),
+        }
+    }
+
+    /// insert a new instance, and re-calculate request queue
+    fn insert(&mut self, py: Python<'_>, instance: &Bound<'_, PyInstance>) -> bool {
+        self.queue.insert(instance.get().to_instance(py))
+    }
+
+    /// insert a list of instances, and re-calculate request queue once
+    fn insert_many(&mut self, py: Python<'_>, instances: Vec<Bound<'_, PyInstance>>) -> bool {
+        let instances: Vec<_> = instances
+            .iter()
+            .map(|instance| instance.get().to_instance(py))
+            .collect();
+        self.queue.insert_many(instances)
+    }
+
+    /// delete certain instance
+    fn delete_instance(&mut self, py: Python<'_>, instance: &Bound<'_, PyInstance>) -> bool {
+        self.queue.delete_instance(instance.get().to_instance(py))
+    }
+
+    /// return the selected instance, None if no instance is up
+    fn select(&mut self, py: Python<'_>) -> Option<PyInstance> {
+        let selected = self.queue.select()?;
+        Some(PyInstance::from_instance(py, selected))
+    }
+
+    /// take the instance holding `data` out of rotation, false if it is not in the queue
+    fn mark_down(&mut self, data: Py<PyAny>) -> bool {
+        self.queue.mark_down(&PyMember(data))
+    }
+
+    /// put the instance holding `data` back in rotation, false if it is not in the queue
+    fn mark_up(&mut self, data: Py<PyAny>) -> bool {
+        self.queue.mark_up(&PyMember(data))
+    }
+
+    /// clear instance in the queue
+    fn clear_instance(&mut self) {
+        self.queue.clear_instance();
+    }
+
+    fn __len__(&self) -> usize {
+        self.queue.len()
+    }
+}
+
+#[pymodule]
+fn async_wrr_queue(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyInstance>()?;
+    m.add_class::<PyWrrQueue>()?;
+    Ok(())
+}
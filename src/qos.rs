@@ -0,0 +1,55 @@
+use crate::consts::InlineVec;
+use crate::engine;
+use crate::instance::{Instance, Member};
+
+/// named traffic class, selecting over the shared members with its own weight multipliers
+#[derive(Debug)]
+pub(crate) struct QosClass<T: Member> {
+    pub(crate) name: String,
+    multipliers: Vec<(T, f64)>,
+    cur_weight: InlineVec<i128>,
+}
+
+impl<T: Member> QosClass<T> {
+    pub(crate) fn new(name: &str, multipliers: impl IntoIterator<Item = (T, f64)>) -> Self {
+        QosClass {
+            name: name.to_string(),
+            multipliers: multipliers.into_iter().collect(),
+            cur_weight: InlineVec::new(),
+        }
+    }
+
+    /// weight of `instance` in this class, `0` keeps the class off the instance
+    fn weight_of(&self, instance: &Instance<T>) -> usize {
+        let multiplier = self
+            .multipliers
+            .iter()
+            .find(|(data, _)| data == instance.data())
+            .map_or(1.0, |(_, multiplier)| multiplier.max(0.0));
+        (instance.weight().get() as f64 * multiplier).round() as usize
+    }
+
+    /// next smooth weighted round-robin pick over the class weights, leaving `skipped` out,
+    /// starting the cycle over when the number of members changes
+    pub(crate) fn select(
+        &mut self,
+        instance_list: &[Instance<T>],
+        skipped: &[usize],
+    ) -> Option<usize> {
+        let weight_vec: InlineVec<usize> = instance_list
+            .iter()
+            .enumerate()
+            .map(|(i, instance)| {
+                if skipped.contains(&i) {
+                    0
+                } else {
+                    self.weight_of(instance)
+                }
+            })
+            .collect();
+        if self.cur_weight.len() != weight_vec.len() {
+            self.cur_weight = engine::initial_weights(&weight_vec);
+        }
+        engine::smooth_select_weights(&mut self.cur_weight, |i| weight_vec[i])
+    }
+}
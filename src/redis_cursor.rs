@@ -0,0 +1,167 @@
+use crate::consts;
+use crate::trace;
+use redis::AsyncCommands;
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
+use tokio::sync::Notify;
+
+/// selection cursor shared by the replicas of a queue through a Redis counter
+///
+/// replicas lease ranges of `batch` consecutive positions with an `INCRBY` of the counter,
+/// and walk the schedule from each position of their lease, so that together they produce
+/// the exact weighted sequence of a single queue rather than one sequence each.
+/// A replica leases its next range ahead of time; should it run out of positions meanwhile,
+/// it keeps counting past its last lease, the sequence being exact again with the next lease.
+///
+/// every replica must hold the same members in the same order, e.g. from [`RedisMembership`](crate::RedisMembership).
+/// The larger the batch, the fewer round trips, but the longer the runs of a single replica
+/// within the fleet-wide sequence.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{RedisCursor, WrrQueue};
+///
+/// let queue = WrrQueue::new().redis_cursor(RedisCursor::new("redis://127.0.0.1/", "wrr:cursor").batch(32));
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RedisCursor {
+    url: String,
+    key: String,
+    batch: u64,
+}
+
+impl RedisCursor {
+    /// lease positions from the counter at `key` on the server at `url`, e.g. `redis://127.0.0.1/`
+    pub fn new(url: impl Into<String>, key: impl Into<String>) -> Self {
+        RedisCursor {
+            url: url.into(),
+            key: key.into(),
+            batch: consts::DEFAULT_CURSOR_BATCH,
+        }
+    }
+
+    /// positions leased at once, 64 by default, `0` is taken as `1`
+    pub fn batch(mut self, batch: u64) -> Self {
+        self.batch = batch.max(1);
+        self
+    }
+
+    /// start leasing positions in a background task
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub(crate) fn spawn(self) -> Arc<CursorLease> {
+        let lease = Arc::new(CursorLease {
+            state: Mutex::new(LeaseState::default()),
+            refill: Arc::new(Notify::new()),
+        });
+        let refill = lease.refill.clone();
+        tokio::spawn(self.run(Arc::downgrade(&lease), refill));
+        lease
+    }
+
+    async fn run(self, lease: Weak<CursorLease>, refill: Arc<Notify>) {
+        let mut connection = None;
+        loop {
+            // the task ends along with the last cursor holding the lease
+            let Some(current) = lease.upgrade() else {
+                return;
+            };
+            if !current.wants_refill() {
+                drop(current);
+                refill.notified().await;
+                continue;
+            }
+            match self.lease(&mut connection).await {
+                Ok(range) => current.push(range),
+                Err(e) => {
+                    trace::lease_failed(&self.key, &e);
+                    connection = None;
+                    drop(current);
+                    tokio::time::sleep(consts::DISCOVERY_RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+
+    async fn lease(
+        &self,
+        connection: &mut Option<redis::aio::MultiplexedConnection>,
+    ) -> redis::RedisResult<Range<u64>> {
+        let connection = match connection {
+            Some(connection) => connection,
+            None => {
+                let client = redis::Client::open(self.url.as_str())?;
+                connection.insert(client.get_multiplexed_async_connection().await?)
+            }
+        };
+        let end: u64 = connection.incr(&self.key, self.batch).await?;
+        Ok(end.saturating_sub(self.batch)..end)
+    }
+}
+
+/// positions leased from a [`RedisCursor`], the one being walked and the one leased ahead
+#[derive(Debug)]
+pub(crate) struct CursorLease {
+    state: Mutex<LeaseState>,
+    refill: Arc<Notify>,
+}
+
+#[derive(Debug, Default)]
+struct LeaseState {
+    current: Range<u64>,
+    ahead: Option<Range<u64>>,
+}
+
+impl CursorLease {
+    /// take the next position, moving on to the lease ahead once the current one is used up
+    pub(crate) fn next(&self) -> u64 {
+        let mut state = self.lock_state();
+        if state.current.is_empty() {
+            if let Some(ahead) = state.ahead.take() {
+                state.current = ahead;
+                self.refill.notify_one();
+            } else {
+                // out of leased positions, count on until the next lease comes in
+                state.current.end = state.current.end.wrapping_add(1);
+            }
+        }
+        let position = state.current.start;
+        state.current.start = position.wrapping_add(1);
+        position
+    }
+
+    /// the position taken next, without taking it
+    pub(crate) fn peek(&self) -> u64 {
+        let state = self.lock_state();
+        match &state.ahead {
+            Some(ahead) if state.current.is_empty() => ahead.start,
+            _ => state.current.start,
+        }
+    }
+
+    fn wants_refill(&self) -> bool {
+        self.lock_state().ahead.is_none()
+    }
+
+    fn push(&self, range: Range<u64>) {
+        let mut state = self.lock_state();
+        if state.current.is_empty() {
+            state.current = range;
+        } else {
+            state.ahead = Some(range);
+        }
+    }
+
+    // the state only holds plain values, a poisoned lock is safe to recover
+    fn lock_state(&self) -> MutexGuard<'_, LeaseState> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Drop for CursorLease {
+    fn drop(&mut self) {
+        // wake the leasing task, so that it notices the lease is gone
+        self.refill.notify_one();
+    }
+}
@@ -0,0 +1,131 @@
+use crate::consts;
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use futures_core::Stream;
+use redis::AsyncCommands;
+use std::future::poll_fn;
+use std::pin::pin;
+use tokio::task::JoinHandle;
+
+/// members of a queue stored in a Redis hash, shared by every process following it
+///
+/// each field of the hash is an endpoint, its value the weight of the endpoint, or nothing for
+/// the default weight. Each process subscribes to the keyspace notifications of the hash,
+/// and reads it again on each change, so that a single command reweights a whole fleet:
+///
+/// ```text
+/// redis-cli HSET wrr:backend 10.0.0.1:80 5 10.0.0.2:80 1
+/// redis-cli HDEL wrr:backend 10.0.0.2:80
+/// ```
+///
+/// keyspace notifications of hash and generic commands must be enabled on the server,
+/// e.g. with `CONFIG SET notify-keyspace-events Kgh`. Fields holding anything else than a
+/// positive integer are skipped. After a lost connection the hash is read again.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{RedisMembership, WrrQueue};
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = RedisMembership::new("redis://127.0.0.1/", "wrr:backend").spawn(writer);
+/// let endpoint = reader.select().await.unwrap();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct RedisMembership {
+    url: String,
+    key: String,
+}
+
+/// handle of a running [`RedisMembership`], following the hash stops once it is dropped
+pub struct RedisWatcher {
+    reader: Reader<String>,
+    task: JoinHandle<()>,
+}
+
+impl RedisMembership {
+    /// follow the hash at `key` on the server at `url`, e.g. `redis://127.0.0.1/`
+    pub fn new(url: impl Into<String>, key: impl Into<String>) -> Self {
+        RedisMembership {
+            url: url.into(),
+            key: key.into(),
+        }
+    }
+
+    /// start following the hash into `writer`
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<String>) -> RedisWatcher {
+        RedisWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<String>) {
+        loop {
+            if let Err(e) = self.follow(&mut writer).await {
+                trace::discovery_failed(&self.key, &e);
+            }
+            tokio::time::sleep(consts::DISCOVERY_RETRY_DELAY).await;
+        }
+    }
+
+    /// mirror the hash into `writer` until the connection is lost
+    async fn follow(&self, writer: &mut Writer<String>) -> redis::RedisResult<()> {
+        let client = redis::Client::open(self.url.as_str())?;
+        let db = client.get_connection_info().redis.db;
+        // subscribed before the first read, so that no change goes unnoticed in between
+        let mut pubsub = client.get_async_pubsub().await?;
+        pubsub
+            .subscribe(format!("__keyspace@{db}__:{}", self.key))
+            .await?;
+        let mut connection = client.get_multiplexed_async_connection().await?;
+        let mut messages = pin!(pubsub.on_message());
+        loop {
+            let fields: Vec<(String, String)> = connection.hgetall(&self.key).await?;
+            let mut instances = Vec::new();
+            for (endpoint, weight) in fields {
+                match instance(endpoint, &weight) {
+                    Some(instance) => instances.push(instance),
+                    None => trace::discovery_failed(&self.key, &"invalid weight"),
+                }
+            }
+            if writer.pending.reconcile_uncalculated(instances) {
+                writer.publish().await;
+            }
+            if poll_fn(|cx| messages.as_mut().poll_next(cx))
+                .await
+                .is_none()
+            {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// instance of `endpoint` holding `weight`, None if it is not a valid weight
+fn instance(endpoint: String, weight: &str) -> Option<Instance<String>> {
+    let weight = weight.trim();
+    if weight.is_empty() {
+        return Some(Instance::new(endpoint));
+    }
+    Instance::try_new_with_weight(endpoint, weight.parse().ok()?).ok()
+}
+
+impl RedisWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<String> {
+        self.reader.clone()
+    }
+
+    /// stop following the hash, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for RedisWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
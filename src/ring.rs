@@ -0,0 +1,188 @@
+use crate::consts::{MAX_RING_POINTS, RING_POINTS_PER_WEIGHT};
+use crate::instance::{Instance, Member};
+use std::hash::{BuildHasher, BuildHasherDefault, DefaultHasher, Hash};
+
+/// consistent hash of keys to instances, each instance owning a share in proportion to its weight
+///
+/// everything is hashed with fixed keys, so that every process building it
+/// from the same members routes each key to the same instance
+#[derive(Debug)]
+pub(crate) enum HashRing {
+    /// ketama-style ring, `(point, instance index)` sorted by point
+    ///
+    /// the points of an instance only depend on its data and weight,
+    /// so adding or removing an instance only moves the keys of the points it owns
+    Ketama(Vec<(u64, usize)>),
+    /// Maglev lookup table, each slot holding an instance index
+    ///
+    /// O(1) lookups, a membership change moves little more than the share of the instance changed
+    Maglev(Vec<usize>),
+}
+
+impl Default for HashRing {
+    fn default() -> Self {
+        HashRing::Ketama(Vec::new())
+    }
+}
+
+impl HashRing {
+    /// build a ketama ring, or a Maglev table of at least `maglev_size` slots,
+    /// over the instances not `skipped`
+    pub(crate) fn new<T: Member + Hash>(
+        instance_list: &[Instance<T>],
+        skipped: &[usize],
+        maglev_size: Option<usize>,
+    ) -> Self {
+        match maglev_size {
+            Some(size) => HashRing::Maglev(maglev_table(instance_list, skipped, size)),
+            None => HashRing::Ketama(ketama_points(instance_list, skipped)),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        match self {
+            HashRing::Ketama(points) => points.is_empty(),
+            HashRing::Maglev(table) => table.is_empty(),
+        }
+    }
+
+    /// index of the instance owning the hash of `key`
+    pub(crate) fn get<K: Hash + ?Sized>(&self, key: &K) -> Option<usize> {
+        let hash = hash(key);
+        match self {
+            HashRing::Ketama(points) => {
+                let pos = points.partition_point(|(point, _)| *point < hash);
+                points
+                    .get(pos)
+                    .or_else(|| points.first())
+                    .map(|(_, index)| *index)
+            }
+            HashRing::Maglev(table) => table
+                .get((hash % table.len().max(1) as u64) as usize)
+                .copied(),
+        }
+    }
+}
+
+fn ketama_points<T: Member + Hash>(
+    instance_list: &[Instance<T>],
+    skipped: &[usize],
+) -> Vec<(u64, usize)> {
+    let mut points = Vec::new();
+    for (index, instance) in instance_list.iter().enumerate() {
+        if skipped.contains(&index) {
+            continue;
+        }
+        let count = instance
+            .weight()
+            .get()
+            .saturating_mul(RING_POINTS_PER_WEIGHT)
+            .min(MAX_RING_POINTS);
+        points.extend((0..count).map(|replica| (hash(&(instance.data(), replica)), index)));
+    }
+    points.sort_unstable();
+    points
+}
+
+/// fill a table of the first prime number of slots from `size`, each instance walking its own
+/// permutation of the slots and claiming the next free one whenever it has gathered enough credit
+///
+/// credit grows by the weight of the instance each round and a slot costs the largest weight,
+/// so that the heaviest instance claims a slot every round, and lighter ones in proportion
+fn maglev_table<T: Member + Hash>(
+    instance_list: &[Instance<T>],
+    skipped: &[usize],
+    size: usize,
+) -> Vec<usize> {
+    if instance_list.len() <= skipped.len() {
+        return Vec::new();
+    }
+    let size = next_prime(size.max(instance_list.len() - skipped.len()));
+    let permutations: Vec<(usize, usize)> = instance_list
+        .iter()
+        .map(|instance| {
+            let offset = hash(&(instance.data(), 0u8)) % size as u64;
+            let skip = hash(&(instance.data(), 1u8)) % (size as u64 - 1).max(1) + 1;
+            (offset as usize, skip as usize)
+        })
+        .collect();
+    let max_weight = instance_list
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| !skipped.contains(index))
+        .map(|(_, instance)| instance.weight().get() as u128)
+        .max()
+        .unwrap_or(1);
+    let mut credits = vec![0u128; instance_list.len()];
+    let mut next = vec![0usize; instance_list.len()];
+    let mut table = vec![usize::MAX; size];
+    let mut filled = 0;
+    while filled < size {
+        for (index, instance) in instance_list.iter().enumerate() {
+            if skipped.contains(&index) {
+                continue;
+            }
+            credits[index] += instance.weight().get() as u128;
+            if credits[index] < max_weight {
+                continue;
+            }
+            credits[index] -= max_weight;
+            let (offset, skip) = permutations[index];
+            let slot = loop {
+                let slot =
+                    ((offset as u128 + next[index] as u128 * skip as u128) % size as u128) as usize;
+                next[index] += 1;
+                if table[slot] == usize::MAX {
+                    break slot;
+                }
+            };
+            table[slot] = index;
+            filled += 1;
+            if filled == size {
+                break;
+            }
+        }
+    }
+    table
+}
+
+/// weighted rendezvous hashing: index of the instance not `skipped` scoring highest for `key`
+///
+/// each instance scores `weight / -ln(h)`, with `h` the hash of the key and its data mapped
+/// to `(0, 1)`, so that it wins a share of the keys in proportion to its weight.
+/// Removing an instance only moves the keys it owned, O(n) per lookup with no state to build
+pub(crate) fn rendezvous<K: Hash + ?Sized, T: Member + Hash>(
+    key: &K,
+    instance_list: &[Instance<T>],
+    skipped: &[usize],
+) -> Option<usize> {
+    let key = hash(key);
+    let mut selected: Option<(usize, f64)> = None;
+    for (index, instance) in instance_list.iter().enumerate() {
+        if skipped.contains(&index) {
+            continue;
+        }
+        let h = ((hash(&(key, instance.data())) >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+        let score = instance.weight().get() as f64 / -h.ln();
+        if selected.is_none_or(|(_, s)| s < score) {
+            selected = Some((index, score));
+        }
+    }
+    selected.map(|(index, _)| index)
+}
+
+/// smallest prime at least `n`, so that every skip walks all the slots
+fn next_prime(n: usize) -> usize {
+    let is_prime = |n: usize| {
+        n >= 2
+            && (2..)
+                .take_while(|d| d * d <= n)
+                .all(|d| !n.is_multiple_of(d))
+    };
+    (n..).find(|n| is_prime(*n)).unwrap_or(n)
+}
+
+/// hash with fixed keys, stable across processes built from the same sources
+pub(crate) fn hash<K: Hash + ?Sized>(key: &K) -> u64 {
+    BuildHasherDefault::<DefaultHasher>::default().hash_one(key)
+}
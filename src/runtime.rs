@@ -0,0 +1,60 @@
+//! primitives of the async flavor, taken from the runtime feature enabled
+//!
+//! only the schedule lock, see `crate::lock`, and a timer are needed, so that the async API
+//! does not tie callers to a given executor. `tokio` primitives are only used when
+//! neither `async-lock` nor `async-std` is enabled, `tokio` is otherwise only an integration
+//! for `BackgroundWriter` and `HealthCheck`.
+//! Along with `blocking`, only the timer is used, by `AsyncWrrQueue`
+
+#[cfg(all(feature = "async-lock", wrr_async))]
+pub(crate) use async_lock::{RwLock, RwLockReadGuard};
+#[cfg(all(feature = "async-std", wrr_async))]
+pub(crate) use async_std::sync::{RwLock, RwLockReadGuard};
+use std::time::Duration;
+#[cfg(all(wrr_tokio_runtime, wrr_async))]
+pub(crate) use tokio::sync::{RwLock, RwLockReadGuard};
+
+/// wait for `duration` without blocking the thread
+#[cfg(wrr_tokio_runtime)]
+pub(crate) async fn sleep(duration: Duration) {
+    tokio::time::sleep(duration).await;
+}
+
+/// read access without waiting, None while the lock is held for writing
+#[cfg(all(wrr_tokio_runtime, wrr_async))]
+pub(crate) fn try_read<T>(lock: &RwLock<T>) -> Option<RwLockReadGuard<'_, T>> {
+    lock.try_read().ok()
+}
+
+/// wait for `duration` without blocking the thread
+#[cfg(feature = "async-std")]
+pub(crate) async fn sleep(duration: Duration) {
+    async_std::task::sleep(duration).await;
+}
+
+/// read access without waiting, None while the lock is held for writing
+#[cfg(all(feature = "async-std", wrr_async))]
+pub(crate) fn try_read<T>(lock: &RwLock<T>) -> Option<RwLockReadGuard<'_, T>> {
+    lock.try_read()
+}
+
+/// wait for `duration` without blocking the thread
+#[cfg(all(
+    feature = "async-lock",
+    not(all(target_arch = "wasm32", target_os = "unknown"))
+))]
+pub(crate) async fn sleep(duration: Duration) {
+    async_io::Timer::after(duration).await;
+}
+
+/// wait for `duration` on a browser timer, `async-io` has no reactor on wasm32
+#[cfg(all(feature = "async-lock", target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) async fn sleep(duration: Duration) {
+    gloo_timers::future::sleep(duration).await;
+}
+
+/// read access without waiting, None while the lock is held for writing
+#[cfg(all(feature = "async-lock", wrr_async))]
+pub(crate) fn try_read<T>(lock: &RwLock<T>) -> Option<RwLockReadGuard<'_, T>> {
+    lock.try_read()
+}
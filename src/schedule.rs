@@ -0,0 +1,97 @@
+use alloc::vec::Vec;
+
+/// schedules whose runs are at least this long on average are stored run-length encoded
+const MIN_AVERAGE_RUN: usize = 4;
+
+/// precomputed selection order of the `Expanded` engine
+///
+/// skewed weights, e.g. `1, 1000`, produce long runs of the same instance.
+/// Such schedules are stored as runs, cutting memory by the average run length,
+/// with an O(log runs) lookup instead of a direct index
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub(crate) enum Schedule {
+    Plain(Vec<usize>),
+    /// `ends[i]` is the exclusive end position of a run of `indices[i]`
+    RunLength {
+        ends: Vec<usize>,
+        indices: Vec<usize>,
+    },
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Plain(Vec::new())
+    }
+}
+
+impl Schedule {
+    /// build a schedule from picks, run-length encoded when runs are long enough to pay off
+    pub(crate) fn from_picks(picks: impl IntoIterator<Item = usize>) -> Self {
+        let mut ends: Vec<usize> = Vec::new();
+        let mut indices: Vec<usize> = Vec::new();
+        let mut len = 0;
+        for pick in picks {
+            len += 1;
+            match indices.last() {
+                Some(last) if *last == pick => *ends.last_mut().unwrap() = len,
+                _ => {
+                    ends.push(len);
+                    indices.push(pick);
+                }
+            }
+        }
+        if indices.len() * MIN_AVERAGE_RUN <= len {
+            ends.shrink_to_fit();
+            indices.shrink_to_fit();
+            return Schedule::RunLength { ends, indices };
+        }
+        let mut plain = Vec::with_capacity(len);
+        let mut start = 0;
+        for (end, index) in ends.into_iter().zip(indices) {
+            plain.extend(core::iter::repeat_n(index, end - start));
+            start = end;
+        }
+        Schedule::Plain(plain)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        match self {
+            Schedule::Plain(queue) => queue.len(),
+            Schedule::RunLength { ends, .. } => ends.last().copied().unwrap_or(0),
+        }
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// instance index at `pos`, panics if `pos` is out of bounds
+    pub(crate) fn get(&self, pos: usize) -> usize {
+        match self {
+            Schedule::Plain(queue) => queue[pos],
+            Schedule::RunLength { ends, indices } => {
+                indices[ends.partition_point(|end| *end <= pos)]
+            }
+        }
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        let mut pos = 0;
+        core::iter::from_fn(move || {
+            let pick = (pos < self.len()).then(|| self.get(pos));
+            pos += 1;
+            pick
+        })
+    }
+
+    /// remove all picks, keeping the allocation
+    pub(crate) fn clear(&mut self) {
+        match self {
+            Schedule::Plain(queue) => queue.clear(),
+            Schedule::RunLength { ends, indices } => {
+                ends.clear();
+                indices.clear();
+            }
+        }
+    }
+}
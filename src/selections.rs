@@ -0,0 +1,57 @@
+use crate::error::WrrError;
+use crate::instance::{Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::iter::FusedIterator;
+
+/// iterator over successive selections of a [`WrrQueue`], see [`WrrQueue::iter_selections`]
+///
+/// endless, unless the queue has no instance up. The queue is borrowed for as long as the
+/// iterator lives, so the selected instances are yielded by reference
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::WrrQueue;
+///
+/// let mut queue = WrrQueue::new();
+/// queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+/// let assigned: Vec<_> = jobs.iter().zip(queue.iter_selections()).collect();
+/// ```
+pub struct Selections<'a, T: Member> {
+    queue: &'a WrrQueue<T>,
+    done: bool,
+}
+
+impl<'a, T: Member> Selections<'a, T> {
+    pub(crate) fn new(queue: &'a WrrQueue<T>) -> Self {
+        Selections { queue, done: false }
+    }
+}
+
+impl<'a, T: Member> Iterator for Selections<'a, T> {
+    type Item = &'a Instance<T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.queue.select_index() {
+            Ok(idx) => Some(self.queue.instance_at(idx)),
+            Err(WrrError::Poisoned) => panic!("Read access acquired failed"),
+            Err(_) => {
+                self.done = true;
+                None
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.done || self.queue.is_empty() {
+            (0, Some(0))
+        } else {
+            (0, None)
+        }
+    }
+}
+
+impl<T: Member> FusedIterator for Selections<'_, T> {}
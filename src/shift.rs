@@ -0,0 +1,54 @@
+use crate::time::Instant;
+use std::time::Duration;
+
+/// weights of two instances interpolated over a duration, see [`WrrQueue::shift_weight`](crate::WrrQueue::shift_weight)
+///
+/// the combined weight of both instances is kept, so that the share of the other instances
+/// does not move during the shift
+#[derive(Debug)]
+pub(crate) struct WeightShift<T> {
+    pub(crate) from: T,
+    pub(crate) to: T,
+    total: usize,
+    start_to: usize,
+    target_to: usize,
+    start: Instant,
+    over: Duration,
+}
+
+impl<T> WeightShift<T> {
+    /// shift `to` from `to_weight` toward `target_pct` of the combined weight, never below `1` each
+    pub(crate) fn new(
+        from: T,
+        to: T,
+        from_weight: usize,
+        to_weight: usize,
+        target_pct: f64,
+        over: Duration,
+    ) -> Self {
+        let total = from_weight.saturating_add(to_weight);
+        let target = (total as f64 * target_pct.clamp(0.0, 100.0) / 100.0).round() as usize;
+        WeightShift {
+            from,
+            to,
+            total,
+            start_to: to_weight,
+            target_to: target.clamp(1, total - 1),
+            start: Instant::now(),
+            over,
+        }
+    }
+
+    /// `(from weight, to weight)` at `now`, and whether the shift is over
+    pub(crate) fn weights_at(&self, now: Instant) -> (usize, usize, bool) {
+        let elapsed = now.saturating_duration_since(self.start);
+        let progress = if elapsed >= self.over {
+            1.0
+        } else {
+            elapsed.as_secs_f64() / self.over.as_secs_f64()
+        };
+        let to = self.start_to as f64 + (self.target_to as f64 - self.start_to as f64) * progress;
+        let to = (to.round() as usize).clamp(1, self.total - 1);
+        (self.total - to, to, progress >= 1.0)
+    }
+}
@@ -0,0 +1,92 @@
+use crate::engine::Engine;
+#[cfg(feature = "serde")]
+use crate::format::FormatVersion;
+use crate::instance::Instance;
+use crate::state::Health;
+
+/// members of a queue along with their health and the phase of its cycle,
+/// see [`WrrQueue::snapshot`](crate::WrrQueue::snapshot)
+///
+/// restoring it with [`WrrQueue::restore`](crate::WrrQueue::restore), e.g. after a restart,
+/// resumes the selections where they were rather than at the head of the cycle.
+/// Outcomes, latencies and selections in flight are not part of it, and ejected instances
+/// are restored in rotation. With the `serde` feature, it can be persisted in any serde format
+/// handling `i128`, such as JSON.
+///
+/// it is written along with a format version, and read back tolerantly, so that a snapshot persisted
+/// by another release of the crate still restores: a missing version is the unversioned format,
+/// missing fields of the cycle phase or the member health fall back to the head of the cycle
+/// and an active instance up, and unknown fields are ignored
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{WrrConfig, WrrQueue};
+///
+/// let config = WrrConfig::new(vec![("a", 1usize).try_into().unwrap(), ("b", 2usize).try_into().unwrap()]);
+/// let snapshot = WrrQueue::from_config(config.clone()).snapshot();
+/// assert_eq!(config.instances(), snapshot.instances().cloned().collect::<Vec<_>>());
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QueueSnapshot<T: PartialEq> {
+    #[cfg(feature = "serde")]
+    #[serde(default)]
+    pub(crate) version: FormatVersion,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) engine: Engine,
+    pub(crate) members: Vec<MemberSnapshot<T>>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) cursor: u64,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) smooth_weight: Vec<i128>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) deficit_turn: usize,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) deficits: Vec<u128>,
+}
+
+/// an instance of a [`QueueSnapshot`] along with its health
+#[derive(PartialEq, Eq, Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemberSnapshot<T: PartialEq> {
+    pub(crate) instance: Instance<T>,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub(crate) standby: bool,
+    #[cfg_attr(feature = "serde", serde(default = "health_up"))]
+    pub(crate) health: Health,
+}
+
+#[cfg(feature = "serde")]
+fn health_up() -> Health {
+    Health::Up
+}
+
+impl<T: PartialEq> QueueSnapshot<T> {
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// members in insertion order
+    pub fn members(&self) -> &[MemberSnapshot<T>] {
+        &self.members
+    }
+
+    /// instances of the members in insertion order
+    pub fn instances(&self) -> impl Iterator<Item = &Instance<T>> {
+        self.members.iter().map(|member| &member.instance)
+    }
+}
+
+impl<T: PartialEq> MemberSnapshot<T> {
+    pub fn instance(&self) -> &Instance<T> {
+        &self.instance
+    }
+
+    pub fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    pub fn health(&self) -> Health {
+        self.health
+    }
+}
@@ -0,0 +1,625 @@
+use crate::drain::Drain;
+use crate::error::WrrError;
+use crate::instance::{checked, Instance, Member};
+#[cfg(feature = "tokio")]
+use crate::membership::Membership;
+#[cfg(not(feature = "arc-swap"))]
+use crate::sync::RwLock;
+use crate::sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering};
+use crate::wrr_queue::WrrQueue;
+use std::num::NonZeroUsize;
+use std::ops::Deref;
+use std::sync::PoisonError;
+#[cfg(not(feature = "arc-swap"))]
+use std::sync::TryLockError;
+
+/// read half of a split [`WrrQueue`], cheap to clone, only able to select
+///
+/// readers select on the latest snapshot published by the [`Writer`],
+/// and never wait on a recalculation. Time-driven changes, such as slow start, cooldown,
+/// the end of an ejection or of a ttl, are applied by the first reader to notice them due,
+/// which publishes a refreshed snapshot while the others keep selecting on the current one.
+///
+/// a select never observes a torn schedule: it always picks from one complete snapshot,
+/// either the one before or the one after a concurrent publish, and concurrent selects
+/// on the same snapshot take distinct cursor positions. Model-checked with loom in `tests/loom_test.rs`
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::WrrQueue;
+///
+/// let mut queue = WrrQueue::new();
+/// queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+/// let (reader, mut writer) = queue.split();
+///
+/// let worker = reader.clone();
+/// assert_eq!(&"b", worker.select().await.unwrap().data());
+///
+/// writer.insert(("c", 3usize)).await;
+/// ```
+pub struct Reader<T: Member> {
+    published: Arc<Published<T>>,
+}
+
+/// write half of a split [`WrrQueue`], the only handle able to change membership
+///
+/// each change (an insert, a delete, a weight update, marking an instance down or up,
+/// pausing, resuming or draining it) recalculates the schedule on the writer side,
+/// then publishes it to every [`Reader`] at once.
+/// the snapshots share the selection cursor, along with the outcome stats, latencies, quota windows
+/// and selections in flight of the instances they both hold: a [`Selected`] still holding a previous
+/// snapshot keeps updating the state of the latest one
+pub struct Writer<T: Member> {
+    pub(crate) pending: WrrQueue<T>,
+    published: Arc<Published<T>>,
+}
+
+/// instance selected by a [`Reader`], keeping the snapshot it was selected from alive
+pub struct Selected<T: Member> {
+    snapshot: Arc<WrrQueue<T>>,
+    index: usize,
+}
+
+/// generation pointer over the published snapshots
+///
+/// without `arc-swap`, two slots are kept: a publish only ever replaces the slot readers
+/// are not pointed at, then bumps the generation, so readers never contend with a publish
+struct Published<T: Member> {
+    generation: AtomicUsize,
+    // serializes the publishes of the writer and the refreshes of the readers
+    publishing: Mutex<()>,
+    // non-zero while a reader refreshes the snapshot, the others keep selecting meanwhile
+    refreshing: AtomicUsize,
+    #[cfg(feature = "tokio")]
+    membership: tokio::sync::watch::Sender<Membership>,
+    #[cfg(not(feature = "arc-swap"))]
+    slots: [RwLock<Arc<WrrQueue<T>>>; 2],
+    #[cfg(feature = "arc-swap")]
+    snapshot: arc_swap::ArcSwap<WrrQueue<T>>,
+}
+
+pub(crate) fn split<T: Member + Clone>(queue: WrrQueue<T>) -> (Reader<T>, Writer<T>) {
+    let pending = queue.fork();
+    let published = Arc::new(Published::new(queue));
+    (
+        Reader {
+            published: published.clone(),
+        },
+        Writer { pending, published },
+    )
+}
+
+impl<T: Member> Published<T> {
+    #[cfg(not(feature = "arc-swap"))]
+    fn new(queue: WrrQueue<T>) -> Self {
+        #[cfg(feature = "tokio")]
+        let membership = tokio::sync::watch::Sender::new(queue.membership(0));
+        let snapshot = Arc::new(queue);
+        Published {
+            generation: AtomicUsize::new(0),
+            publishing: Mutex::new(()),
+            refreshing: AtomicUsize::new(0),
+            #[cfg(feature = "tokio")]
+            membership,
+            slots: [RwLock::new(snapshot.clone()), RwLock::new(snapshot)],
+        }
+    }
+
+    // the locks only guard an `Arc` swap, a poisoned lock is safe to recover
+    #[cfg(not(feature = "arc-swap"))]
+    fn load(&self) -> Arc<WrrQueue<T>> {
+        let generation = self.generation.load(Ordering::Acquire);
+        self.slots[generation % 2]
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// latest snapshot without waiting, [`WrrError::WouldBlock`] if a publish is writing
+    /// the slot of the generation loaded, which only happens once two publishes passed it
+    #[cfg(not(feature = "arc-swap"))]
+    fn try_load(&self) -> Result<Arc<WrrQueue<T>>, WrrError> {
+        let generation = self.generation.load(Ordering::Acquire);
+        match self.slots[generation % 2].try_read() {
+            Ok(slot) => Ok(slot.clone()),
+            Err(TryLockError::WouldBlock) => Err(WrrError::WouldBlock),
+            Err(TryLockError::Poisoned(slot)) => Ok(slot.into_inner().clone()),
+        }
+    }
+
+    // only called with `publishing` held, so the generation cannot move underneath
+    #[cfg(not(feature = "arc-swap"))]
+    fn store(&self, queue: WrrQueue<T>) {
+        let generation = self.generation.load(Ordering::Relaxed);
+        #[cfg(feature = "tokio")]
+        let membership = queue.membership(generation as u64 + 1);
+        *self.slots[(generation + 1) % 2]
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Arc::new(queue);
+        self.generation.store(generation + 1, Ordering::Release);
+        #[cfg(feature = "tokio")]
+        self.membership.send_replace(membership);
+    }
+
+    #[cfg(feature = "arc-swap")]
+    fn new(queue: WrrQueue<T>) -> Self {
+        Published {
+            generation: AtomicUsize::new(0),
+            publishing: Mutex::new(()),
+            refreshing: AtomicUsize::new(0),
+            #[cfg(feature = "tokio")]
+            membership: tokio::sync::watch::Sender::new(queue.membership(0)),
+            snapshot: arc_swap::ArcSwap::from_pointee(queue),
+        }
+    }
+
+    #[cfg(feature = "arc-swap")]
+    fn load(&self) -> Arc<WrrQueue<T>> {
+        self.snapshot.load_full()
+    }
+
+    #[cfg(feature = "arc-swap")]
+    fn try_load(&self) -> Result<Arc<WrrQueue<T>>, WrrError> {
+        Ok(self.load())
+    }
+
+    #[cfg(feature = "arc-swap")]
+    fn store(&self, queue: WrrQueue<T>) {
+        #[cfg(feature = "tokio")]
+        let membership = queue.membership(0);
+        self.snapshot.store(Arc::new(queue));
+        #[cfg(not(feature = "tokio"))]
+        self.generation.fetch_add(1, Ordering::Release);
+        #[cfg(feature = "tokio")]
+        {
+            let generation = self.generation.fetch_add(1, Ordering::Release);
+            self.membership.send_replace(Membership {
+                generation: generation as u64 + 1,
+                ..membership
+            });
+        }
+    }
+
+    // only guards the order of the publishes, a poisoned lock is safe to recover
+    fn lock_publishing(&self) -> MutexGuard<'_, ()> {
+        self.publishing
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T: Member + Clone> Published<T> {
+    /// publish the next snapshot of `queue`, sharing the runtime state of the current one
+    fn publish(&self, queue: &WrrQueue<T>) {
+        let _publishing = self.lock_publishing();
+        let previous = self.load();
+        self.store(next_snapshot(queue, &previous));
+    }
+
+    /// latest snapshot, refreshed first if a time-driven change is due
+    ///
+    /// a single reader refreshes it at a time, the others select on the current snapshot meanwhile
+    fn load_refreshed(&self) -> Arc<WrrQueue<T>> {
+        let snapshot = self.load();
+        if !snapshot.refresh_due() || self.refreshing.fetch_add(1, Ordering::Acquire) != 0 {
+            return snapshot;
+        }
+        let next = next_snapshot(&snapshot, &snapshot);
+        {
+            let _publishing = self.lock_publishing();
+            // a snapshot published by the writer meanwhile takes precedence
+            if Arc::ptr_eq(&self.load(), &snapshot) {
+                self.store(next);
+            }
+        }
+        self.refreshing.store(0, Ordering::Release);
+        self.load()
+    }
+}
+
+/// next snapshot of `queue`, sharing the runtime state and the cursor of `previous`
+fn next_snapshot<T: Member + Clone>(queue: &WrrQueue<T>, previous: &WrrQueue<T>) -> WrrQueue<T> {
+    let mut next = queue.fork();
+    next.carry_state(previous);
+    next.recalculate_queue();
+    // apply the time-driven changes due, on top of the membership
+    next.recalculate_if_dirty();
+    next
+}
+
+impl<T: Member> Reader<T> {
+    /// number of snapshots published since the split, bumped by each change of the [`Writer`]
+    /// and each refresh of the time-driven weights
+    pub fn generation(&self) -> usize {
+        self.published.generation.load(Ordering::Acquire)
+    }
+
+    /// receive the [`Membership`] of each snapshot published, its generation being [`Reader::generation`]
+    #[cfg(feature = "tokio")]
+    pub fn watch_membership(&self) -> tokio::sync::watch::Receiver<Membership> {
+        self.published.membership.subscribe()
+    }
+
+    /// latest snapshot published
+    #[cfg(all(feature = "tokio", wrr_async))]
+    pub(crate) fn snapshot(&self) -> Arc<WrrQueue<T>> {
+        self.published.load()
+    }
+}
+
+impl<T: Member + Clone> Reader<T> {
+    /// return the selected instance from the latest snapshot without waiting, for synchronous callers
+    ///
+    /// [`WrrError::WouldBlock`] if the [`Writer`] is publishing over the snapshot this reader
+    /// last saw, never with the `arc-swap` feature, [`WrrError::Empty`] if the snapshot is empty.
+    /// Time-driven changes due are left to the next [`Reader::select`], as applying them recalculates
+    pub fn try_select(&self) -> Result<Selected<T>, WrrError> {
+        let snapshot = self.published.try_load()?;
+        let index = snapshot.select_index()?;
+        Ok(Selected { snapshot, index })
+    }
+}
+
+impl<T: Member> Clone for Reader<T> {
+    fn clone(&self) -> Self {
+        Reader {
+            published: self.published.clone(),
+        }
+    }
+}
+
+impl<T: Member> Writer<T> {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<T> {
+        Reader {
+            published: self.published.clone(),
+        }
+    }
+}
+
+impl<T: Member> Deref for Selected<T> {
+    type Target = Instance<T>;
+
+    fn deref(&self) -> &Self::Target {
+        self.snapshot.instance_at(self.index)
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member + Clone> Reader<T> {
+    /// return the selected instance from the latest snapshot, None if it is empty
+    pub async fn select(&self) -> Option<Selected<T>> {
+        self.blocking_select()
+    }
+
+    /// return the selected instance from the latest snapshot, None if it is empty, for code
+    /// running outside the runtime, e.g. a `std::thread` or a rayon worker, sharing the queue with async tasks
+    ///
+    /// published snapshots are never written, so this only blocks the thread while a publish
+    /// swaps the snapshot pointer, and never panics within an asynchronous execution context
+    pub fn blocking_select(&self) -> Option<Selected<T>> {
+        let snapshot = self.published.load_refreshed();
+        let index = snapshot.select_index().ok()?;
+        Some(Selected { snapshot, index })
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member + Clone> Writer<T> {
+    /// insert a new instance, and publish the re-calculated queue
+    pub async fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res =
+            checked(instance).is_some_and(|instance| self.pending.insert_uncalculated(instance));
+        self.publish().await;
+        res
+    }
+
+    /// insert a new instance vec, and publish the re-calculated queue
+    pub async fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance)
+                .is_some_and(|instance| self.pending.insert_uncalculated(instance));
+        }
+        self.publish().await;
+        res
+    }
+
+    /// delete certain instance, and publish the re-calculated queue
+    pub async fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        if self.pending.delete_uncalculated(instance) {
+            self.publish().await;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// clear instance, and publish the empty queue
+    pub async fn clear_instance(&mut self) {
+        self.pending.clear_instance();
+        self.publish().await;
+    }
+
+    /// change the weight of the instance holding `data`, and publish the re-calculated queue,
+    /// false if not in the queue
+    pub async fn update_weight(&mut self, data: &T, weight: NonZeroUsize) -> bool {
+        if self.pending.update_weight_uncalculated(data, weight) {
+            self.publish().await;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// take the instance holding `data` out of rotation, see [`WrrQueue::mark_down`],
+    /// false if not in the queue
+    pub async fn mark_down(&mut self, data: &T) -> bool {
+        self.set_down(data, true).await
+    }
+
+    /// put the instance holding `data` back in rotation, see [`WrrQueue::mark_up`],
+    /// false if not in the queue
+    pub async fn mark_up(&mut self, data: &T) -> bool {
+        self.set_down(data, false).await
+    }
+
+    async fn set_down(&mut self, data: &T, down: bool) -> bool {
+        match self.pending.set_down_uncalculated(data, down) {
+            Some(changed) => {
+                if changed {
+                    self.publish().await;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// take the instance holding `data` out of rotation until [`Writer::resume`],
+    /// see [`WrrQueue::pause`], false if not in the queue
+    pub async fn pause(&mut self, data: &T) -> bool {
+        self.set_paused(data, true).await
+    }
+
+    /// put the instance holding `data` back in rotation after [`Writer::pause`],
+    /// false if not in the queue
+    pub async fn resume(&mut self, data: &T) -> bool {
+        self.set_paused(data, false).await
+    }
+
+    async fn set_paused(&mut self, data: &T, paused: bool) -> bool {
+        match self.pending.set_paused_uncalculated(data, paused) {
+            Some(changed) => {
+                if changed {
+                    self.publish().await;
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// stop selecting the instance holding `data` for good, see [`WrrQueue::drain`],
+    /// None if not in the queue
+    ///
+    /// the returned [`Drain`] tracks the selections in flight on every snapshot,
+    /// including the ones made on snapshots published before
+    pub async fn drain(&mut self, data: &T) -> Option<Drain> {
+        if self.pending.drain_uncalculated(data)? {
+            self.publish().await;
+        }
+        self.published.load().track_drain(data)
+    }
+
+    pub(crate) async fn publish(&mut self) {
+        self.published.publish(&self.pending);
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member + Clone> Reader<T> {
+    /// return the selected instance from the latest snapshot, None if it is empty
+    pub fn select(&self) -> Option<Selected<T>> {
+        let snapshot = self.published.load_refreshed();
+        let index = snapshot.select_index().ok()?;
+        Some(Selected { snapshot, index })
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member + Clone> Writer<T> {
+    /// insert a new instance, and publish the re-calculated queue
+    pub fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res =
+            checked(instance).is_some_and(|instance| self.pending.insert_uncalculated(instance));
+        self.publish();
+        res
+    }
+
+    /// insert a new instance vec, and publish the re-calculated queue
+    pub fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance)
+                .is_some_and(|instance| self.pending.insert_uncalculated(instance));
+        }
+        self.publish();
+        res
+    }
+
+    /// delete certain instance, and publish the re-calculated queue
+    pub fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        if self.pending.delete_uncalculated(instance) {
+            self.publish();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// clear instance, and publish the empty queue
+    pub fn clear_instance(&mut self) {
+        self.pending.clear_instance();
+        self.publish();
+    }
+
+    /// change the weight of the instance holding `data`, and publish the re-calculated queue,
+    /// false if not in the queue
+    pub fn update_weight(&mut self, data: &T, weight: NonZeroUsize) -> bool {
+        if self.pending.update_weight_uncalculated(data, weight) {
+            self.publish();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// take the instance holding `data` out of rotation, see [`WrrQueue::mark_down`],
+    /// false if not in the queue
+    pub fn mark_down(&mut self, data: &T) -> bool {
+        self.set_down(data, true)
+    }
+
+    /// put the instance holding `data` back in rotation, see [`WrrQueue::mark_up`],
+    /// false if not in the queue
+    pub fn mark_up(&mut self, data: &T) -> bool {
+        self.set_down(data, false)
+    }
+
+    fn set_down(&mut self, data: &T, down: bool) -> bool {
+        match self.pending.set_down_uncalculated(data, down) {
+            Some(changed) => {
+                if changed {
+                    self.publish();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// take the instance holding `data` out of rotation until [`Writer::resume`],
+    /// see [`WrrQueue::pause`], false if not in the queue
+    pub fn pause(&mut self, data: &T) -> bool {
+        self.set_paused(data, true)
+    }
+
+    /// put the instance holding `data` back in rotation after [`Writer::pause`],
+    /// false if not in the queue
+    pub fn resume(&mut self, data: &T) -> bool {
+        self.set_paused(data, false)
+    }
+
+    fn set_paused(&mut self, data: &T, paused: bool) -> bool {
+        match self.pending.set_paused_uncalculated(data, paused) {
+            Some(changed) => {
+                if changed {
+                    self.publish();
+                }
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// stop selecting the instance holding `data` for good, see [`WrrQueue::drain`],
+    /// None if not in the queue
+    ///
+    /// the returned [`Drain`] tracks the selections in flight on every snapshot,
+    /// including the ones made on snapshots published before
+    pub fn drain(&mut self, data: &T) -> Option<Drain> {
+        if self.pending.drain_uncalculated(data)? {
+            self.publish();
+        }
+        self.published.load().track_drain(data)
+    }
+
+    fn publish(&mut self) {
+        self.published.publish(&self.pending);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::config::WrrConfig;
+    use crate::instance::Instance;
+    use crate::wrr_queue::WrrQueue;
+
+    #[cfg(not(feature = "arc-swap"))]
+    #[test]
+    fn try_select_would_block_test() {
+        use crate::error::WrrError;
+        use crate::sync::Ordering;
+
+        let queue = WrrQueue::from_config(WrrConfig::new(vec![
+            Instance::try_from(("a", 1usize)).unwrap()
+        ]));
+        let (reader, _writer) = queue.split();
+        assert_eq!(&"a", reader.try_select().unwrap().data());
+        // a publish writing the slot of the generation the reader loads
+        let generation = reader.published.generation.load(Ordering::Acquire);
+        let slot = reader.published.slots[generation % 2].write().unwrap();
+        assert_eq!(Some(WrrError::WouldBlock), reader.try_select().err());
+        drop(slot);
+        assert_eq!(&"a", reader.try_select().unwrap().data());
+    }
+
+    #[test]
+    fn shared_state_test() {
+        let queue = WrrQueue::from_config(WrrConfig::new(vec![
+            Instance::try_from(("a", 1usize)).unwrap(),
+            Instance::try_from(("b", 1usize)).unwrap(),
+        ]));
+        let (reader, writer) = queue.split();
+        let previous = reader.published.load();
+        reader.published.publish(&writer.pending);
+        let next = reader.published.load();
+
+        // a selection and an outcome on the previous snapshot, after the next one is published
+        let index = previous.select_index().unwrap();
+        previous.record(index, false);
+        let data = previous.instance_at(index).data();
+        assert_eq!(1, next.stats(data).unwrap().failure);
+        assert_ne!(data, next.instance_at(next.select_index().unwrap()).data());
+    }
+
+    #[test]
+    fn shared_quota_test() {
+        use std::num::NonZeroUsize;
+        use std::time::Duration;
+
+        let mut queue =
+            WrrQueue::from_config(WrrConfig::new(vec![
+                Instance::try_from(("a", 1usize)).unwrap()
+            ]));
+        queue.set_quota(
+            &"a",
+            Some((NonZeroUsize::new(2).unwrap(), Duration::from_secs(60))),
+        );
+        let (reader, mut writer) = queue.split();
+        let previous = reader.published.load();
+        writer
+            .pending
+            .insert_uncalculated(Instance::try_from(("b", 1usize)).unwrap());
+        reader.published.publish(&writer.pending);
+
+        // selections on the previous snapshot, within the window, count against the same quota
+        for _ in 0..2 {
+            let index = previous.select_index().unwrap();
+            assert_eq!(&"a", previous.instance_at(index).data());
+        }
+        assert!(previous.select_index().is_err());
+        let a = (0..6)
+            .filter(|_| *reader.try_select().unwrap().data() == "a")
+            .count();
+        assert_eq!(0, a);
+    }
+}
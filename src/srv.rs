@@ -0,0 +1,161 @@
+use crate::instance::Instance;
+use crate::split::{Reader, Selected, Writer};
+use crate::trace;
+use crate::wrr_queue::WrrQueue;
+use hickory_resolver::error::ResolveError;
+use hickory_resolver::TokioAsyncResolver;
+use std::collections::BTreeMap;
+use std::num::NonZeroUsize;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// target of a SRV record, its hostname without the trailing dot and its port
+pub type SrvTarget = (String, u16);
+
+/// reader of each tier, by priority
+type Tiers = Arc<RwLock<Vec<(u16, Reader<SrvTarget>)>>>;
+
+/// members of a queue fed from the SRV records of a service, re-resolved periodically
+///
+/// each record priority is a tier, selections go to the tier of the highest priority
+/// (the lowest number) holding an instance up, as [`PriorityQueue`](crate::PriorityQueue) does.
+/// Record weights are instance weights, a weight of `0` counting as `1`.
+/// A failed resolution leaves the members as they are.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::SrvDiscovery;
+/// use std::time::Duration;
+///
+/// let watcher = SrvDiscovery::new("_http._tcp.backend.internal")
+///     .interval(Duration::from_secs(10))
+///     .spawn()?;
+/// let target = watcher.select().await.unwrap();
+/// let (host, port) = target.data();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct SrvDiscovery {
+    name: String,
+    interval: Duration,
+}
+
+/// handle of a running [`SrvDiscovery`], the resolution stops once it is dropped
+pub struct SrvWatcher {
+    tiers: Tiers,
+    task: JoinHandle<()>,
+}
+
+impl SrvDiscovery {
+    /// resolve the SRV records of `name`, e.g. `_http._tcp.example.com`, every 30 seconds
+    pub fn new(name: impl Into<String>) -> Self {
+        SrvDiscovery {
+            name: name.into(),
+            interval: Duration::from_secs(30),
+        }
+    }
+
+    /// resolve the records once per `interval`
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// start resolving with the system resolver configuration, right away and then once per interval
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self) -> Result<SrvWatcher, ResolveError> {
+        let resolver = TokioAsyncResolver::tokio_from_system_conf()?;
+        let tiers = Arc::new(RwLock::new(Vec::new()));
+        Ok(SrvWatcher {
+            tiers: tiers.clone(),
+            task: tokio::spawn(self.run(resolver, tiers)),
+        })
+    }
+
+    async fn run(self, resolver: TokioAsyncResolver, tiers: Tiers) {
+        let mut writers: BTreeMap<u16, Writer<SrvTarget>> = BTreeMap::new();
+        loop {
+            match resolver.srv_lookup(self.name.as_str()).await {
+                Ok(lookup) => {
+                    let mut records: BTreeMap<u16, Vec<Instance<SrvTarget>>> = BTreeMap::new();
+                    for srv in lookup.iter() {
+                        let host = srv.target().to_utf8().trim_end_matches('.').to_string();
+                        let weight =
+                            NonZeroUsize::new(srv.weight() as usize).unwrap_or(NonZeroUsize::MIN);
+                        let instance = Instance::new_with_weight((host, srv.port()), weight);
+                        let tier = records.entry(srv.priority()).or_default();
+                        if !tier.iter().any(|x| x.data() == instance.data()) {
+                            tier.push(instance);
+                        }
+                    }
+                    sync_tiers(&mut writers, records, &tiers).await;
+                }
+                Err(e) => trace::discovery_failed(&self.name, &e),
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+}
+
+/// reconcile every tier with the records of its priority, tiers left without records are emptied
+async fn sync_tiers(
+    writers: &mut BTreeMap<u16, Writer<SrvTarget>>,
+    mut records: BTreeMap<u16, Vec<Instance<SrvTarget>>>,
+    tiers: &RwLock<Vec<(u16, Reader<SrvTarget>)>>,
+) {
+    let mut added = false;
+    for priority in records.keys() {
+        if !writers.contains_key(priority) {
+            let (_, writer) = WrrQueue::new().split();
+            writers.insert(*priority, writer);
+            added = true;
+        }
+    }
+    for (priority, writer) in writers.iter_mut() {
+        let instances = records.remove(priority).unwrap_or_default();
+        if writer.pending.reconcile_uncalculated(instances) {
+            writer.publish().await;
+        }
+    }
+    if added {
+        let readers = writers.iter().map(|(p, w)| (*p, w.reader())).collect();
+        *tiers.write().unwrap_or_else(PoisonError::into_inner) = readers;
+    }
+}
+
+impl SrvWatcher {
+    /// return the instance selected in the highest priority tier holding one up,
+    /// None if there is none
+    pub async fn select(&self) -> Option<Selected<SrvTarget>> {
+        let tiers = self
+            .tiers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for (_, reader) in tiers {
+            if let Some(selected) = reader.select().await {
+                return Some(selected);
+            }
+        }
+        None
+    }
+
+    /// priorities of the tiers resolved so far, along with a [`Reader`] of each one
+    pub fn tiers(&self) -> Vec<(u16, Reader<SrvTarget>)> {
+        self.tiers
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// stop resolving, the members are left as last resolved
+    pub fn stop(self) {}
+}
+
+impl Drop for SrvWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,501 @@
+use crate::drain::Drain;
+use crate::in_flight::InFlight;
+use crate::outlier::{Outcomes, OutlierDetection};
+use crate::sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering};
+use crate::time::Instant;
+use std::sync::PoisonError;
+use std::time::Duration;
+
+/// runtime state tracked for each instance of the queue
+#[derive(Debug)]
+pub(crate) struct InstanceState {
+    // shared by the snapshots of a split queue, see `carry`
+    shared: Arc<SharedState>,
+    max_in_flight: Option<usize>,
+    /// `(limit, window)` of the selections
+    quota: Option<(usize, Duration)>,
+    // shared like `shared`, as long as the quota stays the same
+    quota_window: Arc<Mutex<QuotaWindow>>,
+    // only selected while no active instance is up
+    standby: bool,
+    // out of rotation until marked up
+    down: bool,
+    error_factor: f64,
+    // added or marked up, the start of its slow start
+    up_since: Instant,
+    // last marked up after being down, the start of its cooldown
+    recovered_at: Option<Instant>,
+    // last heartbeat, see `WrrQueue::touch`
+    touched: Instant,
+    // marked down by its ttl, marked up by the next heartbeat
+    expired: bool,
+    // out of rotation for good, until its selections in flight complete
+    draining: bool,
+    // out of rotation until resumed, regardless of its health
+    paused: bool,
+    // last health noticed, see `WrrQueue::hooks`
+    health: Health,
+}
+
+/// runtime state updated through `&self`, by the selections and the outcomes reported
+///
+/// readers of a split queue may still select on a snapshot after the next one is published,
+/// so every snapshot holding the instance updates the same state
+#[derive(Debug)]
+struct SharedState {
+    success: AtomicUsize,
+    failure: AtomicUsize,
+    consecutive_failures: AtomicUsize,
+    saturated: AtomicUsize,
+    throttled: AtomicUsize,
+    // shared with the `InFlight` marks, which outlive the borrow of the queue
+    in_flight: Arc<AtomicUsize>,
+    latency: Mutex<PeakEwma>,
+    outcomes: Mutex<Outcomes>,
+}
+
+/// selections admitted in the current quota window
+#[derive(Debug, Default)]
+struct QuotaWindow {
+    start: Option<Instant>,
+    count: usize,
+}
+
+/// peak exponentially weighted moving average of the reported latencies, in nanoseconds
+///
+/// a sample above the average replaces it at once, lower ones are averaged in with a weight
+/// decaying with the time elapsed since the previous sample
+#[derive(Debug, Default, Clone)]
+struct PeakEwma {
+    cost: f64,
+    stamp: Option<Instant>,
+}
+
+impl Default for SharedState {
+    fn default() -> Self {
+        SharedState {
+            success: AtomicUsize::new(0),
+            failure: AtomicUsize::new(0),
+            consecutive_failures: AtomicUsize::new(0),
+            saturated: AtomicUsize::new(0),
+            throttled: AtomicUsize::new(0),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            latency: Mutex::new(PeakEwma::default()),
+            outcomes: Mutex::new(Outcomes::default()),
+        }
+    }
+}
+
+impl Default for InstanceState {
+    fn default() -> Self {
+        InstanceState {
+            shared: Arc::new(SharedState::default()),
+            max_in_flight: None,
+            quota: None,
+            quota_window: Arc::new(Mutex::new(QuotaWindow::default())),
+            standby: false,
+            down: false,
+            error_factor: 1.0,
+            up_since: Instant::now(),
+            recovered_at: None,
+            touched: Instant::now(),
+            expired: false,
+            draining: false,
+            paused: false,
+            health: Health::Up,
+        }
+    }
+}
+
+impl InstanceState {
+    /// fresh runtime state, keeping the marks, the limits and the heartbeat
+    pub(crate) fn fresh(&self) -> Self {
+        InstanceState {
+            max_in_flight: self.max_in_flight,
+            quota: self.quota,
+            standby: self.standby,
+            down: self.down,
+            up_since: self.up_since,
+            recovered_at: self.recovered_at,
+            touched: self.touched,
+            expired: self.expired,
+            draining: self.draining,
+            paused: self.paused,
+            health: self.health,
+            ..Default::default()
+        }
+    }
+
+    /// share the runtime state of `previous`, the same instance in an earlier snapshot:
+    /// outcome stats, latency, selections in flight and, under the same quota, the quota window.
+    /// The error factor is carried over
+    pub(crate) fn carry(&mut self, previous: &InstanceState) {
+        self.shared = previous.shared.clone();
+        if self.quota == previous.quota {
+            self.quota_window = previous.quota_window.clone();
+        }
+        self.error_factor = previous.error_factor;
+    }
+
+    pub(crate) fn is_standby(&self) -> bool {
+        self.standby
+    }
+
+    pub(crate) fn set_standby(&mut self, standby: bool) {
+        self.standby = standby;
+    }
+
+    pub(crate) fn is_down(&self) -> bool {
+        self.down
+    }
+
+    pub(crate) fn set_down(&mut self, down: bool) {
+        if self.down && !down {
+            // marked up counts as a heartbeat
+            self.up_since = Instant::now();
+            self.recovered_at = Some(self.up_since);
+            self.touched = self.up_since;
+            self.expired = false;
+        }
+        self.down = down;
+    }
+
+    /// health at `now`, given whether the instance is `ejected`
+    pub(crate) fn health(&self, ejected: bool) -> Health {
+        if self.draining {
+            Health::Draining
+        } else if self.paused {
+            Health::Paused
+        } else if self.down {
+            Health::Down
+        } else if ejected {
+            Health::Ejected
+        } else {
+            Health::Up
+        }
+    }
+
+    /// note the current `health`, returning the previous one
+    pub(crate) fn notice_health(&mut self, health: Health) -> Health {
+        std::mem::replace(&mut self.health, health)
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub(crate) fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining
+    }
+
+    /// take the instance out of rotation for good, tracking its selections in flight
+    pub(crate) fn drain(&mut self) -> Drain {
+        self.draining = true;
+        self.track_drain()
+    }
+
+    /// track the selections in flight of a draining instance
+    pub(crate) fn track_drain(&self) -> Drain {
+        Drain::new(&self.shared.in_flight)
+    }
+
+    /// mark down an instance whose ttl is over
+    pub(crate) fn expire(&mut self) {
+        self.set_down(true);
+        self.expired = true;
+    }
+
+    /// refresh the heartbeat, true if it marks up an expired instance
+    pub(crate) fn touch(&mut self) -> bool {
+        self.touched = Instant::now();
+        if self.expired {
+            self.set_down(false);
+            return true;
+        }
+        false
+    }
+
+    /// last heartbeat, or when added
+    pub(crate) fn touched(&self) -> Instant {
+        self.touched
+    }
+
+    /// last time the instance entered rotation at `now`: added, marked up or restored from ejection
+    pub(crate) fn up_since(&self, now: Instant) -> Instant {
+        match self.lock_outcomes().restored_at(now) {
+            Some(restored) => restored.max(self.up_since),
+            None => self.up_since,
+        }
+    }
+
+    /// last time the instance came back from a failure at `now`: marked up or restored from ejection
+    pub(crate) fn recovered_at(&self, now: Instant) -> Option<Instant> {
+        match self.lock_outcomes().restored_at(now) {
+            Some(restored) => Some(self.recovered_at.map_or(restored, |at| at.max(restored))),
+            None => self.recovered_at,
+        }
+    }
+
+    /// record the outcome of a request run against the instance, true if `detection` ejects it
+    pub(crate) fn record(&self, success: bool, detection: Option<&OutlierDetection>) -> bool {
+        let consecutive_failures = if success {
+            self.shared.success.fetch_add(1, Ordering::Relaxed);
+            self.shared.consecutive_failures.store(0, Ordering::Relaxed);
+            0
+        } else {
+            self.shared.failure.fetch_add(1, Ordering::Relaxed);
+            self.shared
+                .consecutive_failures
+                .fetch_add(1, Ordering::Relaxed)
+                + 1
+        };
+        let mut outcomes = self.lock_outcomes();
+        outcomes.observe(success);
+        let Some(detection) = detection else {
+            return false;
+        };
+        let ejected = outcomes.record(success, consecutive_failures, detection);
+        if ejected {
+            // restored with a clean record
+            self.shared.consecutive_failures.store(0, Ordering::Relaxed);
+        }
+        ejected
+    }
+
+    /// moving error rate of the outcomes recorded, from `0` to `1`
+    pub(crate) fn error_rate(&self) -> f64 {
+        self.lock_outcomes().error_rate()
+    }
+
+    /// factor of the weight applied from the error rate
+    pub(crate) fn error_factor(&self) -> f64 {
+        self.error_factor
+    }
+
+    pub(crate) fn set_error_factor(&mut self, factor: f64) {
+        self.error_factor = factor;
+    }
+
+    /// end of the ejection in progress at `now`, if any
+    pub(crate) fn ejected_until(&self, now: Instant) -> Option<Instant> {
+        self.lock_outcomes().ejected_until(now)
+    }
+
+    // outcomes only hold plain values, a poisoned lock is safe to recover
+    fn lock_outcomes(&self) -> MutexGuard<'_, Outcomes> {
+        self.shared
+            .outcomes
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// mark a selection as in flight, until the returned mark is dropped
+    pub(crate) fn track(&self) -> InFlight {
+        InFlight::new(&self.shared.in_flight)
+    }
+
+    pub(crate) fn in_flight(&self) -> usize {
+        self.shared.in_flight.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn max_in_flight(&self) -> Option<usize> {
+        self.max_in_flight
+    }
+
+    pub(crate) fn set_max_in_flight(&mut self, limit: Option<usize>) {
+        self.max_in_flight = limit;
+    }
+
+    pub(crate) fn quota(&self) -> Option<(usize, Duration)> {
+        self.quota
+    }
+
+    pub(crate) fn set_quota(&mut self, quota: Option<(usize, Duration)>) {
+        self.quota = quota;
+        // the snapshots still sharing the window keep the previous quota
+        self.quota_window = Arc::new(Mutex::new(QuotaWindow::default()));
+    }
+
+    /// true if the instance is under its limits, counting the selection against its quota,
+    /// or the selection skipping it otherwise
+    pub(crate) fn admit(&self) -> bool {
+        if self
+            .max_in_flight
+            .is_some_and(|limit| self.in_flight() >= limit)
+        {
+            self.shared.saturated.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        let Some((limit, window)) = self.quota else {
+            return true;
+        };
+        let mut quota = self.lock_quota_window();
+        let now = Instant::now();
+        // fixed windows, starting with the first selection
+        if quota
+            .start
+            .is_none_or(|start| now.saturating_duration_since(start) >= window)
+        {
+            quota.start = Some(now);
+            quota.count = 0;
+        }
+        if quota.count >= limit {
+            self.shared.throttled.fetch_add(1, Ordering::Relaxed);
+            return false;
+        }
+        quota.count += 1;
+        true
+    }
+
+    // the window only holds plain values, a poisoned lock is safe to recover
+    fn lock_quota_window(&self) -> MutexGuard<'_, QuotaWindow> {
+        self.quota_window
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// fold a request latency into the average, decaying past samples over `decay`
+    pub(crate) fn observe(&self, latency: Duration, decay: Duration) {
+        let mut ewma = self.lock_latency();
+        let now = Instant::now();
+        let sample = latency.as_nanos() as f64;
+        ewma.cost = match ewma.stamp {
+            Some(stamp) if sample <= ewma.cost => {
+                let elapsed = now.saturating_duration_since(stamp).as_secs_f64();
+                let w = (-elapsed / decay.as_secs_f64().max(f64::MIN_POSITIVE)).exp();
+                ewma.cost * w + sample * (1.0 - w)
+            }
+            _ => sample,
+        };
+        ewma.stamp = Some(now);
+    }
+
+    /// average latency in nanoseconds, `0` until a latency is reported
+    pub(crate) fn latency(&self) -> f64 {
+        self.lock_latency().cost
+    }
+
+    // the average only holds plain numbers, a poisoned lock is safe to recover
+    fn lock_latency(&self) -> MutexGuard<'_, PeakEwma> {
+        self.shared
+            .latency
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn stats(&self) -> InstanceStats {
+        InstanceStats {
+            success: self.shared.success.load(Ordering::Relaxed),
+            failure: self.shared.failure.load(Ordering::Relaxed),
+            consecutive_failures: self.shared.consecutive_failures.load(Ordering::Relaxed),
+            saturated: self.shared.saturated.load(Ordering::Relaxed),
+            throttled: self.shared.throttled.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// health of an instance, whether it is in rotation and why not
+///
+/// an instance out of rotation for several reasons reports the most lasting one:
+/// draining, then paused, down and ejected
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub enum Health {
+    /// in rotation
+    Up,
+    /// ejected by outlier detection, see [`WrrQueue::outlier_detection`](crate::WrrQueue::outlier_detection)
+    Ejected,
+    /// marked down, e.g. by a health check or its ttl, see [`WrrQueue::mark_down`](crate::WrrQueue::mark_down)
+    Down,
+    /// paused, see [`WrrQueue::pause`](crate::WrrQueue::pause)
+    Paused,
+    /// draining, see [`WrrQueue::drain`](crate::WrrQueue::drain)
+    Draining,
+}
+
+/// health of every instance of a queue, returned by [`WrrQueue::health_summary`](crate::WrrQueue::health_summary)
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::WrrQueue;
+///
+/// let queue: WrrQueue<&str> = WrrQueue::new();
+/// let summary = queue.health_summary();
+/// assert_eq!(0, summary.up);
+/// assert!(summary.instances.is_empty());
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct HealthSummary<'a, T> {
+    /// number of instances in rotation
+    pub up: usize,
+    /// number of instances ejected by outlier detection
+    pub ejected: usize,
+    /// number of instances marked down
+    pub down: usize,
+    /// number of instances paused
+    pub paused: usize,
+    /// number of instances draining
+    pub draining: usize,
+    /// health of each instance, in insertion order
+    pub instances: Vec<(&'a T, Health)>,
+}
+
+impl<T> Default for HealthSummary<'_, T> {
+    fn default() -> Self {
+        HealthSummary {
+            up: 0,
+            ejected: 0,
+            down: 0,
+            paused: 0,
+            draining: 0,
+            instances: Vec::new(),
+        }
+    }
+}
+
+impl<'a, T> HealthSummary<'a, T> {
+    pub(crate) fn push(&mut self, data: &'a T, health: Health) {
+        *match health {
+            Health::Up => &mut self.up,
+            Health::Ejected => &mut self.ejected,
+            Health::Down => &mut self.down,
+            Health::Paused => &mut self.paused,
+            Health::Draining => &mut self.draining,
+        } += 1;
+        self.instances.push((data, health));
+    }
+}
+
+/// snapshot of the outcomes reported for an instance
+///
+/// example:
+/// ```rust
+/// use async_wrr_queue::{InstanceStats, WrrQueue};
+///
+/// let queue: WrrQueue<&str> = WrrQueue::new();
+/// assert_eq!(None, queue.stats(&"data"));
+/// assert_eq!(0, InstanceStats::default().success);
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone, Copy, Default)]
+pub struct InstanceStats {
+    /// number of successful requests
+    pub success: usize,
+    /// number of failed requests
+    pub failure: usize,
+    /// number of failed requests since the last successful one
+    pub consecutive_failures: usize,
+    /// number of selections skipping the instance at its in-flight limit,
+    /// see [`WrrQueue::set_max_in_flight`](crate::WrrQueue::set_max_in_flight)
+    pub saturated: usize,
+    /// number of selections skipping the instance over its quota,
+    /// see [`WrrQueue::set_quota`](crate::WrrQueue::set_quota)
+    pub throttled: usize,
+}
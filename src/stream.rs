@@ -0,0 +1,54 @@
+use crate::instance::{Instance, Member};
+use crate::sync::Arc;
+use crate::wrr_queue::WrrQueue;
+use futures_core::Stream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+/// stream of selections over a [`WrrQueue`], see [`WrrQueue::select_stream`]
+///
+/// endless, unless the queue has no instance up. The queue is borrowed for as long as the stream
+/// lives, so each instance is cloned into an `Arc` once, and every item only bumps its count
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::WrrQueue;
+/// use futures::StreamExt;
+///
+/// let mut queue = WrrQueue::new();
+/// queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+/// queue
+///     .select_stream()
+///     .zip(jobs)
+///     .for_each_concurrent(8, |(instance, job)| async move { job.run(instance.data()).await })
+///     .await;
+/// ```
+pub struct SelectStream<'a, T: Member> {
+    queue: &'a WrrQueue<T>,
+    instances: Vec<Arc<Instance<T>>>,
+}
+
+impl<'a, T: Member> SelectStream<'a, T> {
+    pub(crate) fn new(queue: &'a WrrQueue<T>, instances: Vec<Arc<Instance<T>>>) -> Self {
+        SelectStream { queue, instances }
+    }
+}
+
+impl<T: Member> Stream for SelectStream<'_, T> {
+    type Item = Arc<Instance<T>>;
+
+    // a select never waits, so the stream is always ready
+    fn poll_next(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let idx = self.queue.select_index().ok();
+        Poll::Ready(idx.map(|idx| self.instances[idx].clone()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.instances.is_empty() {
+            (0, Some(0))
+        } else {
+            (0, None)
+        }
+    }
+}
@@ -0,0 +1,202 @@
+use crate::instance::{checked, Instance, Member};
+use crate::ring;
+use crate::wrr_queue::WrrQueue;
+use std::hash::Hash;
+
+/// deterministic subset of a large pool, balancing over `k` members picked by a client id
+///
+/// each member is ranked by the hash of `(client id, member)`, the `k` best ranked form the subset,
+/// so that clients with different ids spread over the pool and each one keeps at most `k`
+/// connections. Pool changes only swap the members entering or leaving the top `k`.
+/// Members enter the subset regardless of weight, weights then apply inside the subset.
+/// No randomness is involved, the same client id always picks the same subset of the same pool
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::SubsetWrrQueue;
+///
+/// let mut queue = SubsetWrrQueue::new(2, 42);
+/// queue.insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 1usize)]).await;
+/// assert_eq!(2, queue.subset().len());
+/// ```
+pub struct SubsetWrrQueue<T: Member + Hash + Clone> {
+    pool: Vec<Instance<T>>,
+    subset: WrrQueue<T>,
+    size: usize,
+    client_id: u64,
+}
+
+impl<T: Member + Hash + Clone> SubsetWrrQueue<T> {
+    /// balance over `size` members of the pool, picked for `client_id`
+    pub fn new(size: usize, client_id: u64) -> Self {
+        Self::with_queue(WrrQueue::new(), size, client_id)
+    }
+
+    /// balance with `queue`, e.g. to pick its engine, over `size` members of the pool
+    pub fn with_queue(queue: WrrQueue<T>, size: usize, client_id: u64) -> Self {
+        SubsetWrrQueue {
+            pool: Vec::new(),
+            subset: queue,
+            size,
+            client_id,
+        }
+    }
+
+    /// the queue balancing over the current subset
+    pub fn subset(&self) -> &WrrQueue<T> {
+        &self.subset
+    }
+
+    /// number of members in the whole pool
+    pub fn pool_len(&self) -> usize {
+        self.pool.len()
+    }
+
+    /// the members of the pool ranked in the top `size` for the client id
+    fn ranked(&self) -> Vec<&Instance<T>> {
+        let mut ranked: Vec<_> = self
+            .pool
+            .iter()
+            .map(|instance| (ring::hash(&(self.client_id, instance.data())), instance))
+            .collect();
+        ranked.sort_unstable_by_key(|(rank, _)| std::cmp::Reverse(*rank));
+        ranked.truncate(self.size);
+        ranked.into_iter().map(|(_, instance)| instance).collect()
+    }
+
+    /// `(leaving, entering)` members of the subset after a pool change
+    fn diff(&self) -> (Vec<Instance<T>>, Vec<Instance<T>>) {
+        let ranked = self.ranked();
+        let current = self.subset.weights();
+        let leaving = current
+            .iter()
+            .filter(|(data, weight)| {
+                !ranked
+                    .iter()
+                    .any(|i| i.data() == *data && i.weight() == weight)
+            })
+            .map(|(data, weight)| Instance::new_with_weight((*data).clone(), *weight))
+            .collect();
+        let entering = ranked
+            .into_iter()
+            .filter(|i| {
+                !current
+                    .iter()
+                    .any(|(data, weight)| i.data() == *data && i.weight() == weight)
+            })
+            .cloned()
+            .collect();
+        (leaving, entering)
+    }
+
+    fn insert_pool(&mut self, instance: Instance<T>) -> bool {
+        if self.pool.contains(&instance) {
+            return false;
+        }
+        self.pool.push(instance);
+        true
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member + Hash + Clone> SubsetWrrQueue<T> {
+    /// insert a new instance in the pool, and re-calculate the subset
+    pub async fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_pool(instance));
+        self.rebalance().await;
+        res
+    }
+
+    /// insert a new instance vec in the pool, and re-calculate the subset once
+    pub async fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance).is_some_and(|instance| self.insert_pool(instance));
+        }
+        self.rebalance().await;
+        res
+    }
+
+    /// delete certain instance from the pool, and re-calculate the subset
+    pub async fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        let len = self.pool.len();
+        self.pool.retain(|i| *i != instance);
+        self.rebalance().await;
+        self.pool.len() != len
+    }
+
+    /// return the instance selected in the subset, None if the pool is empty
+    pub async fn select(&mut self) -> Option<&Instance<T>> {
+        self.subset.select().await
+    }
+
+    async fn rebalance(&mut self) {
+        let (leaving, entering) = self.diff();
+        if leaving.is_empty() && entering.is_empty() {
+            return;
+        }
+        let mut update = self.subset.update();
+        for instance in leaving {
+            update.delete_instance(instance);
+        }
+        for instance in entering {
+            update.insert(instance);
+        }
+        update.commit().await;
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member + Hash + Clone> SubsetWrrQueue<T> {
+    /// insert a new instance in the pool, and re-calculate the subset
+    pub fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_pool(instance));
+        self.rebalance();
+        res
+    }
+
+    /// insert a new instance vec in the pool, and re-calculate the subset once
+    pub fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
+    where
+        U: TryInto<Instance<T>>,
+    {
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance).is_some_and(|instance| self.insert_pool(instance));
+        }
+        self.rebalance();
+        res
+    }
+
+    /// delete certain instance from the pool, and re-calculate the subset
+    pub fn delete_instance(&mut self, instance: Instance<T>) -> bool {
+        let len = self.pool.len();
+        self.pool.retain(|i| *i != instance);
+        self.rebalance();
+        self.pool.len() != len
+    }
+
+    /// return the instance selected in the subset, None if the pool is empty
+    pub fn select(&mut self) -> Option<&Instance<T>> {
+        self.subset.select()
+    }
+
+    fn rebalance(&mut self) {
+        let (leaving, entering) = self.diff();
+        if leaving.is_empty() && entering.is_empty() {
+            return;
+        }
+        let mut update = self.subset.update();
+        for instance in leaving {
+            update.delete_instance(instance);
+        }
+        for instance in entering {
+            update.insert(instance);
+        }
+        update.commit();
+    }
+}
@@ -0,0 +1,206 @@
+//! synchronization primitives of the queue, swapped for loom's under `--cfg wrr_loom`,
+//! and for single threaded `Rc` and `Cell` ones under the `local` feature
+//!
+//! only `new`, `lock`, `read`, `try_read`, `write` and plain atomic operations are used on them,
+//! so that every access can be driven by the loom model checker
+
+#[cfg(all(wrr_loom, target_has_atomic = "64"))]
+pub(crate) use loom::sync::atomic::AtomicU64;
+#[cfg(wrr_loom)]
+pub(crate) use loom::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(wrr_loom)]
+pub(crate) use loom::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard};
+
+#[cfg(all(not(wrr_loom), not(feature = "local"), target_has_atomic = "64"))]
+pub(crate) use std::sync::atomic::AtomicU64;
+#[cfg(all(not(wrr_loom), not(feature = "local")))]
+pub(crate) use std::sync::atomic::AtomicUsize;
+#[cfg(not(wrr_loom))]
+pub(crate) use std::sync::atomic::Ordering;
+#[cfg(all(not(wrr_loom), not(feature = "local")))]
+pub(crate) use std::sync::Arc;
+
+// the schedule lock of the blocking flavor, and the snapshot slots of a split queue without `arc-swap`
+#[cfg(all(
+    not(wrr_loom),
+    not(feature = "local"),
+    not(feature = "parking_lot"),
+    any(wrr_sync, not(feature = "arc-swap"))
+))]
+pub(crate) use std::sync::RwLock;
+#[cfg(all(
+    not(wrr_loom),
+    not(feature = "local"),
+    not(feature = "parking_lot"),
+    wrr_sync
+))]
+pub(crate) use std::sync::RwLockReadGuard;
+#[cfg(all(not(wrr_loom), not(feature = "local"), not(feature = "parking_lot")))]
+pub(crate) use std::sync::{Mutex, MutexGuard};
+
+#[cfg(all(not(wrr_loom), not(feature = "local"), feature = "parking_lot"))]
+pub(crate) use parking::Mutex;
+#[cfg(all(
+    not(wrr_loom),
+    not(feature = "local"),
+    feature = "parking_lot",
+    any(wrr_sync, not(feature = "arc-swap"))
+))]
+pub(crate) use parking::RwLock;
+#[cfg(all(not(wrr_loom), not(feature = "local"), feature = "parking_lot"))]
+pub(crate) use parking_lot::MutexGuard;
+#[cfg(all(
+    not(wrr_loom),
+    not(feature = "local"),
+    feature = "parking_lot",
+    wrr_sync
+))]
+pub(crate) use parking_lot::RwLockReadGuard;
+
+#[cfg(all(not(wrr_loom), feature = "local"))]
+pub(crate) use local::{AtomicU64, AtomicUsize, Mutex, RwLock};
+#[cfg(all(not(wrr_loom), feature = "local"))]
+pub(crate) use std::cell::{Ref as RwLockReadGuard, RefMut as MutexGuard};
+#[cfg(all(not(wrr_loom), feature = "local"))]
+pub(crate) use std::rc::Rc as Arc;
+
+/// `parking_lot` locks behind the `std` lock API, they are never poisoned
+#[cfg(all(not(wrr_loom), not(feature = "local"), feature = "parking_lot"))]
+mod parking {
+    use std::sync::LockResult;
+    #[cfg(not(feature = "arc-swap"))]
+    use std::sync::{TryLockError, TryLockResult};
+
+    #[cfg(any(wrr_sync, not(feature = "arc-swap")))]
+    #[derive(Debug)]
+    pub(crate) struct RwLock<T>(parking_lot::RwLock<T>);
+
+    #[cfg(any(wrr_sync, not(feature = "arc-swap")))]
+    impl<T> RwLock<T> {
+        pub(crate) fn new(value: T) -> Self {
+            RwLock(parking_lot::RwLock::new(value))
+        }
+
+        pub(crate) fn read(&self) -> LockResult<parking_lot::RwLockReadGuard<'_, T>> {
+            Ok(self.0.read())
+        }
+
+        #[cfg(not(feature = "arc-swap"))]
+        pub(crate) fn try_read(&self) -> TryLockResult<parking_lot::RwLockReadGuard<'_, T>> {
+            self.0.try_read().ok_or(TryLockError::WouldBlock)
+        }
+
+        pub(crate) fn write(&self) -> LockResult<parking_lot::RwLockWriteGuard<'_, T>> {
+            Ok(self.0.write())
+        }
+
+        #[cfg(wrr_sync)]
+        pub(crate) fn clear_poison(&self) {}
+    }
+
+    #[derive(Debug)]
+    pub(crate) struct Mutex<T>(parking_lot::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Mutex(parking_lot::Mutex::new(value))
+        }
+
+        pub(crate) fn lock(&self) -> LockResult<parking_lot::MutexGuard<'_, T>> {
+            Ok(self.0.lock())
+        }
+    }
+}
+
+/// `Cell` and `RefCell` behind the atomic and lock API, for a queue owned by a single thread
+///
+/// the orderings are ignored, and the locks are never poisoned. A lock taken twice panics,
+/// where its `std` counterpart would deadlock
+#[cfg(all(not(wrr_loom), feature = "local"))]
+mod local {
+    use std::cell::{Cell, Ref, RefCell, RefMut};
+    use std::sync::atomic::Ordering;
+    use std::sync::{LockResult, TryLockError, TryLockResult};
+
+    #[derive(Debug, Default)]
+    pub(crate) struct AtomicUsize(Cell<usize>);
+
+    impl AtomicUsize {
+        pub(crate) const fn new(value: usize) -> Self {
+            AtomicUsize(Cell::new(value))
+        }
+
+        pub(crate) fn load(&self, _: Ordering) -> usize {
+            self.0.get()
+        }
+
+        pub(crate) fn store(&self, value: usize, _: Ordering) {
+            self.0.set(value);
+        }
+
+        pub(crate) fn fetch_add(&self, value: usize, _: Ordering) -> usize {
+            self.0.replace(self.0.get().wrapping_add(value))
+        }
+
+        pub(crate) fn fetch_sub(&self, value: usize, _: Ordering) -> usize {
+            self.0.replace(self.0.get().wrapping_sub(value))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct AtomicU64(Cell<u64>);
+
+    impl AtomicU64 {
+        pub(crate) const fn new(value: u64) -> Self {
+            AtomicU64(Cell::new(value))
+        }
+
+        pub(crate) fn load(&self, _: Ordering) -> u64 {
+            self.0.get()
+        }
+
+        pub(crate) fn store(&self, value: u64, _: Ordering) {
+            self.0.set(value);
+        }
+
+        pub(crate) fn fetch_add(&self, value: u64, _: Ordering) -> u64 {
+            self.0.replace(self.0.get().wrapping_add(value))
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct Mutex<T>(RefCell<T>);
+
+    impl<T> Mutex<T> {
+        pub(crate) fn new(value: T) -> Self {
+            Mutex(RefCell::new(value))
+        }
+
+        pub(crate) fn lock(&self) -> LockResult<RefMut<'_, T>> {
+            Ok(self.0.borrow_mut())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    pub(crate) struct RwLock<T>(RefCell<T>);
+
+    impl<T> RwLock<T> {
+        pub(crate) fn new(value: T) -> Self {
+            RwLock(RefCell::new(value))
+        }
+
+        pub(crate) fn read(&self) -> LockResult<Ref<'_, T>> {
+            Ok(self.0.borrow())
+        }
+
+        pub(crate) fn try_read(&self) -> TryLockResult<Ref<'_, T>> {
+            self.0.try_borrow().map_err(|_| TryLockError::WouldBlock)
+        }
+
+        pub(crate) fn write(&self) -> LockResult<RefMut<'_, T>> {
+            Ok(self.0.borrow_mut())
+        }
+
+        pub(crate) fn clear_poison(&self) {}
+    }
+}
@@ -0,0 +1,172 @@
+use crate::instance::{checked, Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::collections::VecDeque;
+
+/// buffered jobs of type `T` from weighted sources `S`, dequeued in weighted round-robin order
+///
+/// producers push jobs into the buffer of their source, `pop` takes the next job from the source
+/// picked by weight, skipping sources with nothing buffered, so that each busy source gets
+/// its weighted share of the consumers, and an idle source does not hold the others back
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::WrrTaskQueue;
+///
+/// let mut queue = WrrTaskQueue::new();
+/// queue.insert_source(("tenant-a", 1usize)).await;
+/// queue.insert_source(("tenant-b", 3usize)).await;
+/// queue.push(&"tenant-a", "job-1");
+/// queue.push(&"tenant-b", "job-2");
+///
+/// while let Some(job) = queue.pop().await {
+///     // run job
+/// }
+/// ```
+pub struct WrrTaskQueue<T, S: Member + Clone> {
+    sources: WrrQueue<S>,
+    buffers: Vec<(S, VecDeque<T>)>,
+    len: usize,
+}
+
+impl<T, S: Member + Clone> Default for WrrTaskQueue<T, S> {
+    fn default() -> Self {
+        WrrTaskQueue {
+            sources: WrrQueue::default(),
+            buffers: Vec::new(),
+            len: 0,
+        }
+    }
+}
+
+impl<T, S: Member + Clone> WrrTaskQueue<T, S> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// buffer `job` for `source`, handing it back if there is no such source
+    pub fn push(&mut self, source: &S, job: T) -> Result<(), T> {
+        match self.position_of(source) {
+            Some(pos) => {
+                self.buffers[pos].1.push_back(job);
+                self.len += 1;
+                Ok(())
+            }
+            None => Err(job),
+        }
+    }
+
+    /// number of jobs buffered across all sources
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// true if no job is buffered
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// number of jobs buffered for `source`, None if there is no such source
+    pub fn source_len(&self, source: &S) -> Option<usize> {
+        self.position_of(source)
+            .map(|pos| self.buffers[pos].1.len())
+    }
+
+    fn position_of(&self, source: &S) -> Option<usize> {
+        self.buffers.iter().position(|(s, _)| s == source)
+    }
+
+    fn take(&mut self, pos: usize) -> Option<T> {
+        let job = self.buffers[pos].1.pop_front()?;
+        self.len -= 1;
+        Some(job)
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T, S: Member + Clone> WrrTaskQueue<T, S> {
+    /// add a source with an empty buffer, false if it is already in the queue
+    pub async fn insert_source(&mut self, source: impl TryInto<Instance<S>>) -> bool {
+        let Some(source) = checked(source) else {
+            return false;
+        };
+        if self.position_of(source.data()).is_some() {
+            return false;
+        }
+        self.buffers.push((source.data().clone(), VecDeque::new()));
+        self.sources.insert(source).await
+    }
+
+    /// remove a source, returning the jobs still buffered for it
+    pub async fn delete_source(&mut self, source: Instance<S>) -> Option<VecDeque<T>> {
+        let pos = self.position_of(source.data())?;
+        if !self.sources.delete_instance(source).await {
+            return None;
+        }
+        let (_, jobs) = self.buffers.remove(pos);
+        self.len -= jobs.len();
+        Some(jobs)
+    }
+
+    /// take the next job of the source picked by weight among those with jobs buffered,
+    /// None if every buffer is empty
+    pub async fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        for _ in 0..self.sources.len() {
+            let source = self.sources.select().await?.data();
+            let pos = self.buffers.iter().position(|(s, _)| s == source)?;
+            if let Some(job) = self.take(pos) {
+                return Some(job);
+            }
+        }
+        // every source picked in a full round was idle, take from any busy one
+        let pos = self.buffers.iter().position(|(_, jobs)| !jobs.is_empty())?;
+        self.take(pos)
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T, S: Member + Clone> WrrTaskQueue<T, S> {
+    /// add a source with an empty buffer, false if it is already in the queue
+    pub fn insert_source(&mut self, source: impl TryInto<Instance<S>>) -> bool {
+        let Some(source) = checked(source) else {
+            return false;
+        };
+        if self.position_of(source.data()).is_some() {
+            return false;
+        }
+        self.buffers.push((source.data().clone(), VecDeque::new()));
+        self.sources.insert(source)
+    }
+
+    /// remove a source, returning the jobs still buffered for it
+    pub fn delete_source(&mut self, source: Instance<S>) -> Option<VecDeque<T>> {
+        let pos = self.position_of(source.data())?;
+        if !self.sources.delete_instance(source) {
+            return None;
+        }
+        let (_, jobs) = self.buffers.remove(pos);
+        self.len -= jobs.len();
+        Some(jobs)
+    }
+
+    /// take the next job of the source picked by weight among those with jobs buffered,
+    /// None if every buffer is empty
+    pub fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        for _ in 0..self.sources.len() {
+            let source = self.sources.select()?.data();
+            let pos = self.buffers.iter().position(|(s, _)| s == source)?;
+            if let Some(job) = self.take(pos) {
+                return Some(job);
+            }
+        }
+        // every source picked in a full round was idle, take from any busy one
+        let pos = self.buffers.iter().position(|(_, jobs)| !jobs.is_empty())?;
+        self.take(pos)
+    }
+}
@@ -0,0 +1,69 @@
+use crate::consts;
+use crate::health::HealthProbe;
+use std::future::Future;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// ready-made [`HealthProbe`] connecting to an address derived from each instance
+///
+/// an instance is healthy if it accepts a TCP connection within the timeout, 5 seconds by
+/// default, which suits backends without an http endpoint such as databases or message brokers.
+/// The connection is closed right away, on the blocking thread pool of tokio.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{HealthCheck, TcpProbe};
+/// use std::time::Duration;
+///
+/// let probe = TcpProbe::new(|host: &String| format!("{host}:5432"));
+/// let checker = HealthCheck::new(Duration::from_secs(5)).spawn(writer.clone(), probe);
+/// ```
+#[derive(Clone)]
+pub struct TcpProbe<F> {
+    addr: F,
+    timeout: Duration,
+}
+
+impl<F> TcpProbe<F> {
+    /// connect to the `host:port` address returned by `addr` for each instance
+    pub fn new<T>(addr: F) -> Self
+    where
+        F: Fn(&T) -> String,
+    {
+        TcpProbe {
+            addr,
+            timeout: consts::DEFAULT_PROBE_TIMEOUT,
+        }
+    }
+
+    /// give up on a connection after `timeout`, counting it as a failure
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<T, F> HealthProbe<T> for TcpProbe<F>
+where
+    F: Fn(&T) -> String + Send + Sync + 'static,
+{
+    fn probe(&self, data: &T) -> impl Future<Output = bool> + Send {
+        let (addr, timeout) = ((self.addr)(data), self.timeout);
+        async move {
+            tokio::task::spawn_blocking(move || connect(&addr, timeout))
+                .await
+                .unwrap_or(false)
+        }
+    }
+}
+
+/// true if any address `addr` resolves to accepts a connection within `timeout`
+fn connect(addr: &str, timeout: Duration) -> bool {
+    let Ok(addr_list) = addr.to_socket_addrs() else {
+        return false;
+    };
+    addr_list
+        .into_iter()
+        .any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok())
+}
@@ -0,0 +1,9 @@
+//! clock of the queue
+//!
+//! `std::time::Instant` panics on `wasm32-unknown-unknown`, where there is no system clock,
+//! so the browser target reads `performance.now()` through `web-time` instead
+
+#[cfg(not(all(target_arch = "wasm32", target_os = "unknown")))]
+pub(crate) use std::time::Instant;
+#[cfg(all(target_arch = "wasm32", target_os = "unknown"))]
+pub(crate) use web_time::Instant;
@@ -0,0 +1,102 @@
+//! diagnostics of the queue, emitted through `tracing` or `log`, whichever is enabled
+//!
+//! `tracing` takes precedence when both are, its events carry structured fields and
+//! recalculations run in a `recalculate` span. With neither, every event compiles away
+
+use crate::engine::Engine;
+use crate::error::WrrError;
+
+/// the schedule outgrew its length limit, and the smooth engine is used instead
+pub(crate) fn schedule_fallback(instances: usize, max_len: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(
+        instances,
+        max_len,
+        "schedule too long, falling back to smooth engine"
+    );
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!(
+        "schedule of {} instances is longer than {} entries, falling back to smooth engine",
+        instances,
+        max_len
+    );
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = (instances, max_len);
+}
+
+/// a schedule of `entries` was calculated over `instances`
+pub(crate) fn recalculated(engine: Engine, instances: usize, entries: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(?engine, instances, entries, "schedule recalculated");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::debug!(
+        "{:?} schedule of {} entries recalculated over {} instances",
+        engine,
+        entries,
+        instances
+    );
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = (engine, instances, entries);
+}
+
+/// membership or health changed, leaving `skipped` of `instances` out of rotation
+pub(crate) fn membership_changed(instances: usize, skipped: usize) {
+    #[cfg(feature = "tracing")]
+    tracing::debug!(instances, skipped, "membership changed");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::debug!(
+        "membership changed, {} of {} instances out of rotation",
+        skipped,
+        instances
+    );
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = (instances, skipped);
+}
+
+/// a selection failed, only a poisoned lock is worth more than a trace
+pub(crate) fn select_failed(error: &WrrError) {
+    #[cfg(feature = "tracing")]
+    if *error == WrrError::Poisoned {
+        tracing::error!(%error, "selection failed");
+    } else {
+        tracing::trace!(%error, "selection failed");
+    }
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    if *error == WrrError::Poisoned {
+        log::error!("selection failed: {}", error);
+    } else {
+        log::trace!("selection failed: {}", error);
+    }
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = error;
+}
+
+/// an insert refused an instance with a zero weight
+pub(crate) fn zero_weight_refused() {
+    #[cfg(feature = "tracing")]
+    tracing::warn!("instance with a zero weight refused");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!("instance with a zero weight refused");
+}
+
+/// a discovery source failed to fetch the members of `name`, the current ones are kept
+#[cfg(wrr_discovery)]
+pub(crate) fn discovery_failed(name: &str, error: &dyn std::fmt::Display) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(name, %error, "discovery failed");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!("discovery of {} failed: {}", name, error);
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = (name, error);
+}
+
+/// a distributed cursor failed to lease positions from `key`, counting on past its last lease
+#[cfg(all(feature = "redis", wrr_async))]
+pub(crate) fn lease_failed(key: &str, error: &dyn std::fmt::Display) {
+    #[cfg(feature = "tracing")]
+    tracing::warn!(key, %error, "cursor lease failed");
+    #[cfg(all(feature = "log", not(feature = "tracing")))]
+    log::warn!("cursor lease from {} failed: {}", key, error);
+    #[cfg(not(any(feature = "tracing", feature = "log")))]
+    let _ = (key, error);
+}
@@ -0,0 +1,115 @@
+use crate::instance::{Instance, Member};
+use crate::wrr_queue::WrrQueue;
+
+/// basis points in 100%, the precision of the split
+const FULL_SPLIT: u64 = 10_000;
+
+/// stable/canary split, sending a fixed percentage of selections to the canary queue
+///
+/// the percentage is kept exactly over every 10000 selections and spread evenly,
+/// whatever the weights of the instances inside each queue, which are balanced on their own.
+/// If the queue picked is empty, the other one is used instead
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{TrafficSplit, WrrQueue};
+///
+/// let mut split = TrafficSplit::new(WrrQueue::new(), WrrQueue::new()).canary_percent(5.0);
+/// split.stable_mut().insert(("v1", 1usize)).await;
+/// split.canary_mut().insert(("v2", 1usize)).await;
+///
+/// // 1 in 20 selections goes to "v2"
+/// let selected = split.select().await;
+/// ```
+pub struct TrafficSplit<T: Member> {
+    stable: WrrQueue<T>,
+    canary: WrrQueue<T>,
+    canary_basis_points: u64,
+    selections: u64,
+}
+
+impl<T: Member> TrafficSplit<T> {
+    /// split between a `stable` and a `canary` queue, sending nothing to the canary yet
+    pub fn new(stable: WrrQueue<T>, canary: WrrQueue<T>) -> Self {
+        TrafficSplit {
+            stable,
+            canary,
+            canary_basis_points: 0,
+            selections: 0,
+        }
+    }
+
+    /// send `percent` of selections to the canary, see [`TrafficSplit::set_canary_percent`]
+    pub fn canary_percent(mut self, percent: f64) -> Self {
+        self.set_canary_percent(percent);
+        self
+    }
+
+    /// change the share of the canary at runtime, rounded to 0.01% and clamped to `0..=100`
+    pub fn set_canary_percent(&mut self, percent: f64) {
+        let basis_points = (percent * 100.0).round().clamp(0.0, FULL_SPLIT as f64);
+        self.canary_basis_points = basis_points as u64;
+    }
+
+    /// the share of selections currently sent to the canary, in percent
+    pub fn canary_share(&self) -> f64 {
+        self.canary_basis_points as f64 / 100.0
+    }
+
+    pub fn stable(&self) -> &WrrQueue<T> {
+        &self.stable
+    }
+
+    pub fn stable_mut(&mut self) -> &mut WrrQueue<T> {
+        &mut self.stable
+    }
+
+    pub fn canary(&self) -> &WrrQueue<T> {
+        &self.canary
+    }
+
+    pub fn canary_mut(&mut self) -> &mut WrrQueue<T> {
+        &mut self.canary
+    }
+
+    /// whether the next selection goes to the canary, falling back to the queue holding instances
+    ///
+    /// selection `k` goes to the canary when `k * share` crosses a whole number,
+    /// which spreads canary selections evenly
+    fn next_is_canary(&mut self) -> bool {
+        let k = self.selections % FULL_SPLIT;
+        self.selections = self.selections.wrapping_add(1);
+        let canary = (k + 1) * self.canary_basis_points / FULL_SPLIT
+            > k * self.canary_basis_points / FULL_SPLIT;
+        if canary {
+            !self.canary.is_empty() || self.stable.is_empty()
+        } else {
+            self.stable.is_empty() && !self.canary.is_empty()
+        }
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member> TrafficSplit<T> {
+    /// return the instance selected in the stable or canary queue, None if both are empty
+    pub async fn select(&mut self) -> Option<&Instance<T>> {
+        if self.next_is_canary() {
+            self.canary.select().await
+        } else {
+            self.stable.select().await
+        }
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member> TrafficSplit<T> {
+    /// return the instance selected in the stable or canary queue, None if both are empty
+    pub fn select(&mut self) -> Option<&Instance<T>> {
+        if self.next_is_canary() {
+            self.canary.select()
+        } else {
+            self.stable.select()
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use crate::instance::{checked, Instance, Member};
+use crate::wrr_queue::WrrQueue;
+use std::num::NonZeroUsize;
+
+/// batch of membership changes to a [`WrrQueue`], created by [`WrrQueue::update`]
+///
+/// changes are only recorded, and applied at once with a single re-calculation on `commit`.
+/// Dropping the guard, or calling `abort`, discards them and leaves the queue untouched
+pub struct Update<'a, T: Member> {
+    queue: &'a mut WrrQueue<T>,
+    changes: Vec<Change<T>>,
+}
+
+enum Change<T: Member> {
+    Insert(Instance<T>),
+    Delete(Instance<T>),
+    UpdateWeight(T, NonZeroUsize),
+}
+
+impl<'a, T: Member> Update<'a, T> {
+    pub(crate) fn new(queue: &'a mut WrrQueue<T>) -> Self {
+        Update {
+            queue,
+            changes: Vec::new(),
+        }
+    }
+
+    /// record a new instance to be inserted
+    pub fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> &mut Self {
+        if let Some(instance) = checked(instance) {
+            self.changes.push(Change::Insert(instance));
+        }
+        self
+    }
+
+    /// record an instance to be deleted
+    pub fn delete_instance(&mut self, instance: Instance<T>) -> &mut Self {
+        self.changes.push(Change::Delete(instance));
+        self
+    }
+
+    /// record a new weight for the instance holding `data`
+    pub fn update_weight(&mut self, data: T, weight: NonZeroUsize) -> &mut Self {
+        self.changes.push(Change::UpdateWeight(data, weight));
+        self
+    }
+
+    /// discard every recorded change
+    pub fn abort(self) {}
+
+    /// apply every recorded change in order, true if all of them took effect
+    fn apply(&mut self) -> bool {
+        let mut res = true;
+        for change in self.changes.drain(..) {
+            res &= match change {
+                Change::Insert(instance) => self.queue.insert_uncalculated(instance),
+                Change::Delete(instance) => self.queue.delete_uncalculated(instance),
+                Change::UpdateWeight(data, weight) => {
+                    self.queue.update_weight_uncalculated(&data, weight)
+                }
+            };
+        }
+        res
+    }
+}
+
+#[cfg(wrr_async)]
+impl<T: Member> Update<'_, T> {
+    /// apply every recorded change, and re-calculate the schedule once
+    ///
+    /// true if every change took effect, e.g. false if an inserted instance already existed
+    pub async fn commit(mut self) -> bool {
+        let res = self.apply();
+        self.queue.recalculate_queue();
+        res
+    }
+}
+
+#[cfg(wrr_sync)]
+impl<T: Member> Update<'_, T> {
+    /// apply every recorded change, and re-calculate the schedule once
+    ///
+    /// true if every change took effect, e.g. false if an inserted instance already existed
+    pub fn commit(mut self) -> bool {
+        let res = self.apply();
+        self.queue.recalculate_queue();
+        res
+    }
+}
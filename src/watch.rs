@@ -0,0 +1,113 @@
+use crate::config::WrrConfig;
+use crate::file::ConfigError;
+use crate::instance::Member;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use notify::{RecursiveMode, Watcher};
+use serde::de::DeserializeOwned;
+use std::path::{Path, PathBuf};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// handle of a [`Writer`] following a config file, created by [`Writer::watch_config`]
+///
+/// each change of the file is read with [`WrrConfig::from_path`], and reconciled with the queue:
+/// members no longer listed are deleted, new ones inserted and weights updated, while kept members
+/// keep their state. Readers see the whole reload at once, in a single published snapshot.
+/// The engine of the file is not applied, the queue keeps its own.
+///
+/// watching stops once the handle is dropped.
+pub struct ConfigWatcher<T: Member> {
+    reader: Reader<T>,
+    task: JoinHandle<()>,
+    _watcher: notify::RecommendedWatcher,
+}
+
+impl<T: Member + Clone + DeserializeOwned + Send + Sync + 'static> Writer<T> {
+    /// move the writer to a background task reloading the config file at `path` on each change
+    ///
+    /// `on_reload` is called after each reload, with the error if the file could not be read,
+    /// in which case the queue is left untouched
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn watch_config<F>(
+        self,
+        path: impl AsRef<Path>,
+        on_reload: F,
+    ) -> Result<ConfigWatcher<T>, notify::Error>
+    where
+        F: FnMut(Result<(), ConfigError>) + Send + 'static,
+    {
+        let path = path.as_ref().to_path_buf();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let file_name = path.file_name().map(|name| name.to_os_string());
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                // reading the file back is an access, only writes and renames trigger a reload
+                let Ok(event) = event else {
+                    return;
+                };
+                let touched = event
+                    .paths
+                    .iter()
+                    .any(|changed| changed.file_name() == file_name.as_deref());
+                if touched && (event.kind.is_modify() || event.kind.is_create()) {
+                    let _ = sender.send(());
+                }
+            })?;
+        // editors often replace the file, which drops a watch on the file itself
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        let reader = self.reader();
+        let task = tokio::spawn(run(self, path, receiver, on_reload));
+        Ok(ConfigWatcher {
+            reader,
+            task,
+            _watcher: watcher,
+        })
+    }
+}
+
+impl<T: Member> ConfigWatcher<T> {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<T> {
+        self.reader.clone()
+    }
+}
+
+impl<T: Member> Drop for ConfigWatcher<T> {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn run<T, F>(
+    mut writer: Writer<T>,
+    path: PathBuf,
+    mut receiver: mpsc::UnboundedReceiver<()>,
+    mut on_reload: F,
+) where
+    T: Member + Clone + DeserializeOwned,
+    F: FnMut(Result<(), ConfigError>),
+{
+    while receiver.recv().await.is_some() {
+        // a single write may raise several events, reload once for all of them
+        while receiver.try_recv().is_ok() {}
+        match WrrConfig::from_path(&path) {
+            Ok(config) => {
+                let (_, instances) = config.into_parts();
+                if writer.pending.reconcile_uncalculated(instances) {
+                    writer.publish().await;
+                }
+                on_reload(Ok(()));
+            }
+            Err(e) => {
+                trace::discovery_failed(&path.to_string_lossy(), &e);
+                on_reload(Err(e));
+            }
+        }
+    }
+}
@@ -1,7 +1,57 @@
-use crate::instance::Instance;
-use log::error;
-use num::integer::lcm;
-use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(wrr_async)]
+use crate::attempt;
+use crate::config::WrrConfig;
+use crate::consts::{self, InlineVec};
+use crate::cursor::Cursor;
+#[cfg(wrr_discovery)]
+use crate::discover::{Discover, DiscoverWatcher};
+use crate::drain::Drain;
+#[cfg(wrr_sync)]
+use crate::engine::IndexSet;
+use crate::engine::{self, Deficits, Engine, Excluded, Skip};
+use crate::env::EnvConfigError;
+use crate::error::{RetryError, WrrError};
+#[cfg(feature = "tokio")]
+use crate::events::{Events, QueueEvent};
+use crate::expiry::Expiry;
+#[cfg(feature = "config-file")]
+use crate::file::ConfigError;
+#[cfg(feature = "serde")]
+use crate::format::FormatVersion;
+use crate::hooks::Hooks;
+use crate::in_flight::InFlight;
+#[cfg(feature = "hash")]
+use crate::index::MemberIndex;
+use crate::instance::{checked, Instance, Member};
+use crate::lease::{Lease, Leases};
+use crate::lock::{Lock, ScheduleLock};
+#[cfg(feature = "tokio")]
+use crate::membership::Membership;
+use crate::outlier::OutlierDetection;
+use crate::qos::QosClass;
+#[cfg(all(feature = "redis", wrr_async))]
+use crate::redis_cursor::RedisCursor;
+use crate::ring::{self, HashRing};
+use crate::schedule::Schedule;
+#[cfg(wrr_sync)]
+use crate::selections::Selections;
+use crate::shift::WeightShift;
+use crate::snapshot::{MemberSnapshot, QueueSnapshot};
+use crate::split::{self, Reader, Writer};
+use crate::state::{Health, HealthSummary, InstanceState, InstanceStats};
+#[cfg(all(feature = "stream", any(wrr_async, wrr_async_wrapper)))]
+use crate::stream::SelectStream;
+use crate::sync::{Arc, AtomicUsize, Mutex, MutexGuard, Ordering};
+use crate::time::Instant;
+use crate::trace;
+use crate::update::Update;
+use std::collections::hash_map::RandomState;
+#[cfg(wrr_async)]
+use std::future::Future;
+use std::hash::{BuildHasher, Hash};
+use std::num::NonZeroUsize;
+use std::sync::PoisonError;
+use std::time::Duration;
 
 /// weighted round robin queue struct
 ///
@@ -11,6 +61,13 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 /// `select` method requires only an atomic usize and a Read access to the RwLock.
 /// There should be of no runtime performance issue.
 ///
+/// once the schedule is calculated, `select` performs no heap allocation and no formatting.
+/// A recalculation allocates the new schedule: one pending in lazy mode is run on the first select
+/// after a change, and so is one due to a time-driven change noticed by `select`, such as
+/// the end of a ttl or of an ejection, or weights re-scaled by latency, error rate, slow start or cooldown.
+///
+/// see [`Engine`] for how the schedule is computed.
+///
 /// example:
 ///
 /// ```ignore
@@ -24,102 +81,1965 @@ use std::sync::atomic::{AtomicUsize, Ordering};
 /// let selected2 = queue.select();
 /// let selected3 = queue.select();
 /// ```
-pub struct WrrQueue<T: PartialEq> {
-    instance_list: Vec<Instance<T>>,
-    cur_idx: AtomicUsize,
+pub struct WrrQueue<T: Member> {
+    instance_list: InlineVec<Instance<T>>,
+    state_list: InlineVec<InstanceState>,
+    #[cfg(feature = "hash")]
+    index: MemberIndex,
+    cursor: Cursor,
+    engine: Engine,
+    lazy: bool,
+    start_offset: u64,
+    interleaved: bool,
+    ewma_decay: Duration,
+    dirty: bool,
+    schedule_fallback: bool,
+    max_schedule_len: Option<usize>,
+    approximation_error: Option<f64>,
+    smooth_weight: Mutex<InlineVec<i128>>,
+    ring: Mutex<HashRing>,
+    maglev_size: Option<usize>,
+    deficits: Mutex<Deficits>,
+    deficit_quantum: usize,
+    weight_shift: Option<WeightShift<T>>,
+    classes: Vec<QosClass<T>>,
+    #[cfg(feature = "bandit")]
+    bandit_min_share: f64,
+    outlier: Option<OutlierDetection>,
+    /// `(min, max)` factors of the weights scaled inversely to latency
+    latency_weighting: Option<(f64, f64)>,
+    /// `(min factor, hysteresis)` of the weights scaled down by error rate
+    error_weighting: Option<(f64, f64)>,
+    /// `(window, min factor)` of the weights ramped up after entering rotation
+    slow_start: Option<(Duration, f64)>,
+    /// `(period, factor)` of the weights cut after recovering from a failure
+    cooldown: Option<(Duration, f64)>,
+    hooks: Option<std::sync::Arc<dyn Hooks<T>>>,
+    /// events sent to the receivers of `subscribe`, once subscribed
     #[cfg(feature = "tokio")]
-    select_queue: tokio::sync::RwLock<Vec<usize>>,
-    #[cfg(feature = "blocking")]
-    select_queue: std::sync::RwLock<Vec<usize>>,
+    events: Option<Events<T>>,
+    adaptive_refreshed: Option<Instant>,
+    /// weights in rotation, aligned with `instance_list`, see `refresh_effective_weights`
+    effective: InlineVec<usize>,
+    /// number of ejections so far, bumped by the outcomes recorded through `&self`,
+    /// shared by the snapshots of a split queue like the runtime state of the instances
+    ejections: Arc<AtomicUsize>,
+    /// ejections already left out of rotation
+    seen_ejections: usize,
+    /// end of the earliest ejection in progress
+    next_restore: Option<Instant>,
+    /// true if an instance has an in-flight limit or a quota
+    capped: bool,
+    ttl: Option<(Duration, Expiry)>,
+    /// earliest end of the ttl of an instance up
+    next_expiry: Option<Instant>,
+    /// indices left out of rotation: instances marked down, paused, draining or ejected,
+    /// and standby ones while an active instance is up
+    skipped: Vec<usize>,
+    /// membership generations published on each change, once watched
+    #[cfg(feature = "tokio")]
+    membership: Option<tokio::sync::watch::Sender<Membership>>,
+    select_queue: ScheduleLock,
 }
 
-impl<T: PartialEq> Default for WrrQueue<T> {
+impl<T: Member> Default for WrrQueue<T> {
     /// create a default WRR Queue, with no data
     fn default() -> Self {
         WrrQueue {
-            instance_list: Vec::new(),
-            cur_idx: AtomicUsize::new(0),
-
+            instance_list: InlineVec::new(),
+            state_list: InlineVec::new(),
+            #[cfg(feature = "hash")]
+            index: MemberIndex::default(),
+            cursor: Cursor::default(),
+            engine: Engine::default(),
+            lazy: false,
+            start_offset: 0,
+            interleaved: false,
+            ewma_decay: consts::DEFAULT_EWMA_DECAY,
+            dirty: false,
+            schedule_fallback: false,
+            max_schedule_len: None,
+            approximation_error: None,
+            smooth_weight: Mutex::new(InlineVec::new()),
+            ring: Mutex::new(HashRing::default()),
+            maglev_size: None,
+            deficits: Mutex::new(Deficits::default()),
+            deficit_quantum: 1,
+            weight_shift: None,
+            classes: Vec::new(),
+            #[cfg(feature = "bandit")]
+            bandit_min_share: consts::DEFAULT_BANDIT_MIN_SHARE,
+            outlier: None,
+            latency_weighting: None,
+            error_weighting: None,
+            slow_start: None,
+            cooldown: None,
+            hooks: None,
             #[cfg(feature = "tokio")]
-            select_queue: tokio::sync::RwLock::new(Vec::new()),
-            #[cfg(feature = "blocking")]
-            select_queue: std::sync::RwLock::new(Vec::new()),
+            events: None,
+            adaptive_refreshed: None,
+            effective: InlineVec::new(),
+            ejections: Arc::new(AtomicUsize::new(0)),
+            seen_ejections: 0,
+            next_restore: None,
+            capped: false,
+            ttl: None,
+            next_expiry: None,
+            skipped: Vec::new(),
+            #[cfg(feature = "tokio")]
+            membership: None,
+            select_queue: Lock::new(Schedule::default()),
         }
     }
 }
 
-impl<T: PartialEq> WrrQueue<T> {
+impl<T: Member> WrrQueue<T> {
     pub fn new() -> Self {
         Self::default()
     }
 
-    fn insert_uncalculated(&mut self, instance: Instance<T>) -> bool {
-        if self.instance_list.contains(&instance) {
+    /// create an empty WRR Queue selecting with the given [`Engine`]
+    pub fn with_engine(engine: Engine) -> Self {
+        WrrQueue {
+            engine,
+            ..Self::default()
+        }
+    }
+
+    /// create a WRR Queue holding the instances of `config`, selecting with its engine
+    ///
+    /// duplicated instances are only inserted once
+    pub fn from_config(config: WrrConfig<T>) -> Self {
+        let (engine, instances) = config.into_parts();
+        let mut queue = Self::with_engine(engine);
+        for instance in instances {
+            queue.insert_uncalculated(instance);
+        }
+        queue.recalculate_queue();
+        queue
+    }
+
+    /// only mark the schedule dirty on insert/delete, and re-calculate it on the next select
+    ///
+    /// avoids re-calculating the schedule for each insert when inserting in a loop.
+    /// NOTE: `simulate` reflects pending changes only after the next select
+    pub fn lazy_recalculation(mut self, lazy: bool) -> Self {
+        self.lazy = lazy;
+        self
+    }
+
+    /// stripe the selection cursor across `shards` counters, each thread sticking to one of them
+    ///
+    /// removes the contention on a single atomic counter when many threads select at once.
+    /// Each shard walks the whole schedule on its own, so the weighted distribution is kept
+    /// in aggregate, while consecutive selections of different threads may repeat an instance.
+    /// `0` and `1` keep the single shared cursor
+    pub fn sharded_cursor(mut self, shards: usize) -> Self {
+        self.cursor = Cursor::new(shards);
+        self
+    }
+
+    /// bound the `Expanded` schedule to `max_len` entries, approximating the weights beyond it
+    ///
+    /// when the exact cycle would be longer, weights are scaled down to fit instead of falling back
+    /// to `Smooth`, see [`WrrQueue::approximation_error`] for the achieved error.
+    /// Falls back to `Smooth` only if there are `max_len` instances or more
+    pub fn max_schedule_len(mut self, max_len: usize) -> Self {
+        self.max_schedule_len = Some(max_len);
+        self
+    }
+
+    /// largest relative error of an instance's selection share in the approximated schedule
+    ///
+    /// e.g. `0.05` means every instance gets within 5% of its weighted share.
+    /// None if the schedule is exact
+    pub fn approximation_error(&self) -> Option<f64> {
+        self.approximation_error
+    }
+
+    /// let each thread walk the schedule with its own cursor, seeded from the shared one
+    ///
+    /// selects never synchronize across threads, each thread keeps the weighted distribution
+    /// over its own selections, while the global order is no longer strict.
+    /// Overrides [`WrrQueue::sharded_cursor`]
+    pub fn thread_local_cursor(mut self, enabled: bool) -> Self {
+        self.cursor = if enabled {
+            Cursor::thread_local()
+        } else {
+            Cursor::default()
+        };
+        self
+    }
+
+    /// start selecting from position `seed` of the `Expanded` schedule instead of its head
+    ///
+    /// replicas building the same queue with different seeds do not send their bursts
+    /// to the same instance. The `Smooth` engine always starts at the head of its cycle
+    pub fn start_offset(mut self, seed: u64) -> Self {
+        self.start_offset = seed;
+        self.cursor.store(seed);
+        self
+    }
+
+    /// start selecting from a random position of the `Expanded` schedule, see [`WrrQueue::start_offset`]
+    ///
+    /// the only randomized choice of the selection order, pass a fixed seed to `start_offset` instead
+    /// for reproducible sequences in tests and simulations. The probe interval of a `HealthCheck`
+    /// (feature `tokio`) is jittered at random as well, unless seeded with `HealthCheck::seed`
+    pub fn random_start_offset(self) -> Self {
+        let seed = RandomState::new().hash_one(std::process::id());
+        self.start_offset(seed)
+    }
+
+    /// spread the picks of each instance evenly over the `Expanded` schedule
+    ///
+    /// with skewed weights, e.g. `7, 2, 2`, the smooth cycle may send a burst to the heaviest
+    /// instance, the interleaved one keeps repeats of every instance as far apart as its share
+    /// allows, with the exact same proportions. Weight updates re-calculate instead of patching
+    pub fn interleaved(mut self, enabled: bool) -> Self {
+        self.interleaved = enabled;
+        self
+    }
+
+    /// time over which past latencies fade out of the `PeakEwma` average, 10 seconds by default
+    ///
+    /// a shorter decay reacts faster to an instance recovering, a longer one smooths out noise
+    pub fn peak_ewma_decay(mut self, decay: Duration) -> Self {
+        self.ewma_decay = decay;
+        self
+    }
+
+    /// route [`WrrQueue::select_by_key`] with a Maglev lookup table of `size` slots
+    /// instead of the hash ring, rounded up to a prime, e.g. `65537`
+    ///
+    /// lookups are a single table index, for very high rates of keyed selections.
+    /// Each instance gets a share of the slots in proportion to its weight,
+    /// a larger table follows the weights more closely and moves fewer keys on a change
+    pub fn maglev_table(mut self, size: usize) -> Self {
+        self.maglev_size = Some(size);
+        self
+    }
+
+    /// cost credited per unit of weight on each turn of `DeficitRoundRobin`, `1` by default
+    ///
+    /// set it around the typical cost, e.g. the mean request size in bytes,
+    /// so that an instance serves a few selections per turn instead of one
+    pub fn deficit_quantum(mut self, quantum: usize) -> Self {
+        self.deficit_quantum = quantum;
+        self
+    }
+
+    /// share of its configured traffic each instance keeps under [`Engine::Bandit`],
+    /// however poorly it performs, from `0` to `1`. Defaults to `0.1`
+    ///
+    /// `1` ignores the rewards, `0` stops sending traffic to an instance once others perform better
+    #[cfg(feature = "bandit")]
+    pub fn bandit_min_share(mut self, share: f64) -> Self {
+        self.bandit_min_share = share.clamp(0.0, 1.0);
+        self
+    }
+
+    /// eject instances out of rotation from the outcomes reported, see [`OutlierDetection`]
+    ///
+    /// ejections and restores are applied on the next `select`, `select_by_key` and
+    /// `select_hashed` only see them after it. Ejecting every instance leaves
+    /// the queue selecting None until the first one is restored
+    pub fn outlier_detection(mut self, detection: OutlierDetection) -> Self {
+        self.outlier = Some(detection);
+        self
+    }
+
+    /// scale the weight of each instance by the average latency reported over its own latency,
+    /// bounded to `min_factor..=max_factor`, so that slow instances shed load on their own
+    ///
+    /// latencies are the moving averages of [`WrrQueue::report_latency`], instances with no report
+    /// keep their weight. Weights are re-scaled at most once per second on `select`,
+    /// the keyed selections keep the configured weights, so that keys do not move with latency.
+    /// Configured weights are left untouched, see [`WrrQueue::weights`]
+    pub fn latency_weighting(mut self, min_factor: f64, max_factor: f64) -> Self {
+        let min_factor = min_factor.max(f64::MIN_POSITIVE);
+        self.latency_weighting = Some((min_factor, max_factor.max(min_factor)));
+        self
+    }
+
+    /// scale the weight of each instance down by its recent error rate, to no less than `min_factor`,
+    /// and back up as errors subside
+    ///
+    /// the error rate is a moving average of the outcomes recorded, e.g. by [`WrrQueue::run_on`]
+    /// or [`WrrQueue::report_failure`], over about the last 10 ones. The factor applied only
+    /// moves once the error rate moved by `hysteresis`, and only returns to `1` once the error rate
+    /// is below half of it, so that weights do not flap. Re-scaled at most once per second on `select`,
+    /// see [`WrrQueue::error_rate`] and [`WrrQueue::effective_weight`]
+    pub fn error_rate_weighting(mut self, min_factor: f64, hysteresis: f64) -> Self {
+        self.error_weighting = Some((
+            min_factor.clamp(f64::MIN_POSITIVE, 1.0),
+            hysteresis.clamp(0.0, 1.0),
+        ));
+        self
+    }
+
+    /// ramp the weight of instances entering rotation from `min_factor` of it to all of it over `window`
+    ///
+    /// instances enter rotation when inserted, marked up, or restored from an ejection,
+    /// so that cold caches are not flooded at once. Weights are re-scaled at most once per second
+    /// on `select`, see [`WrrQueue::effective_weight`]
+    pub fn slow_start(mut self, window: Duration, min_factor: f64) -> Self {
+        self.slow_start = Some((window, min_factor.clamp(f64::MIN_POSITIVE, 1.0)));
+        self
+    }
+
+    /// only send `factor` of its weight to an instance for `period` after it recovers from a failure,
+    /// then resume its full weight
+    ///
+    /// an instance recovers when marked up after being down, e.g. by a health check,
+    /// or restored from an ejection, so that it only receives probe-level traffic before flapping again.
+    /// Weights are re-scaled at most once per second on `select`, and never below
+    /// 1/16 of a weight of 1, see [`WrrQueue::effective_weight`]
+    pub fn cooldown(mut self, period: Duration, factor: f64) -> Self {
+        self.cooldown = Some((period, factor.clamp(f64::MIN_POSITIVE, 1.0)));
+        self
+    }
+
+    /// run `hooks` on membership changes, health transitions and selections, see [`Hooks`]
+    pub fn hooks(mut self, hooks: impl Hooks<T> + 'static) -> Self {
+        self.hooks = Some(std::sync::Arc::new(hooks));
+        self
+    }
+
+    /// expire instances not refreshed by [`WrrQueue::touch`] within `ttl`, see [`Expiry`]
+    ///
+    /// for push-based discovery, where instances heartbeat and silence means death.
+    /// The ttl of an instance starts when it is inserted, and expiries are applied on `select`
+    pub fn ttl(mut self, ttl: Duration, expiry: Expiry) -> Self {
+        self.ttl = Some((ttl, expiry));
+        self
+    }
+
+    /// the [`Engine`] used to select instances
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    /// the [`Engine`] actually selecting instances
+    ///
+    /// `Smooth` if the `Expanded` schedule would overflow or be too long to store
+    pub fn active_engine(&self) -> Engine {
+        if self.schedule_fallback {
+            Engine::Smooth
+        } else {
+            self.engine
+        }
+    }
+
+    pub(crate) fn insert_uncalculated(&mut self, instance: Instance<T>) -> bool {
+        self.insert_member(instance, false)
+    }
+
+    fn insert_member(&mut self, instance: Instance<T>, standby: bool) -> bool {
+        if self.position_of_instance(&instance).is_some() {
             false
         } else {
+            #[cfg(feature = "hash")]
+            self.index.insert(instance.data(), self.instance_list.len());
+            let weight = instance.weight().get() as i128;
+            self.lock_smooth_weight().push(weight);
             self.instance_list.push(instance);
+            let mut state = InstanceState::default();
+            state.set_standby(standby);
+            self.state_list.push(state);
+            for hooks in self.all_hooks() {
+                hooks.on_insert(&self.instance_list[self.instance_list.len() - 1]);
+            }
+            self.membership_changed();
             true
         }
     }
 
     fn clear_instance_uncalculated(&mut self) {
+        self.notice_removal();
         self.instance_list = Default::default();
-        self.cur_idx = Default::default();
-        self.select_queue = Default::default();
+        self.state_list = Default::default();
+        #[cfg(feature = "hash")]
+        {
+            self.index = Default::default();
+        }
+        self.cursor.store(self.start_offset);
+        *self.lock_smooth_weight() = InlineVec::new();
+        self.membership_changed();
+        self.dirty = false;
+        self.schedule_fallback = false;
+        self.approximation_error = None;
+        self.write_queue(Schedule::default());
     }
 
-    fn delete_uncalculated(&mut self, instance: Instance<T>) -> bool {
-        if self.instance_list.contains(&instance) {
-            false
+    fn clear_instance_keep_capacity_uncalculated(&mut self) {
+        self.notice_removal();
+        self.instance_list.clear();
+        self.state_list.clear();
+        #[cfg(feature = "hash")]
+        self.index.clear();
+        self.cursor.store(self.start_offset);
+        self.lock_smooth_weight().clear();
+        self.membership_changed();
+        self.dirty = false;
+    }
+
+    pub(crate) fn delete_uncalculated(&mut self, instance: Instance<T>) -> bool {
+        match self.position_of_instance(&instance) {
+            Some(index) => {
+                self.remove_at(index);
+                self.membership_changed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// make the members and weights those of `instances`, the other members are deleted
+    ///
+    /// members kept along the way keep their state, true if anything changed
+    #[cfg(wrr_discovery)]
+    pub(crate) fn reconcile_uncalculated(&mut self, instances: Vec<Instance<T>>) -> bool {
+        let mut changed = false;
+        let mut index = 0;
+        while index < self.instance_list.len() {
+            let data = self.instance_list[index].data();
+            if instances.iter().any(|x| x.data() == data) {
+                index += 1;
+            } else {
+                self.remove_at(index);
+                self.membership_changed();
+                changed = true;
+            }
+        }
+        for instance in instances {
+            changed |= self.upsert_uncalculated(instance);
+        }
+        changed
+    }
+
+    /// insert `instance`, or update the weight of the member holding its data, true if anything changed
+    #[cfg(wrr_discovery)]
+    pub(crate) fn upsert_uncalculated(&mut self, instance: Instance<T>) -> bool {
+        match self.position_of(instance.data()) {
+            Some(index) if self.instance_list[index].weight() == instance.weight() => false,
+            Some(_) => self.update_weight_uncalculated(instance.data(), *instance.weight()),
+            None => self.insert_uncalculated(instance),
+        }
+    }
+
+    /// delete the member holding `data`, false if not in the queue
+    #[cfg(wrr_discovery)]
+    pub(crate) fn remove_uncalculated(&mut self, data: &T) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        self.remove_at(index);
+        self.membership_changed();
+        true
+    }
+
+    /// run the removal hook on every instance, before clearing them
+    fn notice_removal(&self) {
+        for hooks in self.all_hooks() {
+            self.instance_list.iter().for_each(|x| hooks.on_remove(x));
+        }
+    }
+
+    /// run the weight change hook on instance `index`, unless its weight is still `old_weight`
+    fn notice_weight_change(&self, index: usize, old_weight: usize) {
+        let instance = &self.instance_list[index];
+        let Some(from) = NonZeroUsize::new(old_weight).filter(|w| w != instance.weight()) else {
+            return;
+        };
+        for hooks in self.all_hooks() {
+            hooks.on_weight_change(instance, from);
+        }
+    }
+
+    /// hooks registered with `WrrQueue::hooks`, then those sending the events of `WrrQueue::subscribe`
+    fn all_hooks(&self) -> impl Iterator<Item = &dyn Hooks<T>> {
+        #[cfg(feature = "tokio")]
+        let events = self.events.as_ref().map(Events::hooks);
+        #[cfg(not(feature = "tokio"))]
+        let events = None;
+        self.hooks.as_deref().into_iter().chain(events)
+    }
+
+    fn remove_at(&mut self, index: usize) {
+        for hooks in self.all_hooks() {
+            hooks.on_remove(&self.instance_list[index]);
+        }
+        #[cfg(feature = "hash")]
+        self.index.remove(self.instance_list[index].data(), index);
+        self.instance_list.remove(index);
+        self.state_list.remove(index);
+        if index < self.lock_smooth_weight().len() {
+            self.lock_smooth_weight().remove(index);
+        }
+    }
+
+    /// position of the first instance holding `data`
+    #[cfg(not(feature = "hash"))]
+    pub(crate) fn position_of(&self, data: &T) -> Option<usize> {
+        self.instance_list.iter().position(|x| x.data() == data)
+    }
+
+    #[cfg(not(feature = "hash"))]
+    fn position_of_instance(&self, instance: &Instance<T>) -> Option<usize> {
+        self.instance_list.iter().position(|x| x == instance)
+    }
+
+    /// position of the first instance holding `data`
+    #[cfg(feature = "hash")]
+    pub(crate) fn position_of(&self, data: &T) -> Option<usize> {
+        self.index
+            .candidates(data)
+            .iter()
+            .copied()
+            .find(|i| self.instance_list[*i].data() == data)
+    }
+
+    #[cfg(feature = "hash")]
+    fn position_of_instance(&self, instance: &Instance<T>) -> Option<usize> {
+        self.index
+            .candidates(instance.data())
+            .iter()
+            .copied()
+            .find(|i| self.instance_list[*i] == *instance)
+    }
+
+    /// split the queue into a cloneable [`Reader`] only able to select,
+    /// and a single [`Writer`] able to change membership
+    ///
+    /// the current queue becomes the first snapshot seen by readers
+    pub fn split(self) -> (Reader<T>, Writer<T>)
+    where
+        T: Clone,
+    {
+        split::split(self)
+    }
+
+    /// create an uncalculated queue with the same engine and members, with fresh runtime state
+    pub(crate) fn fork(&self) -> WrrQueue<T>
+    where
+        T: Clone,
+    {
+        let mut queue = WrrQueue {
+            instance_list: self.instance_list.clone(),
+            #[cfg(feature = "hash")]
+            index: self.index.clone(),
+            state_list: self.state_list.iter().map(InstanceState::fresh).collect(),
+            cursor: self.cursor.fresh(),
+            max_schedule_len: self.max_schedule_len,
+            interleaved: self.interleaved,
+            ewma_decay: self.ewma_decay,
+            maglev_size: self.maglev_size,
+            deficit_quantum: self.deficit_quantum,
+            outlier: self.outlier.clone(),
+            latency_weighting: self.latency_weighting,
+            error_weighting: self.error_weighting,
+            slow_start: self.slow_start,
+            cooldown: self.cooldown,
+            hooks: self.hooks.clone(),
+            #[cfg(feature = "tokio")]
+            events: self.events.clone(),
+            ttl: self.ttl,
+            #[cfg(feature = "bandit")]
+            bandit_min_share: self.bandit_min_share,
+            ..Self::with_engine(self.engine)
+        };
+        queue.membership_changed();
+        queue
+    }
+
+    /// share the runtime state of the instances also in `previous`, its ejections and its cursor,
+    /// see [`WrrQueue::fork`]
+    pub(crate) fn carry_state(&mut self, previous: &WrrQueue<T>) {
+        self.ejections = previous.ejections.clone();
+        self.cursor = previous.cursor.shared();
+        for (instance, state) in self.instance_list.iter().zip(self.state_list.iter_mut()) {
+            if let Some(index) = previous.position_of(instance.data()) {
+                state.carry(&previous.state_list[index]);
+            }
+        }
+        self.membership_changed();
+    }
+
+    /// true if a time-driven change is due: the end of a ttl or of an ejection,
+    /// or a re-scaling of the weights, applied by the next `recalculate_if_dirty`
+    pub(crate) fn refresh_due(&self) -> bool {
+        if self.ttl.is_none() && self.outlier.is_none() && !self.adaptive() {
+            return false;
+        }
+        let now = Instant::now();
+        self.next_expiry.is_some_and(|at| now >= at)
+            || self.next_restore.is_some_and(|at| now >= at)
+            || self.ejections.load(Ordering::Relaxed) != self.seen_ejections
+            || (self.adaptive()
+                && self.adaptive_refreshed.is_none_or(|at| {
+                    now.saturating_duration_since(at) >= consts::ADAPTIVE_WEIGHT_INTERVAL
+                }))
+    }
+
+    pub(crate) fn instance_at(&self, index: usize) -> &Instance<T> {
+        &self.instance_list[index]
+    }
+
+    pub(crate) fn lease_at(&self, index: usize) -> Lease<'_, T> {
+        Lease::new(self, index, self.state_list[index].track())
+    }
+
+    /// change the weight of the instance holding `data`, false if not in the queue
+    ///
+    /// a single weight change is applied incrementally: the `Smooth` engine picks it up on the fly,
+    /// the `Expanded` schedule is patched in place instead of being re-calculated
+    pub fn update_weight(&mut self, data: &T, weight: NonZeroUsize) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        let old_weight = self.instance_list[index].weight().get();
+        let old_sum = self
+            .weight_vec()
+            .iter()
+            .try_fold(0usize, |s, w| s.checked_add(*w));
+        self.instance_list[index].set_weight(weight);
+        self.notice_weight_change(index, old_weight);
+        self.membership_changed();
+        if !self.schedule_recalculation() {
+            return true;
+        }
+        match (self.engine, self.schedule_fallback) {
+            (Engine::Expanded, false) => {
+                let queue = self.take_queue();
+                // only a schedule holding exactly one pick per weight unit can be patched
+                let patchable = !self.interleaved
+                    && !self.adaptive()
+                    && !self.skipped.contains(&index)
+                    && old_sum == Some(queue.len())
+                    && (queue.len() - old_weight)
+                        .checked_add(weight.get())
+                        .is_some_and(|len| {
+                            len <= self.max_schedule_len.unwrap_or(consts::MAX_SCHEDULE_LEN)
+                        });
+                let queue = if patchable {
+                    engine::patch_schedule(&queue, index, old_weight, weight.get())
+                } else {
+                    self.calculate_queue()
+                };
+                self.write_queue(queue);
+            }
+            (Engine::Expanded, true) => {
+                let queue = self.calculate_queue();
+                self.write_queue(queue);
+            }
+            // the other engines pick weights up on the fly
+            _ => {}
+        }
+        true
+    }
+
+    /// move weight from the instance holding `from` to the one holding `to` over `over`,
+    /// until `to` holds `target_pct` percent of their combined weight
+    ///
+    /// weights are interpolated on each [`WrrQueue::tick`], e.g. called from a timer,
+    /// for blue/green migrations without a sudden cutover. The combined weight is kept,
+    /// neither instance going below `1`. Replaces the shift in progress, if any.
+    /// False if either instance is not in the queue
+    pub fn shift_weight(&mut self, from: &T, to: &T, target_pct: f64, over: Duration) -> bool
+    where
+        T: Clone,
+    {
+        let (Some(from_index), Some(to_index)) = (self.position_of(from), self.position_of(to))
+        else {
+            return false;
+        };
+        self.weight_shift = Some(WeightShift::new(
+            from.clone(),
+            to.clone(),
+            self.instance_list[from_index].weight().get(),
+            self.instance_list[to_index].weight().get(),
+            target_pct,
+            over,
+        ));
+        true
+    }
+
+    /// apply the weights the shift in progress has reached, true while it is not over
+    ///
+    /// the shift is dropped once over, or if either instance left the queue
+    pub fn tick(&mut self) -> bool {
+        let Some(shift) = self.weight_shift.take() else {
+            return false;
+        };
+        let (from_weight, to_weight, done) = shift.weights_at(Instant::now());
+        let mut present = true;
+        for (data, weight) in [(&shift.from, from_weight), (&shift.to, to_weight)] {
+            let weight = NonZeroUsize::new(weight).expect("shifted weights are at least 1");
+            present &= match self.position_of(data) {
+                Some(index) if self.instance_list[index].weight() == &weight => true,
+                Some(_) => self.update_weight(data, weight),
+                None => false,
+            };
+        }
+        if present && !done {
+            self.weight_shift = Some(shift);
+        }
+        present && !done
+    }
+
+    pub(crate) fn update_weight_uncalculated(&mut self, data: &T, weight: NonZeroUsize) -> bool {
+        match self.position_of(data) {
+            Some(index) => {
+                let old_weight = self.instance_list[index].weight().get();
+                self.instance_list[index].set_weight(weight);
+                self.notice_weight_change(index, old_weight);
+                self.membership_changed();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// mark the instance holding `data` as standby, or back as active, false if not in the queue
+    ///
+    /// standby instances are never selected while an active instance is up,
+    /// and enter rotation with their weight once the last active one is deleted or marked down
+    pub fn set_standby(&mut self, data: &T, standby: bool) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        if self.state_list[index].is_standby() != standby {
+            self.state_list[index].set_standby(standby);
+            self.rotation_changed();
+        }
+        true
+    }
+
+    /// take the instance holding `data` out of rotation until [`WrrQueue::resume`],
+    /// false if not in the queue
+    ///
+    /// unlike deleting and inserting it again, its weight, outcomes and latency are kept.
+    /// Unlike [`WrrQueue::mark_down`], a paused instance is not put back in rotation by a health
+    /// check or a heartbeat, only by `resume`
+    pub fn pause(&mut self, data: &T) -> bool {
+        self.set_paused(data, true)
+    }
+
+    /// put the instance holding `data` back in rotation after [`WrrQueue::pause`],
+    /// false if not in the queue
+    pub fn resume(&mut self, data: &T) -> bool {
+        self.set_paused(data, false)
+    }
+
+    /// true if the instance holding `data` is paused, None if not in the queue
+    pub fn is_paused(&self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].is_paused())
+    }
+
+    fn set_paused(&mut self, data: &T, paused: bool) -> bool {
+        match self.set_paused_uncalculated(data, paused) {
+            Some(true) => {
+                if self.schedule_recalculation() {
+                    let queue = self.calculate_queue();
+                    self.write_queue(queue);
+                }
+                true
+            }
+            Some(false) => true,
+            None => false,
+        }
+    }
+
+    /// pause or resume the instance holding `data`, true if it changed, None if not in the queue
+    pub(crate) fn set_paused_uncalculated(&mut self, data: &T, paused: bool) -> Option<bool> {
+        let index = self.position_of(data)?;
+        if self.state_list[index].is_paused() == paused {
+            return Some(false);
+        }
+        self.state_list[index].set_paused(paused);
+        self.membership_changed();
+        Some(true)
+    }
+
+    /// stop selecting the instance holding `data` for good, None if not in the queue
+    ///
+    /// selections already in flight are tracked by their [`InFlight`] marks,
+    /// the returned [`Drain`] completes once they are all dropped,
+    /// the instance can then be deleted safely. Draining cannot be undone, other than by
+    /// deleting and inserting the instance again
+    pub fn drain(&mut self, data: &T) -> Option<Drain> {
+        let index = self.position_of(data)?;
+        let draining = self.state_list[index].is_draining();
+        let drain = self.state_list[index].drain();
+        if !draining {
+            self.rotation_changed();
+        }
+        Some(drain)
+    }
+
+    /// mark the instance holding `data` as draining, true if it changed, None if not in the queue
+    pub(crate) fn drain_uncalculated(&mut self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        if self.state_list[index].is_draining() {
+            return Some(false);
+        }
+        self.state_list[index].drain();
+        self.membership_changed();
+        Some(true)
+    }
+
+    /// track the selections in flight of the instance holding `data`, None if not in the queue
+    pub(crate) fn track_drain(&self, data: &T) -> Option<Drain> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].track_drain())
+    }
+
+    /// true if the instance holding `data` is draining, None if not in the queue
+    pub fn is_draining(&self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].is_draining())
+    }
+
+    /// take the instance holding `data` out of rotation, keeping its weight and state,
+    /// false if not in the queue
+    ///
+    /// the instance is not selected until [`WrrQueue::mark_up`], selecting from a queue
+    /// with every instance down returns None
+    pub fn mark_down(&mut self, data: &T) -> bool {
+        self.set_down(data, true)
+    }
+
+    /// put the instance holding `data` back in rotation, false if not in the queue
+    pub fn mark_up(&mut self, data: &T) -> bool {
+        self.set_down(data, false)
+    }
+
+    /// true unless the instance holding `data` is marked down, None if not in the queue
+    pub fn is_up(&self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        Some(!self.state_list[index].is_down())
+    }
+
+    fn set_down(&mut self, data: &T, down: bool) -> bool {
+        match self.set_down_uncalculated(data, down) {
+            Some(true) => {
+                if self.schedule_recalculation() {
+                    let queue = self.calculate_queue();
+                    self.write_queue(queue);
+                }
+                true
+            }
+            Some(false) => true,
+            None => false,
+        }
+    }
+
+    /// mark the instance holding `data` down or up, true if it changed, None if not in the queue
+    pub(crate) fn set_down_uncalculated(&mut self, data: &T, down: bool) -> Option<bool> {
+        let index = self.position_of(data)?;
+        if self.state_list[index].is_down() == down {
+            return Some(false);
+        }
+        self.state_list[index].set_down(down);
+        self.membership_changed();
+        Some(true)
+    }
+
+    /// refresh the heartbeat of the instance holding `data`, false if not in the queue
+    ///
+    /// an instance marked down by its ttl is back in rotation, see [`WrrQueue::ttl`]
+    pub fn touch(&mut self, data: &T) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        if self.state_list[index].touch() {
+            self.rotation_changed();
+        }
+        true
+    }
+
+    /// apply the expiry of the instances whose ttl is over
+    fn refresh_expiry(&mut self) {
+        let Some((ttl, expiry)) = self.ttl else {
+            return;
+        };
+        let now = Instant::now();
+        if self.next_expiry.is_none_or(|at| now < at) {
+            return;
+        }
+        let mut expired = false;
+        // backwards, so that removing an instance leaves the indices still to visit in place
+        for index in (0..self.state_list.len()).rev() {
+            let state = &self.state_list[index];
+            if state.is_down() || now.saturating_duration_since(state.touched()) < ttl {
+                continue;
+            }
+            expired = true;
+            match expiry {
+                Expiry::MarkDown => self.state_list[index].expire(),
+                Expiry::Remove => self.remove_at(index),
+            }
+        }
+        if !expired {
+            // touched since
+            self.next_expiry = self.next_expiry();
+            return;
+        }
+        self.rotation_changed();
+    }
+
+    /// earliest end of the ttl of an instance up
+    fn next_expiry(&self) -> Option<Instant> {
+        let (ttl, _) = self.ttl?;
+        self.state_list
+            .iter()
+            .filter(|state| !state.is_down())
+            .map(|state| state.touched() + ttl)
+            .min()
+    }
+
+    /// leave out the instances ejected since the last call, and restore those whose ejection is over
+    fn refresh_ejections(&mut self) {
+        if self.outlier.is_none() {
+            return;
+        }
+        let ejections = self.ejections.load(Ordering::Relaxed);
+        let restore = self.next_restore.is_some_and(|at| Instant::now() >= at);
+        if ejections != self.seen_ejections || restore {
+            self.seen_ejections = ejections;
+            self.rotation_changed();
+        }
+    }
+
+    /// re-scale the weights by the latencies and error rates reported,
+    /// at most once per `ADAPTIVE_WEIGHT_INTERVAL`
+    fn refresh_adaptive_weights(&mut self) {
+        if !self.adaptive() {
+            return;
+        }
+        let now = Instant::now();
+        if self
+            .adaptive_refreshed
+            .is_some_and(|at| now.saturating_duration_since(at) < consts::ADAPTIVE_WEIGHT_INTERVAL)
+        {
+            return;
+        }
+        self.adaptive_refreshed = Some(now);
+        if let Some((min_factor, hysteresis)) = self.error_weighting {
+            for state in self.state_list.iter_mut() {
+                let error_rate = state.error_rate();
+                let factor = if error_rate < hysteresis / 2.0 {
+                    1.0
+                } else {
+                    (1.0 - error_rate).max(min_factor)
+                };
+                let applied = state.error_factor();
+                if factor != applied && (factor == 1.0 || (factor - applied).abs() >= hysteresis) {
+                    state.set_error_factor(factor);
+                }
+            }
+        }
+        if self.refresh_effective_weights() && self.schedule_recalculation() {
+            let queue = self.calculate_queue();
+            self.write_queue(queue);
+        }
+    }
+
+    /// apply a change of the instances in rotation, re-calculating the schedule unless lazy
+    fn rotation_changed(&mut self) {
+        self.membership_changed();
+        if self.schedule_recalculation() {
+            let queue = self.calculate_queue();
+            self.write_queue(queue);
+        }
+    }
+
+    /// true if the instance holding `data` is a standby one, None if not in the queue
+    pub fn is_standby(&self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].is_standby())
+    }
+
+    /// start a batch of changes, applied with a single re-calculation on [`Update::commit`]
+    ///
+    /// dropping the guard without committing discards the changes
+    pub fn update(&mut self) -> Update<'_, T> {
+        Update::new(self)
+    }
+
+    fn weight_vec(&self) -> InlineVec<usize> {
+        self.effective.clone()
+    }
+
+    /// true if weights are scaled by latency, error rate or slow start
+    fn adaptive(&self) -> bool {
+        self.latency_weighting.is_some()
+            || self.error_weighting.is_some()
+            || self.slow_start.is_some()
+            || self.cooldown.is_some()
+    }
+
+    /// factor of the weight of instance `index` cooling down since it recovered from a failure
+    fn cool_factor(&self, index: usize, now: Instant) -> f64 {
+        let Some((period, factor)) = self.cooldown else {
+            return 1.0;
+        };
+        match self.state_list[index].recovered_at(now) {
+            Some(at) if now.saturating_duration_since(at) < period => factor,
+            _ => 1.0,
+        }
+    }
+
+    /// factor of the weight of instance `index` ramping up since it entered rotation
+    fn warm_factor(&self, index: usize, now: Instant) -> f64 {
+        let Some((window, min_factor)) = self.slow_start else {
+            return 1.0;
+        };
+        let elapsed = now.saturating_duration_since(self.state_list[index].up_since(now));
+        let ramp = elapsed.as_secs_f64() / window.as_secs_f64().max(f64::MIN_POSITIVE);
+        (min_factor + (1.0 - min_factor) * ramp).min(1.0)
+    }
+
+    /// update the weights in rotation in place, `0` for the instances left out,
+    /// scaled by latency, error rate, slow start and cooldown if enabled, true if any changed
+    ///
+    /// runs on `select` once per `ADAPTIVE_WEIGHT_INTERVAL`, so that it does not allocate
+    fn refresh_effective_weights(&mut self) -> bool {
+        let now = Instant::now();
+        let (reported, count) = self
+            .state_list
+            .iter()
+            .map(|s| s.latency())
+            .filter(|latency| *latency > 0.0)
+            .fold((0.0, 0usize), |(sum, count), latency| {
+                (sum + latency, count + 1)
+            });
+        let average = reported / count.max(1) as f64;
+        self.effective.resize(self.instance_list.len(), 0);
+        let mut changed = false;
+        for i in 0..self.instance_list.len() {
+            let weight = self.effective_weight_at(i, average, now);
+            changed |= std::mem::replace(&mut self.effective[i], weight) != weight;
+        }
+        changed
+    }
+
+    /// weight in rotation of instance `index`, given the `average` latency reported
+    fn effective_weight_at(&self, index: usize, average: f64, now: Instant) -> usize {
+        if self.skipped.contains(&index) {
+            return 0;
+        }
+        let weight = self.instance_list[index].weight().get();
+        if !self.adaptive() {
+            return weight;
+        }
+        // instances with no latency report yet keep their weight
+        let latency = self.state_list[index].latency();
+        let latency_factor = match self.latency_weighting {
+            Some((min_factor, max_factor)) if latency > 0.0 => {
+                (average / latency).clamp(min_factor, max_factor)
+            }
+            _ => 1.0,
+        };
+        let factor = latency_factor
+            * self.state_list[index].error_factor()
+            * self.warm_factor(index, now)
+            * self.cool_factor(index, now);
+        let weight = weight as f64 * factor * consts::ADAPTIVE_WEIGHT_SCALE as f64;
+        (weight.round() as usize).max(1)
+    }
+
+    /// weight the instance holding `data` is selected with, after scaling by latency, error rate,
+    /// slow start and cooldown,
+    /// `0` if out of rotation, None if not in the queue
+    ///
+    /// see [`WrrQueue::latency_weighting`], [`WrrQueue::error_rate_weighting`], [`WrrQueue::slow_start`]
+    /// and [`WrrQueue::cooldown`]
+    pub fn effective_weight(&self, data: &T) -> Option<f64> {
+        let index = self.position_of(data)?;
+        let weight = self.effective[index] as f64;
+        if self.adaptive() {
+            Some(weight / consts::ADAPTIVE_WEIGHT_SCALE as f64)
         } else {
-            let index = self
-                .instance_list
-                .iter()
-                .position(|x| *x == instance)
-                .unwrap();
-            self.instance_list.remove(index);
-            true
+            Some(weight)
         }
     }
+
+    /// moving error rate of the outcomes recorded for the instance holding `data`,
+    /// from `0` to `1`, None if not in the queue
+    ///
+    /// see [`WrrQueue::error_rate_weighting`]
+    pub fn error_rate(&self, data: &T) -> Option<f64> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].error_rate())
+    }
+
+    /// number of instances in the queue
+    pub fn len(&self) -> usize {
+        self.instance_list.len()
+    }
+
+    /// true if there is no instance in the queue
+    pub fn is_empty(&self) -> bool {
+        self.instance_list.is_empty()
+    }
+
+    /// return a snapshot of current members and their weights, in insertion order
+    pub fn weights(&self) -> Vec<(&T, NonZeroUsize)> {
+        self.instance_list
+            .iter()
+            .map(|x| (x.data(), *x.weight()))
+            .collect()
+    }
+
+    /// return the members, their configured weights and the engine, as taken by [`WrrQueue::from_config`]
+    pub fn to_config(&self) -> WrrConfig<T>
+    where
+        T: Clone,
+    {
+        WrrConfig::new(self.instance_list.to_vec()).engine(self.engine)
+    }
+
+    /// return the members, their health and the phase of the cycle, as taken by [`WrrQueue::restore`]
+    pub fn snapshot(&self) -> QueueSnapshot<T>
+    where
+        T: Clone,
+    {
+        let now = Instant::now();
+        let members = self
+            .instance_list
+            .iter()
+            .zip(self.state_list.iter())
+            .map(|(instance, state)| MemberSnapshot {
+                instance: instance.clone(),
+                standby: state.is_standby(),
+                health: state.health(state.ejected_until(now).is_some()),
+            })
+            .collect();
+        let deficits = self.lock_deficits();
+        let (deficit_turn, deficits) = deficits.parts();
+        QueueSnapshot {
+            #[cfg(feature = "serde")]
+            version: FormatVersion,
+            engine: self.engine,
+            members,
+            cursor: self.cursor.load(),
+            smooth_weight: self.lock_smooth_weight().to_vec(),
+            deficit_turn,
+            deficits: deficits.to_vec(),
+        }
+    }
+
+    /// replace the members and the engine with those of `snapshot`, resuming its cycle where it was
+    ///
+    /// the options the queue was built with are kept. Down, paused and draining instances are
+    /// restored as such, ejected ones in rotation. The schedule is re-calculated at once, even in lazy mode
+    pub fn restore(&mut self, snapshot: QueueSnapshot<T>) {
+        self.clear_instance_uncalculated();
+        self.engine = snapshot.engine;
+        for member in snapshot.members {
+            if !self.insert_member(member.instance, member.standby) {
+                continue;
+            }
+            let state = self.state_list.last_mut().expect("just inserted");
+            match member.health {
+                Health::Down => state.set_down(true),
+                Health::Paused => state.set_paused(true),
+                Health::Draining => drop(state.drain()),
+                Health::Up | Health::Ejected => {}
+            }
+        }
+        self.membership_changed();
+        let queue = self.calculate_queue();
+        self.write_queue(queue);
+        // the phase is restored last, as re-calculating starts the cycle over
+        self.cursor.store(snapshot.cursor);
+        if snapshot.smooth_weight.len() == self.instance_list.len() {
+            *self.lock_smooth_weight() = snapshot.smooth_weight.into_iter().collect();
+        }
+        *self.lock_deficits() = Deficits::new(snapshot.deficit_turn, snapshot.deficits);
+    }
+
+    /// return the health of every instance along with the count of each health,
+    /// e.g. for a readiness endpoint
+    ///
+    /// ejections are reported as soon as they happen, and their end as soon as it is over
+    pub fn health_summary(&self) -> HealthSummary<'_, T> {
+        let now = Instant::now();
+        let mut summary = HealthSummary::default();
+        for (x, state) in self.instance_list.iter().zip(self.state_list.iter()) {
+            summary.push(x.data(), state.health(state.ejected_until(now).is_some()));
+        }
+        summary
+    }
+
+    /// return the outcomes reported for the instance holding `data`, None if not in the queue
+    pub fn stats(&self, data: &T) -> Option<InstanceStats> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].stats())
+    }
+
+    /// report a successful request run against the instance holding `data`, false if not in the queue
+    ///
+    /// for requests run outside of [`run_on`](WrrQueue::run_on), which reports its outcomes itself.
+    /// See [`WrrQueue::stats`]
+    pub fn report_success(&self, data: &T) -> bool {
+        self.report(data, true)
+    }
+
+    /// report a failed request run against the instance holding `data`, false if not in the queue
+    ///
+    /// see [`WrrQueue::report_success`]
+    pub fn report_failure(&self, data: &T) -> bool {
+        self.report(data, false)
+    }
+
+    /// true if the instance holding `data` is ejected by outlier detection, None if not in the queue
+    ///
+    /// see [`WrrQueue::outlier_detection`]
+    pub fn is_ejected(&self, data: &T) -> Option<bool> {
+        let index = self.position_of(data)?;
+        Some(
+            self.state_list[index]
+                .ejected_until(Instant::now())
+                .is_some(),
+        )
+    }
+
+    /// record an outcome of instance `index`, counting the ejection it may trigger
+    pub(crate) fn record(&self, index: usize, success: bool) {
+        if self.state_list[index].record(success, self.outlier.as_ref()) {
+            self.ejections.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn report(&self, data: &T, success: bool) -> bool {
+        match self.position_of(data) {
+            Some(index) => {
+                self.record(index, success);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// report the latency of a request run against the instance holding `data`,
+    /// false if not in the queue. See [`Engine::PeakEwma`]
+    pub fn report_latency(&self, data: &T, latency: Duration) -> bool {
+        match self.position_of(data) {
+            Some(index) => {
+                self.state_list[index].observe(latency, self.ewma_decay);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// limit the [`InFlight`] selections of the instance holding `data` to `limit`,
+    /// None to lift it, false if not in the queue
+    ///
+    /// a selection picking an instance at its limit skips to the next instance picked instead,
+    /// counted in [`InstanceStats::saturated`], and returns None if every instance is at its limit.
+    /// Only tracked selections count, the limit is best effort under concurrent selections
+    pub fn set_max_in_flight(&mut self, data: &T, limit: Option<NonZeroUsize>) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        self.state_list[index].set_max_in_flight(limit.map(NonZeroUsize::get));
+        self.capped = self.is_capped();
+        true
+    }
+
+    /// allow at most `limit` selections of the instance holding `data` per `window`,
+    /// None to lift it, false if not in the queue
+    ///
+    /// for backends with hard licensing or contractual caps: once the quota of the current window
+    /// is used up, selections skip to the next instance picked, counted in [`InstanceStats::throttled`],
+    /// and return None if every instance is over its quota. Windows are fixed, starting with
+    /// the first selection, and setting a quota starts it over
+    pub fn set_quota(&mut self, data: &T, quota: Option<(NonZeroUsize, Duration)>) -> bool {
+        let Some(index) = self.position_of(data) else {
+            return false;
+        };
+        self.state_list[index].set_quota(quota.map(|(limit, window)| (limit.get(), window)));
+        self.capped = self.is_capped();
+        true
+    }
+
+    /// true if an instance has an in-flight limit or a quota
+    fn is_capped(&self) -> bool {
+        self.state_list
+            .iter()
+            .any(|s| s.max_in_flight().is_some() || s.quota().is_some())
+    }
+
+    /// number of [`InFlight`] selections of the instance holding `data` not yet dropped,
+    /// None if not in the queue
+    pub fn in_flight(&self, data: &T) -> Option<usize> {
+        let index = self.position_of(data)?;
+        Some(self.state_list[index].in_flight())
+    }
+
+    fn count_selections(&self, queue: &Schedule, n: usize) -> Vec<(&Instance<T>, usize)> {
+        let mut counts = vec![0usize; self.instance_list.len()];
+        match self.active_engine() {
+            Engine::Expanded if !queue.is_empty() => {
+                let start = (self.cursor.load() % queue.len() as u64) as usize;
+                for offset in 0..n {
+                    let idx = (start + offset % queue.len()) % queue.len();
+                    counts[queue.get(idx)] += 1;
+                }
+            }
+            Engine::Expanded => {}
+            Engine::Smooth => {
+                let mut cur_weight = self.lock_smooth_weight().clone();
+                for _ in 0..n {
+                    match engine::smooth_select(&self.effective, &mut cur_weight, &self.skipped) {
+                        Some(idx) => counts[idx] += 1,
+                        None => break,
+                    }
+                }
+            }
+            Engine::RoundRobin if self.skipped.len() < self.instance_list.len() => {
+                let len = self.instance_list.len();
+                let mut pos = (self.cursor.load() % len as u64) as usize;
+                for _ in 0..n {
+                    while self.skipped.contains(&pos) {
+                        pos = (pos + 1) % len;
+                    }
+                    counts[pos] += 1;
+                    pos = (pos + 1) % len;
+                }
+            }
+            Engine::RoundRobin => {}
+            Engine::LeastConnections | Engine::PeakEwma => {
+                // selections simulated as never released, so that each one adds to the load
+                let mut in_flight: Vec<usize> =
+                    self.state_list.iter().map(|s| s.in_flight()).collect();
+                let len = self.instance_list.len();
+                let mut start = self.cursor.load();
+                for _ in 0..n {
+                    let pick = engine::least_loaded(
+                        len,
+                        |i| self.load_cost(i, in_flight[i]),
+                        (start % len.max(1) as u64) as usize,
+                        &self.skipped,
+                    );
+                    let Some(idx) = pick else {
+                        break;
+                    };
+                    counts[idx] += 1;
+                    in_flight[idx] += 1;
+                    start = start.wrapping_add(1);
+                }
+            }
+            Engine::WeightedLeastRequest => {
+                // selections simulated as never released, so that each one adds to the load
+                let mut in_flight: Vec<usize> =
+                    self.state_list.iter().map(|s| s.in_flight()).collect();
+                let mut cur_weight = self.lock_smooth_weight().clone();
+                for _ in 0..n {
+                    let weights = self.least_request_weights(|i| in_flight[i], &self.skipped);
+                    let Some(idx) = engine::smooth_select_weights(&mut cur_weight, weights) else {
+                        break;
+                    };
+                    counts[idx] += 1;
+                    in_flight[idx] += 1;
+                }
+            }
+            #[cfg(feature = "bandit")]
+            Engine::Bandit => {
+                // rewards simulated as fixed, no outcome is reported meanwhile
+                let weights: Vec<usize> = (0..self.instance_list.len())
+                    .map(self.bandit_weights(&self.skipped))
+                    .collect();
+                let mut cur_weight = self.lock_smooth_weight().clone();
+                for _ in 0..n {
+                    match engine::smooth_select_weights(&mut cur_weight, |i| weights[i]) {
+                        Some(idx) => counts[idx] += 1,
+                        None => break,
+                    }
+                }
+            }
+            Engine::DeficitRoundRobin => {
+                let mut deficits = self.lock_deficits().clone();
+                for _ in 0..n {
+                    let pick = engine::deficit_select(
+                        &self.effective,
+                        &mut deficits,
+                        self.deficit_quantum,
+                        1,
+                        &self.skipped,
+                    );
+                    match pick {
+                        Some(idx) => counts[idx] += 1,
+                        None => break,
+                    }
+                }
+            }
+        }
+        self.instance_list.iter().zip(counts).collect()
+    }
+
+    /// pick the next instance index, given the schedule read from the lock
+    fn pick_index(&self, queue: &Schedule, excluded: &(impl Excluded + ?Sized)) -> Option<usize> {
+        let index = if excluded.is_empty() && !self.capped {
+            self.pick_index_skipping(queue, &self.skipped)?
+        } else {
+            let mut skip = Skip::new(&self.skipped, excluded);
+            loop {
+                let index = self.pick_index_skipping(queue, &skip)?;
+                // spill over to the next instance picked
+                if self.state_list[index].admit() {
+                    break index;
+                }
+                skip.refuse(index);
+            }
+        };
+        self.notice_select(index);
+        Some(index)
+    }
+
+    /// report the selection of instance `index` to the hooks, on every selection path
+    fn notice_select(&self, index: usize) {
+        if let Some(hooks) = &self.hooks {
+            hooks.on_select(&self.instance_list[index]);
+        }
+    }
+
+    fn pick_index_skipping(
+        &self,
+        queue: &Schedule,
+        excluded: &(impl Excluded + ?Sized),
+    ) -> Option<usize> {
+        match self.active_engine() {
+            Engine::Expanded => {
+                for _ in 0..queue.len() {
+                    let idx = self.cursor.next(queue.len());
+                    let selected = queue.get(idx);
+                    if !excluded.excludes(selected) {
+                        return Some(selected);
+                    }
+                }
+                None
+            }
+            Engine::Smooth => {
+                engine::smooth_select(&self.effective, &mut self.lock_smooth_weight(), excluded)
+            }
+            Engine::RoundRobin => {
+                let len = self.instance_list.len();
+                (0..len)
+                    .map(|_| self.cursor.next(len))
+                    .find(|selected| !excluded.excludes(*selected))
+            }
+            Engine::LeastConnections | Engine::PeakEwma => engine::least_loaded(
+                self.instance_list.len(),
+                |i| self.load_cost(i, self.state_list[i].in_flight()),
+                self.cursor.next(self.instance_list.len()),
+                excluded,
+            ),
+            Engine::DeficitRoundRobin => self.deficit_pick(1, excluded),
+            Engine::WeightedLeastRequest => engine::smooth_select_weights(
+                &mut self.lock_smooth_weight(),
+                self.least_request_weights(|i| self.state_list[i].in_flight(), excluded),
+            ),
+            #[cfg(feature = "bandit")]
+            Engine::Bandit => engine::smooth_select_weights(
+                &mut self.lock_smooth_weight(),
+                self.bandit_weights(excluded),
+            ),
+        }
+    }
+
+    /// per-selection weight of each instance under [`Engine::WeightedLeastRequest`],
+    /// `0` for the `excluded` instances, computed on the fly so that a pick does not allocate
+    fn least_request_weights<'a>(
+        &'a self,
+        in_flight: impl Fn(usize) -> usize + 'a,
+        excluded: &'a (impl Excluded + ?Sized),
+    ) -> impl Fn(usize) -> usize + 'a {
+        move |i| {
+            if excluded.excludes(i) {
+                0
+            } else {
+                engine::least_request_weight(self.effective[i], in_flight(i))
+            }
+        }
+    }
+
+    /// per-selection weight of each instance under [`Engine::Bandit`], `0` for the `excluded` instances
+    ///
+    /// the sums of the shares are taken up front, each weight is then computed on the fly,
+    /// so that a pick does not allocate
+    #[cfg(feature = "bandit")]
+    fn bandit_weights<'a>(
+        &'a self,
+        excluded: &'a (impl Excluded + ?Sized),
+    ) -> impl Fn(usize) -> usize + 'a {
+        let weight = move |i: usize| {
+            if excluded.excludes(i) {
+                0
+            } else {
+                self.effective[i]
+            }
+        };
+        // instances with no latency report yet count as the fastest, so that they are probed
+        let fastest = self
+            .state_list
+            .iter()
+            .map(|s| s.latency())
+            .filter(|latency| *latency > 0.0)
+            .fold(f64::INFINITY, f64::min);
+        let reward = move |i: usize| {
+            let state = &self.state_list[i];
+            let stats = state.stats();
+            // Laplace smoothed, so that an instance with no outcome yet scores `0.5`
+            let success_rate =
+                (stats.success as f64 + 1.0) / ((stats.success + stats.failure) as f64 + 2.0);
+            let latency = state.latency();
+            let speed = if latency > 0.0 {
+                fastest / latency
+            } else {
+                1.0
+            };
+            success_rate * speed
+        };
+        let shares = engine::BanditShares::new(
+            (0..self.effective.len()).map(|i| (weight(i), reward(i))),
+            self.bandit_min_share,
+        );
+        move |i| shares.weight(weight(i), reward(i))
+    }
+
+    fn deficit_pick(&self, cost: usize, excluded: &(impl Excluded + ?Sized)) -> Option<usize> {
+        engine::deficit_select(
+            &self.effective,
+            &mut self.lock_deficits(),
+            self.deficit_quantum,
+            cost,
+            excluded,
+        )
+    }
+
+    /// load of instance `index` with `in_flight` selections, for the load-aware engines
+    fn load_cost(&self, index: usize, in_flight: usize) -> f64 {
+        let weight = self.effective[index].max(1) as f64;
+        match self.engine {
+            Engine::PeakEwma => {
+                // an instance with no report yet counts as the fastest possible, so that it is probed
+                let latency = self.state_list[index].latency().max(1.0);
+                latency * (in_flight as f64 + 1.0) / weight
+            }
+            _ => in_flight as f64 / weight,
+        }
+    }
+
+    /// re-calculate the schedule now, or only mark it dirty in lazy mode
+    fn schedule_recalculation(&mut self) -> bool {
+        if self.lazy {
+            self.dirty = true;
+        }
+        !self.lazy
+    }
+
+    pub(crate) fn recalculate_if_dirty(&mut self) {
+        self.refresh_expiry();
+        self.refresh_ejections();
+        self.refresh_adaptive_weights();
+        if self.dirty {
+            self.dirty = false;
+            let queue = self.calculate_queue();
+            self.write_queue(queue);
+        }
+    }
+
+    pub(crate) fn recalculate_queue(&mut self) {
+        if !self.schedule_recalculation() {
+            return;
+        }
+        let queue = self.calculate_queue();
+        self.write_queue(queue);
+    }
+
+    pub(crate) fn select_index(&self) -> Result<usize, WrrError> {
+        self.select_index_excluding(&[])
+    }
+
+    pub(crate) fn select_index_excluding(
+        &self,
+        excluded: &(impl Excluded + ?Sized),
+    ) -> Result<usize, WrrError> {
+        if self.instance_list.is_empty() {
+            return Err(WrrError::Empty);
+        }
+        let queue = self.read_queue().inspect_err(trace::select_failed)?;
+        self.pick_index(&queue, excluded).ok_or(WrrError::Empty)
+    }
+
+    fn read_queue(&self) -> Result<<ScheduleLock as Lock>::Guard<'_>, WrrError> {
+        Lock::read(&self.select_queue)
+    }
+
+    fn write_queue(&mut self, queue: Schedule) {
+        Lock::replace(&mut self.select_queue, queue);
+    }
+
+    fn take_queue(&mut self) -> Schedule {
+        Lock::replace(&mut self.select_queue, Schedule::default())
+    }
+
+    fn clear_queue_keep_capacity(&mut self) {
+        Lock::clear(&mut self.select_queue);
+    }
+
+    /// compute the schedule to be stored in the lock, carrying the smooth state over
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(name = "recalculate", level = "debug", skip_all)
+    )]
+    fn calculate_queue(&mut self) -> Schedule {
+        let queue = self.build_queue();
+        trace::recalculated(self.engine, self.instance_list.len(), queue.len());
+        queue
+    }
+
+    fn build_queue(&mut self) -> Schedule {
+        let weight_vec = self.weight_vec();
+        let cur_weight = std::mem::take(&mut *self.lock_smooth_weight());
+        *self.lock_smooth_weight() = engine::carried_weights(&weight_vec, &cur_weight);
+        self.schedule_fallback = false;
+        self.approximation_error = None;
+        match self.engine {
+            Engine::Expanded => {
+                let max_len = self.max_schedule_len.unwrap_or(consts::MAX_SCHEDULE_LEN);
+                let schedule = if self.interleaved {
+                    engine::interleaved_schedule
+                } else {
+                    engine::expanded_schedule
+                };
+                if let Some(queue) = schedule(&weight_vec, max_len) {
+                    return queue;
+                }
+                let approximated = self
+                    .max_schedule_len
+                    .and_then(|_| engine::scaled_weights(&weight_vec, max_len))
+                    .and_then(|scaled| {
+                        let queue = schedule(&scaled, max_len)?;
+                        Some((queue, engine::share_error(&weight_vec, &scaled)))
+                    });
+                match approximated {
+                    Some((queue, error)) => {
+                        self.approximation_error = Some(error);
+                        queue
+                    }
+                    None => {
+                        trace::schedule_fallback(weight_vec.len(), max_len);
+                        self.schedule_fallback = true;
+                        Schedule::default()
+                    }
+                }
+            }
+            _ => Schedule::default(),
+        }
+    }
+
+    /// register a traffic class, selecting over the same members with the weight of each instance
+    /// listed in `multipliers` multiplied by its multiplier, and replacing the class of the same name
+    ///
+    /// instances not listed keep their weight, a multiplier of `0` keeps the class off an instance.
+    /// Class weights are rounded to whole numbers, see [`WrrQueue::select_for_class`]
+    pub fn register_class(&mut self, name: &str, multipliers: impl IntoIterator<Item = (T, f64)>) {
+        self.classes.retain(|class| class.name != name);
+        self.classes.push(QosClass::new(name, multipliers));
+    }
+
+    /// forget a traffic class, false if it was not registered
+    pub fn unregister_class(&mut self, name: &str) -> bool {
+        let len = self.classes.len();
+        self.classes.retain(|class| class.name != name);
+        self.classes.len() != len
+    }
+
+    /// return the instance selected with the weights of traffic class `name`,
+    /// None if the class is not registered or has no instance to select
+    ///
+    /// each class runs its own smooth weighted round-robin, independent of [`WrrQueue::select`]
+    pub fn select_for_class(&mut self, name: &str) -> Option<&Instance<T>> {
+        let class = self.classes.iter_mut().find(|class| class.name == name)?;
+        let index = class.select(&self.instance_list, &self.skipped)?;
+        self.notice_select(index);
+        self.instance_list.get(index)
+    }
+
+    /// return the instance owning `key` on a consistent hash ring, None if instance_list is empty
+    ///
+    /// each instance owns points of the ring in proportion to its weight, so that keys are spread
+    /// by weight, while a key keeps going to the same instance as long as it is in the queue.
+    /// Membership changes only move the keys of the points added or removed.
+    /// Weights above 8192 are capped, the ring is built on the first call after a change.
+    /// See [`WrrQueue::maglev_table`] for O(1) lookups
+    pub fn select_by_key<K: Hash + ?Sized>(&self, key: &K) -> Option<&Instance<T>>
+    where
+        T: Hash,
+    {
+        let mut ring = self.lock_ring();
+        if ring.is_empty() && !self.instance_list.is_empty() {
+            *ring = HashRing::new(&self.instance_list, &self.skipped, self.maglev_size);
+        }
+        let index = ring.get(key)?;
+        drop(ring);
+        self.notice_select(index);
+        self.instance_list.get(index)
+    }
+
+    /// return the instance owning `key` by weighted rendezvous hashing, None if instance_list is empty
+    ///
+    /// each instance owns a share of the keys in proportion to its weight, and a key keeps going
+    /// to the same instance as long as it is in the queue, deleting an instance only moves its keys.
+    /// Unlike [`WrrQueue::select_by_key`] nothing is built or cached, each lookup is O(n).
+    /// Leaves the cursor of [`select`](WrrQueue::select) untouched, so that both serve the same members
+    pub fn select_hashed<K: Hash + ?Sized>(&self, key: &K) -> Option<&Instance<T>>
+    where
+        T: Hash,
+    {
+        let index = ring::rendezvous(key, &self.instance_list, &self.skipped)?;
+        self.notice_select(index);
+        self.instance_list.get(index)
+    }
+
+    // drop the ring, to be re-built on the next `select_by_key`, start deficits over,
+    // and leave down and ejected instances out of rotation, standby ones too as long as an active one is up
+    fn membership_changed(&mut self) {
+        *self.lock_ring() = HashRing::default();
+        *self.lock_deficits() = Deficits::default();
+        let now = Instant::now();
+        let ejected: Vec<Option<Instant>> = self
+            .state_list
+            .iter()
+            .map(|state| state.ejected_until(now))
+            .collect();
+        self.next_restore = ejected.iter().flatten().min().copied();
+        self.next_expiry = self.next_expiry();
+        self.capped = self.is_capped();
+        let mut healths = Vec::with_capacity(self.state_list.len());
+        for (i, ejected) in ejected.iter().enumerate() {
+            let health = self.state_list[i].health(ejected.is_some());
+            let from = self.state_list[i].notice_health(health);
+            if from != health {
+                for hooks in self.all_hooks() {
+                    hooks.on_state_change(&self.instance_list[i], from, health);
+                }
+            }
+            healths.push(health);
+        }
+        let out = |i: usize| healths[i] != Health::Up;
+        let active_up =
+            (0..self.state_list.len()).any(|i| !self.state_list[i].is_standby() && !out(i));
+        let skipped: Vec<usize> = (0..self.state_list.len())
+            .filter(|i| out(*i) || (active_up && self.state_list[*i].is_standby()))
+            .collect();
+        let changed = skipped != self.skipped;
+        trace::membership_changed(self.state_list.len(), skipped.len());
+        self.skipped = skipped;
+        self.refresh_effective_weights();
+        if changed {
+            // the smooth state of instances out of rotation has drifted, start the cycle over
+            *self.lock_smooth_weight() = engine::initial_weights(&self.weight_vec());
+        }
+        #[cfg(feature = "tokio")]
+        if let Some(sender) = &self.membership {
+            let (len, in_rotation) = (self.instance_list.len(), self.in_rotation());
+            sender.send_modify(|membership| {
+                membership.generation += 1;
+                membership.len = len;
+                membership.in_rotation = in_rotation;
+            });
+        }
+    }
+
+    // deficits only hold plain integers, a poisoned lock is safe to recover
+    fn lock_deficits(&self) -> MutexGuard<'_, Deficits> {
+        self.deficits.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // the ring is re-built from the members, a poisoned lock is safe to recover
+    fn lock_ring(&self) -> MutexGuard<'_, HashRing> {
+        self.ring.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    // smooth state only holds plain integers, a poisoned lock is safe to recover
+    fn lock_smooth_weight(&self) -> MutexGuard<'_, InlineVec<i128>> {
+        self.smooth_weight
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
 }
 
-#[cfg(feature = "tokio")]
-impl<T: PartialEq> WrrQueue<T> {
+#[cfg(wrr_async)]
+impl<T: Member> WrrQueue<T> {
     /// insert a new instance, and re-calculate request queue
-    pub async fn insert(&mut self, instance: impl Into<Instance<T>>) -> bool {
-        let res = self.insert_uncalculated(instance.into());
-        self.recalculate_queue().await;
+    ///
+    /// false if the instance is already in the queue, or is a `(data, weight)` tuple with a zero weight,
+    /// see [`WrrQueue::try_insert`] to tell them apart
+    pub async fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        self.recalculate_queue();
         res
     }
 
+    /// insert a new standby instance, and re-calculate request queue
+    ///
+    /// see [`WrrQueue::set_standby`]
+    pub async fn insert_standby(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_member(instance, true));
+        self.recalculate_queue();
+        res
+    }
+
+    /// insert a new `(data, weight)` instance, and re-calculate request queue
+    /// [`WrrError::ZeroWeight`] if the weight is zero, instead of panicking
+    pub async fn try_insert<U: Into<usize>>(&mut self, instance: (T, U)) -> Result<bool, WrrError> {
+        let instance = Instance::try_new_with_weight(instance.0, instance.1.into())?;
+        Ok(self.insert(instance).await)
+    }
+
     /// insert a new instance vec, and re-calculate request queue
     /// recommended when have multiple instance to be inserted
     pub async fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
     where
-        T: PartialEq,
-        U: Into<Instance<T>>,
+        T: Member,
+        U: TryInto<Instance<T>>,
     {
-        let res = instance_list
-            .into()
-            .into_iter()
-            .map(|i| self.insert_uncalculated(i.into()))
-            .all(|t| t);
-        self.recalculate_queue().await;
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        }
+        self.recalculate_queue();
         res
     }
 
     /// return the selected instance, None if instance_list is empty
     /// NOTE: select operation used only atomic operation, and can be paralleled  
     pub async fn select(&mut self) -> Option<&Instance<T>> {
+        self.recalculate_if_dirty();
+        let selected_instance_idx = self.select_index().ok()?;
+        self.instance_list.get(selected_instance_idx)
+    }
+
+    /// return the selected instance without awaiting, for synchronous callers
+    ///
+    /// [`WrrError::WouldBlock`] if the schedule lock is held for writing, or if a recalculation is pending:
+    /// a lazy one, or a time-driven change due, which need the `&mut self` of [`WrrQueue::select`].
+    /// [`WrrError::Empty`] if instance_list is empty.
+    /// To select from threads sharing the queue with async tasks, split it and use
+    /// [`Reader::try_select`] or [`Reader::blocking_select`] instead
+    pub fn try_select(&self) -> Result<&Instance<T>, WrrError> {
         if self.instance_list.is_empty() {
-            None
-        } else {
-            let idx = self.cur_idx.fetch_add(1, Ordering::Relaxed);
-            let read_lock = self.select_queue.read().await;
-            let selected_seq_idx = idx % read_lock.len();
-            let selected_instance_idx = read_lock.get(selected_seq_idx)?;
-            self.instance_list.get(*selected_instance_idx)
+            return Err(WrrError::Empty);
+        }
+        if self.dirty || self.refresh_due() {
+            return Err(WrrError::WouldBlock);
         }
+        let queue = crate::runtime::try_read(&self.select_queue).ok_or(WrrError::WouldBlock)?;
+        let index = self.pick_index(&queue, &[]).ok_or(WrrError::Empty)?;
+        Ok(&self.instance_list[index])
+    }
+
+    /// return the selected instance, spending `cost` out of its share, None if instance_list is empty
+    ///
+    /// e.g. the size of the request in bytes. See [`Engine::DeficitRoundRobin`],
+    /// the cost is ignored by the other engines
+    pub async fn select_with_cost(&mut self, cost: usize) -> Option<&Instance<T>> {
+        if self.engine != Engine::DeficitRoundRobin {
+            return self.select().await;
+        }
+        self.recalculate_if_dirty();
+        let idx = self.deficit_pick(cost, &self.skipped)?;
+        self.notice_select(idx);
+        self.instance_list.get(idx)
+    }
+
+    /// return the selected instance, counted as in flight until the returned mark is dropped
+    ///
+    /// None if instance_list is empty. See [`Engine::LeastConnections`]
+    pub async fn select_tracked(&mut self) -> Option<(&Instance<T>, InFlight)> {
+        self.recalculate_if_dirty();
+        let idx = self.select_index().ok()?;
+        Some((&self.instance_list[idx], self.state_list[idx].track()))
+    }
+
+    /// select an instance, counted in flight until the returned [`Lease`] is dropped
+    ///
+    /// the queue is borrowed by the lease, see [`WrrQueue::leases`] to hold several at once
+    pub async fn select_lease(&mut self) -> Option<Lease<'_, T>> {
+        self.recalculate_if_dirty();
+        let idx = self.select_index().ok()?;
+        Some(self.lease_at(idx))
+    }
+
+    /// leases of successive selections, ending only if no instance is up
+    ///
+    /// the queue is borrowed until the last lease is dropped, a lazy schedule is recalculated up front
+    pub async fn leases(&mut self) -> Leases<'_, T> {
+        self.recalculate_if_dirty();
+        Leases::new(self)
+    }
+
+    /// select an instance and run `f` against it, the outcome is reported back to the queue
+    ///
+    /// `E` must be able to represent a [`WrrError`], which is returned if the queue is empty
+    pub async fn run_on<'a, F, Fut, R, E>(&'a mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+        E: From<WrrError>,
+    {
+        self.recalculate_if_dirty();
+        attempt::run_on(self, f).await
+    }
+
+    /// select instances and run `f` against them, until one succeeds or `attempts` instances failed
+    ///
+    /// an instance that failed is never retried within the same call
+    pub async fn select_with_retry<'a, F, Fut, R, E>(
+        &'a mut self,
+        attempts: usize,
+        f: F,
+    ) -> Result<R, RetryError<E>>
+    where
+        F: FnMut(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+    {
+        self.recalculate_if_dirty();
+        attempt::select_with_retry(self, attempts, f).await
+    }
+
+    /// select a primary instance and run `f` against it, if it has not completed after `delay`,
+    /// also run `f` against a secondary instance and return whichever succeeds first
+    ///
+    /// the loser is cancelled by dropping its future, so only use this for idempotent requests.
+    /// a failure of one request waits for the other, the later error is returned if both fail
+    pub async fn hedged<'a, F, Fut, R, E>(&'a mut self, delay: Duration, f: F) -> Result<R, E>
+    where
+        F: Fn(&'a Instance<T>) -> Fut,
+        Fut: Future<Output = Result<R, E>>,
+        E: From<WrrError>,
+    {
+        self.recalculate_if_dirty();
+        attempt::hedged(self, delay, f).await
+    }
+
+    /// simulate the next `n` selections without moving the cursor
+    ///
+    /// return each instance along with the times it would be selected, in insertion order.
+    /// useful to validate a weight config before sending real traffic
+    pub async fn simulate(&self, n: usize) -> Vec<(&Instance<T>, usize)> {
+        let queue = self
+            .read_queue()
+            .expect("schedule is only written through &mut self");
+        self.count_selections(&queue, n)
     }
 
     /// clear instance in the queue
@@ -127,51 +2047,41 @@ impl<T: PartialEq> WrrQueue<T> {
         self.clear_instance_uncalculated();
     }
 
+    /// clear instance and schedule in the queue, keeping the allocated capacity
+    /// recommended for pools frequently rebuilt at the same size
+    pub fn clear_instances_keep_capacity(&mut self) {
+        self.clear_instance_keep_capacity_uncalculated();
+        self.clear_queue_keep_capacity();
+    }
+
     /// delete certain instance
     pub async fn delete_instance(&mut self, instance: Instance<T>) -> bool {
         if self.delete_uncalculated(instance) {
-            self.recalculate_queue().await;
+            self.recalculate_queue();
             true
         } else {
             false
         }
     }
-
-    async fn recalculate_queue(&mut self) {
-        if self.instance_list.is_empty() {
-            self.clear_instance();
-            return;
-        }
-        let lcm = self
-            .instance_list
-            .iter()
-            .map(Instance::weight)
-            .fold(1usize, |acc, a| lcm(acc, a.get()));
-        let mut queue = Vec::new();
-        let weight_vec = self.instance_list.iter().fold(Vec::new(), |mut acc, a| {
-            acc.push(a.weight().get());
-            acc
-        });
-        let mut cur_weight_vec: Vec<isize> =
-            weight_vec.clone().into_iter().map(|u| u as isize).collect();
-        for _ in 0..=lcm {
-            let selected = select_instance(&weight_vec, &mut cur_weight_vec);
-            queue.push(selected);
-        }
-
-        let mut queue_lock = self.select_queue.write().await;
-        queue_lock.clear();
-        for i in queue {
-            queue_lock.push(i);
-        }
-    }
 }
 
-#[cfg(feature = "blocking")]
-impl<T: PartialEq> WrrQueue<T> {
+#[cfg(wrr_sync)]
+impl<T: Member> WrrQueue<T> {
     /// insert a new instance, and re-calculate request queue
-    pub fn insert(&mut self, instance: impl Into<Instance<T>>) -> bool {
-        let res = self.insert_uncalculated(instance.into());
+    ///
+    /// false if the instance is already in the queue, or is a `(data, weight)` tuple with a zero weight,
+    /// see [`WrrQueue::try_insert`] to tell them apart
+    pub fn insert(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        self.recalculate_queue();
+        res
+    }
+
+    /// insert a new standby instance, and re-calculate request queue
+    ///
+    /// see [`WrrQueue::set_standby`]
+    pub fn insert_standby(&mut self, instance: impl TryInto<Instance<T>>) -> bool {
+        let res = checked(instance).is_some_and(|instance| self.insert_member(instance, true));
         self.recalculate_queue();
         res
     }
@@ -180,40 +2090,173 @@ impl<T: PartialEq> WrrQueue<T> {
     /// recommended when have multiple instance to be inserted
     pub fn insert_many<U>(&mut self, instance_list: impl Into<Vec<U>>) -> bool
     where
-        T: PartialEq,
-        U: Into<Instance<T>>,
+        T: Member,
+        U: TryInto<Instance<T>>,
     {
-        let res = instance_list
-            .into()
-            .into_iter()
-            .map(|i| self.insert_uncalculated(i.into()))
-            .all(|t| t);
+        let mut res = true;
+        for instance in instance_list.into() {
+            res &= checked(instance).is_some_and(|instance| self.insert_uncalculated(instance));
+        }
         self.recalculate_queue();
         res
     }
 
+    /// insert a new `(data, weight)` instance, and re-calculate request queue
+    /// [`WrrError::ZeroWeight`] if the weight is zero, instead of panicking
+    pub fn try_insert<U: Into<usize>>(&mut self, instance: (T, U)) -> Result<bool, WrrError> {
+        let instance = Instance::try_new_with_weight(instance.0, instance.1.into())?;
+        Ok(self.insert(instance))
+    }
+
     /// return the selected instance, None if instance_list is empty
     /// NOTE: select operation used only atomic operation, and can be paralleled  
     pub fn select(&mut self) -> Option<&Instance<T>> {
-        if self.instance_list.is_empty() {
-            None
+        match self.try_select() {
+            Ok(instance) => Some(instance),
+            Err(WrrError::Poisoned) => panic!("Read access acquired failed"),
+            Err(_) => None,
+        }
+    }
+
+    /// return the selected instance
+    /// [`WrrError::Empty`] if instance_list is empty, [`WrrError::Poisoned`] if the lock is poisoned
+    pub fn try_select(&mut self) -> Result<&Instance<T>, WrrError> {
+        self.recalculate_if_dirty();
+        let selected_instance_idx = self.select_index()?;
+        self.instance_list
+            .get(selected_instance_idx)
+            .ok_or(WrrError::Empty)
+    }
+
+    /// return the selected instance, spending `cost` out of its share, None if instance_list is empty
+    ///
+    /// e.g. the size of the request in bytes. See [`Engine::DeficitRoundRobin`],
+    /// the cost is ignored by the other engines
+    pub fn select_with_cost(&mut self, cost: usize) -> Option<&Instance<T>> {
+        if self.engine != Engine::DeficitRoundRobin {
+            return self.select();
+        }
+        self.recalculate_if_dirty();
+        let idx = self.deficit_pick(cost, &self.skipped)?;
+        self.notice_select(idx);
+        self.instance_list.get(idx)
+    }
+
+    /// return the selected instance, counted as in flight until the returned mark is dropped
+    ///
+    /// None if instance_list is empty. See [`Engine::LeastConnections`]
+    pub fn select_tracked(&mut self) -> Option<(&Instance<T>, InFlight)> {
+        self.recalculate_if_dirty();
+        let idx = match self.select_index() {
+            Ok(idx) => idx,
+            Err(WrrError::Poisoned) => panic!("Read access acquired failed"),
+            Err(_) => return None,
+        };
+        Some((&self.instance_list[idx], self.state_list[idx].track()))
+    }
+
+    /// select an instance, counted in flight until the returned [`Lease`] is dropped
+    ///
+    /// the queue is borrowed by the lease, see [`WrrQueue::leases`] to hold several at once
+    pub fn select_lease(&mut self) -> Option<Lease<'_, T>> {
+        self.recalculate_if_dirty();
+        let idx = match self.select_index() {
+            Ok(idx) => idx,
+            Err(WrrError::Poisoned) => panic!("Read access acquired failed"),
+            Err(_) => return None,
+        };
+        Some(self.lease_at(idx))
+    }
+
+    /// leases of successive selections, ending only if no instance is up
+    ///
+    /// the queue is borrowed until the last lease is dropped, a lazy schedule is recalculated up front
+    pub fn leases(&mut self) -> Leases<'_, T> {
+        self.recalculate_if_dirty();
+        Leases::new(self)
+    }
+
+    /// select an instance and run `f` against it, the outcome is reported back to the queue
+    ///
+    /// `E` must be able to represent a [`WrrError`], which is returned if the queue is empty
+    pub fn run_on<F, R, E>(&mut self, f: F) -> Result<R, E>
+    where
+        F: FnOnce(&Instance<T>) -> Result<R, E>,
+        E: From<WrrError>,
+    {
+        self.recalculate_if_dirty();
+        let idx = self.select_index()?;
+        let res = f(&self.instance_list[idx]);
+        self.record(idx, res.is_ok());
+        res
+    }
+
+    /// iterator over successive selections, ending only if no instance is up
+    ///
+    /// the queue is borrowed until the iterator is dropped, a lazy schedule is recalculated up front
+    pub fn iter_selections(&mut self) -> Selections<'_, T> {
+        self.recalculate_if_dirty();
+        Selections::new(self)
+    }
+
+    /// select instances and run `f` against them, until one succeeds or `attempts` instances failed
+    ///
+    /// an instance that failed is never retried within the same call
+    pub fn select_with_retry<F, R, E>(
+        &mut self,
+        attempts: usize,
+        mut f: F,
+    ) -> Result<R, RetryError<E>>
+    where
+        F: FnMut(&Instance<T>) -> Result<R, E>,
+    {
+        self.recalculate_if_dirty();
+        let mut failed = IndexSet::default();
+        let mut errors = Vec::new();
+        while errors.len() < attempts {
+            let idx = match self.select_index_excluding(&failed) {
+                Ok(idx) => idx,
+                Err(WrrError::Empty) if !failed.is_empty() => break,
+                Err(e) => return Err(RetryError::Queue(e)),
+            };
+            let res = f(&self.instance_list[idx]);
+            self.record(idx, res.is_ok());
+            match res {
+                Ok(r) => return Ok(r),
+                Err(e) => {
+                    failed.insert(idx);
+                    errors.push(e);
+                }
+            }
+        }
+        if failed.is_empty() {
+            Err(RetryError::Queue(WrrError::Empty))
         } else {
-            let idx = self.cur_idx.fetch_add(1, Ordering::Relaxed);
-            let read_lock = self
-                .select_queue
-                .read()
-                .expect("Read access acquired failed");
-            let selected_seq_idx = idx % read_lock.len();
-            let selected_instance_idx = read_lock.get(selected_seq_idx)?;
-            self.instance_list.get(*selected_instance_idx)
+            Err(RetryError::Exhausted(errors))
         }
     }
 
+    /// simulate the next `n` selections without moving the cursor
+    ///
+    /// return each instance along with the times it would be selected, in insertion order.
+    /// useful to validate a weight config before sending real traffic
+    pub fn simulate(&self, n: usize) -> Vec<(&Instance<T>, usize)> {
+        let queue = self.read_queue().expect("Read access acquired failed");
+        self.count_selections(&queue, n)
+    }
+
     /// clear instance in the queue
     pub fn clear_instance(&mut self) {
         self.clear_instance_uncalculated();
     }
 
+    /// clear instance and schedule in the queue, keeping the allocated capacity
+    /// recommended for pools frequently rebuilt at the same size
+    pub fn clear_instances_keep_capacity(&mut self) {
+        self.clear_instance_keep_capacity_uncalculated();
+        self.clear_queue_keep_capacity();
+    }
+
     /// delete certain instance
     pub fn delete_instance(&mut self, instance: Instance<T>) -> bool {
         if self.delete_uncalculated(instance) {
@@ -223,50 +2266,112 @@ impl<T: PartialEq> WrrQueue<T> {
             false
         }
     }
+}
 
-    fn recalculate_queue(&mut self) {
-        let lcm = self
-            .instance_list
-            .iter()
-            .map(Instance::weight)
-            .fold(1usize, |acc, a| lcm(acc, a.get()));
-        let mut queue = Vec::new();
-        let weight_vec = self.instance_list.iter().fold(Vec::new(), |mut acc, a| {
-            acc.push(a.weight().get());
-            acc
-        });
-        let mut cur_weight_vec: Vec<isize> =
-            weight_vec.clone().into_iter().map(|u| u as isize).collect();
-        for _ in 0..=lcm {
-            let selected = select_instance(&weight_vec, &mut cur_weight_vec);
-            queue.push(selected);
-        }
-
-        let mut queue_lock = self
-            .select_queue
-            .write()
-            .expect("Write lock acquired failed");
-        queue_lock.clear();
-        for i in queue {
-            queue_lock.push(i);
-        }
+impl<T> WrrQueue<T>
+where
+    T: Member + std::str::FromStr,
+    T::Err: std::fmt::Display,
+{
+    /// create a WRR Queue from the environment variable `name`, holding comma separated
+    /// `data=weight` entries such as `WRR_BACKENDS="10.0.0.1:80=5,10.0.0.2:80=1"`
+    ///
+    /// the weight may be left out for the default one, see the `FromStr` implementation of [`WrrConfig`]
+    pub fn from_env(name: &str) -> Result<Self, EnvConfigError> {
+        WrrConfig::from_env(name).map(Self::from_config)
     }
 }
 
-fn select_instance(weight_vec: &Vec<usize>, cur_weight: &mut [isize]) -> usize {
-    if weight_vec.is_empty() {
-        error!("failed to select an instance: instance list is empty");
-        return 0;
+#[cfg(feature = "config-file")]
+impl<T: Member + serde::de::DeserializeOwned> WrrQueue<T> {
+    /// create a WRR Queue from a json, toml or yaml config file, see [`WrrConfig::from_path`]
+    pub fn from_path(path: impl AsRef<std::path::Path>) -> Result<Self, ConfigError> {
+        WrrConfig::from_path(path).map(Self::from_config)
     }
-    let mut selected = 0;
-    let mut acc = 0isize;
-    for i in 0..weight_vec.len() {
-        cur_weight[i] += weight_vec[i] as isize;
-        acc += weight_vec[i] as isize;
-        if cur_weight[selected] < cur_weight[i] {
-            selected = i;
+}
+
+#[cfg(wrr_discovery)]
+impl<T: Member + Clone + Send + Sync + 'static> WrrQueue<T> {
+    /// split the queue, and drive its members from `discover` in a background task,
+    /// see [`Writer::drive`]
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn drive<D>(self, discover: D) -> DiscoverWatcher<T>
+    where
+        D: Discover<T> + Send + 'static,
+    {
+        let (_, writer) = self.split();
+        writer.drive(discover)
+    }
+}
+
+#[cfg(all(feature = "redis", wrr_async))]
+impl<T: Member> WrrQueue<T> {
+    /// take selection positions from a counter shared by every replica, see [`RedisCursor`]
+    ///
+    /// positions are leased in the background, so `select` never waits on Redis.
+    /// Overrides [`WrrQueue::sharded_cursor`], [`WrrQueue::thread_local_cursor`] and [`WrrQueue::start_offset`].
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn redis_cursor(mut self, cursor: RedisCursor) -> Self {
+        self.cursor = Cursor::leased(cursor.spawn());
+        self
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Member + Clone + Send + Sync + 'static> WrrQueue<T> {
+    /// receive a [`QueueEvent`] for each change of the members, their weights or their health
+    ///
+    /// events are sent as of the first call, on a broadcast channel keeping the last 1024 of them,
+    /// a receiver lagging further behind misses the oldest ones, see `tokio::sync::broadcast`.
+    /// Registered along with [`WrrQueue::hooks`], and kept by a split queue
+    pub fn subscribe(&mut self) -> tokio::sync::broadcast::Receiver<QueueEvent<T>> {
+        self.events
+            .get_or_insert_with(|| Events::new(consts::EVENT_CAPACITY))
+            .subscribe()
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<T: Member> WrrQueue<T> {
+    /// receive the [`Membership`] of the queue, a new generation on each change of its members,
+    /// their weights or their health
+    ///
+    /// the channel is created on the first call, its generations count the changes since then.
+    /// A split queue publishes its generations on [`Reader::watch_membership`] instead
+    pub fn watch_membership(&mut self) -> tokio::sync::watch::Receiver<Membership> {
+        let initial = self.membership(0);
+        self.membership
+            .get_or_insert_with(|| tokio::sync::watch::Sender::new(initial))
+            .subscribe()
+    }
+
+    pub(crate) fn membership(&self, generation: u64) -> Membership {
+        Membership {
+            generation,
+            len: self.instance_list.len(),
+            in_rotation: self.in_rotation(),
         }
     }
-    cur_weight[selected] -= acc;
-    selected
+
+    fn in_rotation(&self) -> usize {
+        self.instance_list.len() - self.skipped.len()
+    }
+}
+
+#[cfg(all(feature = "stream", any(wrr_async, wrr_async_wrapper)))]
+impl<T: Member + Clone> WrrQueue<T> {
+    /// stream of selections, ending only if no instance is up
+    ///
+    /// the queue is borrowed until the stream is dropped, a lazy schedule is recalculated up front
+    pub fn select_stream(&mut self) -> SelectStream<'_, T> {
+        self.recalculate_if_dirty();
+        let instances = self
+            .instance_list
+            .iter()
+            .map(|instance| Arc::new(instance.clone()))
+            .collect();
+        SelectStream::new(self, instances)
+    }
 }
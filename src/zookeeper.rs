@@ -0,0 +1,117 @@
+use crate::consts;
+use crate::instance::Instance;
+use crate::split::{Reader, Writer};
+use crate::trace;
+use tokio::task::JoinHandle;
+use zookeeper_client::{Client, EventType};
+
+/// members of a queue fed from the children of a znode, e.g. ephemeral nodes registered by
+/// each endpoint, mirrored as they appear and disappear
+///
+/// each child is named after its endpoint, e.g. `/services/backend/10.0.0.1:80`, and holds its
+/// weight as a decimal payload, or nothing for the default weight. Children holding anything
+/// else are skipped. Weights are read along with the children, so a weight changed in place is
+/// only applied on the next change of the children. After a lost session the children are read again.
+///
+/// example:
+///
+/// ```ignore
+/// use async_wrr_queue::{WrrQueue, ZooKeeperDiscovery};
+///
+/// let (reader, writer) = WrrQueue::new().split();
+/// let watcher = ZooKeeperDiscovery::new("127.0.0.1:2181", "/services/backend").spawn(writer);
+/// let endpoint = reader.select().await.unwrap();
+/// ```
+#[derive(PartialEq, Eq, Debug, Clone)]
+pub struct ZooKeeperDiscovery {
+    cluster: String,
+    path: String,
+}
+
+/// handle of a running [`ZooKeeperDiscovery`], the watch stops once it is dropped
+pub struct ZooKeeperWatcher {
+    reader: Reader<String>,
+    task: JoinHandle<()>,
+}
+
+impl ZooKeeperDiscovery {
+    /// watch the children of `path` on the ensemble reached at `cluster`, e.g. `10.0.0.1:2181,10.0.0.2:2181`
+    pub fn new(cluster: impl Into<String>, path: impl Into<String>) -> Self {
+        ZooKeeperDiscovery {
+            cluster: cluster.into(),
+            path: path.into(),
+        }
+    }
+
+    /// start watching into `writer`
+    ///
+    /// NOTE: must be called within a tokio runtime
+    pub fn spawn(self, writer: Writer<String>) -> ZooKeeperWatcher {
+        ZooKeeperWatcher {
+            reader: writer.reader(),
+            task: tokio::spawn(self.run(writer)),
+        }
+    }
+
+    async fn run(self, mut writer: Writer<String>) {
+        loop {
+            if let Err(e) = self.follow(&mut writer).await {
+                trace::discovery_failed(&self.path, &e);
+            }
+            tokio::time::sleep(consts::DISCOVERY_RETRY_DELAY).await;
+        }
+    }
+
+    /// mirror the children into `writer` until the session is lost
+    async fn follow(&self, writer: &mut Writer<String>) -> Result<(), zookeeper_client::Error> {
+        let client = Client::connect(&self.cluster).await?;
+        loop {
+            let (children, _, watcher) = client.get_and_watch_children(&self.path).await?;
+            let mut instances = Vec::new();
+            for child in children {
+                let path = format!("{}/{}", self.path.trim_end_matches('/'), child);
+                let data = match client.get_data(&path).await {
+                    Ok((data, _)) => data,
+                    // gone since the children were listed, the watch fires for it
+                    Err(zookeeper_client::Error::NoNode) => continue,
+                    Err(e) => return Err(e),
+                };
+                match instance(child, &data) {
+                    Some(instance) => instances.push(instance),
+                    None => trace::discovery_failed(&path, &"invalid weight"),
+                }
+            }
+            if writer.pending.reconcile_uncalculated(instances) {
+                writer.publish().await;
+            }
+            if watcher.changed().await.event_type == EventType::Session {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// instance of the child `endpoint` holding `data`, None if it is not a valid weight
+fn instance(endpoint: String, data: &[u8]) -> Option<Instance<String>> {
+    let weight = std::str::from_utf8(data).ok()?.trim();
+    if weight.is_empty() {
+        return Some(Instance::new(endpoint));
+    }
+    Instance::try_new_with_weight(endpoint, weight.parse().ok()?).ok()
+}
+
+impl ZooKeeperWatcher {
+    /// create another [`Reader`] of the queue
+    pub fn reader(&self) -> Reader<String> {
+        self.reader.clone()
+    }
+
+    /// stop watching, the members are left as last seen
+    pub fn stop(self) {}
+}
+
+impl Drop for ZooKeeperWatcher {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
@@ -0,0 +1,218 @@
+#![cfg(wrr_std)]
+
+use async_wrr_queue::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+#[cfg(any(wrr_sync, wrr_async))]
+use std::num::NonZeroUsize;
+
+/// counts allocations made by the current thread, while enabled
+struct CountingAlloc;
+
+thread_local! {
+    static COUNTING: Cell<bool> = const { Cell::new(false) };
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        if COUNTING.with(Cell::get) {
+            ALLOCATIONS.with(|a| a.set(a.get() + 1));
+        }
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+/// engines whose selection is covered
+fn engines() -> Vec<Engine> {
+    #[allow(unused_mut)]
+    let mut engines = vec![
+        Engine::Expanded,
+        Engine::Smooth,
+        Engine::WeightedLeastRequest,
+    ];
+    #[cfg(feature = "bandit")]
+    engines.push(Engine::Bandit);
+    engines
+}
+
+#[cfg(wrr_sync)]
+fn count_allocations<R>(f: impl FnOnce() -> R) -> usize {
+    ALLOCATIONS.with(|a| a.set(0));
+    COUNTING.with(|c| c.set(true));
+    let res = f();
+    COUNTING.with(|c| c.set(false));
+    drop(res);
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[cfg(wrr_async)]
+async fn count_async_allocations(f: impl std::future::Future<Output = ()>) -> usize {
+    ALLOCATIONS.with(|a| a.set(0));
+    COUNTING.with(|c| c.set(true));
+    // without the cooperative budget, the runtime never gets control back within the loop
+    tokio::task::unconstrained(f).await;
+    COUNTING.with(|c| c.set(false));
+    ALLOCATIONS.with(Cell::get)
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_does_not_allocate_test() {
+    for engine in engines() {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![("a", 1usize), ("b", 1000usize), ("c", 3usize)])
+            .await;
+        // the first read guard lazily allocates the lock's wakeup list under `async-lock`
+        queue.select().await.unwrap();
+        assert_eq!(
+            0,
+            count_async_allocations(async {
+                for _ in 0..1000 {
+                    queue.select().await.unwrap();
+                }
+            })
+            .await
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_does_not_allocate_test() {
+    for engine in engines() {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 1usize), ("b", 1000usize), ("c", 3usize)]);
+        assert_eq!(
+            0,
+            count_allocations(|| {
+                for _ in 0..1000 {
+                    queue.select().unwrap();
+                }
+            })
+        );
+        let (reader, _writer) = queue.split();
+        // the first load lazily registers the thread's debt list under `arc-swap`
+        reader.select().unwrap();
+        assert_eq!(
+            0,
+            count_allocations(|| {
+                for _ in 0..1000 {
+                    reader.select().unwrap();
+                }
+            })
+        );
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_capped_select_does_not_allocate_test() {
+    for engine in engines() {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![
+                ("a", 1usize),
+                ("b", 1000usize),
+                ("c", 3usize),
+                ("d", 1usize),
+            ])
+            .await;
+        queue.mark_down(&"d");
+        queue.set_max_in_flight(&"b", NonZeroUsize::new(1));
+        // keep "b" saturated, so that each select refuses it and spills over
+        let _held = loop {
+            let (instance, in_flight) = queue.select_tracked().await.unwrap();
+            if *instance.data() == "b" {
+                break in_flight;
+            }
+        };
+        queue.select().await.unwrap();
+        assert_eq!(
+            0,
+            count_async_allocations(async {
+                for _ in 0..1000 {
+                    assert_ne!(&"b", queue.select().await.unwrap().data());
+                }
+            })
+            .await
+        );
+        // "a" always fails, so that the next attempt excludes it
+        assert_eq!(
+            0,
+            count_async_allocations(async {
+                for _ in 0..1000 {
+                    let res = queue
+                        .select_with_retry(2, |instance| {
+                            let failed = *instance.data() == "a";
+                            async move {
+                                if failed {
+                                    Err(())
+                                } else {
+                                    Ok(())
+                                }
+                            }
+                        })
+                        .await;
+                    assert!(res.is_ok());
+                }
+            })
+            .await
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn capped_select_does_not_allocate_test() {
+    for engine in engines() {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![
+            ("a", 1usize),
+            ("b", 1000usize),
+            ("c", 3usize),
+            ("d", 1usize),
+        ]);
+        queue.mark_down(&"d");
+        queue.set_max_in_flight(&"b", NonZeroUsize::new(1));
+        // keep "b" saturated, so that each select refuses it and spills over
+        let _held = loop {
+            let (instance, in_flight) = queue.select_tracked().unwrap();
+            if *instance.data() == "b" {
+                break in_flight;
+            }
+        };
+        assert_eq!(
+            0,
+            count_allocations(|| {
+                for _ in 0..1000 {
+                    assert_ne!(&"b", queue.select().unwrap().data());
+                }
+            })
+        );
+        // "a" always fails, so that the next attempt excludes it
+        assert_eq!(
+            0,
+            count_allocations(|| {
+                for _ in 0..1000 {
+                    let res = queue.select_with_retry(2, |instance| {
+                        if *instance.data() == "a" {
+                            Err(())
+                        } else {
+                            Ok(())
+                        }
+                    });
+                    assert!(res.is_ok());
+                }
+            })
+        );
+    }
+}
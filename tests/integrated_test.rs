@@ -1,6 +1,20 @@
+#![cfg(wrr_std)]
+
 use async_wrr_queue::*;
 
-#[cfg(feature = "tokio")]
+#[derive(Debug, PartialEq)]
+enum RequestError {
+    Queue(WrrError),
+    Failed,
+}
+
+impl From<WrrError> for RequestError {
+    fn from(value: WrrError) -> Self {
+        RequestError::Queue(value)
+    }
+}
+
+#[cfg(wrr_async)]
 #[tokio::test]
 async fn tokio_test_usage() {
     let mut queue = WrrQueue::new();
@@ -16,7 +30,88 @@ async fn tokio_test_usage() {
     }
 }
 
-#[cfg(feature = "tokio")]
+#[cfg(all(feature = "tokio", wrr_sync))]
+#[tokio::test]
+async fn tokio_blocking_coexist_test() {
+    use std::time::Duration;
+
+    let mut blocking = WrrQueue::new();
+    blocking.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    assert_eq!(&"b", blocking.select().unwrap().data());
+
+    let mut queue: AsyncWrrQueue<_> = blocking.into();
+    assert_eq!(&"a", queue.select().await.unwrap().data());
+    assert!(queue.insert(("c", 1usize)).await);
+    assert_eq!(3, queue.len());
+
+    let res: Result<&str, RequestError> = queue
+        .run_on(|instance| async move { Ok(*instance.data()) })
+        .await;
+    assert!(res.is_ok());
+    let res: Result<&str, RequestError> = queue
+        .hedged(Duration::from_millis(10), |instance| async move {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            Ok(*instance.data())
+        })
+        .await;
+    assert!(res.is_ok());
+
+    let mut queue: AsyncWrrQueue<&str> = AsyncWrrQueue::new();
+    let res: Result<(), RequestError> = queue.run_on(|_| async { Ok(()) }).await;
+    assert_eq!(Err(RequestError::Queue(WrrError::Empty)), res);
+}
+
+#[cfg(feature = "async-std")]
+#[async_std::test]
+async fn async_std_test_usage() {
+    use std::time::Duration;
+
+    let mut queue = AsyncWrrQueue::new();
+    queue
+        .insert_many(vec![("a".to_string(), 1usize), ("b".to_string(), 2usize)])
+        .await;
+    let mut expected = ["b", "a", "b"].iter().cycle();
+    for _ in 0..20 {
+        assert_eq!(
+            expected.next().unwrap(),
+            queue.select().await.unwrap().data()
+        );
+    }
+    let res: Result<&str, WrrError> = queue
+        .hedged(Duration::from_millis(10), |instance| async move {
+            Ok(instance.data().as_str())
+        })
+        .await;
+    assert!(res.is_ok());
+}
+
+#[cfg(feature = "async-lock")]
+#[test]
+fn smol_test_usage() {
+    use std::time::Duration;
+
+    smol::block_on(async {
+        let mut queue = AsyncWrrQueue::new();
+        queue
+            .insert_many(vec![("a".to_string(), 1usize), ("b".to_string(), 2usize)])
+            .await;
+        let mut expected = ["b", "a", "b"].iter().cycle();
+        for _ in 0..20 {
+            assert_eq!(
+                expected.next().unwrap(),
+                queue.select().await.unwrap().data()
+            );
+        }
+        let res: Result<&str, WrrError> = queue
+            .hedged(Duration::from_millis(10), |instance| async move {
+                Ok(instance.data().as_str())
+            })
+            .await;
+        assert!(res.is_ok());
+    });
+}
+
+#[cfg(wrr_async)]
 #[tokio::test]
 async fn tokio_test_all_equal() {
     let mut queue = WrrQueue::new();
@@ -32,7 +127,7 @@ async fn tokio_test_all_equal() {
     }
 }
 
-#[cfg(feature = "tokio")]
+#[cfg(wrr_async)]
 #[tokio::test]
 async fn tokio_complex_test() {
     let mut queue = WrrQueue::new();
@@ -50,7 +145,7 @@ async fn tokio_complex_test() {
     }
 }
 
-#[cfg(feature = "blocking")]
+#[cfg(wrr_sync)]
 #[test]
 fn test_usage() {
     let mut queue = WrrQueue::new();
@@ -61,7 +156,7 @@ fn test_usage() {
     }
 }
 
-#[cfg(feature = "blocking")]
+#[cfg(wrr_sync)]
 #[test]
 fn test_all_equal() {
     let mut queue = WrrQueue::new();
@@ -72,7 +167,7 @@ fn test_all_equal() {
     }
 }
 
-#[cfg(feature = "blocking")]
+#[cfg(wrr_sync)]
 #[test]
 fn complex_test() {
     let mut queue = WrrQueue::new();
@@ -89,3 +184,3987 @@ fn complex_test() {
         assert_eq!(expected.next().unwrap(), select.unwrap().data(),);
     }
 }
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_simulate_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let simulated = queue.simulate(30).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+    // simulation does not move the cursor
+    assert_eq!(&"b", queue.select().await.unwrap().data());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn simulate_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let simulated = queue.simulate(30);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+    // simulation does not move the cursor
+    assert_eq!(&"b", queue.select().unwrap().data());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_try_insert_test() {
+    let mut queue = WrrQueue::new();
+    assert_eq!(
+        Err(WrrError::ZeroWeight),
+        queue.try_insert(("a", 0usize)).await
+    );
+    assert_eq!(Ok(true), queue.try_insert(("b", 1usize)).await);
+    assert_eq!(&"b", queue.select().await.unwrap().data());
+    // a zero weight is refused like a duplicate, without panicking
+    assert!(!queue.insert(("c", 0usize)).await);
+    assert!(!queue.insert_many(vec![("c", 0usize), ("d", 1usize)]).await);
+    assert_eq!(2, queue.len());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn try_insert_test() {
+    let mut queue = WrrQueue::new();
+    assert_eq!(Err(WrrError::Empty), queue.try_select());
+    assert_eq!(Err(WrrError::ZeroWeight), queue.try_insert(("a", 0usize)));
+    assert_eq!(Ok(true), queue.try_insert(("b", 1usize)));
+    assert_eq!(&"b", queue.try_select().unwrap().data());
+    // a zero weight is refused like a duplicate, without panicking
+    assert!(!queue.insert(("c", 0usize)));
+    assert!(!queue.insert_many(vec![("c", 0usize), ("d", 1usize)]));
+    assert_eq!(2, queue.len());
+}
+
+#[test]
+fn reader_try_select_test() {
+    let queue: WrrQueue<&str> = WrrQueue::new();
+    let (reader, _writer) = queue.split();
+    assert_eq!(Some(WrrError::Empty), reader.try_select().err());
+    let queue = WrrQueue::from_config(WrrConfig::new(vec![
+        ("a", 1usize).try_into().unwrap(),
+        ("b", 2usize).try_into().unwrap(),
+    ]));
+    let (reader, _writer) = queue.split();
+    let mut expected = ["b", "a", "b"].iter().cycle();
+    for _ in 0..20 {
+        assert_eq!(
+            expected.next().unwrap(),
+            reader.try_select().unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_try_select_test() {
+    let mut queue = WrrQueue::new();
+    assert_eq!(Err(WrrError::Empty), queue.try_select());
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let mut expected = ["b", "a", "b"].iter().cycle();
+    for _ in 0..20 {
+        assert_eq!(expected.next().unwrap(), queue.try_select().unwrap().data());
+    }
+
+    // a lazy recalculation is left to the next select
+    let mut queue = WrrQueue::new().lazy_recalculation(true);
+    queue.insert(("a", 1usize)).await;
+    assert_eq!(Err(WrrError::WouldBlock), queue.try_select());
+    assert_eq!(&"a", queue.select().await.unwrap().data());
+    assert_eq!(&"a", queue.try_select().unwrap().data());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tokio_blocking_select_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let (reader, _writer) = queue.split();
+
+    let worker = reader.clone();
+    let thread = std::thread::spawn(move || {
+        (0..300)
+            .map(|_| *worker.blocking_select().unwrap().data())
+            .collect::<Vec<_>>()
+    });
+    let task = tokio::spawn(async move {
+        let mut selected = Vec::new();
+        for _ in 0..300 {
+            selected.push(*reader.select().await.unwrap().data());
+            tokio::task::yield_now().await;
+        }
+        selected
+    });
+    let mut selected = thread.join().unwrap();
+    selected.extend(task.await.unwrap());
+    // both share the cursor of the queue, 600 selections are exactly 200 cycles
+    assert_eq!(200, selected.iter().filter(|data| **data == "a").count());
+    assert_eq!(400, selected.iter().filter(|data| **data == "b").count());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_run_on_test() {
+    let mut queue = WrrQueue::new();
+    let res: Result<(), _> = queue.run_on(|_| async { Ok(()) }).await;
+    assert_eq!(Err(RequestError::Queue(WrrError::Empty)), res);
+
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    for _ in 0..3 {
+        let _ = queue
+            .run_on(|i| async move {
+                match *i.data() {
+                    "a" => Err(RequestError::Failed),
+                    data => Ok(data),
+                }
+            })
+            .await;
+    }
+    assert_eq!(
+        Some(InstanceStats {
+            success: 0,
+            failure: 1,
+            consecutive_failures: 1,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"a")
+    );
+    assert_eq!(
+        Some(InstanceStats {
+            success: 2,
+            failure: 0,
+            consecutive_failures: 0,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"b")
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_delete_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    assert!(
+        !queue
+            .delete_instance(("c", 1usize).try_into().unwrap())
+            .await
+    );
+    assert!(
+        queue
+            .delete_instance(("b", 2usize).try_into().unwrap())
+            .await
+    );
+    for _ in 0..5 {
+        assert_eq!(&"a", queue.select().await.unwrap().data());
+    }
+    assert_eq!(None, queue.stats(&"b"));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn run_on_test() {
+    let mut queue = WrrQueue::new();
+    let res: Result<(), _> = queue.run_on(|_| Ok(()));
+    assert_eq!(Err(RequestError::Queue(WrrError::Empty)), res);
+
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    for _ in 0..3 {
+        let _ = queue.run_on(|i| match *i.data() {
+            "a" => Err(RequestError::Failed),
+            data => Ok(data),
+        });
+    }
+    assert_eq!(
+        Some(InstanceStats {
+            success: 0,
+            failure: 1,
+            consecutive_failures: 1,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"a")
+    );
+    assert_eq!(
+        Some(InstanceStats {
+            success: 2,
+            failure: 0,
+            consecutive_failures: 0,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"b")
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn delete_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    assert!(!queue.delete_instance(("c", 1usize).try_into().unwrap()));
+    assert!(queue.delete_instance(("b", 2usize).try_into().unwrap()));
+    for _ in 0..5 {
+        assert_eq!(&"a", queue.select().unwrap().data());
+    }
+    assert_eq!(None, queue.stats(&"b"));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_with_retry_test() {
+    let mut queue = WrrQueue::new();
+    let res: Result<(), RetryError<RequestError>> =
+        queue.select_with_retry(3, |_| async { Ok(()) }).await;
+    assert_eq!(Err(RetryError::Queue(WrrError::Empty)), res);
+
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let mut tried = Vec::new();
+    let res = queue
+        .select_with_retry(3, |i| {
+            tried.push(*i.data());
+            async move {
+                match *i.data() {
+                    "b" => Err(RequestError::Failed),
+                    data => Ok(data),
+                }
+            }
+        })
+        .await;
+    assert_eq!(Ok("a"), res);
+    assert_eq!(tried, vec!["b", "a"]);
+
+    let res: Result<(), _> = queue
+        .select_with_retry(3, |_| async { Err(RequestError::Failed) })
+        .await;
+    assert_eq!(
+        Err(RetryError::Exhausted(vec![
+            RequestError::Failed,
+            RequestError::Failed
+        ])),
+        res
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_with_retry_test() {
+    let mut queue = WrrQueue::new();
+    let res: Result<(), RetryError<RequestError>> = queue.select_with_retry(3, |_| Ok(()));
+    assert_eq!(Err(RetryError::Queue(WrrError::Empty)), res);
+
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let mut tried = Vec::new();
+    let res = queue.select_with_retry(3, |i| {
+        tried.push(*i.data());
+        match *i.data() {
+            "b" => Err(RequestError::Failed),
+            data => Ok(data),
+        }
+    });
+    assert_eq!(Ok("a"), res);
+    assert_eq!(tried, vec!["b", "a"]);
+
+    let res: Result<(), _> = queue.select_with_retry(3, |_| Err(RequestError::Failed));
+    assert_eq!(
+        Err(RetryError::Exhausted(vec![
+            RequestError::Failed,
+            RequestError::Failed
+        ])),
+        res
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_hedged_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+
+    // "b" is selected first but is too slow, the hedged request on "a" wins
+    let res = queue
+        .hedged(Duration::from_millis(10), |i| async move {
+            if *i.data() == "b" {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+            }
+            Ok::<_, RequestError>(*i.data())
+        })
+        .await;
+    assert_eq!(Ok("a"), res);
+    assert_eq!(Some(InstanceStats::default()), queue.stats(&"b"));
+
+    // "b" answers before the hedge delay, no secondary request is issued
+    let res = queue
+        .hedged(Duration::from_secs(5), |i| async move {
+            Ok::<_, RequestError>(*i.data())
+        })
+        .await;
+    assert_eq!(Ok("b"), res);
+    assert_eq!(
+        Some(InstanceStats {
+            success: 1,
+            failure: 0,
+            consecutive_failures: 0,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"a")
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_weights_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    assert_eq!(
+        queue.weights(),
+        vec![
+            (&"a", NonZeroUsize::new(1).unwrap()),
+            (&"b", NonZeroUsize::new(2).unwrap())
+        ]
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn weights_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    assert_eq!(
+        queue.weights(),
+        vec![
+            (&"a", NonZeroUsize::new(1).unwrap()),
+            (&"b", NonZeroUsize::new(2).unwrap())
+        ]
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_clear_keep_capacity_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    queue.select().await;
+    queue.clear_instances_keep_capacity();
+    assert!(queue.select().await.is_none());
+    queue.insert_many(vec![("c", 1usize), ("d", 2usize)]).await;
+    let mut expected = ["d", "c", "d"].iter().cycle();
+    for _ in 0..6 {
+        assert_eq!(
+            expected.next().unwrap(),
+            queue.select().await.unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn clear_keep_capacity_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    queue.select();
+    queue.clear_instances_keep_capacity();
+    assert!(queue.select().is_none());
+    queue.insert_many(vec![("c", 1usize), ("d", 2usize)]);
+    let mut expected = ["d", "c", "d"].iter().cycle();
+    for _ in 0..6 {
+        assert_eq!(expected.next().unwrap(), queue.select().unwrap().data());
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_smooth_engine_test() {
+    let mut queue = WrrQueue::with_engine(Engine::Smooth);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    queue.insert(("c", 3usize)).await;
+    queue.insert_many(vec![("d", 5usize), ("e", 2usize)]).await;
+    let mut expected = [
+        "d", "c", "b", "d", "e", "d", "c", "a", "d", "b", "e", "c", "d",
+    ]
+    .iter()
+    .cycle();
+    for _ in 0..30 {
+        let select = queue.select().await;
+        assert_eq!(expected.next().unwrap(), select.unwrap().data(),);
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_every_instance_scheduled_test() {
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 1usize)])
+            .await;
+        let mut expected = ["a", "b", "c"].iter().cycle();
+        for _ in 0..9 {
+            assert_eq!(
+                expected.next().unwrap(),
+                queue.select().await.unwrap().data()
+            );
+        }
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn smooth_engine_test() {
+    let mut queue = WrrQueue::with_engine(Engine::Smooth);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    queue.insert(("c", 3usize));
+    queue.insert_many(vec![("d", 5usize), ("e", 2usize)]);
+    let mut expected = [
+        "d", "c", "b", "d", "e", "d", "c", "a", "d", "b", "e", "c", "d",
+    ]
+    .iter()
+    .cycle();
+    for _ in 0..30 {
+        let select = queue.select();
+        assert_eq!(expected.next().unwrap(), select.unwrap().data(),);
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn every_instance_scheduled_test() {
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 1usize)]);
+        let mut expected = ["a", "b", "c"].iter().cycle();
+        for _ in 0..9 {
+            assert_eq!(expected.next().unwrap(), queue.select().unwrap().data());
+        }
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_oversized_schedule_fallback_test() {
+    let mut queue = WrrQueue::new();
+    queue
+        .insert_many(vec![
+            ("a", 7usize),
+            ("b", 11usize),
+            ("c", 13usize),
+            ("d", 997usize),
+        ])
+        .await;
+    assert_eq!(Engine::Expanded, queue.active_engine());
+
+    queue.clear_instance();
+    queue
+        .insert_many(vec![("a", usize::MAX), ("b", usize::MAX - 1)])
+        .await;
+    assert_eq!(Engine::Expanded, queue.engine());
+    assert_eq!(Engine::Smooth, queue.active_engine());
+    let mut expected = ["a", "b"].iter().cycle();
+    for _ in 0..10 {
+        assert_eq!(
+            expected.next().unwrap(),
+            queue.select().await.unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn oversized_schedule_fallback_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![
+        ("a", 7usize),
+        ("b", 11usize),
+        ("c", 13usize),
+        ("d", 997usize),
+    ]);
+    assert_eq!(Engine::Expanded, queue.active_engine());
+
+    queue.clear_instance();
+    queue.insert_many(vec![("a", usize::MAX), ("b", usize::MAX - 1)]);
+    assert_eq!(Engine::Expanded, queue.engine());
+    assert_eq!(Engine::Smooth, queue.active_engine());
+    let mut expected = ["a", "b"].iter().cycle();
+    for _ in 0..10 {
+        assert_eq!(expected.next().unwrap(), queue.select().unwrap().data());
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_split_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let (reader, mut writer) = queue.split();
+
+    let handles: Vec<_> = (0..3)
+        .map(|_| {
+            let reader = reader.clone();
+            tokio::spawn(async move { *reader.select().await.unwrap().data() })
+        })
+        .collect();
+    let mut selected = Vec::new();
+    for handle in handles {
+        selected.push(handle.await.unwrap());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "b", "b"]);
+
+    writer
+        .delete_instance(("b", 2usize).try_into().unwrap())
+        .await;
+    writer.insert(("c", 1usize)).await;
+    let mut selected = Vec::new();
+    for _ in 0..4 {
+        selected.push(*reader.select().await.unwrap().data());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "c", "c"]);
+
+    writer.clear_instance().await;
+    assert!(writer.reader().select().await.is_none());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn split_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let (reader, mut writer) = queue.split();
+
+    let mut selected: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..3)
+            .map(|_| {
+                let reader = reader.clone();
+                scope.spawn(move || *reader.select().unwrap().data())
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    selected.sort();
+    assert_eq!(selected, vec!["a", "b", "b"]);
+
+    writer.delete_instance(("b", 2usize).try_into().unwrap());
+    writer.insert(("c", 1usize));
+    let mut selected: Vec<_> = (0..4).map(|_| *reader.select().unwrap().data()).collect();
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "c", "c"]);
+
+    writer.clear_instance();
+    assert!(writer.reader().select().is_none());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_writer_marks_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    let (reader, mut writer) = queue.split();
+    async fn select(reader: &Reader<&'static str>, n: usize) -> Vec<&'static str> {
+        let mut selected = Vec::new();
+        for _ in 0..n {
+            selected.push(*reader.select().await.unwrap().data());
+        }
+        selected.sort();
+        selected
+    }
+
+    let generation = reader.generation();
+    assert!(writer.mark_down(&"b").await);
+    assert_eq!(generation + 1, reader.generation());
+    assert_eq!(vec!["a", "a"], select(&reader, 2).await);
+    // already down, nothing to publish
+    assert!(writer.mark_down(&"b").await);
+    assert_eq!(generation + 1, reader.generation());
+    assert!(writer.mark_up(&"b").await);
+    assert!(
+        writer
+            .update_weight(&"b", NonZeroUsize::new(3).unwrap())
+            .await
+    );
+    assert_eq!(vec!["a", "b", "b", "b"], select(&reader, 4).await);
+
+    assert!(writer.pause(&"a").await);
+    assert_eq!(vec!["b", "b"], select(&reader, 2).await);
+    assert!(writer.resume(&"a").await);
+    assert!(writer.drain(&"b").await.unwrap().is_drained());
+    assert_eq!(vec!["a", "a"], select(&reader, 2).await);
+
+    assert!(!writer.mark_down(&"c").await);
+    assert!(!writer.pause(&"c").await);
+    assert!(writer.drain(&"c").await.is_none());
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn writer_marks_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    let (reader, mut writer) = queue.split();
+    let select = |n| {
+        let mut selected: Vec<_> = (0..n).map(|_| *reader.select().unwrap().data()).collect();
+        selected.sort();
+        selected
+    };
+
+    let generation = reader.generation();
+    assert!(writer.mark_down(&"b"));
+    assert_eq!(generation + 1, reader.generation());
+    assert_eq!(vec!["a", "a"], select(2));
+    // already down, nothing to publish
+    assert!(writer.mark_down(&"b"));
+    assert_eq!(generation + 1, reader.generation());
+    assert!(writer.mark_up(&"b"));
+    assert!(writer.update_weight(&"b", NonZeroUsize::new(3).unwrap()));
+    assert_eq!(vec!["a", "b", "b", "b"], select(4));
+
+    assert!(writer.pause(&"a"));
+    assert_eq!(vec!["b", "b"], select(2));
+    assert!(writer.resume(&"a"));
+    assert!(writer.drain(&"b").unwrap().is_drained());
+    assert_eq!(vec!["a", "a"], select(2));
+
+    assert!(!writer.mark_down(&"c"));
+    assert!(!writer.pause(&"c"));
+    assert!(writer.drain(&"c").is_none());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_split_slow_start_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::with_engine(Engine::Smooth).slow_start(Duration::from_secs(1), 0.25);
+    queue.insert(("a", 4usize)).await;
+    tokio::time::sleep(Duration::from_millis(1050)).await;
+    let (reader, mut writer) = queue.split();
+    writer.insert(("b", 4usize)).await;
+
+    let mut b = 0;
+    for _ in 0..80 {
+        b += (*reader.select().await.unwrap().data() == "b") as usize;
+    }
+    assert_eq!(16, b);
+
+    // picked up by the readers, with no publish of the writer
+    tokio::time::sleep(Duration::from_millis(1050)).await;
+    let mut b = 0;
+    for _ in 0..8 {
+        b += (*reader.select().await.unwrap().data() == "b") as usize;
+    }
+    assert_eq!(4, b);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn split_slow_start_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::with_engine(Engine::Smooth).slow_start(Duration::from_secs(1), 0.25);
+    queue.insert(("a", 4usize));
+    std::thread::sleep(Duration::from_millis(1050));
+    let (reader, mut writer) = queue.split();
+    writer.insert(("b", 4usize));
+
+    let b = (0..80)
+        .filter(|_| *reader.select().unwrap().data() == "b")
+        .count();
+    assert_eq!(16, b);
+
+    // picked up by the readers, with no publish of the writer
+    std::thread::sleep(Duration::from_millis(1050));
+    let b = (0..8)
+        .filter(|_| *reader.select().unwrap().data() == "b")
+        .count();
+    assert_eq!(4, b);
+}
+
+#[cfg(all(feature = "tokio", wrr_async))]
+#[tokio::test]
+async fn tokio_background_writer_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize)).await;
+    let (reader, writer) = queue.split();
+    let writer = writer.into_background();
+
+    writer.insert(("b", 2usize));
+    writer.insert_many(vec![("c", 1usize), ("d", 1usize)]);
+    writer.delete_instance(("d", 1usize).try_into().unwrap());
+    writer.flush().await;
+
+    let mut selected = Vec::new();
+    for _ in 0..8 {
+        selected.push(*reader.select().await.unwrap().data());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "b", "b", "b", "b", "c", "c"]);
+
+    writer.clear_instance();
+    writer.flush().await;
+    assert!(writer.reader().select().await.is_none());
+}
+
+#[cfg(all(feature = "tokio", wrr_async))]
+#[tokio::test]
+async fn tokio_health_check_test() {
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    let (reader, writer) = queue.split();
+    let writer = writer.into_background();
+
+    let healthy = Arc::new(AtomicBool::new(false));
+    let flag = healthy.clone();
+    let checker = HealthCheck::new(Duration::from_millis(10))
+        .fall(2)
+        .rise(2)
+        .jitter(0.0)
+        .spawn(writer.clone(), move |data: &&str| {
+            let up = *data != "b" || flag.load(Ordering::Relaxed);
+            async move { up }
+        });
+
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    writer.flush().await;
+    for _ in 0..4 {
+        assert_eq!(*reader.select().await.unwrap().data(), "a");
+    }
+
+    healthy.store(true, Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    writer.flush().await;
+    let mut selected: Vec<_> = Vec::new();
+    for _ in 0..4 {
+        selected.push(*reader.select().await.unwrap().data());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "b", "b"]);
+
+    // instances keep their state once the checks stop
+    checker.stop();
+    healthy.store(false, Ordering::Relaxed);
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    writer.flush().await;
+    assert!(reader.select().await.is_some());
+    assert!(writer.reader().select().await.is_some());
+}
+
+#[cfg(all(feature = "http-probe", not(wrr_sync)))]
+#[tokio::test]
+async fn tokio_http_probe_test() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut buf = [0u8; 1024];
+            let read = stream.read(&mut buf).unwrap();
+            let status = if buf[..read].starts_with(b"GET /up ") {
+                "200 OK"
+            } else {
+                "503 Service Unavailable"
+            };
+            let response = format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n");
+            let _ = stream.write_all(response.as_bytes());
+        }
+    });
+
+    let probe = HttpProbe::new(move |path: &&str| format!("http://{addr}/{path}"));
+    assert!(probe.probe(&"up").await);
+    assert!(!probe.probe(&"down").await);
+    let probe = probe.status(200..=599);
+    assert!(probe.probe(&"down").await);
+
+    let closed = HttpProbe::new(|_: &&str| "http://127.0.0.1:1/".to_string());
+    assert!(!closed.probe(&"up").await);
+    let https = HttpProbe::new(|_: &&str| "https://127.0.0.1/".to_string());
+    assert!(!https.probe(&"up").await);
+}
+
+#[cfg(all(feature = "tcp-probe", not(wrr_sync)))]
+#[tokio::test]
+async fn tokio_tcp_probe_test() {
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let probe = TcpProbe::new(|addr: &String| addr.clone()).timeout(Duration::from_secs(1));
+    assert!(probe.probe(&addr.to_string()).await);
+    drop(listener);
+    assert!(!probe.probe(&addr.to_string()).await);
+    assert!(!probe.probe(&"not an address".to_string()).await);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_lazy_recalculation_test() {
+    let mut queue = WrrQueue::new().lazy_recalculation(true);
+    for (data, weight) in [("a", 1usize), ("b", 2usize), ("c", 3usize)] {
+        queue.insert((data, weight)).await;
+    }
+    queue.insert_many(vec![("d", 5usize), ("e", 2usize)]).await;
+    let mut expected = [
+        "d", "c", "b", "d", "e", "d", "c", "a", "d", "b", "e", "c", "d",
+    ]
+    .iter()
+    .cycle();
+    for _ in 0..30 {
+        assert_eq!(
+            expected.next().unwrap(),
+            queue.select().await.unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn lazy_recalculation_test() {
+    let mut queue = WrrQueue::new().lazy_recalculation(true);
+    for (data, weight) in [("a", 1usize), ("b", 2usize), ("c", 3usize)] {
+        queue.insert((data, weight));
+    }
+    queue.insert_many(vec![("d", 5usize), ("e", 2usize)]);
+    let mut expected = [
+        "d", "c", "b", "d", "e", "d", "c", "a", "d", "b", "e", "c", "d",
+    ]
+    .iter()
+    .cycle();
+    for _ in 0..30 {
+        assert_eq!(expected.next().unwrap(), queue.select().unwrap().data());
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_update_weight_test() {
+    use std::num::NonZeroUsize;
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 4usize)])
+            .await;
+        assert!(queue.update_weight(&"b", NonZeroUsize::new(5).unwrap()));
+        let counts: Vec<_> = queue
+            .simulate(100)
+            .await
+            .iter()
+            .map(|(i, c)| (*i.data(), *c))
+            .collect();
+        assert_eq!(counts, vec![("a", 10), ("b", 50), ("c", 40)]);
+        assert!(queue.update_weight(&"c", NonZeroUsize::new(1).unwrap()));
+        let counts: Vec<_> = queue
+            .simulate(70)
+            .await
+            .iter()
+            .map(|(i, c)| (*i.data(), *c))
+            .collect();
+        assert_eq!(counts, vec![("a", 10), ("b", 50), ("c", 10)]);
+        assert!(!queue.update_weight(&"d", NonZeroUsize::new(1).unwrap()));
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn update_weight_test() {
+    use std::num::NonZeroUsize;
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 4usize)]);
+        assert!(queue.update_weight(&"b", NonZeroUsize::new(5).unwrap()));
+        let counts: Vec<_> = queue
+            .simulate(100)
+            .iter()
+            .map(|(i, c)| (*i.data(), *c))
+            .collect();
+        assert_eq!(counts, vec![("a", 10), ("b", 50), ("c", 40)]);
+        assert!(queue.update_weight(&"c", NonZeroUsize::new(1).unwrap()));
+        let counts: Vec<_> = queue
+            .simulate(70)
+            .iter()
+            .map(|(i, c)| (*i.data(), *c))
+            .collect();
+        assert_eq!(counts, vec![("a", 10), ("b", 50), ("c", 10)]);
+        assert!(!queue.update_weight(&"d", NonZeroUsize::new(1).unwrap()));
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_large_schedule_test() {
+    let instances: Vec<_> = (0..1500usize).map(|i| (i, i % 3 + 1)).collect();
+    let mut expanded = WrrQueue::new();
+    expanded.insert_many(instances.clone()).await;
+    let mut smooth = WrrQueue::with_engine(Engine::Smooth);
+    smooth.insert_many(instances).await;
+    // one cycle, with `rayon` a list this long is scheduled by chunks, in another order
+    let mut counts = vec![0; 1500];
+    for _ in 0..3000 {
+        let pick = *expanded.select().await.unwrap().data();
+        #[cfg(not(feature = "rayon"))]
+        assert_eq!(smooth.select().await.unwrap().data(), &pick);
+        counts[pick] += 1;
+    }
+    assert!(counts.iter().enumerate().all(|(i, c)| *c == i % 3 + 1));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn large_schedule_test() {
+    let instances: Vec<_> = (0..1500usize).map(|i| (i, i % 3 + 1)).collect();
+    let mut expanded = WrrQueue::new();
+    expanded.insert_many(instances.clone());
+    let mut smooth = WrrQueue::with_engine(Engine::Smooth);
+    smooth.insert_many(instances);
+    // one cycle, with `rayon` a list this long is scheduled by chunks, in another order
+    let mut counts = vec![0; 1500];
+    for _ in 0..3000 {
+        let pick = *expanded.select().unwrap().data();
+        #[cfg(not(feature = "rayon"))]
+        assert_eq!(smooth.select().unwrap().data(), &pick);
+        counts[pick] += 1;
+    }
+    assert!(counts.iter().enumerate().all(|(i, c)| *c == i % 3 + 1));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_skewed_weights_test() {
+    let instances = vec![("a", 1usize), ("b", 1000usize), ("c", 3usize)];
+    let mut expanded = WrrQueue::new();
+    expanded.insert_many(instances.clone()).await;
+    let mut smooth = WrrQueue::with_engine(Engine::Smooth);
+    smooth.insert_many(instances).await;
+    let counts: Vec<_> = expanded
+        .simulate(1004)
+        .await
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 1), ("b", 1000), ("c", 3)]);
+    for _ in 0..2008 {
+        assert_eq!(
+            smooth.select().await.unwrap().data(),
+            expanded.select().await.unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn skewed_weights_test() {
+    let instances = vec![("a", 1usize), ("b", 1000usize), ("c", 3usize)];
+    let mut expanded = WrrQueue::new();
+    expanded.insert_many(instances.clone());
+    let mut smooth = WrrQueue::with_engine(Engine::Smooth);
+    smooth.insert_many(instances);
+    let counts: Vec<_> = expanded
+        .simulate(1004)
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 1), ("b", 1000), ("c", 3)]);
+    for _ in 0..2008 {
+        assert_eq!(
+            smooth.select().unwrap().data(),
+            expanded.select().unwrap().data()
+        );
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_sharded_cursor_test() {
+    let mut queue = WrrQueue::new().sharded_cursor(4);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let mut counts = [0usize; 2];
+    for _ in 0..30 {
+        match *queue.select().await.unwrap().data() {
+            "a" => counts[0] += 1,
+            _ => counts[1] += 1,
+        }
+    }
+    assert_eq!(counts, [10, 20]);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn sharded_cursor_test() {
+    let mut queue = WrrQueue::new().sharded_cursor(4);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let (reader, _writer) = queue.split();
+
+    let selected: Vec<Vec<_>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let reader = reader.clone();
+                scope.spawn(move || (0..300).map(|_| *reader.select().unwrap().data()).collect())
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    let a_count = selected.iter().flatten().filter(|x| **x == "a").count();
+    assert_eq!(a_count, 800);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_max_schedule_len_test() {
+    let instances = vec![
+        ("a", 7usize),
+        ("b", 11usize),
+        ("c", 13usize),
+        ("d", 997usize),
+    ];
+    let mut queue = WrrQueue::new();
+    queue.insert_many(instances.clone()).await;
+    assert_eq!(None, queue.approximation_error());
+
+    let mut queue = WrrQueue::new().max_schedule_len(500);
+    queue.insert_many(instances).await;
+    assert_eq!(Engine::Expanded, queue.active_engine());
+    assert!(queue.approximation_error().unwrap() < 0.2);
+    let counts: Vec<_> = queue
+        .simulate(495)
+        .await
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 3), ("b", 5), ("c", 6), ("d", 481)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn max_schedule_len_test() {
+    let instances = vec![
+        ("a", 7usize),
+        ("b", 11usize),
+        ("c", 13usize),
+        ("d", 997usize),
+    ];
+    let mut queue = WrrQueue::new();
+    queue.insert_many(instances.clone());
+    assert_eq!(None, queue.approximation_error());
+
+    let mut queue = WrrQueue::new().max_schedule_len(500);
+    queue.insert_many(instances);
+    assert_eq!(Engine::Expanded, queue.active_engine());
+    assert!(queue.approximation_error().unwrap() < 0.2);
+    let counts: Vec<_> = queue
+        .simulate(495)
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 3), ("b", 5), ("c", 6), ("d", 481)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_many_members_test() {
+    let mut queue = WrrQueue::new();
+    assert!(
+        queue
+            .insert_many((0..200usize).map(|i| (i, 1usize)).collect::<Vec<_>>())
+            .await
+    );
+    assert!(!queue.insert((150usize, 1usize)).await);
+    for i in (0..200usize).step_by(2) {
+        assert!(queue.delete_instance((i, 1usize).try_into().unwrap()).await);
+    }
+    assert!(
+        !queue
+            .delete_instance((0usize, 1usize).try_into().unwrap())
+            .await
+    );
+    assert_eq!(100, queue.weights().len());
+    assert!(queue.stats(&100).is_none());
+    assert!(queue.stats(&101).is_some());
+    assert!(queue.update_weight(&199, std::num::NonZeroUsize::new(3).unwrap()));
+    assert_eq!(&199, queue.weights()[99].0);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn many_members_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.insert_many((0..200usize).map(|i| (i, 1usize)).collect::<Vec<_>>()));
+    assert!(!queue.insert((150usize, 1usize)));
+    for i in (0..200usize).step_by(2) {
+        assert!(queue.delete_instance((i, 1usize).try_into().unwrap()));
+    }
+    assert!(!queue.delete_instance((0usize, 1usize).try_into().unwrap()));
+    assert_eq!(100, queue.weights().len());
+    assert!(queue.stats(&100).is_none());
+    assert!(queue.stats(&101).is_some());
+    assert!(queue.update_weight(&199, std::num::NonZeroUsize::new(3).unwrap()));
+    assert_eq!(&199, queue.weights()[99].0);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_continuity_across_recalculation_test() {
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 5usize), ("b", 1usize)]).await;
+        let mut b_count = 0;
+        for _ in 0..12 {
+            queue.insert(("churn", 1usize)).await;
+            queue
+                .delete_instance(("churn", 1usize).try_into().unwrap())
+                .await;
+            if *queue.select().await.unwrap().data() == "b" {
+                b_count += 1;
+            }
+        }
+        assert_eq!(2, b_count);
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn continuity_across_recalculation_test() {
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 5usize), ("b", 1usize)]);
+        let mut b_count = 0;
+        for _ in 0..12 {
+            queue.insert(("churn", 1usize));
+            queue.delete_instance(("churn", 1usize).try_into().unwrap());
+            if *queue.select().unwrap().data() == "b" {
+                b_count += 1;
+            }
+        }
+        assert_eq!(2, b_count);
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_update_guard_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize)).await;
+    let mut update = queue.update();
+    update
+        .insert(("b", 2usize))
+        .update_weight("a", NonZeroUsize::new(3).unwrap());
+    update.abort();
+    assert_eq!(vec![(&"a", NonZeroUsize::new(1).unwrap())], queue.weights());
+
+    let mut update = queue.update();
+    update
+        .insert(("b", 2usize))
+        .insert(("c", 1usize))
+        .delete_instance(("c", 1usize).try_into().unwrap())
+        .update_weight("a", NonZeroUsize::new(3).unwrap());
+    assert!(update.commit().await);
+    let counts: Vec<_> = queue
+        .simulate(50)
+        .await
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 30), ("b", 20)]);
+
+    let mut update = queue.update();
+    update.insert(("b", 2usize));
+    assert!(!update.commit().await);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn update_guard_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize));
+    let mut update = queue.update();
+    update
+        .insert(("b", 2usize))
+        .update_weight("a", NonZeroUsize::new(3).unwrap());
+    update.abort();
+    assert_eq!(vec![(&"a", NonZeroUsize::new(1).unwrap())], queue.weights());
+
+    let mut update = queue.update();
+    update
+        .insert(("b", 2usize))
+        .insert(("c", 1usize))
+        .delete_instance(("c", 1usize).try_into().unwrap())
+        .update_weight("a", NonZeroUsize::new(3).unwrap());
+    assert!(update.commit());
+    let counts: Vec<_> = queue
+        .simulate(50)
+        .iter()
+        .map(|(i, c)| (*i.data(), *c))
+        .collect();
+    assert_eq!(counts, vec![("a", 30), ("b", 20)]);
+
+    let mut update = queue.update();
+    update.insert(("b", 2usize));
+    assert!(!update.commit());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_snapshot_generation_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize)).await;
+    let (reader, mut writer) = queue.split();
+    assert_eq!(0, reader.generation());
+
+    let selected = reader.select().await.unwrap();
+    writer
+        .delete_instance(("a", 1usize).try_into().unwrap())
+        .await;
+    writer.insert(("b", 1usize)).await;
+    assert_eq!(2, reader.generation());
+    // the old snapshot stays alive as long as it is used
+    assert_eq!(&"a", selected.data());
+    assert_eq!(&"b", reader.select().await.unwrap().data());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn snapshot_generation_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize));
+    let (reader, mut writer) = queue.split();
+    assert_eq!(0, reader.generation());
+
+    let selected = reader.select().unwrap();
+    writer.delete_instance(("a", 1usize).try_into().unwrap());
+    writer.insert(("b", 1usize));
+    assert_eq!(2, reader.generation());
+    // the old snapshot stays alive as long as it is used
+    assert_eq!(&"a", selected.data());
+    assert_eq!(&"b", reader.select().unwrap().data());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_thread_local_cursor_test() {
+    let mut queue = WrrQueue::new().thread_local_cursor(true);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let mut counts = [0usize; 2];
+    for _ in 0..30 {
+        match *queue.select().await.unwrap().data() {
+            "a" => counts[0] += 1,
+            _ => counts[1] += 1,
+        }
+    }
+    assert_eq!(counts, [10, 20]);
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn thread_local_cursor_test() {
+    let mut queue = WrrQueue::new().thread_local_cursor(true);
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let (reader, mut writer) = queue.split();
+
+    let a_counts: Vec<usize> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let reader = reader.clone();
+                scope.spawn(move || {
+                    (0..300)
+                        .filter(|_| *reader.select().unwrap().data() == "a")
+                        .count()
+                })
+            })
+            .collect();
+        handles.into_iter().map(|h| h.join().unwrap()).collect()
+    });
+    // each thread keeps the weighted distribution on its own
+    assert_eq!(a_counts, vec![100; 4]);
+
+    writer.insert(("c", 3usize));
+    let c_count = (0..60)
+        .filter(|_| *reader.select().unwrap().data() == "c")
+        .count();
+    assert_eq!(30, c_count);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_start_offset_test() {
+    let mut queue = WrrQueue::new().start_offset(4);
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 1usize)])
+        .await;
+    assert_eq!(&"b", queue.select().await.unwrap().data());
+    queue.clear_instance();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    assert_eq!(&"a", queue.select().await.unwrap().data());
+
+    let mut queue = WrrQueue::new().random_start_offset();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let simulated = queue.simulate(30).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn start_offset_test() {
+    let mut queue = WrrQueue::new().start_offset(4);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 1usize)]);
+    assert_eq!(&"b", queue.select().unwrap().data());
+    queue.clear_instance();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    assert_eq!(&"a", queue.select().unwrap().data());
+
+    let mut queue = WrrQueue::new().random_start_offset();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let simulated = queue.simulate(30);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_interleaved_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = WrrQueue::new().interleaved(true);
+    queue
+        .insert_many(vec![("a", 7usize), ("b", 2usize), ("c", 2usize)])
+        .await;
+    let mut result = Vec::new();
+    for _ in 0..11 {
+        result.push(*queue.select().await.unwrap().data());
+    }
+    assert_eq!(
+        result,
+        vec!["a", "a", "b", "a", "a", "c", "a", "a", "b", "a", "c"]
+    );
+
+    assert!(queue.update_weight(&"b", NonZeroUsize::new(4).unwrap()));
+    let simulated = queue.simulate(26).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 14), ("b", 8), ("c", 4)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn interleaved_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = WrrQueue::new().interleaved(true);
+    queue.insert_many(vec![("a", 7usize), ("b", 2usize), ("c", 2usize)]);
+    let result: Vec<_> = (0..11).map(|_| *queue.select().unwrap().data()).collect();
+    assert_eq!(
+        result,
+        vec!["a", "a", "b", "a", "a", "c", "a", "a", "b", "a", "c"]
+    );
+
+    assert!(queue.update_weight(&"b", NonZeroUsize::new(4).unwrap()));
+    let simulated = queue.simulate(26);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 14), ("b", 8), ("c", 4)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_round_robin_test() {
+    let mut queue = WrrQueue::with_engine(Engine::RoundRobin);
+    assert!(queue.select().await.is_none());
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 5usize), ("c", 2usize)])
+        .await;
+    let mut result = Vec::new();
+    for _ in 0..6 {
+        result.push(*queue.select().await.unwrap().data());
+    }
+    assert_eq!(result, vec!["a", "b", "c", "a", "b", "c"]);
+    assert_eq!(Engine::RoundRobin, queue.active_engine());
+
+    let simulated = queue.simulate(30).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 10), ("c", 10)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn round_robin_test() {
+    let mut queue = WrrQueue::with_engine(Engine::RoundRobin);
+    assert!(queue.select().is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 5usize), ("c", 2usize)]);
+    let result: Vec<_> = (0..6).map(|_| *queue.select().unwrap().data()).collect();
+    assert_eq!(result, vec!["a", "b", "c", "a", "b", "c"]);
+    assert_eq!(Engine::RoundRobin, queue.active_engine());
+
+    let simulated = queue.simulate(30);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 10), ("c", 10)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_least_connections_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    assert!(queue.select_tracked().await.is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let mut marks = Vec::new();
+    let mut result = Vec::new();
+    for _ in 0..3 {
+        let (instance, mark) = queue.select_tracked().await.unwrap();
+        result.push(*instance.data());
+        marks.push(mark);
+    }
+    assert_eq!(result, vec!["a", "b", "b"]);
+    assert_eq!(Some(1), queue.in_flight(&"a"));
+    assert_eq!(Some(2), queue.in_flight(&"b"));
+
+    marks.remove(0);
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(&"a", queue.select_tracked().await.unwrap().0.data());
+    drop(marks);
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+
+    let simulated = queue.simulate(30).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn least_connections_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    assert!(queue.select_tracked().is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let mut marks = Vec::new();
+    let mut result = Vec::new();
+    for _ in 0..3 {
+        let (instance, mark) = queue.select_tracked().unwrap();
+        result.push(*instance.data());
+        marks.push(mark);
+    }
+    assert_eq!(result, vec!["a", "b", "b"]);
+    assert_eq!(Some(1), queue.in_flight(&"a"));
+    assert_eq!(Some(2), queue.in_flight(&"b"));
+
+    marks.remove(0);
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(&"a", queue.select_tracked().unwrap().0.data());
+    drop(marks);
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+
+    let simulated = queue.simulate(30);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 20)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_weighted_least_request_test() {
+    let mut queue = WrrQueue::with_engine(Engine::WeightedLeastRequest);
+    assert!(queue.select_tracked().await.is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize)]).await;
+    let simulated = queue.simulate(400).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert!(counts[0].1 > 100 && counts[1].1 < 300);
+
+    let mut idle = 0;
+    for _ in 0..40 {
+        idle += usize::from(queue.select().await.unwrap().data() == &"a");
+    }
+    assert_eq!(10, idle);
+
+    // hold 3 selections of `b` in flight, its weight drops from 3 to 3 / 4
+    let mut marks = Vec::new();
+    while marks.len() < 3 {
+        let (instance, mark) = queue.select_tracked().await.unwrap();
+        if instance.data() == &"b" {
+            marks.push(mark);
+        }
+    }
+    assert_eq!(Some(3), queue.in_flight(&"b"));
+    let mut loaded = 0;
+    for _ in 0..40 {
+        loaded += usize::from(queue.select().await.unwrap().data() == &"a");
+    }
+    assert!(loaded > 20);
+    drop(marks);
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn weighted_least_request_test() {
+    let mut queue = WrrQueue::with_engine(Engine::WeightedLeastRequest);
+    assert!(queue.select_tracked().is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize)]);
+    let simulated = queue.simulate(400);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert!(counts[0].1 > 100 && counts[1].1 < 300);
+
+    let mut idle = 0;
+    for _ in 0..40 {
+        idle += usize::from(queue.select().unwrap().data() == &"a");
+    }
+    assert_eq!(10, idle);
+
+    // hold 3 selections of `b` in flight, its weight drops from 3 to 3 / 4
+    let mut marks = Vec::new();
+    while marks.len() < 3 {
+        let (instance, mark) = queue.select_tracked().unwrap();
+        if instance.data() == &"b" {
+            marks.push(mark);
+        }
+    }
+    assert_eq!(Some(3), queue.in_flight(&"b"));
+    let mut loaded = 0;
+    for _ in 0..40 {
+        loaded += usize::from(queue.select().unwrap().data() == &"a");
+    }
+    assert!(loaded > 20);
+    drop(marks);
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_peak_ewma_test() {
+    use std::time::Duration;
+    let mut queue =
+        WrrQueue::with_engine(Engine::PeakEwma).peak_ewma_decay(Duration::from_secs(60));
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    assert!(queue.report_latency(&"a", Duration::from_millis(100)));
+    assert!(queue.report_latency(&"b", Duration::from_millis(10)));
+    assert!(!queue.report_latency(&"c", Duration::from_millis(10)));
+
+    let simulated = queue.simulate(11).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 1), ("b", 10)]);
+
+    // a peak is taken at once
+    queue.report_latency(&"b", Duration::from_secs(1));
+    let (instance, _mark) = queue.select_tracked().await.unwrap();
+    assert_eq!(&"a", instance.data());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn peak_ewma_test() {
+    use std::time::Duration;
+    let mut queue =
+        WrrQueue::with_engine(Engine::PeakEwma).peak_ewma_decay(Duration::from_secs(60));
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    assert!(queue.report_latency(&"a", Duration::from_millis(100)));
+    assert!(queue.report_latency(&"b", Duration::from_millis(10)));
+    assert!(!queue.report_latency(&"c", Duration::from_millis(10)));
+
+    let simulated = queue.simulate(11);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 1), ("b", 10)]);
+
+    // a peak is taken at once
+    queue.report_latency(&"b", Duration::from_secs(1));
+    let (instance, _mark) = queue.select_tracked().unwrap();
+    assert_eq!(&"a", instance.data());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_by_key_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.select_by_key(&0).is_none());
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)])
+        .await;
+    let routed: Vec<_> = (0..4000usize)
+        .map(|key| *queue.select_by_key(&key).unwrap().data())
+        .collect();
+    let b_count = routed.iter().filter(|d| **d == "b").count();
+    assert!((1200..1800).contains(&b_count), "{b_count}");
+    assert_eq!(&routed[7], queue.select_by_key(&7).unwrap().data());
+
+    queue
+        .delete_instance(("a", 1usize).try_into().unwrap())
+        .await;
+    for (key, data) in routed.iter().enumerate() {
+        if *data != "a" {
+            assert_eq!(data, queue.select_by_key(&key).unwrap().data());
+        }
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_by_key_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.select_by_key(&0).is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)]);
+    let routed: Vec<_> = (0..4000usize)
+        .map(|key| *queue.select_by_key(&key).unwrap().data())
+        .collect();
+    let b_count = routed.iter().filter(|d| **d == "b").count();
+    assert!((1200..1800).contains(&b_count), "{b_count}");
+    assert_eq!(&routed[7], queue.select_by_key(&7).unwrap().data());
+
+    queue.delete_instance(("a", 1usize).try_into().unwrap());
+    for (key, data) in routed.iter().enumerate() {
+        if *data != "a" {
+            assert_eq!(data, queue.select_by_key(&key).unwrap().data());
+        }
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_maglev_table_test() {
+    let mut queue = WrrQueue::new().maglev_table(65537);
+    assert!(queue.select_by_key(&0).is_none());
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)])
+        .await;
+    let routed: Vec<_> = (0..4000usize)
+        .map(|key| *queue.select_by_key(&key).unwrap().data())
+        .collect();
+    let b_count = routed.iter().filter(|d| **d == "b").count();
+    assert!((1300..1700).contains(&b_count), "{b_count}");
+
+    queue
+        .delete_instance(("a", 1usize).try_into().unwrap())
+        .await;
+    let moved = routed
+        .iter()
+        .enumerate()
+        .filter(|(key, data)| **data != "a" && *data != queue.select_by_key(key).unwrap().data())
+        .count();
+    assert!(moved < 200, "{moved}");
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn maglev_table_test() {
+    let mut queue = WrrQueue::new().maglev_table(65537);
+    assert!(queue.select_by_key(&0).is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)]);
+    let routed: Vec<_> = (0..4000usize)
+        .map(|key| *queue.select_by_key(&key).unwrap().data())
+        .collect();
+    let b_count = routed.iter().filter(|d| **d == "b").count();
+    assert!((1300..1700).contains(&b_count), "{b_count}");
+
+    queue.delete_instance(("a", 1usize).try_into().unwrap());
+    let moved = routed
+        .iter()
+        .enumerate()
+        .filter(|(key, data)| **data != "a" && *data != queue.select_by_key(key).unwrap().data())
+        .count();
+    assert!(moved < 200, "{moved}");
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_priority_queue_test() {
+    let mut queue = PriorityQueue::new();
+    assert!(queue.select().await.is_none());
+    queue.insert(1, ("backup", 1usize)).await;
+    assert_eq!(&"backup", queue.select().await.unwrap().data());
+
+    queue.insert(0, ("a", 1usize)).await;
+    queue.insert(0, ("b", 2usize)).await;
+    assert_eq!(Some(0), queue.active_priority());
+    let mut result = Vec::new();
+    for _ in 0..6 {
+        result.push(*queue.select().await.unwrap().data());
+    }
+    assert_eq!(result, vec!["b", "a", "b", "b", "a", "b"]);
+
+    assert!(
+        queue
+            .delete_instance(0, ("a", 1usize).try_into().unwrap())
+            .await
+    );
+    assert!(
+        queue
+            .delete_instance(0, ("b", 2usize).try_into().unwrap())
+            .await
+    );
+    assert!(
+        !queue
+            .delete_instance(2, ("b", 2usize).try_into().unwrap())
+            .await
+    );
+    assert_eq!(Some(1), queue.active_priority());
+    assert_eq!(&"backup", queue.select().await.unwrap().data());
+    assert_eq!(0, queue.tier(0).unwrap().len());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn priority_queue_test() {
+    let mut queue = PriorityQueue::new();
+    assert!(queue.select().is_none());
+    queue.insert(1, ("backup", 1usize));
+    assert_eq!(&"backup", queue.select().unwrap().data());
+
+    queue.insert(0, ("a", 1usize));
+    queue.insert(0, ("b", 2usize));
+    assert_eq!(Some(0), queue.active_priority());
+    let result: Vec<_> = (0..6).map(|_| *queue.select().unwrap().data()).collect();
+    assert_eq!(result, vec!["b", "a", "b", "b", "a", "b"]);
+
+    assert!(queue.delete_instance(0, ("a", 1usize).try_into().unwrap()));
+    assert!(queue.delete_instance(0, ("b", 2usize).try_into().unwrap()));
+    assert!(!queue.delete_instance(2, ("b", 2usize).try_into().unwrap()));
+    assert_eq!(Some(1), queue.active_priority());
+    assert_eq!(&"backup", queue.select().unwrap().data());
+    assert_eq!(0, queue.tier(0).unwrap().len());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_grouped_queue_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = GroupedWrrQueue::new();
+    assert!(queue.insert_group(("eu", 1usize)).await);
+    assert!(queue.insert_group(("us", 3usize)).await);
+    assert!(!queue.insert_group(("us", 1usize)).await);
+    assert!(queue.select().await.is_none());
+
+    assert!(queue.insert(&"eu", ("eu-1", 1usize)).await);
+    assert!(queue.insert(&"us", ("us-1", 1usize)).await);
+    assert!(queue.insert(&"us", ("us-2", 2usize)).await);
+    assert!(!queue.insert(&"ap", ("ap-1", 1usize)).await);
+    let mut counts = std::collections::HashMap::new();
+    for _ in 0..36 {
+        *counts
+            .entry(*queue.select().await.unwrap().data())
+            .or_insert(0) += 1;
+    }
+    assert_eq!(9, counts["eu-1"]);
+    assert_eq!(9, counts["us-1"]);
+    assert_eq!(18, counts["us-2"]);
+
+    assert!(queue.update_group_weight(&"eu", NonZeroUsize::new(3).unwrap()));
+    assert!(
+        queue
+            .delete_instance(&"us", ("us-1", 1usize).try_into().unwrap())
+            .await
+    );
+    assert!(
+        queue
+            .delete_instance(&"us", ("us-2", 2usize).try_into().unwrap())
+            .await
+    );
+    assert_eq!(&"eu-1", queue.select().await.unwrap().data());
+    assert_eq!(0, queue.delete_group(&"us").await.unwrap().len());
+    assert_eq!(
+        vec![(&"eu", NonZeroUsize::new(3).unwrap())],
+        queue.group_weights()
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn grouped_queue_test() {
+    use std::num::NonZeroUsize;
+    let mut queue = GroupedWrrQueue::new();
+    assert!(queue.insert_group(("eu", 1usize)));
+    assert!(queue.insert_group(("us", 3usize)));
+    assert!(!queue.insert_group(("us", 1usize)));
+    assert!(queue.select().is_none());
+
+    assert!(queue.insert(&"eu", ("eu-1", 1usize)));
+    assert!(queue.insert(&"us", ("us-1", 1usize)));
+    assert!(queue.insert(&"us", ("us-2", 2usize)));
+    assert!(!queue.insert(&"ap", ("ap-1", 1usize)));
+    let mut counts = std::collections::HashMap::new();
+    for _ in 0..36 {
+        *counts.entry(*queue.select().unwrap().data()).or_insert(0) += 1;
+    }
+    assert_eq!(9, counts["eu-1"]);
+    assert_eq!(9, counts["us-1"]);
+    assert_eq!(18, counts["us-2"]);
+
+    assert!(queue.update_group_weight(&"eu", NonZeroUsize::new(3).unwrap()));
+    assert!(queue.delete_instance(&"us", ("us-1", 1usize).try_into().unwrap()));
+    assert!(queue.delete_instance(&"us", ("us-2", 2usize).try_into().unwrap()));
+    assert_eq!(&"eu-1", queue.select().unwrap().data());
+    assert_eq!(0, queue.delete_group(&"us").unwrap().len());
+    assert_eq!(
+        vec![(&"eu", NonZeroUsize::new(3).unwrap())],
+        queue.group_weights()
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_deficit_round_robin_test() {
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin).deficit_quantum(4);
+    assert!(queue.select_with_cost(1).await.is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    let mut result = Vec::new();
+    for cost in [4, 1, 1, 1, 1, 4] {
+        result.push(*queue.select_with_cost(cost).await.unwrap().data());
+    }
+    assert_eq!(result, vec!["a", "b", "b", "b", "b", "a"]);
+    assert!(queue.select_with_cost(1000).await.is_some());
+
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin);
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize)]).await;
+    let simulated = queue.simulate(40).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 30)]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn deficit_round_robin_test() {
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin).deficit_quantum(4);
+    assert!(queue.select_with_cost(1).is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    let result: Vec<_> = [4, 1, 1, 1, 1, 4]
+        .into_iter()
+        .map(|cost| *queue.select_with_cost(cost).unwrap().data())
+        .collect();
+    assert_eq!(result, vec!["a", "b", "b", "b", "b", "a"]);
+    assert!(queue.select_with_cost(1000).is_some());
+
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin);
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize)]);
+    let simulated = queue.simulate(40);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("a", 10), ("b", 30)]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_traffic_split_test() {
+    let mut split = TrafficSplit::new(WrrQueue::new(), WrrQueue::new()).canary_percent(5.0);
+    assert!(split.select().await.is_none());
+    split
+        .stable_mut()
+        .insert_many(vec![("v1-a", 1usize), ("v1-b", 9usize)])
+        .await;
+    split.canary_mut().insert(("v2", 1usize)).await;
+    let mut canary = 0;
+    for _ in 0..10_000 {
+        if *split.select().await.unwrap().data() == "v2" {
+            canary += 1;
+        }
+    }
+    assert_eq!(500, canary);
+
+    split.set_canary_percent(100.0);
+    assert_eq!(100.0, split.canary_share());
+    assert_eq!(&"v2", split.select().await.unwrap().data());
+    split.canary_mut().clear_instance();
+    assert!(split.select().await.is_some());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn traffic_split_test() {
+    let mut split = TrafficSplit::new(WrrQueue::new(), WrrQueue::new()).canary_percent(5.0);
+    assert!(split.select().is_none());
+    split
+        .stable_mut()
+        .insert_many(vec![("v1-a", 1usize), ("v1-b", 9usize)]);
+    split.canary_mut().insert(("v2", 1usize));
+    let canary = (0..10_000)
+        .filter(|_| *split.select().unwrap().data() == "v2")
+        .count();
+    assert_eq!(500, canary);
+
+    split.set_canary_percent(100.0);
+    assert_eq!(100.0, split.canary_share());
+    assert_eq!(&"v2", split.select().unwrap().data());
+    split.canary_mut().clear_instance();
+    assert!(split.select().is_some());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_shift_weight_test() {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+    let mut queue = WrrQueue::new();
+    queue
+        .insert_many(vec![("blue", 9usize), ("green", 1usize), ("other", 5usize)])
+        .await;
+    assert!(!queue.tick());
+    assert!(!queue.shift_weight(&"blue", &"red", 50.0, Duration::ZERO));
+
+    assert!(queue.shift_weight(&"blue", &"green", 50.0, Duration::from_secs(3600)));
+    assert!(queue.tick());
+    assert_eq!(
+        Some(&NonZeroUsize::new(9).unwrap()),
+        queue.weights().iter().map(|(_, w)| w).next()
+    );
+
+    assert!(queue.shift_weight(&"blue", &"green", 100.0, Duration::ZERO));
+    assert!(!queue.tick());
+    let weights: Vec<_> = queue
+        .weights()
+        .into_iter()
+        .map(|(d, w)| (*d, w.get()))
+        .collect();
+    assert_eq!(weights, vec![("blue", 1), ("green", 9), ("other", 5)]);
+    let simulated = queue.simulate(15).await;
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("blue", 1), ("green", 9), ("other", 5)]);
+
+    assert!(queue.shift_weight(&"green", &"blue", 50.0, Duration::from_secs(3600)));
+    queue
+        .delete_instance(("blue", 1usize).try_into().unwrap())
+        .await;
+    assert!(!queue.tick());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn shift_weight_test() {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("blue", 9usize), ("green", 1usize), ("other", 5usize)]);
+    assert!(!queue.tick());
+    assert!(!queue.shift_weight(&"blue", &"red", 50.0, Duration::ZERO));
+
+    assert!(queue.shift_weight(&"blue", &"green", 50.0, Duration::from_secs(3600)));
+    assert!(queue.tick());
+    assert_eq!(
+        Some(&NonZeroUsize::new(9).unwrap()),
+        queue.weights().iter().map(|(_, w)| w).next()
+    );
+
+    assert!(queue.shift_weight(&"blue", &"green", 100.0, Duration::ZERO));
+    assert!(!queue.tick());
+    let weights: Vec<_> = queue
+        .weights()
+        .into_iter()
+        .map(|(d, w)| (*d, w.get()))
+        .collect();
+    assert_eq!(weights, vec![("blue", 1), ("green", 9), ("other", 5)]);
+    let simulated = queue.simulate(15);
+    let counts: Vec<_> = simulated.iter().map(|(i, c)| (*i.data(), *c)).collect();
+    assert_eq!(counts, vec![("blue", 1), ("green", 9), ("other", 5)]);
+
+    assert!(queue.shift_weight(&"green", &"blue", 50.0, Duration::from_secs(3600)));
+    queue.delete_instance(("blue", 1usize).try_into().unwrap());
+    assert!(!queue.tick());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_task_queue_test() {
+    let mut queue = WrrTaskQueue::new();
+    assert!(queue.insert_source(("a", 1usize)).await);
+    assert!(queue.insert_source(("b", 2usize)).await);
+    assert!(!queue.insert_source(("b", 1usize)).await);
+    assert_eq!(Err(("c", 0)), queue.push(&"c", ("c", 0)));
+    assert!(queue.pop().await.is_none());
+
+    for job in 0..4 {
+        queue.push(&"a", ("a", job)).unwrap();
+        queue.push(&"b", ("b", job)).unwrap();
+    }
+    assert_eq!(8, queue.len());
+    let mut sources = Vec::new();
+    while let Some((source, _)) = queue.pop().await {
+        sources.push(source);
+    }
+    assert_eq!(sources, vec!["b", "a", "b", "b", "a", "b", "a", "a"]);
+
+    queue.push(&"a", ("a", 4)).unwrap();
+    assert_eq!(Some(("a", 4)), queue.pop().await);
+    queue.push(&"b", ("b", 4)).unwrap();
+    let left = queue
+        .delete_source(("b", 2usize).try_into().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(1, left.len());
+    assert!(queue.is_empty());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn task_queue_test() {
+    let mut queue = WrrTaskQueue::new();
+    assert!(queue.insert_source(("a", 1usize)));
+    assert!(queue.insert_source(("b", 2usize)));
+    assert!(!queue.insert_source(("b", 1usize)));
+    assert_eq!(Err(("c", 0)), queue.push(&"c", ("c", 0)));
+    assert!(queue.pop().is_none());
+
+    for job in 0..4 {
+        queue.push(&"a", ("a", job)).unwrap();
+        queue.push(&"b", ("b", job)).unwrap();
+    }
+    assert_eq!(8, queue.len());
+    let mut sources = Vec::new();
+    while let Some((source, _)) = queue.pop() {
+        sources.push(source);
+    }
+    assert_eq!(sources, vec!["b", "a", "b", "b", "a", "b", "a", "a"]);
+
+    queue.push(&"a", ("a", 4)).unwrap();
+    assert_eq!(Some(("a", 4)), queue.pop());
+    queue.push(&"b", ("b", 4)).unwrap();
+    let left = queue
+        .delete_source(("b", 2usize).try_into().unwrap())
+        .unwrap();
+    assert_eq!(1, left.len());
+    assert!(queue.is_empty());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_for_class_test() {
+    let mut queue = WrrQueue::new();
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 2usize)])
+        .await;
+    assert!(queue.select_for_class("batch").is_none());
+    queue.register_class("batch", vec![("a", 3.0), ("c", 0.0)]);
+    queue.register_class("interactive", vec![("a", 0.0)]);
+
+    let batch: Vec<_> = (0..8)
+        .map(|_| *queue.select_for_class("batch").unwrap().data())
+        .collect();
+    assert_eq!(6, batch.iter().filter(|d| **d == "a").count());
+    assert_eq!(2, batch.iter().filter(|d| **d == "b").count());
+    let interactive: Vec<_> = (0..6)
+        .map(|_| *queue.select_for_class("interactive").unwrap().data())
+        .collect();
+    assert_eq!(4, interactive.iter().filter(|d| **d == "c").count());
+    assert!(!interactive.contains(&"a"));
+
+    queue.insert(("d", 2usize)).await;
+    assert!((0..5).any(|_| *queue.select_for_class("interactive").unwrap().data() == "d"));
+    assert!(queue.unregister_class("batch"));
+    assert!(!queue.unregister_class("batch"));
+    assert!(queue.select_for_class("batch").is_none());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_for_class_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize), ("c", 2usize)]);
+    assert!(queue.select_for_class("batch").is_none());
+    queue.register_class("batch", vec![("a", 3.0), ("c", 0.0)]);
+    queue.register_class("interactive", vec![("a", 0.0)]);
+
+    let batch: Vec<_> = (0..8)
+        .map(|_| *queue.select_for_class("batch").unwrap().data())
+        .collect();
+    assert_eq!(6, batch.iter().filter(|d| **d == "a").count());
+    assert_eq!(2, batch.iter().filter(|d| **d == "b").count());
+    let interactive: Vec<_> = (0..6)
+        .map(|_| *queue.select_for_class("interactive").unwrap().data())
+        .collect();
+    assert_eq!(4, interactive.iter().filter(|d| **d == "c").count());
+    assert!(!interactive.contains(&"a"));
+
+    queue.insert(("d", 2usize));
+    assert!((0..5).any(|_| *queue.select_for_class("interactive").unwrap().data() == "d"));
+    assert!(queue.unregister_class("batch"));
+    assert!(!queue.unregister_class("batch"));
+    assert!(queue.select_for_class("batch").is_none());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_subset_test() {
+    let pool: Vec<_> = (0..50usize).map(|i| (i, 1usize)).collect();
+    let mut queue = SubsetWrrQueue::new(5, 7);
+    assert!(queue.select().await.is_none());
+    assert!(queue.insert_many(pool.clone()).await);
+    assert_eq!(50, queue.pool_len());
+    assert_eq!(5, queue.subset().len());
+
+    let mut same = SubsetWrrQueue::new(5, 7);
+    same.insert_many(pool.clone()).await;
+    assert_eq!(queue.subset().weights(), same.subset().weights());
+    let mut other = SubsetWrrQueue::new(5, 8);
+    other.insert_many(pool).await;
+    assert_ne!(queue.subset().weights(), other.subset().weights());
+
+    let before: Vec<usize> = queue.subset().weights().iter().map(|(d, _)| **d).collect();
+    let leaving = before[0];
+    assert!(
+        queue
+            .delete_instance((leaving, 1usize).try_into().unwrap())
+            .await
+    );
+    let after: Vec<usize> = queue.subset().weights().iter().map(|(d, _)| **d).collect();
+    assert_eq!(5, after.len());
+    assert!(!after.contains(&leaving));
+    assert!(before[1..].iter().all(|d| after.contains(d)));
+    assert!(after.contains(queue.select().await.unwrap().data()));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn subset_test() {
+    let pool: Vec<_> = (0..50usize).map(|i| (i, 1usize)).collect();
+    let mut queue = SubsetWrrQueue::new(5, 7);
+    assert!(queue.select().is_none());
+    assert!(queue.insert_many(pool.clone()));
+    assert_eq!(50, queue.pool_len());
+    assert_eq!(5, queue.subset().len());
+
+    let mut same = SubsetWrrQueue::new(5, 7);
+    same.insert_many(pool.clone());
+    assert_eq!(queue.subset().weights(), same.subset().weights());
+    let mut other = SubsetWrrQueue::new(5, 8);
+    other.insert_many(pool);
+    assert_ne!(queue.subset().weights(), other.subset().weights());
+
+    let before: Vec<usize> = queue.subset().weights().iter().map(|(d, _)| **d).collect();
+    let leaving = before[0];
+    assert!(queue.delete_instance((leaving, 1usize).try_into().unwrap()));
+    let after: Vec<usize> = queue.subset().weights().iter().map(|(d, _)| **d).collect();
+    assert_eq!(5, after.len());
+    assert!(!after.contains(&leaving));
+    assert!(before[1..].iter().all(|d| after.contains(d)));
+    assert!(after.contains(queue.select().unwrap().data()));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_standby_test() {
+    for engine in [Engine::Expanded, Engine::Smooth, Engine::RoundRobin] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert(("active1", 1usize)).await;
+        queue.insert(("active2", 2usize)).await;
+        assert!(queue.insert_standby(("backup", 5usize)).await);
+        assert_eq!(Some(true), queue.is_standby(&"backup"));
+        for _ in 0..30 {
+            assert_ne!(&"backup", queue.select().await.unwrap().data());
+        }
+        let simulated = queue.simulate(30).await;
+        assert_eq!(0, simulated[2].1);
+        assert_ne!(Some("backup"), queue.select_by_key(&7).map(|i| *i.data()));
+
+        queue
+            .delete_instance(("active1", 1usize).try_into().unwrap())
+            .await;
+        queue
+            .delete_instance(("active2", 2usize).try_into().unwrap())
+            .await;
+        assert_eq!(&"backup", queue.select().await.unwrap().data());
+
+        queue.insert(("active3", 1usize)).await;
+        for _ in 0..10 {
+            assert_eq!(&"active3", queue.select().await.unwrap().data());
+        }
+        assert!(queue.set_standby(&"backup", false));
+        let simulated = queue.simulate(6).await;
+        assert!(simulated.iter().all(|(_, n)| *n > 0));
+        assert!(!queue.set_standby(&"missing", true));
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn standby_test() {
+    for engine in [Engine::Expanded, Engine::Smooth, Engine::RoundRobin] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert(("active1", 1usize));
+        queue.insert(("active2", 2usize));
+        assert!(queue.insert_standby(("backup", 5usize)));
+        assert_eq!(Some(true), queue.is_standby(&"backup"));
+        for _ in 0..30 {
+            assert_ne!(&"backup", queue.select().unwrap().data());
+        }
+        let simulated = queue.simulate(30);
+        assert_eq!(0, simulated[2].1);
+        assert_ne!(Some("backup"), queue.select_by_key(&7).map(|i| *i.data()));
+
+        queue.delete_instance(("active1", 1usize).try_into().unwrap());
+        queue.delete_instance(("active2", 2usize).try_into().unwrap());
+        assert_eq!(&"backup", queue.select().unwrap().data());
+
+        queue.insert(("active3", 1usize));
+        for _ in 0..10 {
+            assert_eq!(&"active3", queue.select().unwrap().data());
+        }
+        assert!(queue.set_standby(&"backup", false));
+        let simulated = queue.simulate(6);
+        assert!(simulated.iter().all(|(_, n)| *n > 0));
+        assert!(!queue.set_standby(&"missing", true));
+    }
+}
+
+#[cfg(all(feature = "tokio", not(wrr_sync), feature = "bandit"))]
+#[tokio::test]
+async fn tokio_bandit_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::with_engine(Engine::Bandit).bandit_min_share(0.2);
+    queue
+        .insert_many(vec![("good", 1usize), ("bad", 1usize)])
+        .await;
+    let simulated = queue.simulate(1000).await;
+    assert_eq!(500, simulated[0].1);
+
+    for _ in 0..200 {
+        let _: Result<(), WrrError> = queue
+            .run_on(|i| async move {
+                match *i.data() {
+                    "good" => Ok(()),
+                    _ => Err(WrrError::Empty),
+                }
+            })
+            .await;
+    }
+    let simulated = queue.simulate(1000).await;
+    assert!(simulated[0].1 > 800);
+    assert!(simulated[1].1 >= 100);
+
+    let mut queue = WrrQueue::with_engine(Engine::Bandit);
+    queue
+        .insert_many(vec![("fast", 1usize), ("slow", 1usize)])
+        .await;
+    queue.report_latency(&"fast", Duration::from_millis(10));
+    queue.report_latency(&"slow", Duration::from_millis(90));
+    let simulated = queue.simulate(1000).await;
+    assert!(simulated[0].1 > 800);
+    assert!(simulated[1].1 >= 50);
+}
+
+#[cfg(all(wrr_sync, feature = "bandit"))]
+#[test]
+fn bandit_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::with_engine(Engine::Bandit).bandit_min_share(0.2);
+    queue.insert_many(vec![("good", 1usize), ("bad", 1usize)]);
+    let simulated = queue.simulate(1000);
+    assert_eq!(500, simulated[0].1);
+
+    for _ in 0..200 {
+        let _: Result<(), WrrError> = queue.run_on(|i| match *i.data() {
+            "good" => Ok(()),
+            _ => Err(WrrError::Empty),
+        });
+    }
+    let simulated = queue.simulate(1000);
+    assert!(simulated[0].1 > 800);
+    assert!(simulated[1].1 >= 100);
+
+    let mut queue = WrrQueue::with_engine(Engine::Bandit);
+    queue.insert_many(vec![("fast", 1usize), ("slow", 1usize)]);
+    queue.report_latency(&"fast", Duration::from_millis(10));
+    queue.report_latency(&"slow", Duration::from_millis(90));
+    let simulated = queue.simulate(1000);
+    assert!(simulated[0].1 > 800);
+    assert!(simulated[1].1 >= 50);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_hashed_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.select_hashed(&1).is_none());
+    queue
+        .insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)])
+        .await;
+    let routed: Vec<&str> = (0..8000usize)
+        .map(|key| *queue.select_hashed(&key).unwrap().data())
+        .collect();
+    let share = |data: &str| routed.iter().filter(|d| **d == data).count();
+    assert!((800..1200).contains(&share("a")));
+    assert!((2700..3300).contains(&share("b")));
+    assert!((3600..4400).contains(&share("c")));
+
+    // the cursor-based selection runs over the same members
+    assert_eq!(&"c", queue.select().await.unwrap().data());
+    assert_eq!(routed[42], *queue.select_hashed(&42usize).unwrap().data());
+
+    queue
+        .delete_instance(("b", 3usize).try_into().unwrap())
+        .await;
+    for (key, data) in routed.iter().enumerate() {
+        let moved = *queue.select_hashed(&key).unwrap().data();
+        if *data != "b" {
+            assert_eq!(*data, moved);
+        }
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_hashed_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.select_hashed(&1).is_none());
+    queue.insert_many(vec![("a", 1usize), ("b", 3usize), ("c", 4usize)]);
+    let routed: Vec<&str> = (0..8000usize)
+        .map(|key| *queue.select_hashed(&key).unwrap().data())
+        .collect();
+    let share = |data: &str| routed.iter().filter(|d| **d == data).count();
+    assert!((800..1200).contains(&share("a")));
+    assert!((2700..3300).contains(&share("b")));
+    assert!((3600..4400).contains(&share("c")));
+
+    // the cursor-based selection runs over the same members
+    assert_eq!(&"c", queue.select().unwrap().data());
+    assert_eq!(routed[42], *queue.select_hashed(&42usize).unwrap().data());
+
+    queue.delete_instance(("b", 3usize).try_into().unwrap());
+    for (key, data) in routed.iter().enumerate() {
+        let moved = *queue.select_hashed(&key).unwrap().data();
+        if *data != "b" {
+            assert_eq!(*data, moved);
+        }
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_mark_down_test() {
+    for engine in [
+        Engine::Expanded,
+        Engine::Smooth,
+        Engine::RoundRobin,
+        Engine::LeastConnections,
+        Engine::DeficitRoundRobin,
+    ] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 3usize)])
+            .await;
+        assert!(queue.mark_down(&"b"));
+        assert_eq!(Some(false), queue.is_up(&"b"));
+        for _ in 0..20 {
+            assert_ne!(&"b", queue.select().await.unwrap().data());
+        }
+        assert_eq!(0, queue.simulate(20).await[1].1);
+        assert_ne!(&"b", queue.select_hashed(&3).unwrap().data());
+        assert_eq!(vec![(&"a", 1), (&"b", 2), (&"c", 3)], {
+            let weights = queue.weights();
+            weights
+                .into_iter()
+                .map(|(d, w)| (d, w.get()))
+                .collect::<Vec<_>>()
+        });
+
+        queue.mark_down(&"a");
+        queue.mark_down(&"c");
+        assert!(queue.select().await.is_none());
+        assert!(queue.insert_standby(("backup", 1usize)).await);
+        assert_eq!(&"backup", queue.select().await.unwrap().data());
+
+        assert!(queue.mark_up(&"b"));
+        assert_eq!(Some(true), queue.is_up(&"b"));
+        for _ in 0..10 {
+            assert_eq!(&"b", queue.select().await.unwrap().data());
+        }
+        assert!(!queue.mark_down(&"missing"));
+        assert_eq!(None, queue.is_up(&"missing"));
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn mark_down_test() {
+    for engine in [
+        Engine::Expanded,
+        Engine::Smooth,
+        Engine::RoundRobin,
+        Engine::LeastConnections,
+        Engine::DeficitRoundRobin,
+    ] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 3usize)]);
+        assert!(queue.mark_down(&"b"));
+        assert_eq!(Some(false), queue.is_up(&"b"));
+        for _ in 0..20 {
+            assert_ne!(&"b", queue.select().unwrap().data());
+        }
+        assert_eq!(0, queue.simulate(20)[1].1);
+        assert_ne!(&"b", queue.select_hashed(&3).unwrap().data());
+        assert_eq!(vec![(&"a", 1), (&"b", 2), (&"c", 3)], {
+            let weights = queue.weights();
+            weights
+                .into_iter()
+                .map(|(d, w)| (d, w.get()))
+                .collect::<Vec<_>>()
+        });
+
+        queue.mark_down(&"a");
+        queue.mark_down(&"c");
+        assert!(queue.select().is_none());
+        assert!(queue.insert_standby(("backup", 1usize)));
+        assert_eq!(&"backup", queue.select().unwrap().data());
+
+        assert!(queue.mark_up(&"b"));
+        assert_eq!(Some(true), queue.is_up(&"b"));
+        for _ in 0..10 {
+            assert_eq!(&"b", queue.select().unwrap().data());
+        }
+        assert!(!queue.mark_down(&"missing"));
+        assert_eq!(None, queue.is_up(&"missing"));
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_report_outcome_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize)).await;
+    assert!(!queue.report_success(&"b"));
+    assert!(!queue.report_failure(&"b"));
+
+    let selected = *queue.select().await.unwrap().data();
+    assert!(queue.report_failure(&selected));
+    assert!(queue.report_failure(&selected));
+    assert_eq!(2, queue.stats(&"a").unwrap().consecutive_failures);
+    assert!(queue.report_success(&selected));
+    assert_eq!(
+        Some(InstanceStats {
+            success: 1,
+            failure: 2,
+            consecutive_failures: 0,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"a")
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn report_outcome_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert(("a", 1usize));
+    assert!(!queue.report_success(&"b"));
+    assert!(!queue.report_failure(&"b"));
+
+    let selected = *queue.select().unwrap().data();
+    assert!(queue.report_failure(&selected));
+    assert!(queue.report_failure(&selected));
+    assert_eq!(2, queue.stats(&"a").unwrap().consecutive_failures);
+    assert!(queue.report_success(&selected));
+    assert_eq!(
+        Some(InstanceStats {
+            success: 1,
+            failure: 2,
+            consecutive_failures: 0,
+            saturated: 0,
+            throttled: 0
+        }),
+        queue.stats(&"a")
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_outlier_detection_test() {
+    use std::time::Duration;
+
+    let detection = OutlierDetection::new(Duration::from_millis(50)).consecutive_failures(2);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    assert!(queue.report_failure(&"a"));
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    assert!(queue.report_failure(&"a"));
+    assert_eq!(Some(true), queue.is_ejected(&"a"));
+    assert_eq!(0, queue.stats(&"a").unwrap().consecutive_failures);
+    for _ in 0..10 {
+        assert_eq!(&"b", queue.select().await.unwrap().data());
+    }
+
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    let mut selected = Vec::new();
+    for _ in 0..2 {
+        selected.push(*queue.select().await.unwrap().data());
+    }
+    assert!(selected.contains(&"a"));
+
+    let detection = OutlierDetection::new(Duration::from_secs(60)).failure_rate(0.5, 4);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    for success in [false, true, false] {
+        queue.report_success(&"b");
+        if success {
+            queue.report_success(&"a");
+        } else {
+            queue.report_failure(&"a");
+        }
+    }
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    queue.report_failure(&"a");
+    assert_eq!(Some(true), queue.is_ejected(&"a"));
+    assert_eq!(Some(false), queue.is_ejected(&"b"));
+    assert_eq!(&"b", queue.select().await.unwrap().data());
+    assert_eq!(&"b", queue.select_hashed(&"key").unwrap().data());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn outlier_detection_test() {
+    use std::time::Duration;
+
+    let detection = OutlierDetection::new(Duration::from_millis(50)).consecutive_failures(2);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    assert!(queue.report_failure(&"a"));
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    assert!(queue.report_failure(&"a"));
+    assert_eq!(Some(true), queue.is_ejected(&"a"));
+    assert_eq!(0, queue.stats(&"a").unwrap().consecutive_failures);
+    for _ in 0..10 {
+        assert_eq!(&"b", queue.select().unwrap().data());
+    }
+
+    std::thread::sleep(Duration::from_millis(60));
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    let mut selected = Vec::new();
+    for _ in 0..2 {
+        selected.push(*queue.select().unwrap().data());
+    }
+    assert!(selected.contains(&"a"));
+
+    let detection = OutlierDetection::new(Duration::from_secs(60)).failure_rate(0.5, 4);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    for success in [false, true, false] {
+        queue.report_success(&"b");
+        if success {
+            queue.report_success(&"a");
+        } else {
+            queue.report_failure(&"a");
+        }
+    }
+    assert_eq!(Some(false), queue.is_ejected(&"a"));
+    queue.report_failure(&"a");
+    assert_eq!(Some(true), queue.is_ejected(&"a"));
+    assert_eq!(Some(false), queue.is_ejected(&"b"));
+    assert_eq!(&"b", queue.select().unwrap().data());
+    assert_eq!(&"b", queue.select_hashed(&"key").unwrap().data());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_latency_weighting_test() {
+    use std::time::Duration;
+
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine).latency_weighting(0.25, 4.0);
+        queue
+            .insert_many(vec![("fast", 1usize), ("slow", 1usize)])
+            .await;
+        queue.report_latency(&"fast", Duration::from_millis(10));
+        queue.report_latency(&"slow", Duration::from_millis(90));
+        queue.select().await;
+        // average 50ms: `fast` is scaled up to the 4x bound, `slow` down to 5/9
+        let simulated = queue.simulate(730).await;
+        assert_eq!(640, simulated[0].1);
+        assert_eq!(90, simulated[1].1);
+        let weights: Vec<_> = queue.weights().into_iter().map(|(_, w)| w.get()).collect();
+        assert_eq!(vec![1, 1], weights);
+    }
+
+    let mut queue = WrrQueue::new();
+    queue
+        .insert_many(vec![("fast", 1usize), ("slow", 1usize)])
+        .await;
+    queue.report_latency(&"slow", Duration::from_millis(90));
+    queue.select().await;
+    assert_eq!(50, queue.simulate(100).await[0].1);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn latency_weighting_test() {
+    use std::time::Duration;
+
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        let mut queue = WrrQueue::with_engine(engine).latency_weighting(0.25, 4.0);
+        queue.insert_many(vec![("fast", 1usize), ("slow", 1usize)]);
+        queue.report_latency(&"fast", Duration::from_millis(10));
+        queue.report_latency(&"slow", Duration::from_millis(90));
+        queue.select();
+        // average 50ms: `fast` is scaled up to the 4x bound, `slow` down to 5/9
+        let simulated = queue.simulate(730);
+        assert_eq!(640, simulated[0].1);
+        assert_eq!(90, simulated[1].1);
+        let weights: Vec<_> = queue.weights().into_iter().map(|(_, w)| w.get()).collect();
+        assert_eq!(vec![1, 1], weights);
+    }
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("fast", 1usize), ("slow", 1usize)]);
+    queue.report_latency(&"slow", Duration::from_millis(90));
+    queue.select();
+    assert_eq!(50, queue.simulate(100)[0].1);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_error_rate_weighting_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().error_rate_weighting(0.2, 0.2);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    for _ in 0..10 {
+        queue.report_failure(&"a");
+    }
+    let error_rate = queue.error_rate(&"a").unwrap();
+    assert!((error_rate - 0.651).abs() < 0.001);
+    queue.select().await;
+    // scaled by 1 - 0.651, rounded to sixteenths
+    assert_eq!(Some(0.375), queue.effective_weight(&"a"));
+    assert_eq!(Some(1.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(22).await;
+    assert_eq!(6, simulated[0].1);
+
+    // the error rate falls to 0.08, below half of the hysteresis
+    for _ in 0..20 {
+        queue.report_success(&"a");
+    }
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    queue.select().await;
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    assert_eq!(None, queue.error_rate(&"c"));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn error_rate_weighting_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().error_rate_weighting(0.2, 0.2);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    for _ in 0..10 {
+        queue.report_failure(&"a");
+    }
+    let error_rate = queue.error_rate(&"a").unwrap();
+    assert!((error_rate - 0.651).abs() < 0.001);
+    queue.select();
+    // scaled by 1 - 0.651, rounded to sixteenths
+    assert_eq!(Some(0.375), queue.effective_weight(&"a"));
+    assert_eq!(Some(1.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(22);
+    assert_eq!(6, simulated[0].1);
+
+    // the error rate falls to 0.08, below half of the hysteresis
+    for _ in 0..20 {
+        queue.report_success(&"a");
+    }
+    std::thread::sleep(Duration::from_millis(1100));
+    queue.select();
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    assert_eq!(None, queue.error_rate(&"c"));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_slow_start_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().slow_start(Duration::from_secs(1), 0.25);
+    queue.insert(("a", 4usize)).await;
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    tokio::time::sleep(Duration::from_millis(1050)).await;
+    queue.insert(("b", 4usize)).await;
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(1.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(5).await;
+    assert_eq!(1, simulated[1].1);
+
+    queue.mark_down(&"a");
+    queue.mark_up(&"a");
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    tokio::time::sleep(Duration::from_millis(1050)).await;
+    queue.select().await;
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(4.0), queue.effective_weight(&"b"));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn slow_start_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().slow_start(Duration::from_secs(1), 0.25);
+    queue.insert(("a", 4usize));
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    std::thread::sleep(Duration::from_millis(1050));
+    queue.insert(("b", 4usize));
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(1.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(5);
+    assert_eq!(1, simulated[1].1);
+
+    queue.mark_down(&"a");
+    queue.mark_up(&"a");
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    std::thread::sleep(Duration::from_millis(1050));
+    queue.select();
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(4.0), queue.effective_weight(&"b"));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_cooldown_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().cooldown(Duration::from_secs(1), 0.25);
+    queue.insert_many(vec![("a", 4usize), ("b", 4usize)]).await;
+    // inserted instances are not cooling down
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+
+    queue.mark_down(&"a");
+    assert_eq!(Some(0.0), queue.effective_weight(&"a"));
+    queue.mark_up(&"a");
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(4.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(5).await;
+    assert_eq!(1, simulated[0].1);
+
+    tokio::time::sleep(Duration::from_millis(1050)).await;
+    queue.select().await;
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn cooldown_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().cooldown(Duration::from_secs(1), 0.25);
+    queue.insert_many(vec![("a", 4usize), ("b", 4usize)]);
+    // inserted instances are not cooling down
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+
+    queue.mark_down(&"a");
+    assert_eq!(Some(0.0), queue.effective_weight(&"a"));
+    queue.mark_up(&"a");
+    assert_eq!(Some(1.0), queue.effective_weight(&"a"));
+    assert_eq!(Some(4.0), queue.effective_weight(&"b"));
+    let simulated = queue.simulate(5);
+    assert_eq!(1, simulated[0].1);
+
+    std::thread::sleep(Duration::from_millis(1050));
+    queue.select();
+    assert_eq!(Some(4.0), queue.effective_weight(&"a"));
+}
+
+#[derive(Default, Clone)]
+struct RecordingHooks {
+    events: std::sync::Arc<std::sync::Mutex<Vec<String>>>,
+}
+
+impl RecordingHooks {
+    fn push(&self, event: String) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+impl Hooks<&'static str> for RecordingHooks {
+    fn on_insert(&self, instance: &Instance<&'static str>) {
+        self.push(format!("insert {}", instance.data()));
+    }
+
+    fn on_remove(&self, instance: &Instance<&'static str>) {
+        self.push(format!("remove {}", instance.data()));
+    }
+
+    fn on_state_change(&self, instance: &Instance<&'static str>, from: Health, to: Health) {
+        self.push(format!("{} {from:?} -> {to:?}", instance.data()));
+    }
+
+    fn on_select(&self, instance: &Instance<&'static str>) {
+        self.push(format!("select {}", instance.data()));
+    }
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_hooks_test() {
+    let hooks = RecordingHooks::default();
+    let mut queue = WrrQueue::new().hooks(hooks.clone());
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    queue.mark_down(&"a");
+    queue.select().await;
+    queue.pause(&"a");
+    queue.mark_up(&"a");
+    queue.resume(&"a");
+    queue
+        .delete_instance(("b", 1usize).try_into().unwrap())
+        .await;
+    queue.clear_instance();
+    assert_eq!(
+        *hooks.events.lock().unwrap(),
+        vec![
+            "insert a",
+            "insert b",
+            "a Up -> Down",
+            "select b",
+            "a Down -> Paused",
+            "a Paused -> Up",
+            "remove b",
+            "remove a",
+        ]
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn hooks_test() {
+    let hooks = RecordingHooks::default();
+    let mut queue = WrrQueue::new().hooks(hooks.clone());
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    queue.mark_down(&"a");
+    queue.select();
+    queue.pause(&"a");
+    queue.mark_up(&"a");
+    queue.resume(&"a");
+    queue.delete_instance(("b", 1usize).try_into().unwrap());
+    queue.clear_instance();
+    assert_eq!(
+        *hooks.events.lock().unwrap(),
+        vec![
+            "insert a",
+            "insert b",
+            "a Up -> Down",
+            "select b",
+            "a Down -> Paused",
+            "a Paused -> Up",
+            "remove b",
+            "remove a",
+        ]
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_hooks_on_every_select_test() {
+    let hooks = RecordingHooks::default();
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin).hooks(hooks.clone());
+    queue.insert(("a", 1usize)).await;
+    queue.register_class("bulk", [("a", 2.0)]);
+    queue.select_with_cost(1).await;
+    queue.select_by_key(&1);
+    queue.select_hashed(&1);
+    queue.select_for_class("bulk");
+    assert_eq!(
+        *hooks.events.lock().unwrap(),
+        vec!["insert a", "select a", "select a", "select a", "select a"]
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn hooks_on_every_select_test() {
+    let hooks = RecordingHooks::default();
+    let mut queue = WrrQueue::with_engine(Engine::DeficitRoundRobin).hooks(hooks.clone());
+    queue.insert(("a", 1usize));
+    queue.register_class("bulk", [("a", 2.0)]);
+    queue.select_with_cost(1);
+    queue.select_by_key(&1);
+    queue.select_hashed(&1);
+    queue.select_for_class("bulk");
+    assert_eq!(
+        *hooks.events.lock().unwrap(),
+        vec!["insert a", "select a", "select a", "select a", "select a"]
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_health_summary_test() {
+    use std::time::Duration;
+
+    let detection = OutlierDetection::new(Duration::from_secs(30)).consecutive_failures(1);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue
+        .insert_many(vec![
+            ("a", 1usize),
+            ("b", 1usize),
+            ("c", 1usize),
+            ("d", 1usize),
+            ("e", 1usize),
+        ])
+        .await;
+    queue.report_failure(&"b");
+    queue.mark_down(&"c");
+    queue.pause(&"d");
+    queue.drain(&"e");
+
+    let summary = queue.health_summary();
+    assert_eq!(
+        (1, 1, 1, 1, 1),
+        (
+            summary.up,
+            summary.ejected,
+            summary.down,
+            summary.paused,
+            summary.draining
+        )
+    );
+    assert_eq!(
+        summary.instances,
+        vec![
+            (&"a", Health::Up),
+            (&"b", Health::Ejected),
+            (&"c", Health::Down),
+            (&"d", Health::Paused),
+            (&"e", Health::Draining),
+        ]
+    );
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn health_summary_test() {
+    use std::time::Duration;
+
+    let detection = OutlierDetection::new(Duration::from_secs(30)).consecutive_failures(1);
+    let mut queue = WrrQueue::new().outlier_detection(detection);
+    queue.insert_many(vec![
+        ("a", 1usize),
+        ("b", 1usize),
+        ("c", 1usize),
+        ("d", 1usize),
+        ("e", 1usize),
+    ]);
+    queue.report_failure(&"b");
+    queue.mark_down(&"c");
+    queue.pause(&"d");
+    queue.drain(&"e");
+
+    let summary = queue.health_summary();
+    assert_eq!(
+        (1, 1, 1, 1, 1),
+        (
+            summary.up,
+            summary.ejected,
+            summary.down,
+            summary.paused,
+            summary.draining
+        )
+    );
+    assert_eq!(
+        summary.instances,
+        vec![
+            (&"a", Health::Up),
+            (&"b", Health::Ejected),
+            (&"c", Health::Down),
+            (&"d", Health::Paused),
+            (&"e", Health::Draining),
+        ]
+    );
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_ttl_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().ttl(Duration::from_millis(100), Expiry::MarkDown);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    for _ in 0..3 {
+        tokio::time::sleep(Duration::from_millis(40)).await;
+        assert!(queue.touch(&"a"));
+    }
+    assert_eq!("a", *queue.select().await.unwrap().data());
+    assert_eq!("a", *queue.select().await.unwrap().data());
+    assert_eq!(Some(false), queue.is_up(&"b"));
+
+    assert!(queue.touch(&"b"));
+    assert_eq!(Some(true), queue.is_up(&"b"));
+    assert!(!queue.touch(&"c"));
+
+    let mut queue = WrrQueue::new().ttl(Duration::from_millis(50), Expiry::Remove);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    queue.touch(&"a");
+    assert_eq!("a", *queue.select().await.unwrap().data());
+    assert_eq!(None, queue.is_up(&"b"));
+    tokio::time::sleep(Duration::from_millis(60)).await;
+    assert!(queue.select().await.is_none());
+    assert!(queue.is_empty());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn ttl_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new().ttl(Duration::from_millis(100), Expiry::MarkDown);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    for _ in 0..3 {
+        std::thread::sleep(Duration::from_millis(40));
+        assert!(queue.touch(&"a"));
+    }
+    assert_eq!("a", *queue.select().unwrap().data());
+    assert_eq!("a", *queue.select().unwrap().data());
+    assert_eq!(Some(false), queue.is_up(&"b"));
+
+    assert!(queue.touch(&"b"));
+    assert_eq!(Some(true), queue.is_up(&"b"));
+    assert!(!queue.touch(&"c"));
+
+    let mut queue = WrrQueue::new().ttl(Duration::from_millis(50), Expiry::Remove);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    std::thread::sleep(Duration::from_millis(60));
+    queue.touch(&"a");
+    assert_eq!("a", *queue.select().unwrap().data());
+    assert_eq!(None, queue.is_up(&"b"));
+    std::thread::sleep(Duration::from_millis(60));
+    assert!(queue.select().is_none());
+    assert!(queue.is_empty());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_drain_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    let mut marks = Vec::new();
+    for _ in 0..4 {
+        let (instance, mark) = queue.select_tracked().await.unwrap();
+        if *instance.data() == "b" {
+            marks.push(mark);
+        }
+    }
+    assert_eq!(2, marks.len());
+
+    let drain = queue.drain(&"b").unwrap();
+    assert_eq!(Some(true), queue.is_draining(&"b"));
+    assert_eq!(Some(false), queue.is_draining(&"a"));
+    assert!(queue.drain(&"c").is_none());
+    for _ in 0..4 {
+        assert_eq!("a", *queue.select().await.unwrap().data());
+    }
+    assert_eq!(2, drain.in_flight());
+    assert!(!drain.wait_timeout(Duration::from_millis(20)).await);
+
+    let release = tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(marks);
+    });
+    drain.wait().await;
+    assert!(drain.is_drained());
+    release.await.unwrap();
+    assert!(
+        queue
+            .delete_instance(("b", 1usize).try_into().unwrap())
+            .await
+    );
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn drain_test() {
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    let mut marks = Vec::new();
+    for _ in 0..4 {
+        let (instance, mark) = queue.select_tracked().unwrap();
+        if *instance.data() == "b" {
+            marks.push(mark);
+        }
+    }
+    assert_eq!(2, marks.len());
+
+    let drain = queue.drain(&"b").unwrap();
+    assert_eq!(Some(true), queue.is_draining(&"b"));
+    assert_eq!(Some(false), queue.is_draining(&"a"));
+    assert!(queue.drain(&"c").is_none());
+    for _ in 0..4 {
+        assert_eq!("a", *queue.select().unwrap().data());
+    }
+    assert_eq!(2, drain.in_flight());
+    assert!(!drain.wait_timeout(Duration::from_millis(20)));
+
+    let release = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(20));
+        drop(marks);
+    });
+    drain.wait();
+    assert!(drain.is_drained());
+    release.join().unwrap();
+    assert!(queue.delete_instance(("b", 1usize).try_into().unwrap()));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_pause_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+    queue.report_failure(&"b");
+
+    assert!(queue.pause(&"b"));
+    assert!(!queue.pause(&"c"));
+    assert_eq!(Some(true), queue.is_paused(&"b"));
+    // a paused instance is not put back by marking it up
+    queue.mark_up(&"b");
+    for _ in 0..4 {
+        assert_eq!("a", *queue.select().await.unwrap().data());
+    }
+
+    assert!(queue.resume(&"b"));
+    assert_eq!(Some(false), queue.is_paused(&"b"));
+    assert_eq!(1, queue.stats(&"b").unwrap().failure);
+    let mut selected: Vec<_> = Vec::new();
+    for _ in 0..4 {
+        selected.push(*queue.select().await.unwrap().data());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "b", "b"]);
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn pause_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    queue.report_failure(&"b");
+
+    assert!(queue.pause(&"b"));
+    assert!(!queue.pause(&"c"));
+    assert_eq!(Some(true), queue.is_paused(&"b"));
+    // a paused instance is not put back by marking it up
+    queue.mark_up(&"b");
+    for _ in 0..4 {
+        assert_eq!("a", *queue.select().unwrap().data());
+    }
+
+    assert!(queue.resume(&"b"));
+    assert_eq!(Some(false), queue.is_paused(&"b"));
+    assert_eq!(1, queue.stats(&"b").unwrap().failure);
+    let mut selected: Vec<_> = Vec::new();
+    for _ in 0..4 {
+        selected.push(*queue.select().unwrap().data());
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["a", "a", "b", "b"]);
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_max_in_flight_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 3usize), ("b", 1usize)]).await;
+    assert!(queue.set_max_in_flight(&"a", NonZeroUsize::new(1)));
+    assert!(!queue.set_max_in_flight(&"c", NonZeroUsize::new(1)));
+
+    let (instance, a) = queue.select_tracked().await.unwrap();
+    assert_eq!("a", *instance.data());
+    // "a" spills over to "b" while at its limit
+    let mut marks = Vec::new();
+    for _ in 0..2 {
+        let (instance, mark) = queue.select_tracked().await.unwrap();
+        assert_eq!("b", *instance.data());
+        marks.push(mark);
+    }
+    assert!(queue.stats(&"a").unwrap().saturated > 0);
+    assert_eq!(0, queue.stats(&"b").unwrap().saturated);
+
+    queue.set_max_in_flight(&"b", NonZeroUsize::new(2));
+    assert!(queue.select().await.is_none());
+
+    drop(a);
+    assert_eq!("a", *queue.select().await.unwrap().data());
+    queue.set_max_in_flight(&"b", None);
+    assert!(queue.select().await.is_some());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn max_in_flight_test() {
+    use std::num::NonZeroUsize;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 3usize), ("b", 1usize)]);
+    assert!(queue.set_max_in_flight(&"a", NonZeroUsize::new(1)));
+    assert!(!queue.set_max_in_flight(&"c", NonZeroUsize::new(1)));
+
+    let (instance, a) = queue.select_tracked().unwrap();
+    assert_eq!("a", *instance.data());
+    // "a" spills over to "b" while at its limit
+    let mut marks = Vec::new();
+    for _ in 0..2 {
+        let (instance, mark) = queue.select_tracked().unwrap();
+        assert_eq!("b", *instance.data());
+        marks.push(mark);
+    }
+    assert!(queue.stats(&"a").unwrap().saturated > 0);
+    assert_eq!(0, queue.stats(&"b").unwrap().saturated);
+
+    queue.set_max_in_flight(&"b", NonZeroUsize::new(2));
+    assert!(queue.select().is_none());
+
+    drop(a);
+    assert_eq!("a", *queue.select().unwrap().data());
+    queue.set_max_in_flight(&"b", None);
+    assert!(queue.select().is_some());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_select_lease_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+
+    let lease = queue.select_lease().await.unwrap();
+    let data = *lease.data();
+    lease.report_failure();
+    assert_eq!(Some(0), queue.in_flight(&data));
+    assert_eq!(1, queue.stats(&data).unwrap().failure);
+
+    let lease = queue.select_lease().await.unwrap();
+    let data = *lease.instance().data();
+    lease.report_success();
+    assert_eq!(1, queue.stats(&data).unwrap().success);
+
+    drop(queue.select_lease().await.unwrap());
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+    assert!(WrrQueue::<&str>::new().select_lease().await.is_none());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_leases_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]).await;
+
+    let mut leases = queue.leases().await;
+    let first = leases.next().unwrap();
+    let second = leases.next().unwrap();
+    // the first lease is still in flight, so the second one goes to the other instance
+    assert_ne!(first.data(), second.data());
+    let (first_data, second_data) = (*first.data(), *second.data());
+    first.report_success();
+    second.report_failure();
+    assert_eq!(1, queue.stats(&first_data).unwrap().success);
+    assert_eq!(1, queue.stats(&second_data).unwrap().failure);
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+    assert!(WrrQueue::<&str>::new().leases().await.next().is_none());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn select_lease_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+
+    let lease = queue.select_lease().unwrap();
+    let data = *lease.data();
+    lease.report_failure();
+    assert_eq!(Some(0), queue.in_flight(&data));
+    assert_eq!(1, queue.stats(&data).unwrap().failure);
+
+    let lease = queue.select_lease().unwrap();
+    let data = *lease.instance().data();
+    lease.report_success();
+    assert_eq!(1, queue.stats(&data).unwrap().success);
+
+    drop(queue.select_lease().unwrap());
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+    assert!(WrrQueue::<&str>::new().select_lease().is_none());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn leases_test() {
+    let mut queue = WrrQueue::with_engine(Engine::LeastConnections);
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+
+    let mut leases = queue.leases();
+    let first = leases.next().unwrap();
+    let second = leases.next().unwrap();
+    // the first lease is still in flight, so the second one goes to the other instance
+    assert_ne!(first.data(), second.data());
+    let (first_data, second_data) = (*first.data(), *second.data());
+    first.report_success();
+    second.report_failure();
+    assert_eq!(1, queue.stats(&first_data).unwrap().success);
+    assert_eq!(1, queue.stats(&second_data).unwrap().failure);
+    assert_eq!(Some(0), queue.in_flight(&"a"));
+    assert_eq!(Some(0), queue.in_flight(&"b"));
+    assert!(WrrQueue::<&str>::new().leases().next().is_none());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn iter_selections_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let selected: Vec<_> = queue
+        .iter_selections()
+        .take(6)
+        .map(|instance| *instance.data())
+        .collect();
+    assert_eq!(vec!["b", "a", "b", "b", "a", "b"], selected);
+
+    let jobs = [1, 2, 3, 4];
+    let assigned: Vec<_> = jobs
+        .iter()
+        .zip(queue.iter_selections())
+        .map(|(job, instance)| (*job, *instance.data()))
+        .collect();
+    assert_eq!(4, assigned.len());
+
+    assert!(queue.mark_down(&"a") && queue.mark_down(&"b"));
+    assert_eq!(0, queue.iter_selections().count());
+    assert!(WrrQueue::<&str>::new().iter_selections().next().is_none());
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_quota_test() {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 3usize), ("b", 1usize)]).await;
+    let quota = (NonZeroUsize::new(2).unwrap(), Duration::from_millis(100));
+    assert!(queue.set_quota(&"a", Some(quota)));
+    assert!(!queue.set_quota(&"c", Some(quota)));
+
+    let mut selected = Vec::new();
+    for _ in 0..6 {
+        selected.push(*queue.select().await.unwrap().data());
+    }
+    assert_eq!(2, selected.iter().filter(|data| **data == "a").count());
+    assert!(queue.stats(&"a").unwrap().throttled > 0);
+
+    queue.set_quota(&"b", Some(quota));
+    for _ in 0..2 {
+        assert_eq!("b", *queue.select().await.unwrap().data());
+    }
+    assert!(queue.select().await.is_none());
+    tokio::time::sleep(Duration::from_millis(110)).await;
+    assert!(queue.select().await.is_some());
+
+    queue.set_quota(&"a", None);
+    queue.set_quota(&"b", None);
+    for _ in 0..8 {
+        assert!(queue.select().await.is_some());
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn quota_test() {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 3usize), ("b", 1usize)]);
+    let quota = (NonZeroUsize::new(2).unwrap(), Duration::from_millis(100));
+    assert!(queue.set_quota(&"a", Some(quota)));
+    assert!(!queue.set_quota(&"c", Some(quota)));
+
+    let mut selected = Vec::new();
+    for _ in 0..6 {
+        selected.push(*queue.select().unwrap().data());
+    }
+    assert_eq!(2, selected.iter().filter(|data| **data == "a").count());
+    assert!(queue.stats(&"a").unwrap().throttled > 0);
+
+    queue.set_quota(&"b", Some(quota));
+    for _ in 0..2 {
+        assert_eq!("b", *queue.select().unwrap().data());
+    }
+    assert!(queue.select().is_none());
+    std::thread::sleep(Duration::from_millis(110));
+    assert!(queue.select().is_some());
+
+    queue.set_quota(&"a", None);
+    queue.set_quota(&"b", None);
+    for _ in 0..8 {
+        assert!(queue.select().is_some());
+    }
+}
+
+#[cfg(all(feature = "tokio", not(wrr_sync), feature = "stream"))]
+#[tokio::test]
+async fn tokio_select_stream_test() {
+    use futures::StreamExt;
+
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    let selected: Vec<_> = queue
+        .select_stream()
+        .take(6)
+        .map(|instance| *instance.data())
+        .collect()
+        .await;
+    assert_eq!(vec!["b", "a", "b", "b", "a", "b"], selected);
+
+    let jobs = futures::stream::iter(0..3);
+    let zipped: Vec<_> = queue
+        .select_stream()
+        .zip(jobs)
+        .map(|(instance, job)| (*instance.data(), job))
+        .collect()
+        .await;
+    assert_eq!(3, zipped.len());
+
+    let mut empty: WrrQueue<&str> = WrrQueue::new();
+    assert!(empty.select_stream().next().await.is_none());
+}
+
+#[cfg(feature = "ffi")]
+#[test]
+fn ffi_test() {
+    use async_wrr_queue::ffi::*;
+    use std::ffi::c_void;
+    use std::ptr;
+
+    let mut a = 1u8;
+    let mut b = 2u8;
+    let a_ptr = &mut a as *mut u8 as *mut c_void;
+    let b_ptr = &mut b as *mut u8 as *mut c_void;
+    unsafe {
+        let queue = wrr_queue_new();
+        assert_eq!(-1, wrr_queue_select(queue));
+        assert!(wrr_queue_insert(queue, a_ptr, 1));
+        assert!(wrr_queue_insert(queue, b_ptr, 2));
+        assert!(!wrr_queue_insert(queue, b_ptr, 2));
+        assert!(!wrr_queue_insert(queue, ptr::null_mut(), 0));
+
+        let selected: Vec<_> = (0..3).map(|_| wrr_queue_select(queue)).collect();
+        assert_eq!(vec![1, 0, 1], selected);
+        assert_eq!(b_ptr, wrr_queue_data(queue, 1));
+        assert!(wrr_queue_data(queue, 2).is_null());
+        wrr_queue_free(queue);
+
+        assert!(!wrr_queue_insert(ptr::null_mut(), a_ptr, 1));
+        assert_eq!(-1, wrr_queue_select(ptr::null_mut()));
+        wrr_queue_free(ptr::null_mut());
+    }
+}
+
+#[cfg(all(wrr_sync, feature = "serde"))]
+#[test]
+fn serde_instance_test() {
+    use std::num::NonZeroUsize;
+
+    let instance = Instance::new_with_weight("a".to_string(), NonZeroUsize::new(3).unwrap());
+    let json = serde_json::to_string(&instance).unwrap();
+    assert_eq!(r#"{"data":"a","weight":3}"#, json);
+    assert_eq!(instance, serde_json::from_str(&json).unwrap());
+
+    let instance: Instance<String> = serde_json::from_str(r#"{"data":"b"}"#).unwrap();
+    assert_eq!(Instance::new("b".to_string()), instance);
+
+    let err = serde_json::from_str::<Instance<String>>(r#"{"data":"c","weight":0}"#).unwrap_err();
+    assert!(err.to_string().contains("instance weight must be non-zero"));
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_config_test() {
+    let config = WrrConfig::new(vec![
+        ("a", 1usize).try_into().unwrap(),
+        ("b", 2usize).try_into().unwrap(),
+        ("a", 1usize).try_into().unwrap(),
+    ])
+    .engine(Engine::Smooth);
+    let mut queue = WrrQueue::from_config(config);
+    assert_eq!(2, queue.len());
+    assert_eq!(Engine::Smooth, queue.engine());
+    assert_eq!(&"b", queue.select().await.unwrap().data());
+
+    queue.insert(("c", 4usize)).await;
+    let config = queue.to_config();
+    assert_eq!(3, config.instances().len());
+    assert_eq!(config, WrrQueue::from_config(config.clone()).to_config());
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn config_test() {
+    let config = WrrConfig::new(vec![
+        ("a", 1usize).try_into().unwrap(),
+        ("b", 2usize).try_into().unwrap(),
+        ("a", 1usize).try_into().unwrap(),
+    ])
+    .engine(Engine::Smooth);
+    let mut queue = WrrQueue::from_config(config);
+    assert_eq!(2, queue.len());
+    assert_eq!(Engine::Smooth, queue.engine());
+    assert_eq!(&"b", queue.select().unwrap().data());
+
+    queue.insert(("c", 4usize));
+    let config = queue.to_config();
+    assert_eq!(3, config.instances().len());
+    assert_eq!(config, WrrQueue::from_config(config.clone()).to_config());
+}
+
+#[cfg(all(wrr_sync, feature = "serde"))]
+#[test]
+fn serde_config_test() {
+    let json = r#"{"engine":"round_robin","instances":[{"data":"a","weight":1},{"data":"b"}]}"#;
+    let config: WrrConfig<String> = serde_json::from_str(json).unwrap();
+    let queue = WrrQueue::from_config(config);
+    assert_eq!(Engine::RoundRobin, queue.engine());
+    assert_eq!(
+        json.replacen('{', r#"{"version":1,"#, 1)
+            .replace(r#""b"}"#, r#""b","weight":20}"#),
+        serde_json::to_string(&queue.to_config()).unwrap()
+    );
+}
+
+#[cfg(all(wrr_sync, feature = "config-file"))]
+#[test]
+fn from_path_test() {
+    let dir = std::env::temp_dir().join(format!("wrr_from_path_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let files = [
+        ("json", r#"{"engine":"smooth","instances":[{"data":"a","weight":1},{"data":"b","weight":2}]}"#),
+        ("toml", "engine = \"smooth\"\n[[instances]]\ndata = \"a\"\nweight = 1\n[[instances]]\ndata = \"b\"\nweight = 2\n"),
+        ("yaml", "engine: smooth\ninstances:\n  - data: a\n    weight: 1\n  - data: b\n    weight: 2\n"),
+    ];
+    for (extension, content) in files {
+        let path = dir.join(format!("queue.{extension}"));
+        std::fs::write(&path, content).unwrap();
+        let mut queue: WrrQueue<String> = WrrQueue::from_path(&path).unwrap();
+        assert_eq!(Engine::Smooth, queue.engine());
+        assert_eq!("b", queue.select().unwrap().data());
+    }
+
+    let path = dir.join("zero.json");
+    std::fs::write(&path, r#"{"instances":[{"data":"a","weight":0}]}"#).unwrap();
+    let err = WrrConfig::<String>::from_path(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::Json(..)));
+    assert!(err.to_string().contains("instance weight must be non-zero"));
+
+    let path = dir.join("duplicate.json");
+    std::fs::write(
+        &path,
+        r#"{"instances":[{"data":"a"},{"data":"b"},{"data":"a"}]}"#,
+    )
+    .unwrap();
+    let err = WrrConfig::<String>::from_path(&path).unwrap_err();
+    assert!(matches!(err, ConfigError::Duplicate(_, 2)));
+
+    let err = WrrConfig::<String>::from_path(dir.join("queue.ini")).unwrap_err();
+    assert!(matches!(err, ConfigError::UnknownFormat(_)));
+    let err = WrrConfig::<String>::from_path(dir.join("missing.json")).unwrap_err();
+    assert!(matches!(err, ConfigError::Io(..)));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(all(feature = "tokio", not(wrr_sync), feature = "watch"))]
+#[tokio::test]
+async fn tokio_watch_config_test() {
+    use std::time::Duration;
+
+    let dir = std::env::temp_dir().join(format!("wrr_watch_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("queue.json");
+    std::fs::write(
+        &path,
+        r#"{"instances":[{"data":"a","weight":1},{"data":"b","weight":2}]}"#,
+    )
+    .unwrap();
+
+    let queue: WrrQueue<String> = WrrQueue::from_path(&path).unwrap();
+    let (reader, writer) = queue.split();
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = writer
+        .watch_config(&path, move |res| {
+            let _ = sender.send(res.is_ok());
+        })
+        .unwrap();
+    assert_eq!("b", reader.select().await.unwrap().data());
+
+    std::fs::write(&path, r#"{"instances":[{"data":"c","weight":1}]}"#).unwrap();
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await;
+    assert_eq!(Ok(Some(true)), reloaded);
+    assert_eq!("c", watcher.reader().select().await.unwrap().data());
+
+    std::fs::write(&path, r#"{"instances":[{"data":"d","weight":0}]}"#).unwrap();
+    let reloaded = tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await;
+    assert_eq!(Ok(Some(false)), reloaded);
+    assert_eq!("c", reader.select().await.unwrap().data());
+
+    drop(watcher);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_from_env_test() {
+    std::env::set_var("WRR_TOKIO_FROM_ENV_TEST", "10.0.0.1:80=1, 10.0.0.2:80=2,");
+    let mut queue: WrrQueue<String> = WrrQueue::from_env("WRR_TOKIO_FROM_ENV_TEST").unwrap();
+    assert_eq!(2, queue.len());
+    assert_eq!("10.0.0.2:80", queue.select().await.unwrap().data());
+
+    let err = WrrConfig::<String>::from_env("WRR_TOKIO_FROM_ENV_TEST_UNSET").unwrap_err();
+    assert!(matches!(err, EnvConfigError::Var(..)));
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn from_env_test() {
+    std::env::set_var("WRR_FROM_ENV_TEST", "10.0.0.1:80=1, 10.0.0.2:80=2,");
+    let mut queue: WrrQueue<String> = WrrQueue::from_env("WRR_FROM_ENV_TEST").unwrap();
+    assert_eq!(2, queue.len());
+    assert_eq!("10.0.0.2:80", queue.select().unwrap().data());
+
+    let err = WrrConfig::<String>::from_env("WRR_FROM_ENV_TEST_UNSET").unwrap_err();
+    assert!(matches!(err, EnvConfigError::Var(..)));
+
+    let config: WrrConfig<std::net::SocketAddr> = "10.0.0.1:80, 10.0.0.2:80=3".parse().unwrap();
+    assert_eq!(20, config.instances()[0].weight().get());
+    assert_eq!(3, config.instances()[1].weight().get());
+    assert_eq!(
+        Err(EnvConfigError::Weight(1, "0".to_string())),
+        "10.0.0.1:80, 10.0.0.2:80=0".parse::<WrrConfig<std::net::SocketAddr>>()
+    );
+    assert_eq!(
+        Err(EnvConfigError::Weight(0, "x".to_string())),
+        "a=x".parse::<WrrConfig<String>>()
+    );
+    assert!(matches!(
+        "10.0.0.1=1".parse::<WrrConfig<std::net::SocketAddr>>(),
+        Err(EnvConfigError::Data(0, _))
+    ));
+    assert_eq!(
+        Err(EnvConfigError::Duplicate(2)),
+        "a,b,a".parse::<WrrConfig<String>>()
+    );
+    assert_eq!(
+        0,
+        "".parse::<WrrConfig<String>>().unwrap().instances().len()
+    );
+}
+
+#[cfg(all(feature = "tokio", not(wrr_sync), feature = "dns"))]
+#[tokio::test]
+async fn tokio_dns_discovery_test() {
+    use std::num::NonZeroUsize;
+    use std::time::Duration;
+
+    let (reader, writer) = WrrQueue::new().split();
+    let watcher = DnsDiscovery::new("localhost", 8080)
+        .interval(Duration::from_millis(50))
+        .weight(NonZeroUsize::new(3).unwrap())
+        .spawn(writer);
+    for _ in 0..100 {
+        if reader.generation() > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let selected = watcher.reader().select().await.unwrap();
+    assert!(selected.data().ip().is_loopback());
+    assert_eq!(8080, selected.data().port());
+    assert_eq!(3, selected.weight().get());
+    watcher.stop();
+}
+
+#[cfg(wrr_async)]
+#[tokio::test]
+async fn tokio_snapshot_test() {
+    for engine in [Engine::Expanded, Engine::Smooth, Engine::DeficitRoundRobin] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue
+            .insert_many(vec![
+                ("a", 1usize),
+                ("b", 2usize),
+                ("c", 3usize),
+                ("d", 1usize),
+            ])
+            .await;
+        queue.mark_down(&"d");
+        for _ in 0..4 {
+            queue.select().await.unwrap();
+        }
+
+        let mut restored = WrrQueue::new();
+        restored.restore(queue.snapshot());
+        assert_eq!(engine, restored.engine());
+        assert_eq!(Some(false), restored.is_up(&"d"));
+        for _ in 0..12 {
+            assert_eq!(
+                queue.select().await.unwrap().data(),
+                restored.select().await.unwrap().data()
+            );
+        }
+    }
+}
+
+#[cfg(wrr_sync)]
+#[test]
+fn snapshot_test() {
+    for engine in [Engine::Expanded, Engine::Smooth, Engine::DeficitRoundRobin] {
+        let mut queue = WrrQueue::with_engine(engine);
+        queue.insert_many(vec![
+            ("a", 1usize),
+            ("b", 2usize),
+            ("c", 3usize),
+            ("d", 1usize),
+        ]);
+        queue.mark_down(&"d");
+        queue.set_standby(&"a", true);
+        for _ in 0..4 {
+            queue.select().unwrap();
+        }
+
+        let snapshot = queue.snapshot();
+        assert_eq!(Health::Down, snapshot.members()[3].health());
+        assert!(snapshot.members()[0].is_standby());
+
+        let mut restored = WrrQueue::new().lazy_recalculation(true);
+        restored.restore(snapshot);
+        assert_eq!(engine, restored.engine());
+        assert_eq!(Some(false), restored.is_up(&"d"));
+        assert_eq!(Some(true), restored.is_standby(&"a"));
+        for _ in 0..12 {
+            assert_eq!(
+                queue.select().unwrap().data(),
+                restored.select().unwrap().data()
+            );
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_snapshot_test() {
+    let queue = WrrQueue::from_config(WrrConfig::new(vec![("a".to_string(), 2usize)
+        .try_into()
+        .unwrap()]));
+    let snapshot = queue.snapshot();
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert_eq!(snapshot, serde_json::from_str(&json).unwrap());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn serde_format_version_test() {
+    // unversioned, and without the cycle phase nor the member health
+    let json = r#"{"engine":"smooth","members":[{"instance":{"data":"a","weight":2}}]}"#;
+    let snapshot: QueueSnapshot<String> = serde_json::from_str(json).unwrap();
+    assert_eq!(Engine::Smooth, snapshot.engine());
+    assert_eq!(Health::Up, snapshot.members()[0].health());
+    assert!(!snapshot.members()[0].is_standby());
+    assert!(serde_json::to_string(&snapshot)
+        .unwrap()
+        .starts_with(r#"{"version":1,"#));
+
+    // written by a newer release, with fields unknown to this one
+    let json = r#"{"version":7,"members":[],"cursor":3,"shard_cursors":[1,2]}"#;
+    let snapshot: QueueSnapshot<String> = serde_json::from_str(json).unwrap();
+    assert_eq!(Engine::default(), snapshot.engine());
+
+    let json = r#"{"version":7,"instances":[{"data":"a","zone":"eu"}],"retries":2}"#;
+    let config: WrrConfig<String> = serde_json::from_str(json).unwrap();
+    assert_eq!(1, config.instances().len());
+}
+
+#[cfg(all(feature = "tokio", wrr_async))]
+#[tokio::test]
+async fn tokio_watch_membership_test() {
+    let mut queue = WrrQueue::new();
+    let mut membership = queue.watch_membership();
+    assert_eq!(Membership::default(), *membership.borrow());
+
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]).await;
+    assert!(membership.has_changed().unwrap());
+    let first = membership.borrow_and_update().generation();
+    assert!(first > 0);
+    assert_eq!(2, membership.borrow().len());
+
+    queue.mark_down(&"a");
+    membership.changed().await.unwrap();
+    assert!(membership.borrow().generation() > first);
+    assert_eq!(1, membership.borrow().in_rotation());
+
+    let (reader, mut writer) = queue.split();
+    let mut published = reader.watch_membership();
+    assert_eq!(0, published.borrow().generation());
+    writer.insert(("c", 3usize)).await;
+    published.changed().await.unwrap();
+    assert_eq!(reader.generation() as u64, published.borrow().generation());
+    assert_eq!(3, published.borrow().len());
+    assert_eq!(2, published.borrow().in_rotation());
+}
+
+#[cfg(all(feature = "tokio", wrr_sync))]
+#[test]
+fn watch_membership_test() {
+    let mut queue = WrrQueue::new();
+    let mut membership = queue.watch_membership();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+    let first = membership.borrow_and_update().generation();
+    assert!(first > 0);
+
+    queue.mark_down(&"a");
+    assert!(membership.has_changed().unwrap());
+    assert!(membership.borrow().generation() > first);
+    assert_eq!(
+        (2, 1),
+        (membership.borrow().len(), membership.borrow().in_rotation())
+    );
+
+    let (reader, mut writer) = queue.split();
+    let published = reader.watch_membership();
+    writer.insert(("c", 3usize));
+    assert_eq!(1, published.borrow().generation());
+    assert_eq!(3, published.borrow().len());
+}
+
+#[cfg(all(feature = "tokio", wrr_async))]
+#[tokio::test]
+async fn tokio_subscribe_test() {
+    let mut queue = WrrQueue::new();
+    let mut events = queue.subscribe();
+    queue.insert(("a", 1usize)).await;
+    queue.update_weight(&"a", std::num::NonZeroUsize::new(3).unwrap());
+    queue.mark_down(&"a");
+    queue.mark_up(&"a");
+    queue
+        .delete_instance(("a", 3usize).try_into().unwrap())
+        .await;
+
+    let a = Instance::new_with_weight("a", std::num::NonZeroUsize::new(3).unwrap());
+    assert_eq!(
+        QueueEvent::Inserted(("a", 1usize).try_into().unwrap()),
+        events.recv().await.unwrap()
+    );
+    assert_eq!(
+        QueueEvent::WeightChanged {
+            instance: a.clone(),
+            from: std::num::NonZeroUsize::new(1).unwrap()
+        },
+        events.recv().await.unwrap()
+    );
+    assert_eq!(
+        QueueEvent::MarkedDown {
+            instance: a.clone(),
+            health: Health::Down
+        },
+        events.recv().await.unwrap()
+    );
+    assert_eq!(
+        QueueEvent::Recovered(a.clone()),
+        events.recv().await.unwrap()
+    );
+    assert_eq!(QueueEvent::Removed(a), events.recv().await.unwrap());
+    assert!(events.try_recv().is_err());
+
+    // kept by a split queue
+    let (_reader, mut writer) = queue.split();
+    writer.insert(("b", 2usize)).await;
+    assert_eq!(
+        QueueEvent::Inserted(("b", 2usize).try_into().unwrap()),
+        events.recv().await.unwrap()
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[cfg(all(feature = "tokio", wrr_sync))]
+#[test]
+fn subscribe_test() {
+    let mut queue = WrrQueue::new();
+    let mut events = queue.subscribe();
+    queue.insert(("a", 1usize));
+    queue.pause(&"a");
+    queue.resume(&"a");
+    queue.clear_instance();
+
+    assert_eq!(
+        QueueEvent::Inserted(("a", 1usize).try_into().unwrap()),
+        events.try_recv().unwrap()
+    );
+    assert_eq!(
+        QueueEvent::MarkedDown {
+            instance: ("a", 1usize).try_into().unwrap(),
+            health: Health::Paused
+        },
+        events.try_recv().unwrap()
+    );
+    assert_eq!(
+        QueueEvent::Recovered(("a", 1usize).try_into().unwrap()),
+        events.try_recv().unwrap()
+    );
+    assert_eq!(
+        QueueEvent::Removed(("a", 1usize).try_into().unwrap()),
+        events.try_recv().unwrap()
+    );
+    assert!(events.try_recv().is_err());
+}
+
+#[cfg(all(feature = "tokio", wrr_async))]
+#[tokio::test]
+async fn tokio_drive_test() {
+    let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+    let watcher = WrrQueue::new().drive(receiver);
+    let reader = watcher.reader();
+    let mut membership = reader.watch_membership();
+
+    sender
+        .send(Change::Insert(("a", 1usize).try_into().unwrap()))
+        .unwrap();
+    sender
+        .send(Change::Insert(("b", 2usize).try_into().unwrap()))
+        .unwrap();
+    membership.changed().await.unwrap();
+    while membership.borrow_and_update().len() < 2 {
+        membership.changed().await.unwrap();
+    }
+    assert_eq!(&"b", reader.select().await.unwrap().data());
+
+    sender
+        .send(Change::Insert(("a", 5usize).try_into().unwrap()))
+        .unwrap();
+    sender.send(Change::Remove("b")).unwrap();
+    membership.changed().await.unwrap();
+    while membership.borrow_and_update().len() > 1 {
+        membership.changed().await.unwrap();
+    }
+    let selected = reader.select().await.unwrap();
+    assert_eq!((&"a", 5), (selected.data(), selected.weight().get()));
+
+    sender
+        .send(Change::Reset(vec![("c", 3usize).try_into().unwrap()]))
+        .unwrap();
+    drop(sender);
+    while !watcher.is_finished() {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(&"c", reader.select().await.unwrap().data());
+    watcher.stop();
+}
@@ -0,0 +1,34 @@
+#![cfg(wrr_loom)]
+
+use async_wrr_queue::*;
+use loom::thread;
+
+#[test]
+fn loom_select_during_publish_test() {
+    loom::model(|| {
+        let mut queue = WrrQueue::new();
+        queue.insert_many(vec![("a", 1usize), ("b", 2usize)]);
+        let (reader, mut writer) = queue.split();
+
+        let handle = thread::spawn(move || *reader.select().unwrap().data());
+        writer.insert(("c", 1usize));
+        writer.delete_instance(("a", 1usize).try_into().unwrap());
+        assert!(["a", "b", "c"].contains(&handle.join().unwrap()));
+    });
+}
+
+#[test]
+fn loom_concurrent_select_test() {
+    for engine in [Engine::Expanded, Engine::Smooth] {
+        loom::model(move || {
+            let mut queue = WrrQueue::with_engine(engine);
+            queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+            let (reader, _writer) = queue.split();
+
+            let other = reader.clone();
+            let handle = thread::spawn(move || *other.select().unwrap().data());
+            let selected = *reader.select().unwrap().data();
+            assert_ne!(selected, handle.join().unwrap());
+        });
+    }
+}
@@ -0,0 +1,95 @@
+#![cfg(not(wrr_std))]
+
+use async_wrr_queue::*;
+use std::num::NonZeroUsize;
+
+fn picks(queue: &WrrQueue<&'static str>, n: usize) -> Vec<&'static str> {
+    (0..n).map(|_| *queue.select().unwrap().data()).collect()
+}
+
+#[test]
+fn select_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 3usize)]));
+    assert_eq!(Engine::Expanded, queue.active_engine());
+    assert_eq!(
+        vec!["c", "b", "c", "a", "b", "c", "c", "b", "c", "a", "b", "c"],
+        picks(&queue, 12)
+    );
+}
+
+#[test]
+fn insert_test() {
+    let mut queue = WrrQueue::new();
+    assert!(queue.insert(("a", 1usize)));
+    assert!(!queue.insert(("a", 2usize)));
+    assert!(!queue.insert(("b", 0usize)));
+    assert_eq!(Err(WrrError::ZeroWeight), queue.try_insert(("b", 0usize)));
+    assert_eq!(Ok(true), queue.try_insert(("b", 3usize)));
+    assert!(!queue.insert_many(vec![("c", 1usize), ("a", 1usize)]));
+    assert_eq!(3, queue.len());
+    assert_eq!(
+        vec![
+            ("a", NonZeroUsize::new(1).unwrap()),
+            ("b", NonZeroUsize::new(3).unwrap()),
+            ("c", NonZeroUsize::new(1).unwrap())
+        ],
+        queue
+            .weights()
+            .into_iter()
+            .map(|(data, weight)| (*data, weight))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn membership_change_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 1usize)]);
+    assert!(queue.update_weight(&"a", NonZeroUsize::new(3).unwrap()));
+    assert!(!queue.update_weight(&"c", NonZeroUsize::new(3).unwrap()));
+    let selected = picks(&queue, 8);
+    assert_eq!(6, selected.iter().filter(|data| **data == "a").count());
+
+    assert!(!queue.delete_instance(Instance::new_with_weight("a", NonZeroUsize::MIN)));
+    assert!(queue.delete_instance(Instance::new_with_weight(
+        "a",
+        NonZeroUsize::new(3).unwrap()
+    )));
+    assert_eq!(vec!["b"; 3], picks(&queue, 3));
+
+    queue.clear_instance();
+    assert!(queue.is_empty());
+    assert!(queue.select().is_none());
+    assert_eq!(Err(WrrError::Empty), queue.try_select().map(|_| ()));
+}
+
+#[test]
+fn smooth_fallback_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize << 20), ("b", 1usize)]);
+    assert_eq!(Engine::Smooth, queue.active_engine());
+    assert_eq!(vec!["a"; 8], picks(&queue, 8));
+
+    // back to a stored schedule once the cycle fits
+    assert!(queue.update_weight(&"a", NonZeroUsize::new(2).unwrap()));
+    assert_eq!(Engine::Expanded, queue.active_engine());
+    let selected = picks(&queue, 9);
+    assert_eq!(3, selected.iter().filter(|data| **data == "b").count());
+}
+
+#[test]
+fn concurrent_select_test() {
+    let mut queue = WrrQueue::new();
+    queue.insert_many(vec![("a", 1usize), ("b", 2usize), ("c", 5usize)]);
+    let counts = std::thread::scope(|s| {
+        let handles: Vec<_> = (0..4).map(|_| s.spawn(|| picks(&queue, 200))).collect();
+        let mut counts = [0; 3];
+        for selected in handles.into_iter().flat_map(|h| h.join().unwrap()) {
+            counts[["a", "b", "c"].iter().position(|d| *d == selected).unwrap()] += 1;
+        }
+        counts
+    });
+    // 800 selections are exactly 100 cycles, whatever the interleaving
+    assert_eq!([100, 200, 500], counts);
+}